@@ -0,0 +1,94 @@
+use std::collections::{HashMap, HashSet};
+
+use uv_normalize::{PackageId, PackageName, PackageNameInterner};
+
+use bench::criterion::{
+    criterion_group, criterion_main, measurement::WallTime, BenchmarkId, Criterion, Throughput,
+};
+
+/// The number of packages in the synthetic dependency graph.
+const GRAPH_NODES: usize = 5_000;
+
+/// Returns the dependencies of the `i`-th package in a synthetic graph in which each package
+/// depends on a few others.
+fn dependencies(i: usize) -> impl Iterator<Item = usize> {
+    [1, 7, 31]
+        .into_iter()
+        .map(move |step| (i * step + step) % GRAPH_NODES)
+}
+
+/// Counts the packages reachable from the first package, keyed by name.
+fn reachable_by_name(graph: &HashMap<PackageName, Vec<PackageName>>, root: &PackageName) -> usize {
+    let mut seen = HashSet::from([root.clone()]);
+    let mut stack = vec![root];
+    while let Some(name) = stack.pop() {
+        for dependency in &graph[name] {
+            if seen.insert(dependency.clone()) {
+                stack.push(dependency);
+            }
+        }
+    }
+    seen.len()
+}
+
+/// Counts the packages reachable from the first package, keyed by interned identifier.
+fn reachable_by_id(graph: &[Vec<PackageId>], root: PackageId) -> usize {
+    let mut seen = vec![false; graph.len()];
+    seen[root.index()] = true;
+    let mut stack = vec![root];
+    let mut count = 1;
+    while let Some(id) = stack.pop() {
+        for dependency in &graph[id.index()] {
+            if !seen[dependency.index()] {
+                seen[dependency.index()] = true;
+                count += 1;
+                stack.push(*dependency);
+            }
+        }
+    }
+    count
+}
+
+/// Benchmarks traversing a dependency graph with thousands of nodes, keyed by `PackageName`
+/// against keyed by `PackageId` from a `PackageNameInterner`.
+fn benchmark_package_graph(c: &mut Criterion<WallTime>) {
+    let names: Vec<PackageName> = (0..GRAPH_NODES)
+        .map(|i| format!("package-{i}").parse().expect("valid package name"))
+        .collect();
+
+    let by_name: HashMap<PackageName, Vec<PackageName>> = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let dependencies = dependencies(i).map(|j| names[j].clone()).collect();
+            (name.clone(), dependencies)
+        })
+        .collect();
+
+    let interner = PackageNameInterner::new();
+    let ids: Vec<PackageId> = names.iter().map(|name| interner.intern(name)).collect();
+    let by_id: Vec<Vec<PackageId>> = (0..GRAPH_NODES)
+        .map(|i| dependencies(i).map(|j| ids[j]).collect())
+        .collect();
+
+    let mut group = c.benchmark_group("package_graph");
+    group.throughput(Throughput::Elements(GRAPH_NODES as u64));
+    group.bench_function(BenchmarkId::from_parameter("intern"), |b| {
+        b.iter(|| {
+            let interner = PackageNameInterner::new();
+            for name in &names {
+                std::hint::black_box(interner.intern(name));
+            }
+        });
+    });
+    group.bench_function(BenchmarkId::from_parameter("traverse_by_name"), |b| {
+        b.iter(|| std::hint::black_box(reachable_by_name(&by_name, &names[0])));
+    });
+    group.bench_function(BenchmarkId::from_parameter("traverse_by_id"), |b| {
+        b.iter(|| std::hint::black_box(reachable_by_id(&by_id, ids[0])));
+    });
+    group.finish();
+}
+
+criterion_group!(uv_normalize, benchmark_package_graph);
+criterion_main!(uv_normalize);
@@ -21,11 +21,49 @@ impl CanonicalUrl {
     pub fn new(url: &Url) -> CanonicalUrl {
         let mut url = url.clone();
 
+        // Lower-case the host, and drop the port if it's the default for the scheme. The `url`
+        // crate already does both for special schemes (like `https`), but not for others (like
+        // `git+https`).
+        if let Some(host) = url.host_str() {
+            if host.bytes().any(|byte| byte.is_ascii_uppercase()) {
+                let host = host.to_ascii_lowercase();
+                url.set_host(Some(&host)).unwrap();
+            }
+        }
+        if url.port().is_some()
+            && url.port() == default_port(url.scheme().trim_start_matches("git+"))
+        {
+            url.set_port(None).unwrap();
+        }
+
         // Strip a trailing slash.
         if url.path().ends_with('/') {
             url.path_segments_mut().unwrap().pop_if_empty();
         }
 
+        // Percent-encode the path consistently. Note that paths are case-sensitive, so (outside
+        // of the percent-encodings themselves) their case is preserved.
+        let path = normalize_percent_encoding(url.path());
+        url.set_path(&path);
+
+        // Sort the query parameters by name (keeping repeated parameters in order), and
+        // percent-encode them consistently. The parameters are sorted as written rather than
+        // decoded, as decoding conflates parameters that servers may tell apart (e.g., `?foo`
+        // and `?foo=`, or `+` and `%2B`).
+        match url.query() {
+            Some("") => url.set_query(None),
+            Some(query) => {
+                let mut params: Vec<String> =
+                    query.split('&').map(normalize_percent_encoding).collect();
+                params.sort_by(|a, b| {
+                    let name = |param: &str| param.split('=').next().unwrap_or_default();
+                    name(a).cmp(name(b))
+                });
+                url.set_query(Some(&params.join("&")));
+            }
+            None => {}
+        }
+
         // For GitHub URLs specifically, just lower-case everything. GitHub
         // treats both the same, but they hash differently, and we're gonna be
         // hashing them. This wants a more general solution, and also we're
@@ -70,6 +108,45 @@ impl CanonicalUrl {
     }
 }
 
+/// Returns the default port for the given scheme, for the schemes we fetch from.
+fn default_port(scheme: &str) -> Option<u16> {
+    match scheme {
+        "http" => Some(80),
+        "https" => Some(443),
+        _ => None,
+    }
+}
+
+/// Decode percent-encoded unreserved characters, and upper-case the hex digits of the remaining
+/// percent-encodings, such that equivalent paths (or query parameters) compare equal (see RFC
+/// 3986, Section 6.2.2).
+fn normalize_percent_encoding(path: &str) -> String {
+    let mut normalized = String::with_capacity(path.len());
+    let mut rest = path;
+    while let Some(index) = rest.find('%') {
+        normalized.push_str(&rest[..index]);
+        rest = &rest[index..];
+        let byte = rest
+            .get(1..3)
+            .filter(|hex| hex.bytes().all(|byte| byte.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match byte {
+            Some(byte) if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) => {
+                normalized.push(char::from(byte));
+            }
+            Some(_) => normalized.push_str(&rest[..3].to_ascii_uppercase()),
+            None => {
+                normalized.push('%');
+                rest = &rest[1..];
+                continue;
+            }
+        }
+        rest = &rest[3..];
+    }
+    normalized.push_str(rest);
+    normalized
+}
+
 impl CacheKey for CanonicalUrl {
     fn cache_key(&self, state: &mut CacheKeyHasher) {
         // `as_str` gives the serialisation of a url (which has a spec) and so insulates against
@@ -190,6 +267,55 @@ mod tests {
             )?,
         );
 
+        // Two URLs should be considered equal regardless of the case of the host, an explicit
+        // default port, or a trailing slash.
+        assert_eq!(
+            CanonicalUrl::parse("https://Example.com/foo/")?,
+            CanonicalUrl::parse("https://example.com:443/foo")?,
+        );
+        assert_eq!(
+            CanonicalUrl::parse("git+https://Example.com:443/pypa/sample-namespace-packages")?,
+            CanonicalUrl::parse("git+https://example.com/pypa/sample-namespace-packages")?,
+        );
+
+        // Two URLs should _not_ be considered equal if they use different ports.
+        assert_ne!(
+            CanonicalUrl::parse("git+https://example.com:8443/pypa/sample-namespace-packages")?,
+            CanonicalUrl::parse("git+https://example.com/pypa/sample-namespace-packages")?,
+        );
+
+        // Two URLs should be considered equal regardless of the order of their query parameters,
+        // or how they're percent-encoded.
+        assert_eq!(
+            CanonicalUrl::parse("https://example.com/foo?b=2&a=1&b=1")?,
+            CanonicalUrl::parse("https://example.com/foo?a=%31&b=2&b=1")?,
+        );
+        assert_eq!(
+            CanonicalUrl::parse("https://example.com/%7efoo/a%2fb%")?,
+            CanonicalUrl::parse("https://example.com/~foo/a%2Fb%")?,
+        );
+
+        // Two URLs should _not_ be considered equal if their query parameters only match once
+        // decoded, as servers may tell them apart.
+        assert_ne!(
+            CanonicalUrl::parse("https://example.com/foo?a")?,
+            CanonicalUrl::parse("https://example.com/foo?a=")?,
+        );
+        assert_ne!(
+            CanonicalUrl::parse("https://example.com/foo?a=1+2")?,
+            CanonicalUrl::parse("https://example.com/foo?a=1%2B2")?,
+        );
+        assert_ne!(
+            CanonicalUrl::parse("https://example.com/foo?a=1+2")?,
+            CanonicalUrl::parse("https://example.com/foo?a=1%202")?,
+        );
+
+        // Two URLs should _not_ be considered equal if their paths differ in case.
+        assert_ne!(
+            CanonicalUrl::parse("https://example.com/Foo")?,
+            CanonicalUrl::parse("https://example.com/foo")?,
+        );
+
         Ok(())
     }
 
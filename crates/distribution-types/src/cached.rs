@@ -1,4 +1,5 @@
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 use anyhow::Result;
 
@@ -8,8 +9,8 @@ use uv_normalize::PackageName;
 
 use crate::direct_url::{DirectUrl, LocalFileUrl};
 use crate::{
-    BuiltDist, Dist, DistributionMetadata, InstalledMetadata, InstalledVersion, Name, SourceDist,
-    VersionOrUrl,
+    BuiltDist, Dist, DistributionMetadata, Error, InstalledMetadata, InstalledVersion, Name,
+    SourceDist, VersionOrUrl,
 };
 
 /// A built distribution (wheel) that exists in the local cache.
@@ -78,6 +79,50 @@ impl CachedDist {
         }
     }
 
+    /// Initialize a [`CachedDist::Url`] for the wheel at the given path, e.g., to inject a
+    /// distribution that is known to be in the cache (as when installing from a lockfile).
+    ///
+    /// The wheel filename is inferred from the last component of the path, which may be either
+    /// a wheel filename (`flask-3.0.0-py3-none-any.whl`) or the stem of one, as used for unzipped
+    /// wheels in the cache (`flask-3.0.0-py3-none-any`). Returns an error if the path doesn't
+    /// exist, or if the wheel isn't for the given package.
+    pub fn from_url(name: &PackageName, url: VerbatimUrl, path: PathBuf) -> Result<Self, Error> {
+        if !path.try_exists()? {
+            return Err(Error::CachedNotFound(path));
+        }
+        Self::from_url_unchecked(name, url, path)
+    }
+
+    /// Initialize a [`CachedDist::Url`] like [`CachedDist::from_url`], but without checking
+    /// that the path exists, e.g., for tests.
+    pub fn from_url_unchecked(
+        name: &PackageName,
+        url: VerbatimUrl,
+        path: PathBuf,
+    ) -> Result<Self, Error> {
+        let Some(file_name) = path.file_name().and_then(|file_name| file_name.to_str()) else {
+            return Err(Error::PathFilename(path));
+        };
+        let filename = if Path::new(file_name)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("whl"))
+        {
+            WheelFilename::from_str(file_name)?
+        } else {
+            WheelFilename::from_stem(file_name)?
+        };
+        if filename.name != *name {
+            return Err(Error::PackageNameMismatch {
+                expected: name.clone(),
+                actual: filename.name,
+                path,
+            });
+        }
+        Ok(Self::Url(CachedDirectUrlDist::from_url(
+            filename, url, path,
+        )))
+    }
+
     /// Return the [`Path`] at which the distribution is stored on-disk.
     pub fn path(&self) -> &Path {
         match self {
@@ -193,3 +238,69 @@ impl InstalledMetadata for CachedDist {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::str::FromStr;
+
+    use anyhow::Result;
+    use pep508_rs::VerbatimUrl;
+    use url::Url;
+    use uv_normalize::PackageName;
+
+    use crate::{CachedDist, Error, Name};
+
+    fn url() -> VerbatimUrl {
+        VerbatimUrl::unknown(
+            Url::parse("https://example.com/flask-3.0.0-py3-none-any.whl").unwrap(),
+        )
+    }
+
+    #[test]
+    fn from_url_unchecked() -> Result<()> {
+        let name = PackageName::from_str("flask")?;
+
+        // Both unzipped wheels and wheel archives are supported.
+        for path in [
+            "/cache/wheels/flask-3.0.0-py3-none-any",
+            "/cache/wheels/flask-3.0.0-py3-none-any.whl",
+        ] {
+            let dist = CachedDist::from_url_unchecked(&name, url(), PathBuf::from(path))?;
+            assert_eq!(dist.name(), &name);
+            assert_eq!(dist.filename().version.to_string(), "3.0.0");
+            assert_eq!(dist.path(), PathBuf::from(path));
+            assert!(matches!(dist, CachedDist::Url(_)));
+        }
+
+        // The wheel must be for the given package.
+        let err = CachedDist::from_url_unchecked(
+            &PackageName::from_str("django")?,
+            url(),
+            PathBuf::from("/cache/wheels/flask-3.0.0-py3-none-any"),
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::PackageNameMismatch { .. }));
+
+        // The path must name a wheel.
+        let err =
+            CachedDist::from_url_unchecked(&name, url(), PathBuf::from("/cache/wheels/flask"))
+                .unwrap_err();
+        assert!(matches!(err, Error::WheelFilename(_)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_url_missing() -> Result<()> {
+        let name = PackageName::from_str("flask")?;
+        let err = CachedDist::from_url(
+            &name,
+            url(),
+            PathBuf::from("/nonexistent/flask-3.0.0-py3-none-any"),
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::CachedNotFound(_)));
+        Ok(())
+    }
+}
@@ -1,5 +1,9 @@
+use std::path::PathBuf;
+
 use url::Url;
 
+use uv_normalize::PackageName;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error(transparent)]
@@ -11,6 +15,22 @@ pub enum Error {
     #[error("Unable to extract filename from URL: {0}")]
     UrlFilename(Url),
 
+    #[error("Expected a `file://` URL for a local path, but found: {0}")]
+    NonLocalFileUrl(Url),
+
     #[error("Distribution not found at: {0}")]
     NotFound(Url),
+
+    #[error("Cached distribution not found at: {}", _0.display())]
+    CachedNotFound(PathBuf),
+
+    #[error("Unable to extract a wheel filename from path: {}", _0.display())]
+    PathFilename(PathBuf),
+
+    #[error("Expected a wheel for `{expected}`, but found one for `{actual}` at: {}", path.display())]
+    PackageNameMismatch {
+        expected: PackageName,
+        actual: PackageName,
+        path: PathBuf,
+    },
 }
@@ -228,10 +228,12 @@ impl Dist {
         }
 
         if url.scheme().eq_ignore_ascii_case("file") {
-            // Store the canonicalized path, which also serves to validate that it exists.
+            // Store the canonicalized path, which also serves to validate that it exists. Outside
+            // of Windows (where they're UNC paths), URLs with a host (e.g.,
+            // `file://server/share/foo.whl`) don't refer to a local path.
             let path = match url
                 .to_file_path()
-                .map_err(|()| Error::UrlFilename(url.to_url()))?
+                .map_err(|()| Error::NonLocalFileUrl(url.to_url()))?
                 .canonicalize()
             {
                 Ok(path) => path,
@@ -869,7 +871,13 @@ impl Identifier for Dist {
 
 #[cfg(test)]
 mod test {
-    use crate::{BuiltDist, Dist, SourceDist};
+    use std::str::FromStr;
+
+    use anyhow::Result;
+    use pep508_rs::VerbatimUrl;
+    use uv_normalize::PackageName;
+
+    use crate::{BuiltDist, Dist, Error, SourceDist};
 
     /// Ensure that we don't accidentally grow the `Dist` sizes.
     #[test]
@@ -892,4 +900,22 @@ mod test {
             std::mem::size_of::<SourceDist>()
         );
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn from_file_url() -> Result<()> {
+        let name = PackageName::from_str("flask")?;
+
+        // Local files must exist.
+        let url = VerbatimUrl::from_path("/missing/flask-3.0.0-py3-none-any.whl", "/");
+        let err = Dist::from_url(name.clone(), url).unwrap_err();
+        assert!(matches!(err, Error::NotFound(_)), "{err}");
+
+        // URLs with a host don't refer to a local file.
+        let url = VerbatimUrl::parse("file://server/share/flask-3.0.0-py3-none-any.whl")?;
+        let err = Dist::from_url(name, url).unwrap_err();
+        assert!(matches!(err, Error::NonLocalFileUrl(_)), "{err}");
+
+        Ok(())
+    }
 }
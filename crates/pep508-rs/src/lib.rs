@@ -18,7 +18,7 @@
 
 #[cfg(feature = "pyo3")]
 use std::collections::hash_map::DefaultHasher;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 use std::fmt::{Display, Formatter};
 #[cfg(feature = "pyo3")]
 use std::hash::{Hash, Hasher};
@@ -350,6 +350,19 @@ impl Requirement {
             .all(|specifier| specifier.contains(version))
     }
 
+    /// If both requirements are for the same project, regardless of their extras, version
+    /// specifiers (or URLs), and markers, returns the union of their extras. Otherwise, returns
+    /// `None`.
+    ///
+    /// For example, `flask[async] >= 3.0` and `Flask[dotenv]` are for the same project, with the
+    /// extras `async` and `dotenv` between them, while `flask` and `flask-login` are not.
+    pub fn same_project(&self, other: &Self) -> Option<BTreeSet<ExtraName>> {
+        if self.name != other.name {
+            return None;
+        }
+        Some(self.extras.iter().chain(&other.extras).cloned().collect())
+    }
+
     /// Returns whether the markers apply for the given environment
     pub fn evaluate_markers(&self, env: &MarkerEnvironment, extras: &[ExtraName]) -> bool {
         if let Some(marker) = &self.marker {
@@ -618,6 +631,11 @@ fn parse_extras(cursor: &mut Cursor) -> Result<Vec<ExtraName>, Pep508Error> {
     let Some(bracket_pos) = cursor.eat_char('[') else {
         return Ok(vec![]);
     };
+    // The list of extras is optional within the brackets, as in `black[]`.
+    cursor.eat_whitespace();
+    if cursor.eat_char(']').is_some() {
+        return Ok(vec![]);
+    }
     let mut extras = Vec::new();
 
     loop {
@@ -1110,6 +1128,39 @@ mod tests {
         assert_eq!(requests, expected);
     }
 
+    #[test]
+    fn same_project() {
+        let flask = Requirement::from_str("flask[async] >= 3.0").unwrap();
+        let extras = |extras: &[&str]| {
+            extras
+                .iter()
+                .map(|extra| ExtraName::from_str(extra).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        let other =
+            Requirement::from_str("Flask[dotenv, async] ; python_version >= '3.8'").unwrap();
+        assert_eq!(
+            flask
+                .same_project(&other)
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            extras(&["async", "dotenv"])
+        );
+        let other = Requirement::from_str("flask").unwrap();
+        assert_eq!(
+            flask
+                .same_project(&other)
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            extras(&["async"])
+        );
+        let other = Requirement::from_str("flask-login[async]").unwrap();
+        assert_eq!(flask.same_project(&other), None);
+    }
+
     #[test]
     fn parenthesized_single() {
         let numpy = Requirement::from_str("numpy ( >=1.19 )").unwrap();
@@ -1224,6 +1275,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn empty_extras() {
+        // An empty list of extras is allowed, and never yields an (invalid) empty extra name.
+        for input in ["black[]", "black[ ]", "black [] >=23"] {
+            let black = Requirement::from_str(input).unwrap();
+            assert_eq!(black.name, PackageName::from_str("black").unwrap());
+            assert!(black.extras.is_empty(), "{input}");
+        }
+        assert_err(
+            "black[,]",
+            indoc! {"
+                Expected an alphanumeric character starting the extra name, found ','
+                black[,]
+                      ^"
+            },
+        );
+    }
+
     #[test]
     fn error_parenthesized_pep440() {
         assert_err(
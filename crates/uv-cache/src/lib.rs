@@ -16,8 +16,8 @@ pub use crate::by_timestamp::CachedByTimestamp;
 pub use crate::cli::CacheArgs;
 use crate::removal::{rm_rf, Removal};
 pub use crate::timestamp::Timestamp;
-pub use crate::wheel::WheelCache;
 use crate::wheel::WheelCacheKind;
+pub use crate::wheel::{WheelCache, WheelCacheEntry};
 
 mod by_timestamp;
 mod cli;
@@ -224,6 +224,44 @@ impl Cache {
         Ok(archive_entry.into_path_buf())
     }
 
+    /// Atomically point the symlink at `link` to the cache directory at `target`, returning the
+    /// previous target of the symlink, if any.
+    ///
+    /// This enables blue/green deployments of prebuilt caches: populate a new cache directory in
+    /// full, then swap the symlink that serves as the cache root over to it. `link` must either
+    /// not exist yet, or be a symlink; an existing directory is never replaced.
+    ///
+    /// # Consistency
+    ///
+    /// [`Cache::from_path`] resolves the symlink once, when the cache is opened, and every entry
+    /// path is derived from the resolved root. As such, each [`Cache`] (and any client reading
+    /// entries through it) sees a single snapshot for its entire lifetime: either the previous
+    /// target or the new one, never a mix of both. Reads in flight during the swap complete
+    /// against the previous target, as do all later reads through caches opened before the swap.
+    /// Consequently, the previous target must not be removed until every process that may have
+    /// opened it has exited.
+    ///
+    /// # Platform support
+    ///
+    /// On Unix, the swap is atomic: a new symlink is created alongside `link` and renamed over
+    /// it. On Windows, the link is a junction, which can't be replaced atomically; it's removed
+    /// and then recreated. A cache opened in between would create an empty cache directory in
+    /// place of the link (and cause the swap to fail), so no cache may be opened while swapping.
+    pub fn swap_root(
+        link: impl AsRef<Path>,
+        target: impl AsRef<Path>,
+    ) -> Result<Option<PathBuf>, io::Error> {
+        let link = link.as_ref();
+        let previous = match fs::read_link(link) {
+            Ok(previous) => Some(previous),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err),
+        };
+        let target = Self::init(target.as_ref())?;
+        uv_fs::replace_symlink(target, link)?;
+        Ok(previous)
+    }
+
     /// Initialize a directory for use as a cache.
     fn init(root: impl Into<PathBuf>) -> Result<PathBuf, io::Error> {
         let root = root.into();
@@ -286,6 +324,9 @@ pub enum CacheBucket {
     /// See `uv_client::RegistryClient::wheel_metadata` for information on how wheel metadata
     /// is fetched.
     ///
+    /// Wheels read via range requests also have their parsed central directory cached here, as
+    /// `foo-1.0.0-py3-none-any.zip-index.msgpack`. See `uv_client::RegistryClient::wheel_file`.
+    ///
     /// # Example
     ///
     /// Consider the following `requirements.in`:
@@ -346,7 +387,7 @@ pub enum CacheBucket {
     /// If we run first `pip compile` and then `pip sync` on the same machine, we get both:
     ///
     /// ```text
-    /// wheels-v0
+    /// wheels-v1
     /// ├── pypi
     /// │   ├── ...
     /// │   ├── pandas
@@ -387,15 +428,15 @@ pub enum CacheBucket {
     /// directories in the cache.
     ///
     /// Cache structure:
-    ///  * `built-wheels-v0/pypi/foo/34a17436ed1e9669/{manifest.msgpack, metadata.msgpack, foo-1.0.0.zip, foo-1.0.0-py3-none-any.whl, ...other wheels}`
-    ///  * `built-wheels-v0/<digest(index-url)>/foo/foo-1.0.0.zip/{manifest.msgpack, metadata.msgpack, foo-1.0.0-py3-none-any.whl, ...other wheels}`
-    ///  * `built-wheels-v0/url/<digest(url)>/foo/foo-1.0.0.zip/{manifest.msgpack, metadata.msgpack, foo-1.0.0-py3-none-any.whl, ...other wheels}`
-    ///  * `built-wheels-v0/git/<digest(url)>/<git sha>/foo/foo-1.0.0.zip/{metadata.msgpack, foo-1.0.0-py3-none-any.whl, ...other wheels}`
+    ///  * `built-wheels-v1/pypi/foo/34a17436ed1e9669/{manifest.msgpack, metadata.msgpack, foo-1.0.0.zip, foo-1.0.0-py3-none-any.whl, ...other wheels}`
+    ///  * `built-wheels-v1/<digest(index-url)>/foo/foo-1.0.0.zip/{manifest.msgpack, metadata.msgpack, foo-1.0.0-py3-none-any.whl, ...other wheels}`
+    ///  * `built-wheels-v1/url/<digest(url)>/foo/foo-1.0.0.zip/{manifest.msgpack, metadata.msgpack, foo-1.0.0-py3-none-any.whl, ...other wheels}`
+    ///  * `built-wheels-v1/git/<digest(url)>/<git sha>/foo/foo-1.0.0.zip/{metadata.msgpack, foo-1.0.0-py3-none-any.whl, ...other wheels}`
     ///
     /// But the url filename does not need to be a valid source dist filename
     /// (<https://github.com/search?q=path%3A**%2Frequirements.txt+master.zip&type=code>),
     /// so it could also be the following and we have to take any string as filename:
-    ///  * `built-wheels-v0/url/<sha256(url)>/master.zip/metadata.msgpack`
+    ///  * `built-wheels-v1/url/<sha256(url)>/master.zip/metadata.msgpack`
     ///
     /// # Example
     ///
@@ -411,7 +452,7 @@ pub enum CacheBucket {
     ///
     /// ...may be cached as:
     /// ```text
-    /// built-wheels-v0/
+    /// built-wheels-v1/
     /// ├── git
     /// │   └── a67db8ed076e3814
     /// │       └── 843b753e9e8cb74e83cac55598719b39a4d5ef1f
@@ -440,7 +481,7 @@ pub enum CacheBucket {
     /// Flat index responses, a format very similar to the simple metadata API.
     ///
     /// Cache structure:
-    ///  * `flat-index-v0/index/<digest(flat_index_url)>.msgpack`
+    ///  * `flat-index-v1/index/<digest(flat_index_url)>.msgpack`
     ///
     /// The response is stored as `Vec<File>`.
     FlatIndex,
@@ -488,8 +529,8 @@ pub enum CacheBucket {
     /// Index responses through the simple metadata API.
     ///
     /// Cache structure:
-    ///  * `simple-v0/pypi/<package_name>.rkyv`
-    ///  * `simple-v0/<digest(index_url)>/<package_name>.rkyv`
+    ///  * `simple-v1/pypi/<package_name>.rkyv`
+    ///  * `simple-v1/<digest(index_url)>/<package_name>.rkyv`
     ///
     /// The response is parsed into `uv_client::SimpleMetadata` before storage.
     Simple,
@@ -504,12 +545,12 @@ pub enum CacheBucket {
 impl CacheBucket {
     fn to_str(self) -> &'static str {
         match self {
-            CacheBucket::BuiltWheels => "built-wheels-v0",
-            CacheBucket::FlatIndex => "flat-index-v0",
+            CacheBucket::BuiltWheels => "built-wheels-v1",
+            CacheBucket::FlatIndex => "flat-index-v1",
             CacheBucket::Git => "git-v0",
             CacheBucket::Interpreter => "interpreter-v0",
-            CacheBucket::Simple => "simple-v0",
-            CacheBucket::Wheels => "wheels-v0",
+            CacheBucket::Simple => "simple-v1",
+            CacheBucket::Wheels => "wheels-v1",
             CacheBucket::Archive => "archive-v0",
         }
     }
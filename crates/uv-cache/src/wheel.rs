@@ -1,12 +1,17 @@
+use std::io;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
 
+use tracing::{debug, warn};
 use url::Url;
 
 use cache_key::{digest, CanonicalUrl};
 use distribution_types::IndexUrl;
+use uv_normalize::PackageName;
 
-#[allow(unused_imports)] // For rustdoc
-use crate::CacheBucket;
+use crate::removal::{rm_rf, Removal};
+use crate::{Cache, CacheBucket};
 
 /// Cache wheels and their metadata, both from remote wheels and built from source distributions.
 ///
@@ -56,6 +61,221 @@ impl<'a> WheelCache<'a> {
     pub fn built_wheel_dir(&self, filename: impl AsRef<Path>) -> PathBuf {
         self.bucket().join(filename)
     }
+
+    /// Remove the entries for a package from this part of the given bucket (i.e.,
+    /// [`CacheBucket::Wheels`] or [`CacheBucket::BuiltWheels`]).
+    ///
+    /// Unlike [`Cache::remove`], this only touches the entries under this index, URL, path, or
+    /// Git revision. If the package has no entries, nothing is removed.
+    pub fn remove_package(
+        &self,
+        cache: &Cache,
+        cache_bucket: CacheBucket,
+        name: &PackageName,
+    ) -> io::Result<Removal> {
+        let shard = cache.shard(cache_bucket, self.remote_wheel_dir(name.to_string()));
+        debug!("Removing cache entries at: {}", shard.display());
+        rm_rf(&*shard)
+    }
+
+    /// Iterate over the entries in this part of the given bucket (i.e., [`CacheBucket::Wheels`]
+    /// or [`CacheBucket::BuiltWheels`]), along with the packages they belong to.
+    ///
+    /// Each file or directory directly within a [`WheelCache::remote_wheel_dir`] is an entry
+    /// (e.g., an unzipped wheel, or its cached metadata). The directories are walked lazily, as
+    /// the iterator advances, so the entries are never all held in memory. Temporary files of
+    /// atomic writes are skipped.
+    ///
+    /// Failures to read an entry, and directories that aren't named after a package, are
+    /// returned as errors in place of their entries, after which iteration continues.
+    pub fn iter_entries(
+        &self,
+        cache: &Cache,
+        cache_bucket: CacheBucket,
+    ) -> impl Iterator<Item = io::Result<WheelCacheEntry>> {
+        let shard = cache.shard(cache_bucket, self.bucket());
+        walkdir::WalkDir::new(&*shard)
+            .min_depth(2)
+            .max_depth(2)
+            .into_iter()
+            .filter_map(|entry| {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    // If nothing was cached yet, there are no entries.
+                    Err(err)
+                        if err.depth() == 0
+                            && err.io_error().map(io::Error::kind)
+                                == Some(io::ErrorKind::NotFound) =>
+                    {
+                        return None;
+                    }
+                    Err(err) => return Some(Err(err.into())),
+                };
+                if entry.file_name().to_string_lossy().starts_with(".tmp") {
+                    return None;
+                }
+                let path = entry.into_path();
+                let package = path
+                    .parent()
+                    .and_then(Path::file_name)
+                    .expect("entry to be within a package directory");
+                let package_name = match PackageName::from_str(&package.to_string_lossy()) {
+                    Ok(package_name) => package_name,
+                    Err(err) => return Some(Err(io::Error::new(io::ErrorKind::InvalidData, err))),
+                };
+                Some(Ok(WheelCacheEntry { package_name, path }))
+            })
+    }
+
+    /// Remove the least recently used entries from this part of the given bucket (i.e.,
+    /// [`CacheBucket::Wheels`] or [`CacheBucket::BuiltWheels`]) until it takes up at most
+    /// `max_bytes`, returning the number of bytes freed.
+    ///
+    /// Each directory under [`WheelCache::remote_wheel_dir`] or [`WheelCache::built_wheel_dir`]
+    /// is an entry, which is used as of the last access to any file within it. Unzipped wheels
+    /// are counted (and removed) along with the entry that links to them in
+    /// [`CacheBucket::Archive`]. Entries with a write in progress (i.e., containing the temporary
+    /// file of an atomic write) are left alone.
+    pub fn prune_to_size(
+        &self,
+        cache: &Cache,
+        cache_bucket: CacheBucket,
+        max_bytes: u64,
+    ) -> io::Result<u64> {
+        let shard = cache.shard(cache_bucket, self.bucket());
+        let read_dir = match fs_err::read_dir(&*shard) {
+            Ok(read_dir) => read_dir,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(err),
+        };
+
+        let archive = cache.bucket(CacheBucket::Archive);
+        let mut entries = Vec::new();
+        for entry in read_dir {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    warn!("Failed to read entry: {}", err);
+                    continue;
+                }
+            };
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => {}
+                Ok(_) => continue,
+                Err(err) => {
+                    warn!(
+                        "Failed to read file type of {}: {}",
+                        entry.path().display(),
+                        err
+                    );
+                    continue;
+                }
+            }
+            entries.push(PrunableEntry::from_path(entry.path(), &archive)?);
+        }
+
+        let mut total: u64 = entries.iter().map(|entry| entry.size).sum();
+        if total <= max_bytes {
+            return Ok(0);
+        }
+
+        // Remove the least recently used entries first.
+        entries.sort_by_key(|entry| entry.last_access);
+        let mut freed = 0;
+        for entry in entries {
+            if total <= max_bytes {
+                break;
+            }
+            if entry.writing {
+                debug!("Skipping entry being written: {}", entry.path.display());
+                continue;
+            }
+            debug!("Pruning cache entry: {}", entry.path.display());
+            let mut removal = rm_rf(&entry.path)?;
+            for target in &entry.archives {
+                removal += rm_rf(target)?;
+            }
+            total = total.saturating_sub(entry.size);
+            freed += removal.total_bytes;
+        }
+        Ok(freed)
+    }
+}
+
+/// An entry in the wheel cache, as returned by [`WheelCache::iter_entries`].
+#[derive(Debug, Clone)]
+pub struct WheelCacheEntry {
+    /// The package the entry belongs to.
+    pub package_name: PackageName,
+    /// The path to the entry.
+    pub path: PathBuf,
+}
+
+/// A directory that may be removed by [`WheelCache::prune_to_size`].
+#[derive(Debug)]
+struct PrunableEntry {
+    path: PathBuf,
+    /// The size of the files in the directory, and in the archives it links to.
+    size: u64,
+    /// The last access to any file in the directory.
+    last_access: SystemTime,
+    /// The unzipped wheels in [`CacheBucket::Archive`] that the directory links to.
+    archives: Vec<PathBuf>,
+    /// Whether the directory contains the temporary file of an atomic write (as created by
+    /// [`uv_fs::write_atomic`]).
+    writing: bool,
+}
+
+impl PrunableEntry {
+    fn from_path(path: PathBuf, archive: &Path) -> io::Result<Self> {
+        let mut entry = Self {
+            path,
+            size: 0,
+            last_access: SystemTime::UNIX_EPOCH,
+            archives: Vec::new(),
+            writing: false,
+        };
+        for file in walkdir::WalkDir::new(&entry.path) {
+            let file = file?;
+            if file.file_name().to_string_lossy().starts_with(".tmp") {
+                entry.writing = true;
+            }
+            if file.file_type().is_dir() {
+                continue;
+            }
+            let metadata = file.metadata()?;
+            let last_access = metadata.accessed().or_else(|_| metadata.modified())?;
+            entry.last_access = entry.last_access.max(last_access);
+            if file.file_type().is_symlink() {
+                let target = fs_err::read_link(file.path())?;
+                if target.starts_with(archive) {
+                    entry.size += dir_size(&target)?;
+                    entry.archives.push(target);
+                }
+            } else {
+                entry.size += metadata.len();
+            }
+        }
+        Ok(entry)
+    }
+}
+
+/// Returns the size of the files in a directory, or zero if it doesn't exist.
+fn dir_size(path: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for file in walkdir::WalkDir::new(path) {
+        let file = match file {
+            Ok(file) => file,
+            Err(err) if err.io_error().map(io::Error::kind) == Some(io::ErrorKind::NotFound) => {
+                return Ok(size);
+            }
+            Err(err) => return Err(err.into()),
+        };
+        if file.file_type().is_file() {
+            size += file.metadata()?.len();
+        }
+    }
+    Ok(size)
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -1,22 +1,76 @@
+use std::collections::{BTreeSet, HashMap};
+use std::hash::Hasher;
+use std::io::{Read, Seek, SeekFrom};
+use std::marker::PhantomData;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
 use std::{borrow::Cow, future::Future, path::Path};
 
-use futures::FutureExt;
-use reqwest::{Request, Response};
+use futures::{FutureExt, StreamExt};
+use reqwest::{Request, Response, ResponseBuilderExt};
 use reqwest_middleware::ClientWithMiddleware;
 use rkyv::util::AlignedVec;
+use rustc_hash::FxHashSet;
+use seahash::SeaHasher;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, info_span, instrument, trace, warn, Instrument};
+use tempfile::NamedTempFile;
+use tokio::io::{
+    AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter,
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{debug, info, info_span, instrument, trace, warn, Instrument};
+use url::Url;
 
+use distribution_filename::DistFilename;
 use uv_cache::{CacheEntry, Freshness};
-use uv_fs::write_atomic;
+use uv_fs::{atomic_temp_dir, write_atomic, write_atomic_in};
+use uv_normalize::PackageName;
 
+#[cfg(feature = "encryption")]
+use crate::encryption::CacheEncryptionKey;
+#[cfg(feature = "mmap")]
+use crate::mmap::MappedBytes;
 use crate::{
-    httpcache::{AfterResponse, BeforeRequest, CachePolicy, CachePolicyBuilder},
+    audit::AuditLog,
+    compression::{self, CompressionHint, DEFAULT_COMPRESSION_LEVEL},
+    content_length::ContentLengthCheck,
+    encryption,
+    evictor::{self, ActiveEntries, EntryLimit, EvictionPolicy, EvictorHandle},
+    host_headers::{HostHeaders, HostPort},
+    httpcache::{
+        content_disposition_filename, AfterResponse, BeforeRequest, CachePolicy,
+        CachePolicyBuilder, StaleReason,
+    },
+    mirror::MirrorRewrite,
+    not_found::NotFound,
+    observed_body::{observe_body, BodyObserver},
+    policy_overrides::CachePolicyOverrides,
+    rate_limit::{self, RateLimitHeaders, RateLimitState, RateLimiter},
     rkyvutil::OwnedArchive,
+    sidecar::{self, SidecarRef},
+    single_flight::{FlightLeader, Join, SharedPayload, SingleFlight},
+    stats::{CacheStats, StatsCounters},
+    weak_cache::WeakCache,
     Error, ErrorKind,
 };
 
+/// The header that carries the correlation ID of a request, as per
+/// [`CachedClientBuilder::correlation_id`].
+const CORRELATION_ID_HEADER: &str = "x-request-id";
+
+/// How long the temporary file of an atomic write may go unmodified before [`CachedClient::gc`]
+/// considers the write abandoned.
+const ABANDONED_TEMP_FILE_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// The extensions of the files that [`CachedClient::gc`] examines as cache entries.
+const GC_EXTENSIONS: &[&str] = &["rkyv", "msgpack", "http"];
+
 /// A trait the generalizes (de)serialization at a high level.
 ///
 /// The main purpose of this trait is to make the `CachedClient` work for
@@ -44,27 +98,87 @@ pub trait Cacheable: Sized + Send {
     fn to_bytes(&self) -> Result<Cow<'_, [u8]>, crate::Error>;
     /// Convert this type into its final form.
     fn into_target(self) -> Self::Target;
+
+    /// Whether payloads of this type are worth compressing, if compression is enabled (see
+    /// [`crate::CachedClientBuilder::compression_level`]).
+    fn compression_hint() -> CompressionHint {
+        CompressionHint::Compressible
+    }
+}
+
+/// Like [`Cacheable`], but for payloads that are too large to hold in memory in
+/// their entirety (e.g., wheels), and so are written to and read from the
+/// cache entry incrementally.
+///
+/// Used by `CachedClient::get_stream_cacheable`. Small payloads are better
+/// served by `Cacheable`, which reads the cache entry in one go.
+pub trait StreamCacheable: Sized + Send {
+    /// The type returned when reading the payload back, e.g., a handle to the
+    /// data on disk.
+    type Target;
+
+    /// Write the payload to the given writer.
+    ///
+    /// The writer is backed by a temporary file, which is only moved into
+    /// place once the payload has been written successfully.
+    fn write_to<W: AsyncWrite + Unpin + Send>(
+        self,
+        wtr: &mut W,
+    ) -> impl Future<Output = Result<(), crate::Error>> + Send;
+
+    /// Read the payload from the given reader, which yields exactly the bytes
+    /// written by `write_to`.
+    fn read_from<R: AsyncRead + Unpin + Send + 'static>(
+        rdr: R,
+    ) -> impl Future<Output = Result<Self::Target, crate::Error>> + Send;
+}
+
+/// The format in which payloads with Serde support are stored in the cache.
+///
+/// Used by `CachedClient::get_serde_with_codec`; `CachedClient::get_serde`
+/// uses [`MsgPackCodec`]. Payloads stored with one codec can't be read with
+/// another, so a cache entry must always be read with the codec it was
+/// written with (e.g., by giving each codec its own cache bucket).
+pub trait CacheCodec: Send + 'static {
+    /// Serialize a payload to bytes.
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Error>;
+    /// Deserialize a payload from bytes.
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Error>;
+}
+
+/// The default [`CacheCodec`], which stores payloads as MessagePack.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MsgPackCodec;
+
+impl CacheCodec for MsgPackCodec {
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+        Ok(rmp_serde::to_vec(value).map_err(ErrorKind::Encode)?)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Error> {
+        Ok(rmp_serde::from_slice::<T>(bytes).map_err(ErrorKind::Decode)?)
+    }
 }
 
 /// A wrapper type that makes anything with Serde support automatically
-/// implement `Cacheable`.
+/// implement `Cacheable`, stored in the format of the given codec.
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(transparent)]
-pub struct SerdeCacheable<T> {
+pub struct SerdeCacheable<T, C = MsgPackCodec> {
     inner: T,
+    #[serde(skip)]
+    codec: PhantomData<C>,
 }
 
-impl<T: Send + Serialize + DeserializeOwned> Cacheable for SerdeCacheable<T> {
+impl<T: Send + Serialize + DeserializeOwned, C: CacheCodec> Cacheable for SerdeCacheable<T, C> {
     type Target = T;
 
     fn from_aligned_bytes(bytes: AlignedVec) -> Result<T, Error> {
-        Ok(rmp_serde::from_slice::<T>(&bytes).map_err(ErrorKind::Decode)?)
+        C::decode(&bytes)
     }
 
     fn to_bytes(&self) -> Result<Cow<'_, [u8]>, Error> {
-        Ok(Cow::from(
-            rmp_serde::to_vec(&self.inner).map_err(ErrorKind::Encode)?,
-        ))
+        Ok(Cow::from(C::encode(&self.inner)?))
     }
 
     fn into_target(self) -> Self::Target {
@@ -94,6 +208,27 @@ where
     }
 }
 
+/// Raw bytes are cacheable as-is, as used by `CachedClient::get_cached`.
+///
+/// Use this for payloads that are already opaque bytes (e.g., a prebuilt blob), which would
+/// otherwise be encoded again by [`SerdeCacheable`]. The bytes are still stored alongside their
+/// cache policy, so they're revalidated like any other payload.
+impl Cacheable for Vec<u8> {
+    type Target = Vec<u8>;
+
+    fn from_aligned_bytes(bytes: AlignedVec) -> Result<Vec<u8>, Error> {
+        Ok(bytes.into_vec())
+    }
+
+    fn to_bytes(&self) -> Result<Cow<'_, [u8]>, Error> {
+        Ok(Cow::Borrowed(self))
+    }
+
+    fn into_target(self) -> Self::Target {
+        self
+    }
+}
+
 /// Either a cached client error or a (user specified) error from the callback
 #[derive(Debug)]
 pub enum CachedClientError<CallbackError> {
@@ -122,14 +257,38 @@ impl<E: Into<Error>> From<CachedClientError<E>> for Error {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum CacheControl {
     /// Respect the `cache-control` header from the response.
     None,
     /// Apply `max-age=0, must-revalidate` to the request.
     MustRevalidate,
+    /// Always revalidate a stored response with the server before using it, even if it's fresh
+    /// (or `immutable`), as per the `no-cache` request directive.
+    ///
+    /// Unlike [`CacheControl::MustRevalidate`], which defers to responses marked `immutable`, a
+    /// stored response is never used without contacting the server. It's still served from the
+    /// cache if the server confirms it's unchanged, and new responses are still stored.
+    NoCache,
     /// Allow the client to return stale responses.
     AllowStale,
+    /// Never contact the server: serve any readable cached response, regardless of its freshness,
+    /// and fail with [`ErrorKind::Offline`] if there is none.
+    ///
+    /// Unlike [`CacheControl::AllowStale`], a missing (or unreadable) cache entry is an error,
+    /// rather than being fetched. This is never derived from a [`Freshness`]; callers have to opt
+    /// into it explicitly.
+    Offline,
+    /// Revalidate a stored response once it's older than the given age, regardless of the
+    /// freshness lifetime set by the server, as per the `max-age` request directive.
+    ///
+    /// Until then, the stored response is served from the cache, even if the server considers it
+    /// stale (unless it has a `no-cache` directive, or varies on the request). Responses marked
+    /// `immutable` are exempt: as with [`CacheControl::MustRevalidate`], they're used for as long
+    /// as the server considers them fresh, no matter their age.
+    #[serde(skip)]
+    MaxAge(Duration),
 }
 
 impl From<Freshness> for CacheControl {
@@ -142,6 +301,725 @@ impl From<Freshness> for CacheControl {
     }
 }
 
+/// A builder for a [`CachedClient`].
+///
+/// All settings default to the behavior of [`CachedClient::new`].
+#[derive(Debug, Clone)]
+pub struct CachedClientBuilder {
+    client: ClientWithMiddleware,
+    weak_cache: Option<usize>,
+    #[cfg(feature = "encryption")]
+    encryption_key: Option<Arc<CacheEncryptionKey>>,
+    rate_limit_headers: RateLimitHeaders,
+    throttle_threshold: Option<u64>,
+    repair_on_read: bool,
+    verify_immutable: bool,
+    verify_content_length: bool,
+    temp_dir: Option<(PathBuf, PathBuf)>,
+    sidecar_payloads: bool,
+    policy_overrides: CachePolicyOverrides,
+    stale_on_error: Option<Duration>,
+    max_entries: Option<(PathBuf, NonZeroUsize)>,
+    default_max_age: Option<Duration>,
+    correlation_id: Option<String>,
+    audit_log: Option<PathBuf>,
+    compression_level: Option<u32>,
+    retry_after: RetryAfter,
+    not_found_ttl: Option<Duration>,
+    host_headers: HostHeaders,
+    max_concurrency: Option<NonZeroUsize>,
+}
+
+impl CachedClientBuilder {
+    /// Start building a [`CachedClient`] on top of the given client and its middleware.
+    pub fn new(client: ClientWithMiddleware) -> Self {
+        Self {
+            client,
+            weak_cache: None,
+            #[cfg(feature = "encryption")]
+            encryption_key: None,
+            rate_limit_headers: RateLimitHeaders::default(),
+            throttle_threshold: None,
+            repair_on_read: false,
+            verify_immutable: false,
+            verify_content_length: true,
+            temp_dir: None,
+            sidecar_payloads: false,
+            policy_overrides: CachePolicyOverrides::default(),
+            stale_on_error: None,
+            max_entries: None,
+            default_max_age: None,
+            correlation_id: None,
+            audit_log: None,
+            compression_level: Some(DEFAULT_COMPRESSION_LEVEL),
+            retry_after: RetryAfter::default(),
+            not_found_ttl: None,
+            host_headers: HostHeaders::default(),
+            max_concurrency: None,
+        }
+    }
+
+    /// Keep recently used payloads in memory, for use by
+    /// [`CachedClient::get_cacheable_memoized`].
+    ///
+    /// Payloads are held by weak reference, so they're reclaimed once no caller is using them,
+    /// except for the `max_live_entries` most recently used payloads, which are kept alive by the
+    /// client itself.
+    ///
+    /// Disabled by default.
+    #[must_use]
+    pub fn weak_cache(mut self, max_live_entries: usize) -> Self {
+        self.weak_cache = Some(max_live_entries);
+        self
+    }
+
+    /// Encrypt the payloads of cache entries at rest with the given key.
+    ///
+    /// Each payload is bound to the path of its entry. Entries that can't be decrypted with the
+    /// key (e.g., because they were written without encryption, with a different key, or to a
+    /// different path) are treated as cache misses, and overwritten.
+    ///
+    /// Disabled by default.
+    #[cfg(feature = "encryption")]
+    #[must_use]
+    pub fn encryption_key(mut self, encryption_key: CacheEncryptionKey) -> Self {
+        self.encryption_key = Some(Arc::new(encryption_key));
+        self
+    }
+
+    /// Compress the payloads of cache entries at the given zstd level (from 1 to 22), or not at
+    /// all with `None`.
+    ///
+    /// Payloads are only stored compressed if that makes them smaller. Those of [`StreamCacheable`]
+    /// entries (e.g., wheels), and of [`Cacheable`] types that declare themselves
+    /// [`CompressionHint::AlreadyCompressed`], are never compressed. Compressed entries are
+    /// readable whether or not compression is enabled, including incrementally, with
+    /// [`CachedClient::read_stream`].
+    ///
+    /// Defaults to level 3, which favors speed over size.
+    #[must_use]
+    pub fn compression_level(mut self, compression_level: Option<u32>) -> Self {
+        self.compression_level = compression_level;
+        self
+    }
+
+    /// Read rate limits from the given headers, rather than the `X-RateLimit-*` defaults.
+    ///
+    /// See [`CachedClient::rate_limit_state`].
+    #[must_use]
+    pub fn rate_limit_headers(mut self, rate_limit_headers: RateLimitHeaders) -> Self {
+        self.rate_limit_headers = rate_limit_headers;
+        self
+    }
+
+    /// Proactively slow down requests to a host once it reports that `remaining` or fewer
+    /// requests are left in its rate limit window.
+    ///
+    /// The remaining requests are spread evenly until the window resets, rather than being
+    /// sent in a burst that ends in `429 Too Many Requests`. Hosts that don't advertise when their
+    /// window resets are never throttled.
+    ///
+    /// Disabled by default.
+    #[must_use]
+    pub fn throttle_rate_limit(mut self, remaining: u64) -> Self {
+        self.throttle_threshold = Some(remaining);
+        self
+    }
+
+    /// Re-fetch corrupt cache entries encountered by [`CachedClient::read_raw_or_repair`] and
+    /// [`CachedClient::peek_or_repair`], rather than surfacing them as errors.
+    ///
+    /// Disabled by default, since callers may not expect network requests when inspecting the
+    /// cache.
+    #[must_use]
+    pub fn repair_on_read(mut self, repair_on_read: bool) -> Self {
+        self.repair_on_read = repair_on_read;
+        self
+    }
+
+    /// Verify cache entries of [`StreamCacheable`] payloads for `immutable` responses against
+    /// their stored checksum on every read.
+    ///
+    /// Entries that are read into memory are always verified, but streamed payloads (e.g.,
+    /// wheels) are read from the entry as they're used, so verifying them means reading them
+    /// twice. Immutable responses are never revalidated with the server, so without this, an
+    /// entry that's corrupted on disk would be served indefinitely. With it, a corrupt entry is
+    /// removed and fetched anew. Intact entries are still served without any network traffic.
+    ///
+    /// Disabled by default.
+    #[must_use]
+    pub fn verify_immutable(mut self, verify_immutable: bool) -> Self {
+        self.verify_immutable = verify_immutable;
+        self
+    }
+
+    /// Whether to check the body of each new response against its declared `Content-Length`.
+    ///
+    /// A body that's shorter (e.g., because the connection was closed early) or longer than
+    /// declared fails the request with [`ErrorKind::ContentLengthMismatch`] before anything is
+    /// written to the cache, rather than caching a corrupt payload. The check only applies once
+    /// the body has been read to the end, and responses without a declared length (like chunked
+    /// ones) aren't checked.
+    ///
+    /// Enabled by default.
+    #[must_use]
+    pub fn verify_content_length(mut self, verify_content_length: bool) -> Self {
+        self.verify_content_length = verify_content_length;
+        self
+    }
+
+    /// Retry requests that are throttled with a `429 Too Many Requests` or `503 Service
+    /// Unavailable` response carrying a `Retry-After` header up to `max_retries` times, waiting
+    /// as long as the header asks (but no longer than `max_delay`) before each retry.
+    ///
+    /// Requests with a body that can't be replayed (i.e., a stream) are never retried. A value of
+    /// `0` for `max_retries` disables retries, such that throttled responses fail the request.
+    ///
+    /// The retry middleware of the underlying client, if any, shouldn't retry these responses
+    /// itself; the one set up by [`crate::RegistryClientBuilder`] leaves them to this.
+    ///
+    /// Defaults to a single retry, waiting at most a minute.
+    #[must_use]
+    pub fn retry_after(mut self, max_retries: u32, max_delay: Duration) -> Self {
+        self.retry_after = RetryAfter {
+            max_retries,
+            max_delay,
+        };
+        self
+    }
+
+    /// Remember `404 Not Found` and `410 Gone` responses for the given duration, during which
+    /// requests for the same cache entry fail with [`ErrorKind::NotFoundCached`] rather than going
+    /// to the network.
+    ///
+    /// The remembered response is ignored by requests with [`CacheControl::MustRevalidate`] or
+    /// [`CacheControl::NoCache`], and replaced by the response to the next request once it
+    /// expires. This only applies to [`Cacheable`] payloads, as requested through
+    /// [`CachedClient::get_cacheable`] (and the methods built on it) and
+    /// [`CachedClient::get_cacheable_probed`].
+    ///
+    /// Disabled by default.
+    #[must_use]
+    pub fn cache_not_found(mut self, ttl: Duration) -> Self {
+        self.not_found_ttl = Some(ttl);
+        self
+    }
+
+    /// Stage the temporary files used to atomically write cache entries in the given directory,
+    /// rather than alongside the entries themselves.
+    ///
+    /// The directory must be on the same filesystem as the cache entries, which are written
+    /// under `cache_root`, since entries are moved into place with an atomic rename. This is
+    /// checked once, when the client is built; if it isn't, temporary files are written
+    /// alongside the entries as usual.
+    #[must_use]
+    pub fn temp_dir(
+        mut self,
+        temp_dir: impl Into<PathBuf>,
+        cache_root: impl Into<PathBuf>,
+    ) -> Self {
+        self.temp_dir = Some((temp_dir.into(), cache_root.into()));
+        self
+    }
+
+    /// Store the payloads written by [`CachedClient::get_stream_cacheable`] in a sidecar file
+    /// next to the cache entry (at the path of the entry with `.data` appended), rather than in
+    /// the entry itself.
+    ///
+    /// The entry then only holds the cache policy and a reference to the sidecar, such that
+    /// revalidating the entry doesn't copy its payload. A missing or truncated sidecar is
+    /// treated as a cache miss. Entries are read correctly regardless of this setting, but
+    /// entries with a sidecar can only be read by [`CachedClient::get_stream_cacheable`].
+    ///
+    /// Ignored if encryption is enabled, since sidecars aren't encrypted. Disabled by default.
+    #[must_use]
+    pub fn sidecar_payloads(mut self, sidecar_payloads: bool) -> Self {
+        self.sidecar_payloads = sidecar_payloads;
+        self
+    }
+
+    /// Override the [`CacheControl`] requested by callers for URLs matching the patterns in the
+    /// given policy file, as loaded by [`CachePolicyOverrides::from_path`].
+    ///
+    /// The overrides are consulted whenever a cached response is considered for a request.
+    ///
+    /// None by default.
+    #[must_use]
+    pub fn policy_overrides(mut self, policy_overrides: CachePolicyOverrides) -> Self {
+        self.policy_overrides = policy_overrides;
+        self
+    }
+
+    /// Serve a stale cached response when revalidating it fails with a transient error (i.e., a
+    /// network error, a server error, or `429 Too Many Requests`), rather than failing the
+    /// request.
+    ///
+    /// Only successful (`2xx`) cached responses that are no older than `max_age` are served this
+    /// way. Unlike the `stale-if-error` directive, this is a client-side policy that applies
+    /// regardless of what the server allows.
+    ///
+    /// Disabled by default.
+    #[must_use]
+    pub fn stale_on_error(mut self, max_age: Duration) -> Self {
+        self.stale_on_error = Some(max_age);
+        self
+    }
+
+    /// Cap the number of cache entries under `dir` at `max_entries`, deleting the least recently
+    /// written entries to make room for new ones.
+    ///
+    /// This bounds the number of files (i.e., inodes) in the cache, which some filesystems handle
+    /// poorly past a certain count, regardless of their size. The entries under `dir` are counted
+    /// on the first write, and the count is then maintained in memory, shared by the client and
+    /// its clones. Once the cap is reached, `dir` is scanned again before deleting any entries,
+    /// such that entries deleted by other processes are accounted for, and entries are deleted
+    /// until the count is 90% of the cap, such that `dir` isn't scanned again on every write.
+    ///
+    /// Entries are ordered by when they were last written, which includes revalidation (and
+    /// [`CachedClient::touch`]), since access times aren't tracked reliably across filesystems.
+    /// Entries in use by requests are
+    /// never deleted, so the cap may be exceeded temporarily while the client is busy. A sidecar
+    /// (see [`CachedClientBuilder::sidecar_payloads`]) counts as part of its entry.
+    ///
+    /// Unlimited by default.
+    #[must_use]
+    pub fn max_entries(mut self, dir: impl Into<PathBuf>, max_entries: NonZeroUsize) -> Self {
+        self.max_entries = Some((dir.into(), max_entries));
+        self
+    }
+
+    /// Cache responses that carry no caching information at all (i.e., neither `Cache-Control`
+    /// freshness directives, nor `Expires`, nor an `ETag` or `Last-Modified` validator) for the
+    /// given duration, as if they had a `max-age` directive.
+    ///
+    /// Such responses are otherwise stale as soon as they're received, which defeats caching
+    /// against simple indexes (like many private ones) that omit caching headers entirely. The
+    /// duration is stored in the cache entry, so it also applies to entries read back later.
+    ///
+    /// Disabled by default.
+    #[must_use]
+    pub fn default_max_age(mut self, max_age: Duration) -> Self {
+        self.default_max_age = Some(max_age);
+        self
+    }
+
+    /// Identify the requests made through the client with the given correlation ID, as sent in
+    /// the `X-Request-Id` header and recorded in the tracing spans of each request.
+    ///
+    /// This allows correlating the requests seen by an index (or a proxy in front of it) with a
+    /// single invocation. To identify a single operation instead, use
+    /// [`CachedClient::with_correlation_id`], or set the header on the request itself.
+    ///
+    /// Defaults to a random UUID, generated when the client is built.
+    #[must_use]
+    pub fn correlation_id(mut self, correlation_id: impl Into<String>) -> Self {
+        self.correlation_id = Some(correlation_id.into());
+        self
+    }
+
+    /// Append a record of each response fetched or revalidated, and each cache entry evicted, to
+    /// the file at the given path, as a line of JSON.
+    ///
+    /// Unlike the tracing output, the log is meant to be kept as a trail of what was downloaded
+    /// and when, e.g., for compliance. Each line records the `operation` (`fetch`, `revalidate`,
+    /// or `evict`), its `timestamp`, and, where known, the `url` (with any credentials removed),
+    /// the HTTP `status`, the number of `bytes`, the `sha256` of the body as received, and the
+    /// `path` of the evicted entry. A fetch is recorded once its body has been read (or
+    /// dropped); if the body wasn't read to the end, it's recorded without a checksum.
+    ///
+    /// The file is created if it doesn't exist. Failing to write to it is reported as a warning,
+    /// but doesn't fail the request. Disabled by default.
+    #[must_use]
+    pub fn audit_log(mut self, path: impl Into<PathBuf>) -> Self {
+        self.audit_log = Some(path.into());
+        self
+    }
+
+    /// Send the given headers with every request to the matching host and port, e.g., to
+    /// authenticate with a private index via an `Authorization` header.
+    ///
+    /// The headers are only added to requests for the exact host and port (not, e.g., to their
+    /// subdomains), and only where the request doesn't set them itself. They're added as each
+    /// request is sent, including revalidation requests, but aren't part of the request as far as
+    /// the cache is concerned: they're never written to cache entries, and responses that vary on
+    /// them are matched as if they were absent.
+    #[must_use]
+    pub fn host_headers(mut self, host_headers: HashMap<HostPort, http::HeaderMap>) -> Self {
+        self.host_headers = HostHeaders::new(host_headers);
+        self
+    }
+
+    /// Send at most `max_concurrency` requests at a time, across the client and its clones.
+    ///
+    /// Only sending a request (including any revalidation request, and each retry) waits for a
+    /// slot, which is held until the body of the response has been read or dropped; reading and
+    /// writing cache entries are never limited, such that fresh cache entries are served without
+    /// delay.
+    ///
+    /// Unlimited by default.
+    #[must_use]
+    pub fn max_concurrency(mut self, max_concurrency: NonZeroUsize) -> Self {
+        self.max_concurrency = Some(max_concurrency);
+        self
+    }
+
+    pub fn build(self) -> CachedClient {
+        CachedClient {
+            client: self.client,
+            weak_cache: self
+                .weak_cache
+                .map(|max_live_entries| Arc::new(WeakCache::new(max_live_entries))),
+            #[cfg(feature = "encryption")]
+            encryption_key: self.encryption_key,
+            rate_limiter: Arc::new(RateLimiter::new(
+                self.rate_limit_headers,
+                self.throttle_threshold,
+            )),
+            repair_on_read: self.repair_on_read,
+            verify_immutable: self.verify_immutable,
+            verify_content_length: self.verify_content_length,
+            temp_dir: self.temp_dir.and_then(|(temp_dir, cache_root)| {
+                (atomic_temp_dir(&cache_root, &temp_dir) == temp_dir).then_some(temp_dir)
+            }),
+            sidecar_payloads: self.sidecar_payloads,
+            policy_overrides: Arc::new(self.policy_overrides),
+            stale_on_error: self.stale_on_error,
+            entry_limit: self
+                .max_entries
+                .map(|(dir, max_entries)| Arc::new(EntryLimit::new(dir, max_entries))),
+            default_max_age: self.default_max_age,
+            correlation_id: self
+                .correlation_id
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+                .into(),
+            audit_log: self.audit_log.map(|path| Arc::new(AuditLog::new(path))),
+            revalidating: Arc::default(),
+            single_flight: Arc::default(),
+            compression_level: self.compression_level,
+            retry_after: self.retry_after,
+            not_found_ttl: self.not_found_ttl,
+            host_headers: Arc::new(self.host_headers),
+            concurrency_limit: self
+                .max_concurrency
+                .map(|max_concurrency| Arc::new(Semaphore::new(max_concurrency.get()))),
+            mirror: None,
+            active_entries: Arc::default(),
+            stats: Arc::default(),
+        }
+    }
+}
+
+/// A permit to send a request, as per [`CachedClientBuilder::max_concurrency`], held by the
+/// body of its response.
+///
+/// The permit is released once the body has been read to the end or failed to be read, or when
+/// the body is dropped.
+struct ConcurrencyPermit(Option<OwnedSemaphorePermit>);
+
+impl BodyObserver for ConcurrencyPermit {
+    fn chunk(&mut self, _chunk: &[u8]) {}
+
+    fn end(&mut self, _complete: bool) {
+        self.0.take();
+    }
+}
+
+/// How to retry throttled requests, as per [`CachedClientBuilder::retry_after`].
+#[derive(Debug, Clone, Copy)]
+struct RetryAfter {
+    max_retries: u32,
+    max_delay: Duration,
+}
+
+impl Default for RetryAfter {
+    fn default() -> Self {
+        Self {
+            max_retries: 1,
+            max_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+/// A request to warm the cache with, as per [`CachedClient::warm`].
+#[derive(Debug)]
+pub struct WarmRequest {
+    /// The request to make.
+    pub request: Request,
+    /// The cache entry to store the response in.
+    pub cache_entry: CacheEntry,
+    /// The priority of the request, such that requests with higher priorities are started first.
+    pub priority: i32,
+}
+
+impl WarmRequest {
+    /// Create a [`WarmRequest`] with the default priority, zero.
+    pub fn new(request: Request, cache_entry: CacheEntry) -> Self {
+        Self {
+            request,
+            cache_entry,
+            priority: 0,
+        }
+    }
+
+    /// Set the priority of the request.
+    #[must_use]
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+/// A snapshot of the freshness of a cache entry, as computed from its stored cache policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheStatus {
+    /// The current age of the cached response.
+    ///
+    /// This includes any `Age` reported by upstream caches (e.g., a CDN), and not just the time
+    /// since the response was received.
+    pub age: Duration,
+    /// How long the cached response is considered fresh for, e.g., as set by `max-age`.
+    pub freshness_lifetime: Duration,
+    /// The filename advertised by the `Content-Disposition` header of the cached response, if
+    /// any.
+    pub filename: Option<String>,
+}
+
+impl CacheStatus {
+    /// Returns how much longer the cached response will be considered fresh.
+    pub fn time_to_live(&self) -> Duration {
+        self.freshness_lifetime.saturating_sub(self.age)
+    }
+
+    /// Returns `true` if the cached response is fresh.
+    pub fn is_fresh(&self) -> bool {
+        self.age <= self.freshness_lifetime
+    }
+}
+
+/// Metadata about a cache entry written by [`CachedClient`], as passed to the predicate of
+/// [`CachedClient::invalidate_where`].
+#[derive(Debug, Clone)]
+pub struct CacheEntryMeta {
+    /// The path to the cache entry.
+    pub path: PathBuf,
+    /// The size of the cache entry on disk, in bytes.
+    pub size: u64,
+    /// The freshness of the cached response.
+    pub freshness: CacheStatus,
+    /// Whether the cached response was marked as `immutable`.
+    pub immutable: bool,
+    /// The HTTP status code of the cached response.
+    pub status_code: u16,
+    /// The URL the cached response was fetched from, if it can be parsed.
+    pub url: Option<Url>,
+    /// The host the cached response was fetched from, if any.
+    pub host: Option<String>,
+    /// The package the cached response belongs to, if it can be recovered.
+    ///
+    /// Package names aren't stored in cache entries, so they're recovered from the response: from
+    /// the filename advertised by its `Content-Disposition` header or the last segment of its URL,
+    /// parsed as a wheel or source distribution filename (optionally with a `.metadata` suffix,
+    /// as for PEP 658 metadata), or, for Simple API pages, from the segment following `simple`
+    /// in its URL (as in `/simple/flask/`).
+    pub package_name: Option<PackageName>,
+}
+
+/// The cache entries that an operation would delete, as returned by
+/// [`CachedClient::invalidate_where_preview`] and its siblings.
+#[derive(Debug, Clone, Default)]
+pub struct EvictionPreview {
+    /// The entries that would be deleted.
+    pub entries: Vec<CacheEntryMeta>,
+}
+
+impl EvictionPreview {
+    /// Returns the total size of the entries that would be deleted, in bytes, including their
+    /// sidecars.
+    pub fn bytes(&self) -> u64 {
+        self.entries.iter().map(|entry| entry.size).sum()
+    }
+}
+
+/// What [`CachedClient::gc`] deleted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcReport {
+    /// The number of broken cache entries deleted.
+    pub removed_entries: usize,
+    /// The number of abandoned temporary files deleted.
+    pub removed_temp_files: usize,
+    /// The total size of the deleted files, in bytes, including the sidecars of deleted entries.
+    pub bytes_reclaimed: u64,
+}
+
+impl CacheEntryMeta {
+    /// Read the metadata of the cache entry at the given path.
+    ///
+    /// Returns `None` if the file can't be read as a cache entry written by a [`CachedClient`].
+    pub(crate) fn from_path(path: &Path, now: SystemTime) -> Result<Option<Self>, Error> {
+        let Ok(cached) = DataWithCachePolicy::from_path_sync(path) else {
+            return Ok(None);
+        };
+        // Include the payload stored in the sidecar, if any.
+        let sidecar_len = SidecarRef::decode(&cached.data).map_or(0, |sidecar| sidecar.len);
+        let url = Url::parse(cached.cache_policy.url()).ok();
+        Ok(Some(Self {
+            path: path.to_path_buf(),
+            size: fs_err::metadata(path).map_err(ErrorKind::Io)?.len() + sidecar_len,
+            freshness: CacheStatus {
+                age: cached.cache_policy.age(now),
+                freshness_lifetime: cached.cache_policy.freshness_lifetime(),
+                filename: cached.cache_policy.filename().map(ToString::to_string),
+            },
+            immutable: cached.cache_policy.is_immutable(),
+            status_code: cached.cache_policy.status(),
+            host: url
+                .as_ref()
+                .and_then(|url| url.host_str().map(ToString::to_string)),
+            url,
+            package_name: Self::recover_package_name(&cached.cache_policy),
+        }))
+    }
+
+    /// Recover the name of the package a cached response belongs to.
+    ///
+    /// See [`CacheEntryMeta::package_name`].
+    fn recover_package_name(cache_policy: &OwnedArchive<CachePolicy>) -> Option<PackageName> {
+        let from_filename = |filename: &str| {
+            let filename = filename.strip_suffix(".metadata").unwrap_or(filename);
+            DistFilename::try_from_normalized_filename(filename)
+                .map(|filename| filename.name().clone())
+        };
+        if let Some(name) = cache_policy.filename().and_then(from_filename) {
+            return Some(name);
+        }
+        let url = Url::parse(cache_policy.url()).ok()?;
+        let segments: Vec<&str> = url
+            .path_segments()?
+            .filter(|segment| !segment.is_empty())
+            .collect();
+        let (last, rest) = segments.split_last()?;
+        from_filename(last).or_else(|| {
+            if rest.last() == Some(&"simple") {
+                PackageName::from_str(last).ok()
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// What [`CachedClient`] would do to serve a request, as determined by [`CachedClient::plan`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachePlan {
+    /// The request would be served from the cache, without any network traffic.
+    FreshCache,
+    /// The cached response is stale, so it would be revalidated with the server, for the given
+    /// reason.
+    WouldRevalidate(StaleReason),
+    /// There is no usable cached response, so the request would be sent to the server.
+    WouldFetch,
+    /// There is no usable cached response, and the request wouldn't be sent to the server, as per
+    /// [`CacheControl::Offline`], so it would fail.
+    Unavailable,
+}
+
+/// How [`CachedClient::get_fresh_or_refresh`] obtained a payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheOutcome {
+    /// The cache entry was fresh, so no request was sent.
+    Fresh,
+    /// The cache entry was stale, but the server confirmed it was still valid.
+    Revalidated,
+    /// The cache entry was missing or outdated, so the response was fetched anew.
+    Fetched,
+}
+
+impl CacheOutcome {
+    /// Record the outcome as the `cache_outcome` field of the current span, as declared by the
+    /// request methods of [`CachedClient`], such that hit ratios can be aggregated from logs.
+    fn record(self) {
+        let outcome = match self {
+            Self::Fresh => "fresh",
+            Self::Revalidated => "not_modified",
+            Self::Fetched => "miss",
+        };
+        tracing::Span::current().record("cache_outcome", outcome);
+    }
+}
+
+/// Whether a cache entry changed when it was revalidated by [`CachedClient::revalidate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RevalidationOutcome {
+    /// The server confirmed that the cached response is still valid, so the entry was refreshed
+    /// in place.
+    NotModified,
+    /// The server returned new content (or there was no entry), which was written to the entry.
+    Updated,
+}
+
+/// A payload returned by [`CachedClient::get_fresh_or_refresh`].
+#[derive(Debug, Clone)]
+pub struct CacheResponse<T> {
+    /// The payload, as produced by the `Cacheable` implementation.
+    pub payload: T,
+    /// How the payload was obtained.
+    pub outcome: CacheOutcome,
+    /// The filename advertised by the `Content-Disposition` header of the response, if any.
+    ///
+    /// For responses served from the cache, this is the filename stored alongside the entry
+    /// when it was fetched.
+    pub filename: Option<String>,
+    /// The URL the response was served from, after following any redirects.
+    ///
+    /// This is `None` if the response couldn't be cached.
+    pub url: Option<Url>,
+}
+
+/// How a payload returned by [`CachedClient::get_cacheable_with_info`] was obtained.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheInfo {
+    /// Whether the payload was served from the cache, revalidated, or fetched anew.
+    pub outcome: CacheOutcome,
+    /// The age of the response as per its cache policy, i.e., how long ago it was fetched or
+    /// last revalidated (plus any `Age` reported by the server).
+    ///
+    /// This is `None` if the response couldn't be cached.
+    pub age: Option<Duration>,
+}
+
+/// A payload obtained by [`CachedClient`], along with the cache policy stored alongside it (if
+/// the response was storable), how it was obtained, and its `Content-Disposition` filename.
+struct CachedPayload<T> {
+    payload: T,
+    cache_policy: Option<OwnedArchive<CachePolicy>>,
+    outcome: CacheOutcome,
+    filename: Option<String>,
+}
+
+impl<T> CachedPayload<T> {
+    fn info(&self) -> CacheInfo {
+        CacheInfo {
+            outcome: self.outcome,
+            age: self
+                .cache_policy
+                .as_ref()
+                .map(|cache_policy| cache_policy.age(SystemTime::now())),
+        }
+    }
+
+    fn into_response(self) -> CacheResponse<T> {
+        CacheResponse {
+            url: self
+                .cache_policy
+                .as_ref()
+                .and_then(|cache_policy| Url::parse(cache_policy.final_url()).ok()),
+            payload: self.payload,
+            outcome: self.outcome,
+            filename: self.filename,
+        }
+    }
+}
+
 /// Custom caching layer over [`reqwest::Client`].
 ///
 /// The implementation takes inspiration from the `http-cache` crate, but adds support for running
@@ -150,24 +1028,125 @@ impl From<Freshness> for CacheControl {
 /// to read a single file from a remote zip using range requests (so we don't have to download the
 /// entire file). We send a HEAD request in the caching layer to check if the remote file has
 /// changed (and if range requests are supported), and in the callback we make the actual range
-/// requests if required.
+/// requests if required. What the callback reads (e.g., the parsed central directory of the zip)
+/// is cached under the policy of the HEAD response, so while that's fresh, the HEAD request is
+/// skipped entirely. For files marked `immutable` (as wheels on PyPI are), this holds even when
+/// revalidation is requested via [`CacheControl::MustRevalidate`]; other files are revalidated.
 ///
 /// Unlike `http-cache`, all outputs must be serializable/deserializable in some way, by
 /// implementing the `Cacheable` trait.
 ///
 /// Again unlike `http-cache`, the caller gets full control over the cache key with the assumption
 /// that it's a file.
+///
+/// Use [`CachedClientBuilder`] to configure the client beyond the defaults.
+///
+/// Cloning a client is cheap, and clones share all of its in-memory state: the in-memory payloads
+/// of [`CachedClient::get_cacheable_memoized`], the rate limits advertised by each host, and the
+/// cache entries in use by requests (which an evictor spawned from any of the clones respects),
+/// the [`CachedClientBuilder::max_concurrency`] limit, and the [`CachedClient::stats`].
+/// As such, prefer cloning a client over building a second one for the same cache.
 #[derive(Debug, Clone)]
-pub struct CachedClient(ClientWithMiddleware);
+pub struct CachedClient {
+    /// The underlying client, including any middleware (e.g., the retry strategy).
+    client: ClientWithMiddleware,
+    /// An in-memory cache of recently used payloads, if enabled.
+    weak_cache: Option<Arc<WeakCache>>,
+    /// The key used to encrypt cached payloads at rest, if enabled.
+    #[cfg(feature = "encryption")]
+    encryption_key: Option<Arc<CacheEncryptionKey>>,
+    /// The rate limits advertised by each host.
+    rate_limiter: Arc<RateLimiter>,
+    /// Whether to re-fetch corrupt cache entries encountered while inspecting the cache.
+    repair_on_read: bool,
+    /// Whether to verify the checksum of cache entries for immutable responses on every read.
+    verify_immutable: bool,
+    /// Whether to check the body of new responses against their declared `Content-Length`.
+    verify_content_length: bool,
+    /// The directory in which to stage temporary files when writing cache entries, if not
+    /// alongside the entries themselves. It's known to be on the same filesystem as the cache.
+    temp_dir: Option<PathBuf>,
+    /// Whether to store the payloads of [`StreamCacheable`] entries in sidecar files.
+    sidecar_payloads: bool,
+    /// The cache controls to use for requests to specific URLs, regardless of the caller.
+    policy_overrides: Arc<CachePolicyOverrides>,
+    /// The maximum age of a successful cached response to serve when revalidating it fails with
+    /// a transient error, if enabled.
+    stale_on_error: Option<Duration>,
+    /// The cap on the number of cache entries, if any.
+    entry_limit: Option<Arc<EntryLimit>>,
+    /// The freshness lifetime of responses without any caching headers, if any.
+    default_max_age: Option<Duration>,
+    /// The correlation ID sent with requests that don't carry their own.
+    correlation_id: Arc<str>,
+    /// The log of fetches, revalidations, and evictions, if enabled.
+    audit_log: Option<Arc<AuditLog>>,
+    /// The cache entries being revalidated in the background, as per `stale-while-revalidate`.
+    revalidating: Arc<Mutex<FxHashSet<PathBuf>>>,
+    /// The requests in flight, which concurrent requests for the same cache entry wait on.
+    single_flight: Arc<SingleFlight>,
+    /// The zstd level at which to compress payloads, if enabled.
+    compression_level: Option<u32>,
+    /// How to retry requests that are throttled with a `Retry-After` header.
+    retry_after: RetryAfter,
+    /// How long to remember "not found" responses for, if at all.
+    not_found_ttl: Option<Duration>,
+    /// The headers to send with requests to each host.
+    host_headers: Arc<HostHeaders>,
+    /// The permits for sending requests, if the number of concurrent requests is limited.
+    concurrency_limit: Option<Arc<Semaphore>>,
+    /// The mirror to send requests to, as per [`CachedClient::get_cacheable_with_fallback`].
+    mirror: Option<Arc<MirrorRewrite>>,
+    /// The cache entries in use by requests, which the evictor must not delete.
+    pub(crate) active_entries: Arc<ActiveEntries>,
+    /// How the requests made through the client (and its clones) were served.
+    stats: Arc<StatsCounters>,
+}
 
 impl CachedClient {
+    /// Create a [`CachedClient`] with the default settings.
+    ///
+    /// Equivalent to `CachedClientBuilder::new(client).build()`.
     pub fn new(client: ClientWithMiddleware) -> Self {
-        Self(client)
+        CachedClientBuilder::new(client).build()
     }
 
     /// The middleware is the retry strategy
     pub fn uncached(&self) -> ClientWithMiddleware {
-        self.0.clone()
+        self.client.clone()
+    }
+
+    /// Returns the correlation ID sent with requests made through this client, as per
+    /// [`CachedClientBuilder::correlation_id`].
+    pub fn correlation_id(&self) -> &str {
+        &self.correlation_id
+    }
+
+    /// Returns a clone of this client that identifies its requests with the given correlation ID,
+    /// e.g., to tell apart the requests made for each of several concurrent operations.
+    ///
+    /// As with any clone, the new client shares all of its in-memory state with this one.
+    #[must_use]
+    pub fn with_correlation_id(&self, correlation_id: impl Into<String>) -> Self {
+        Self {
+            correlation_id: correlation_id.into().into(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a snapshot of how the requests made through this client (and its clones) were
+    /// served so far.
+    pub fn stats(&self) -> CacheStats {
+        self.stats.snapshot()
+    }
+
+    /// Log a one-line summary of the [`CachedClient::stats`] at the info level, e.g., at the end
+    /// of a command.
+    ///
+    /// The summary is a series of `key=value` pairs (see [`CacheStats`]), such that it can be
+    /// parsed by scripts.
+    pub fn log_summary(&self) {
+        info!("Cache summary: {}", self.stats());
     }
 
     /// Make a cached request with a custom response transformation
@@ -177,6 +1156,9 @@ impl CachedClient {
     /// on the remote), the response is passed through `response_callback` and
     /// only the result is cached and returned. The `response_callback` is
     /// allowed to make subsequent requests, e.g. through the uncached client.
+    ///
+    /// Payloads are cached as MessagePack; see [`CachedClient::get_serde_with_codec`] to use
+    /// another format.
     #[instrument(skip_all)]
     pub async fn get_serde<
         Payload: Serialize + DeserializeOwned + Send + 'static,
@@ -194,16 +1176,154 @@ impl CachedClient {
         Callback: FnOnce(Response) -> CallbackReturn + Send,
         CallbackReturn: Future<Output = Result<Payload, CallBackError>> + Send,
     {
-        let payload = self
-            .get_cacheable(req, cache_entry, cache_control, move |resp| async {
-                let payload = response_callback(resp).await?;
-                Ok(SerdeCacheable { inner: payload })
-            })
-            .await?;
-        Ok(payload)
+        self.get_serde_with_codec::<MsgPackCodec, _, _, _, _>(
+            req,
+            cache_entry,
+            cache_control,
+            response_callback,
+        )
+        .await
     }
 
-    /// Make a cached request with a custom response transformation while using
+    /// Make a cached `GET` request, returning the body of the response as-is.
+    ///
+    /// This is a shorthand for [`CachedClient::get_cacheable`] for callers that need the raw
+    /// bytes, and so have no use for a `response_callback`. The body of a new response is read
+    /// chunk by chunk into a buffer sized by its `Content-Length` (where known), rather than
+    /// being buffered by `reqwest` and then copied.
+    #[instrument(skip_all)]
+    pub async fn get_cached(
+        &self,
+        req: Request,
+        cache_entry: &CacheEntry,
+        cache_control: CacheControl,
+    ) -> Result<Vec<u8>, Error> {
+        let body = self
+            .get_cacheable(req, cache_entry, cache_control, |mut response| async move {
+                // Don't trust the declared length with an arbitrarily large allocation.
+                let capacity = response
+                    .content_length()
+                    .and_then(|len| usize::try_from(len).ok())
+                    .unwrap_or_default()
+                    .min(64 * 1024 * 1024);
+                let mut body = Vec::with_capacity(capacity);
+                while let Some(chunk) = response.chunk().await.map_err(ErrorKind::RequestError)? {
+                    body.extend_from_slice(&chunk);
+                }
+                Ok::<_, Error>(body)
+            })
+            .await?;
+        Ok(body)
+    }
+
+    /// Make a cached request like [`CachedClient::get_serde`], but cache the payload in the
+    /// format of the given [`CacheCodec`].
+    ///
+    /// A cache entry that was written with another codec fails to decode, so each codec should be
+    /// given its own cache bucket.
+    #[instrument(skip_all)]
+    pub async fn get_serde_with_codec<
+        Codec: CacheCodec,
+        Payload: Serialize + DeserializeOwned + Send + 'static,
+        CallBackError,
+        Callback,
+        CallbackReturn,
+    >(
+        &self,
+        req: Request,
+        cache_entry: &CacheEntry,
+        cache_control: CacheControl,
+        response_callback: Callback,
+    ) -> Result<Payload, CachedClientError<CallBackError>>
+    where
+        Callback: FnOnce(Response) -> CallbackReturn + Send,
+        CallbackReturn: Future<Output = Result<Payload, CallBackError>> + Send,
+    {
+        let payload = self
+            .get_cacheable(req, cache_entry, cache_control, move |resp| async {
+                let payload = response_callback(resp).await?;
+                Ok(SerdeCacheable::<_, Codec> {
+                    inner: payload,
+                    codec: PhantomData,
+                })
+            })
+            .await?;
+        Ok(payload)
+    }
+
+    /// Make a cached request like [`CachedClient::get_serde`], but verify that the payload
+    /// belongs to the `expected` package before it's cached or returned.
+    ///
+    /// Only the caller knows how to find the package name of a given payload (e.g., from the
+    /// `Name` field of its metadata), so it's extracted with `name_of`. If the name doesn't match,
+    /// this fails with [`ErrorKind::NameMismatch`] and the payload isn't cached. A cached payload
+    /// that doesn't match is removed from the cache, such that it's fetched anew next time.
+    ///
+    /// This guards against misconfigured mirrors that serve the content of one package in place of
+    /// another, which would otherwise be cached under the wrong package.
+    #[instrument(skip_all)]
+    pub async fn get_serde_validated<
+        Payload: Serialize + DeserializeOwned + Send + 'static,
+        CallBackError,
+        Callback,
+        CallbackReturn,
+    >(
+        &self,
+        req: Request,
+        cache_entry: &CacheEntry,
+        cache_control: CacheControl,
+        expected: &PackageName,
+        name_of: impl Fn(&Payload) -> PackageName + Send + Sync,
+        response_callback: Callback,
+    ) -> Result<Payload, CachedClientError<CallBackError>>
+    where
+        Callback: FnOnce(Response) -> CallbackReturn + Send,
+        CallbackReturn: Future<Output = Result<Payload, CallBackError>> + Send,
+    {
+        let validate = |payload: &Payload| -> Result<(), Error> {
+            let found = name_of(payload);
+            if found == *expected {
+                Ok(())
+            } else {
+                Err(ErrorKind::NameMismatch {
+                    given: expected.clone(),
+                    metadata: found,
+                }
+                .into())
+            }
+        };
+        let result = self
+            .get_serde(req, cache_entry, cache_control, |response| async {
+                let payload = response_callback(response)
+                    .await
+                    .map_err(CachedClientError::Callback)?;
+                validate(&payload)?;
+                Ok::<_, CachedClientError<CallBackError>>(payload)
+            })
+            .await;
+        let payload = match result {
+            Ok(payload) => payload,
+            Err(
+                CachedClientError::Client(err)
+                | CachedClientError::Callback(CachedClientError::Client(err)),
+            ) => return Err(CachedClientError::Client(err)),
+            Err(CachedClientError::Callback(CachedClientError::Callback(err))) => {
+                return Err(CachedClientError::Callback(err))
+            }
+        };
+        // The payload may have been read from a cache entry written before it was validated.
+        if let Err(err) = validate(&payload) {
+            warn!(
+                "Removing cache entry for the wrong package at {}: {err}",
+                cache_entry.path().display()
+            );
+            let _ = fs_err::tokio::remove_file(cache_entry.path()).await;
+            return Err(err.into());
+        }
+        Ok(payload)
+    }
+
+    /// Make a cached request with a custom response transformation while using
     /// the `Cacheable` trait to (de)serialize cached responses.
     ///
     /// The purpose of this routine is the use of `Cacheable`. Namely, it
@@ -215,7 +1335,14 @@ impl CachedClient {
     /// on the remote), the response is passed through `response_callback` and
     /// only the result is cached and returned. The `response_callback` is
     /// allowed to make subsequent requests, e.g. through the uncached client.
-    #[instrument(skip_all)]
+    ///
+    /// A stale response within its `stale-while-revalidate` window is returned
+    /// right away, and revalidated in the background.
+    ///
+    /// Requests with a streaming body (e.g., uploads) can't be replayed, so
+    /// they bypass the cache: the request is sent as-is, and the response is
+    /// passed through `response_callback` without being cached.
+    #[instrument(skip_all, fields(cache_outcome = tracing::field::Empty))]
     pub async fn get_cacheable<Payload: Cacheable, CallBackError, Callback, CallbackReturn>(
         &self,
         req: Request,
@@ -227,436 +1354,6162 @@ impl CachedClient {
         Callback: FnOnce(Response) -> CallbackReturn,
         CallbackReturn: Future<Output = Result<Payload, CallBackError>> + Send,
     {
-        let cached_response = match Self::read_cache(cache_entry).await {
-            Some(cached) => self.send_cached(req, cache_control, cached).boxed().await?,
-            None => {
-                debug!("No cache entry for: {}", req.url());
-                self.fresh_request(req).await?
-            }
+        let cached = self
+            .get_cacheable_inner(req, cache_entry, cache_control, response_callback)
+            .await?;
+        Ok(cached.payload)
+    }
+
+    /// Make a cached request like [`CachedClient::get_cacheable`], but also return how the
+    /// payload was obtained, e.g., for diagnostics.
+    #[instrument(skip_all, fields(cache_outcome = tracing::field::Empty))]
+    pub async fn get_cacheable_with_info<
+        Payload: Cacheable,
+        CallBackError,
+        Callback,
+        CallbackReturn,
+    >(
+        &self,
+        req: Request,
+        cache_entry: &CacheEntry,
+        cache_control: CacheControl,
+        response_callback: Callback,
+    ) -> Result<(Payload::Target, CacheInfo), CachedClientError<CallBackError>>
+    where
+        Callback: FnOnce(Response) -> CallbackReturn,
+        CallbackReturn: Future<Output = Result<Payload, CallBackError>> + Send,
+    {
+        let cached = self
+            .get_cacheable_inner(req, cache_entry, cache_control, response_callback)
+            .await?;
+        let info = cached.info();
+        Ok((cached.payload, info))
+    }
+
+    /// Make a cached request like [`CachedClient::get_cacheable`], but send it to each of the
+    /// given mirrors in turn until one of them answers, e.g., to fall back to the upstream index
+    /// when a mirror is down.
+    ///
+    /// Only the origin (i.e., the scheme, host, and port) of each mirror is used: the request,
+    /// like any revalidation request, is sent to the same path on the mirror. The cache entry and
+    /// its cache policy remain those of the request's own URL, regardless of which mirror
+    /// answered, such that an entry written with the response of one mirror can be revalidated
+    /// with another.
+    ///
+    /// A mirror is skipped if it can't be reached, or answers with a server error (`5xx`); any
+    /// other response, including a `404 Not Found`, is authoritative. If all mirrors fail, the
+    /// error of the last one is returned. Credentials on the request aren't sent to mirrors on
+    /// another origin; use [`CachedClientBuilder::host_headers`] to authenticate with them.
+    #[instrument(skip_all, fields(cache_outcome = tracing::field::Empty))]
+    pub async fn get_cacheable_with_fallback<
+        Payload: Cacheable,
+        CallBackError,
+        Callback,
+        CallbackReturn,
+    >(
+        &self,
+        req: Request,
+        mirrors: &[Url],
+        cache_entry: &CacheEntry,
+        cache_control: CacheControl,
+        response_callback: Callback,
+    ) -> Result<Payload::Target, CachedClientError<CallBackError>>
+    where
+        Callback: Fn(Response) -> CallbackReturn,
+        CallbackReturn: Future<Output = Result<Payload, CallBackError>> + Send,
+    {
+        let origins = mirrors
+            .iter()
+            .map(Url::origin)
+            .chain(std::iter::once(req.url().origin()))
+            .collect::<Vec<_>>();
+        let with_mirror = |mirror: &Url| Self {
+            mirror: Some(Arc::new(MirrorRewrite::new(
+                origins.clone(),
+                mirror.clone(),
+            ))),
+            ..self.clone()
         };
-        match cached_response {
-            CachedResponse::FreshCache(cached) => Ok(Payload::from_aligned_bytes(cached.data)?),
-            CachedResponse::NotModified { cached, new_policy } => {
-                let refresh_cache =
-                    info_span!("refresh_cache", file = %cache_entry.path().display());
-                async {
-                    let data_with_cache_policy_bytes =
-                        DataWithCachePolicy::serialize(&new_policy, &cached.data)?;
-                    write_atomic(cache_entry.path(), data_with_cache_policy_bytes)
-                        .await
-                        .map_err(ErrorKind::CacheWrite)?;
-                    Ok(Payload::from_aligned_bytes(cached.data)?)
-                }
-                .instrument(refresh_cache)
-                .await
-            }
-            CachedResponse::ModifiedOrNew {
-                response,
-                cache_policy,
-            } => {
-                let new_cache = info_span!("new_cache", file = %cache_entry.path().display());
-                let data = response_callback(response)
-                    .boxed()
+        let Some((last, rest)) = mirrors.split_last() else {
+            return self
+                .get_cacheable(req, cache_entry, cache_control, response_callback)
+                .await;
+        };
+        for mirror in rest {
+            // A streaming body can only be sent once, so there's no falling back.
+            let Some(attempt) = req.try_clone() else {
+                return with_mirror(mirror)
+                    .get_cacheable_inner(req, cache_entry, cache_control, &response_callback)
                     .await
-                    .map_err(|err| CachedClientError::Callback(err))?;
-                let Some(cache_policy) = cache_policy else {
-                    return Ok(data.into_target());
-                };
-                async {
-                    fs_err::tokio::create_dir_all(cache_entry.dir())
-                        .await
-                        .map_err(ErrorKind::CacheWrite)?;
-                    let data_with_cache_policy_bytes =
-                        DataWithCachePolicy::serialize(&cache_policy, &data.to_bytes()?)?;
-                    write_atomic(cache_entry.path(), data_with_cache_policy_bytes)
-                        .await
-                        .map_err(ErrorKind::CacheWrite)?;
-                    Ok(data.into_target())
-                }
-                .instrument(new_cache)
+                    .map(|cached| cached.payload);
+            };
+            match with_mirror(mirror)
+                .get_cacheable_inner(attempt, cache_entry, cache_control, &response_callback)
                 .await
+            {
+                Err(CachedClientError::Client(err)) if Self::is_mirror_failure(&err) => {
+                    warn!("Falling back from mirror {mirror}: {err}");
+                }
+                result => return result.map(|cached| cached.payload),
             }
         }
+        with_mirror(last)
+            .get_cacheable_inner(req, cache_entry, cache_control, &response_callback)
+            .await
+            .map(|cached| cached.payload)
     }
 
-    async fn read_cache(cache_entry: &CacheEntry) -> Option<DataWithCachePolicy> {
-        let span = info_span!("read_and_parse_cache", file = %cache_entry.path().display());
-        match span
-            .in_scope(|| DataWithCachePolicy::from_path_async(cache_entry.path()))
-            .await
-        {
-            Ok(data) => Some(data),
-            Err(err) => {
-                warn!(
-                    "Broken cache entry at {}, removing: {err}",
-                    cache_entry.path().display()
-                );
-                let _ = fs_err::tokio::remove_file(&cache_entry.path()).await;
-                None
+    /// Returns `true` if an error indicates that a mirror is unavailable, rather than that the
+    /// request failed, as per [`CachedClient::get_cacheable_with_fallback`].
+    fn is_mirror_failure(err: &Error) -> bool {
+        match err.kind() {
+            ErrorKind::RequestError(err) => {
+                err.status().map_or(true, |status| status.is_server_error())
             }
+            ErrorKind::RequestMiddlewareError(_) => true,
+            _ => false,
         }
     }
 
-    /// Send a request given that we have a (possibly) stale cached response.
+    /// Make a cached request, returning the payload without consulting the network if the cache
+    /// entry is fresh, and refreshing it otherwise.
     ///
-    /// If the cached response is valid but stale, then this will attempt a
-    /// revalidation request.
-    async fn send_cached(
+    /// This is equivalent to checking [`CachedClient::peek`] before calling
+    /// [`CachedClient::get_cacheable`], but reads the cache entry only once. The returned
+    /// [`CacheResponse`] indicates how the payload was obtained, along with the filename from
+    /// the response's `Content-Disposition` header, if any.
+    #[instrument(skip_all, fields(cache_outcome = tracing::field::Empty))]
+    pub async fn get_fresh_or_refresh<Payload: Cacheable, CallBackError, Callback, CallbackReturn>(
         &self,
-        mut req: Request,
-        cache_control: CacheControl,
-        cached: DataWithCachePolicy,
-    ) -> Result<CachedResponse, Error> {
-        // Apply the cache control header, if necessary.
-        match cache_control {
-            CacheControl::None | CacheControl::AllowStale => {}
-            CacheControl::MustRevalidate => {
-                req.headers_mut().insert(
-                    http::header::CACHE_CONTROL,
-                    http::HeaderValue::from_static("no-cache"),
-                );
-            }
-        }
-        Ok(match cached.cache_policy.before_request(&mut req) {
-            BeforeRequest::Fresh => {
-                debug!("Found fresh response for: {}", req.url());
-                CachedResponse::FreshCache(cached)
-            }
-            BeforeRequest::Stale(new_cache_policy_builder) => match cache_control {
-                CacheControl::None | CacheControl::MustRevalidate => {
-                    debug!("Found stale response for: {}", req.url());
-                    self.send_cached_handle_stale(req, cached, new_cache_policy_builder)
-                        .await?
-                }
-                CacheControl::AllowStale => {
-                    debug!("Found stale (but allowed) response for: {}", req.url());
-                    CachedResponse::FreshCache(cached)
-                }
-            },
-            BeforeRequest::NoMatch => {
-                // This shouldn't happen; if it does, we'll override the cache.
-                warn!(
-                    "Cached request doesn't match current request for: {}",
-                    req.url()
-                );
-                self.fresh_request(req).await?
-            }
-        })
+        req: Request,
+        cache_entry: &CacheEntry,
+        response_callback: Callback,
+    ) -> Result<CacheResponse<Payload::Target>, CachedClientError<CallBackError>>
+    where
+        Callback: FnOnce(Response) -> CallbackReturn,
+        CallbackReturn: Future<Output = Result<Payload, CallBackError>> + Send,
+    {
+        let cached = self
+            .get_cacheable_inner(req, cache_entry, CacheControl::None, response_callback)
+            .await?;
+        Ok(cached.into_response())
     }
 
-    async fn send_cached_handle_stale(
+    /// Warm the cache with the responses to the given requests, e.g., ahead of a resolution,
+    /// returning the result of each request in the order given.
+    ///
+    /// Requests are started in order of descending [`WarmRequest::priority`] (and in the order
+    /// given among equal priorities), with at most `concurrency` requests in flight at a time. As
+    /// such, the caller can front-load the requests that matter most, like those for the root
+    /// dependencies of a resolution, which unblock most of the graph.
+    ///
+    /// Each request is made as in [`CachedClient::get_cacheable`], with new responses passed
+    /// through `response_callback`; cache entries that are already fresh are left as-is.
+    #[instrument(skip_all)]
+    pub async fn warm<Payload: Cacheable, CallBackError, Callback, CallbackReturn>(
         &self,
-        req: Request,
-        cached: DataWithCachePolicy,
-        new_cache_policy_builder: CachePolicyBuilder,
-    ) -> Result<CachedResponse, Error> {
-        let url = req.url().clone();
-        debug!("Sending revalidation request for: {url}");
-        let response = self
-            .0
-            .execute(req)
-            .instrument(info_span!("revalidation_request", url = url.as_str()))
-            .await
-            .map_err(ErrorKind::from_middleware)?
-            .error_for_status()
-            .map_err(ErrorKind::RequestError)?;
-        match cached
-            .cache_policy
-            .after_response(new_cache_policy_builder, &response)
-        {
-            AfterResponse::NotModified(new_policy) => {
-                debug!("Found not-modified response for: {url}");
-                Ok(CachedResponse::NotModified {
-                    cached,
-                    new_policy: Box::new(new_policy),
-                })
-            }
-            AfterResponse::Modified(new_policy) => {
-                debug!("Found modified response for: {url}");
-                Ok(CachedResponse::ModifiedOrNew {
-                    response,
-                    cache_policy: new_policy
-                        .to_archived()
-                        .is_storable()
-                        .then(|| Box::new(new_policy)),
-                })
-            }
-        }
+        requests: impl IntoIterator<Item = WarmRequest>,
+        concurrency: NonZeroUsize,
+        response_callback: Callback,
+    ) -> Vec<Result<(), CachedClientError<CallBackError>>>
+    where
+        Callback: Fn(Response) -> CallbackReturn,
+        CallbackReturn: Future<Output = Result<Payload, CallBackError>> + Send,
+    {
+        let mut requests = requests.into_iter().enumerate().collect::<Vec<_>>();
+        // A stable sort, such that requests of equal priority keep their order.
+        requests.sort_by_key(|(_, request)| std::cmp::Reverse(request.priority));
+
+        let response_callback = &response_callback;
+        let mut results = futures::stream::iter(requests)
+            .map(|(index, request)| async move {
+                let result = self
+                    .get_cacheable(
+                        request.request,
+                        &request.cache_entry,
+                        CacheControl::None,
+                        response_callback,
+                    )
+                    .await
+                    .map(|_| ());
+                (index, result)
+            })
+            // Futures are started in the order of the stream, i.e., by priority.
+            .buffer_unordered(concurrency.get())
+            .collect::<Vec<_>>()
+            .await;
+        results.sort_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, result)| result).collect()
     }
 
-    #[instrument(skip_all, fields(url = req.url().as_str()))]
-    async fn fresh_request(&self, req: Request) -> Result<CachedResponse, Error> {
-        trace!("Sending fresh {} request for {}", req.method(), req.url());
-        let cache_policy_builder = CachePolicyBuilder::new(&req);
-        let response = self
-            .0
-            .execute(req)
-            .await
-            .map_err(ErrorKind::from_middleware)?
-            .error_for_status()
-            .map_err(ErrorKind::RequestError)?;
-        let cache_policy = cache_policy_builder.build(&response);
-        Ok(CachedResponse::ModifiedOrNew {
-            response,
-            cache_policy: cache_policy
-                .to_archived()
-                .is_storable()
-                .then(|| Box::new(cache_policy)),
+    /// Revalidate a cache entry with the server, regardless of its freshness, and report whether
+    /// its content changed.
+    ///
+    /// The request is always sent, as with [`CacheControl::NoCache`]. If the server confirms
+    /// that the cached response is still valid, the entry's cache policy is refreshed in place;
+    /// otherwise, the new response is passed through `response_callback` and written to the
+    /// entry, as in [`CachedClient::get_cacheable`]. A missing entry is fetched, and reported as
+    /// [`RevalidationOutcome::Updated`].
+    ///
+    /// Unlike the other request methods, [`CachedClientBuilder::policy_overrides`] and
+    /// [`CachedClientBuilder::stale_on_error`] don't apply: an explicit revalidation always
+    /// contacts the server, and failing to do so is an error.
+    #[instrument(skip_all, fields(cache_outcome = tracing::field::Empty))]
+    pub async fn revalidate<Payload: Cacheable, CallBackError, Callback, CallbackReturn>(
+        &self,
+        req: Request,
+        cache_entry: &CacheEntry,
+        response_callback: Callback,
+    ) -> Result<RevalidationOutcome, CachedClientError<CallBackError>>
+    where
+        Callback: FnOnce(Response) -> CallbackReturn,
+        CallbackReturn: Future<Output = Result<Payload, CallBackError>> + Send,
+    {
+        // Clones share all in-memory state, so this only changes the settings for this request.
+        let client = Self {
+            policy_overrides: Arc::default(),
+            stale_on_error: None,
+            ..self.clone()
+        };
+        let cached = client
+            .get_cacheable_inner(req, cache_entry, CacheControl::NoCache, response_callback)
+            .await?;
+        Ok(match cached.outcome {
+            // With `no-cache`, a cached response is never used without revalidating it.
+            CacheOutcome::Fresh | CacheOutcome::Revalidated => RevalidationOutcome::NotModified,
+            CacheOutcome::Fetched => RevalidationOutcome::Updated,
         })
     }
-}
 
-#[derive(Debug)]
-enum CachedResponse {
-    /// The cached response is fresh without an HTTP request (e.g. age < max-age).
-    FreshCache(DataWithCachePolicy),
-    /// The cached response is fresh after an HTTP request (e.g. 304 not modified)
-    NotModified {
-        /// The cached response (with its old cache policy).
-        cached: DataWithCachePolicy,
-        /// The new [`CachePolicy`] is used to determine if the response
-        /// is fresh or stale when making subsequent requests for the same
-        /// resource. This policy should overwrite the old policy associated
-        /// with the cached response. In particular, this new policy is derived
-        /// from data received in a revalidation response, which might change
-        /// the parameters of cache behavior.
-        ///
-        /// The policy is large (352 bytes at time of writing), so we reduce
-        /// the stack size by boxing it.
-        new_policy: Box<CachePolicy>,
-    },
-    /// There was no prior cached response or the cache was outdated
+    /// Make a cached request like [`CachedClient::get_fresh_or_refresh`], but check whether a
+    /// stale cache entry is still current with a `HEAD` request, rather than a conditional `GET`.
     ///
-    /// The cache policy is `None` if it isn't storable
-    ModifiedOrNew {
-        /// The response received from the server.
-        response: Response,
-        /// The [`CachePolicy`] is used to determine if the response is fresh or
-        /// stale when making subsequent requests for the same resource.
-        ///
-        /// The policy is large (352 bytes at time of writing), so we reduce
-        /// the stack size by boxing it.
-        cache_policy: Option<Box<CachePolicy>>,
-    },
-}
+    /// This is for large files on servers that report validators on `HEAD` requests, but don't
+    /// honor `If-None-Match` (or `If-Modified-Since`) on `GET` requests, such that revalidating
+    /// the entry would download the file in its entirety. If the `ETag` and `Last-Modified`
+    /// headers (and `Content-Length`, if any) of the `HEAD` response match those stored with the
+    /// entry, the cached payload is returned as [`CacheOutcome::Revalidated`] and the entry's
+    /// cache policy is refreshed, without sending a `GET`. Otherwise, the `GET` request is sent
+    /// and its response is written to the entry, as in [`CachedClient::get_cacheable`].
+    ///
+    /// A fresh entry is returned without any requests, as usual.
+    #[instrument(skip_all, fields(cache_outcome = tracing::field::Empty))]
+    pub async fn get_cacheable_probed<Payload: Cacheable, CallBackError, Callback, CallbackReturn>(
+        &self,
+        req: Request,
+        cache_entry: &CacheEntry,
+        response_callback: Callback,
+    ) -> Result<CacheResponse<Payload::Target>, CachedClientError<CallBackError>>
+    where
+        Callback: FnOnce(Response) -> CallbackReturn,
+        CallbackReturn: Future<Output = Result<Payload, CallBackError>> + Send,
+    {
+        let _active_entry = self.active_entries.acquire(cache_entry.path());
+        let cached = self.read_cache(cache_entry).await;
+        let cached = self.check_not_found(&req, CacheControl::None, cached)?;
+        let url = req.url().clone();
+        let cached_response = match cached {
+            Some(cached) => self.send_probed(req, cached).boxed().await,
+            None => self.send_uncached(req, CacheControl::None).await,
+        };
+        let cached_response = self
+            .cache_not_found(cached_response, &url, cache_entry)
+            .await?;
+        let cached = self
+            .handle_cached_response(cached_response, cache_entry, None, response_callback)
+            .await?;
+        Ok(cached.into_response())
+    }
 
-/// Represents an arbitrary data blob with an associated HTTP cache policy.
-///
-/// The cache policy is used to determine whether the data blob is stale or
-/// not.
-///
-/// # Format
-///
-/// This type encapsulates the format for how blobs of data are stored on
-/// disk. The format is very simple. First, the blob of data is written as-is.
-/// Second, the archived representation of a `CachePolicy` is written. Thirdly,
-/// the length, in bytes, of the archived `CachePolicy` is written as a 64-bit
-/// little endian integer.
-///
-/// Reading the format is done via an `AlignedVec` so that `rkyv` can correctly
-/// read the archived representation of the data blob. The cache policy is
-/// split into its own `AlignedVec` allocation.
-///
-/// # Future ideas
-///
-/// This format was also chosen because it should in theory permit rewriting
-/// the cache policy without needing to rewrite the data blob if the blob has
-/// not changed. For example, this case occurs when a revalidation request
-/// responds with HTTP 304 NOT MODIFIED. At time of writing, this is not yet
-/// implemented because 1) the synchronization specifics of mutating a cache
-/// file have not been worked out and 2) it's not clear if it's a win.
-///
-/// An alternative format would be to write the cache policy and the
-/// blob in two distinct files. This would avoid needing to worry about
-/// synchronization, but it means reading two files instead of one for every
-/// cached response in the fast path. It's unclear whether it's worth it.
-/// (Experiments have not yet been done.)
-///
-/// Another approach here would be to memory map the file and rejigger
-/// `OwnedArchive` (or create a new type) that works with a memory map instead
-/// of an `AlignedVec`. This will require care to ensure alignment is handled
-/// correctly. This approach has not been litigated yet. I did not start with
-/// it because experiments with ripgrep have tended to show that (on Linux)
-/// memory mapping a bunch of small files ends up being quite a bit slower than
-/// just reading them on to the heap.
-#[derive(Debug)]
-pub struct DataWithCachePolicy {
-    pub data: AlignedVec,
-    cache_policy: OwnedArchive<CachePolicy>,
-}
+    /// Make a cached request like [`CachedClient::get_cacheable`], but fail with
+    /// [`ErrorKind::DeadlineExceeded`] unless the whole operation completes by the given deadline.
+    ///
+    /// Unlike a timeout on individual requests, the deadline bounds the total time spent reading
+    /// the cache entry, revalidating or fetching the response (including any retries), running
+    /// the callback, and writing the cache entry. If the deadline has already passed, nothing is
+    /// done. Since cache entries are written atomically, missing the deadline never leaves a
+    /// partially written entry behind.
+    #[instrument(skip_all)]
+    pub async fn get_cacheable_with_deadline<
+        Payload: Cacheable,
+        CallBackError,
+        Callback,
+        CallbackReturn,
+    >(
+        &self,
+        req: Request,
+        cache_entry: &CacheEntry,
+        cache_control: CacheControl,
+        deadline: Instant,
+        response_callback: Callback,
+    ) -> Result<Payload::Target, CachedClientError<CallBackError>>
+    where
+        Callback: FnOnce(Response) -> CallbackReturn,
+        CallbackReturn: Future<Output = Result<Payload, CallBackError>> + Send,
+    {
+        let url = req.url().clone();
+        if Instant::now() >= deadline {
+            return Err(ErrorKind::DeadlineExceeded(url).into());
+        }
+        tokio::time::timeout_at(
+            tokio::time::Instant::from_std(deadline),
+            self.get_cacheable(req, cache_entry, cache_control, response_callback),
+        )
+        .await
+        .map_err(|_| ErrorKind::DeadlineExceeded(url))?
+    }
+
+    /// Make a cached request like [`CachedClient::get_cacheable`], but keep recently used
+    /// payloads in memory, such that hot entries can skip reading and decoding the cache entry.
+    ///
+    /// An in-memory payload is only returned if its cache policy deems it fresh for the given
+    /// request; otherwise, this falls back to the usual flow and refreshes the in-memory payload.
+    ///
+    /// The payload is shared between callers, and stays in memory for as long as any caller holds
+    /// on to it. If the client was not built with [`CachedClientBuilder::weak_cache`], this is
+    /// equivalent to [`CachedClient::get_cacheable`].
+    #[instrument(skip_all, fields(cache_outcome = tracing::field::Empty))]
+    pub async fn get_cacheable_memoized<
+        Payload: Cacheable,
+        CallBackError,
+        Callback,
+        CallbackReturn,
+    >(
+        &self,
+        req: Request,
+        cache_entry: &CacheEntry,
+        cache_control: CacheControl,
+        response_callback: Callback,
+    ) -> Result<Arc<Payload::Target>, CachedClientError<CallBackError>>
+    where
+        Payload::Target: Send + Sync + 'static,
+        Callback: FnOnce(Response) -> CallbackReturn,
+        CallbackReturn: Future<Output = Result<Payload, CallBackError>> + Send,
+    {
+        let Some(weak_cache) = self.weak_cache.as_ref() else {
+            return self
+                .get_cacheable(req, cache_entry, cache_control, response_callback)
+                .await
+                .map(Arc::new);
+        };
+
+        if let Some((payload, cache_policy)) = weak_cache.get::<Payload::Target>(cache_entry.path())
+        {
+            let cache_control = self.policy_overrides.apply(req.url(), cache_control);
+            if Self::is_fresh_in_memory(&req, cache_control, &cache_policy) {
+                trace!("Found fresh in-memory payload for: {}", req.url());
+                self.stats.fresh(0);
+                CacheOutcome::Fresh.record();
+                return Ok(payload);
+            }
+        }
+
+        let CachedPayload {
+            payload,
+            cache_policy,
+            ..
+        } = self
+            .get_cacheable_inner(req, cache_entry, cache_control, response_callback)
+            .await?;
+        let payload = Arc::new(payload);
+        match cache_policy {
+            Some(cache_policy) => weak_cache.insert(
+                cache_entry.path().to_path_buf(),
+                payload.clone(),
+                cache_policy,
+            ),
+            None => weak_cache.remove(cache_entry.path()),
+        }
+        Ok(payload)
+    }
+
+    /// Returns `true` if a payload held in memory with the given cache policy can be returned
+    /// for the request without consulting the cache entry on disk.
+    fn is_fresh_in_memory(
+        req: &Request,
+        cache_control: CacheControl,
+        cache_policy: &OwnedArchive<CachePolicy>,
+    ) -> bool {
+        if matches!(
+            cache_control,
+            CacheControl::MustRevalidate | CacheControl::NoCache
+        ) {
+            return false;
+        }
+        // Streaming bodies can't be cloned, but then, they also can't be cached.
+        let Some(mut req) = req.try_clone() else {
+            return false;
+        };
+        match Self::before_request(cache_policy, &mut req, cache_control) {
+            BeforeRequest::Fresh => true,
+            BeforeRequest::Stale(_) => matches!(
+                cache_control,
+                CacheControl::AllowStale | CacheControl::Offline
+            ),
+            BeforeRequest::VaryMismatch | BeforeRequest::NoMatch => false,
+        }
+    }
+
+    /// Make a cached request like [`CachedClient::get_cacheable`], but stream the payload to and
+    /// from the cache entry, rather than holding it in memory in its entirety.
+    ///
+    /// Use this for large payloads (e.g., wheels). Entries are written in the same format as
+    /// those of [`CachedClient::get_cacheable`], but when reading, only the cache policy is
+    /// decoded up front, and the payload is read incrementally from the entry on disk. Likewise,
+    /// revalidating an entry copies its payload to the new entry without reading it into memory.
+    ///
+    /// If encryption is enabled, payloads are held in memory after all, since cache entries are
+    /// encrypted as a whole.
+    #[instrument(skip_all, fields(cache_outcome = tracing::field::Empty))]
+    pub async fn get_stream_cacheable<
+        Payload: StreamCacheable,
+        CallBackError,
+        Callback,
+        CallbackReturn,
+    >(
+        &self,
+        req: Request,
+        cache_entry: &CacheEntry,
+        cache_control: CacheControl,
+        response_callback: Callback,
+    ) -> Result<Payload::Target, CachedClientError<CallBackError>>
+    where
+        Callback: FnOnce(Response) -> CallbackReturn,
+        CallbackReturn: Future<Output = Result<Payload, CallBackError>> + Send,
+    {
+        let _active_entry = self.active_entries.acquire(cache_entry.path());
+        let (cached_response, streamed) = match self.read_cache_stream(cache_entry).await {
+            Some((cached, streamed)) => (
+                self.send_cached(req, cache_control, cached).boxed().await?,
+                Some(streamed),
+            ),
+            None => (self.send_uncached(req, cache_control).await?, None),
+        };
+        let streamed = match (cached_response, streamed) {
+            (CachedResponse::FreshCache(_), Some(streamed)) => {
+                self.stats.fresh(streamed.len());
+                CacheOutcome::Fresh.record();
+                streamed
+            }
+            (CachedResponse::NotModified { new_policy, .. }, Some(streamed)) => {
+                self.stats.revalidated(streamed.len());
+                CacheOutcome::Revalidated.record();
+                self.rewrite_cache_policy(cache_entry, streamed, &new_policy)
+                    .instrument(info_span!("refresh_cache", file = %cache_entry.path().display()))
+                    .await?
+            }
+            (
+                CachedResponse::ModifiedOrNew {
+                    response,
+                    cache_policy,
+                    length_check,
+                },
+                _,
+            ) => {
+                self.stats
+                    .fetched(fetched_len(&response, length_check.as_ref()));
+                CacheOutcome::Fetched.record();
+                let payload = response_callback(response).boxed().await;
+                if let Some(length_check) = length_check.as_ref() {
+                    length_check.verify()?;
+                }
+                let payload = payload.map_err(|err| CachedClientError::Callback(err))?;
+                fs_err::tokio::create_dir_all(cache_entry.dir())
+                    .await
+                    .map_err(ErrorKind::CacheWrite)?;
+                self.write_cache_stream(
+                    cache_entry,
+                    cache_policy.as_deref(),
+                    payload,
+                    length_check.as_ref(),
+                )
+                .instrument(info_span!("new_cache", file = %cache_entry.path().display()))
+                .await?
+            }
+            (CachedResponse::FreshCache(_) | CachedResponse::NotModified { .. }, None) => {
+                unreachable!("a cached response requires a cache entry")
+            }
+        };
+        Ok(streamed.read::<Payload>().await?)
+    }
+
+    /// Read the cache policy of a cache entry for a [`StreamCacheable`] payload, without reading
+    /// the payload itself (unless the entry is encrypted).
+    ///
+    /// As in [`CachedClient::read_cache`], a broken entry is removed and treated as missing.
+    async fn read_cache_stream(
+        &self,
+        cache_entry: &CacheEntry,
+    ) -> Option<(DataWithCachePolicy, StreamedPayload)> {
+        if self.is_encrypted() {
+            let mut cached = self.read_cache(cache_entry).await?;
+            let data = std::mem::take(&mut cached.data).into_vec();
+            return Some((cached, StreamedPayload::Memory(data)));
+        }
+        let path = cache_entry.path().to_path_buf();
+        let verify_immutable = self.verify_immutable;
+        let span = info_span!("read_and_parse_cache", file = %path.display());
+        let result = tokio::task::spawn_blocking(move || {
+            let _enter = span.enter();
+            let (cached, mut file, len) = DataWithCachePolicy::from_path_without_data(&path)?;
+            let mut prefix = Vec::with_capacity(SidecarRef::ENCODED_LEN);
+            file.rewind().map_err(ErrorKind::Io)?;
+            (&mut file)
+                .take(len.min(SidecarRef::ENCODED_LEN as u64))
+                .read_to_end(&mut prefix)
+                .map_err(ErrorKind::Io)?;
+            if encryption::is_sealed(&prefix) {
+                return Err(ErrorKind::CacheDecrypt.into());
+            }
+            if let Some(sidecar) = SidecarRef::decode(&prefix) {
+                let file = Self::open_sidecar(
+                    &path,
+                    sidecar,
+                    verify_immutable && cached.cache_policy.is_immutable(),
+                )?;
+                return Ok((cached, StreamedPayload::Sidecar(file, sidecar)));
+            }
+            if verify_immutable && cached.cache_policy.is_immutable() {
+                file.rewind().map_err(ErrorKind::Io)?;
+                let mut wtr = ChecksumWriter::new(std::io::sink());
+                std::io::copy(&mut (&mut file).take(len), &mut wtr).map_err(ErrorKind::Io)?;
+                if wtr.hasher.finish() != cached.checksum {
+                    return Err(ErrorKind::ArchiveRead(
+                        "data doesn't match its checksum".to_string(),
+                    )
+                    .into());
+                }
+            }
+            Ok::<_, Error>((cached, StreamedPayload::File(file.into_parts().0, len)))
+        })
+        .await
+        // This just forwards panics from the closure.
+        .unwrap();
+        match result {
+            Ok(cached) => Some(cached),
+            Err(err) if matches!(err.kind(), ErrorKind::CacheDecrypt) => {
+                warn!(
+                    "Ignoring unreadable cache entry at {}: {err}",
+                    cache_entry.path().display()
+                );
+                None
+            }
+            Err(err) => {
+                warn!(
+                    "Broken cache entry at {}, removing: {err}",
+                    cache_entry.path().display()
+                );
+                let _ = fs_err::tokio::remove_file(&cache_entry.path()).await;
+                let _ = sidecar::remove_sidecar(cache_entry.path());
+                None
+            }
+        }
+    }
+
+    /// Open the sidecar of the cache entry at the given path, checking that it matches the
+    /// reference stored in the entry.
+    fn open_sidecar(
+        path: &Path,
+        sidecar: SidecarRef,
+        verify: bool,
+    ) -> Result<std::fs::File, Error> {
+        let mut file = fs_err::File::open(sidecar::sidecar_path(path)).map_err(ErrorKind::Io)?;
+        let len = file.metadata().map_err(ErrorKind::Io)?.len();
+        if len != sidecar.len {
+            let msg = format!(
+                "sidecar should be {} bytes in length, but is {len} bytes",
+                sidecar.len
+            );
+            return Err(ErrorKind::ArchiveRead(msg).into());
+        }
+        if verify {
+            let mut wtr = ChecksumWriter::new(std::io::sink());
+            std::io::copy(&mut file, &mut wtr).map_err(ErrorKind::Io)?;
+            if wtr.hasher.finish() != sidecar.checksum {
+                return Err(ErrorKind::ArchiveRead(
+                    "sidecar doesn't match its checksum".to_string(),
+                )
+                .into());
+            }
+        }
+        Ok(file.into_parts().0)
+    }
+
+    /// Atomically write a [`StreamCacheable`] payload to the cache, without holding it in memory
+    /// (unless encryption is enabled), returning the written payload.
+    ///
+    /// If the response isn't storable, the payload is written to an anonymous temporary file
+    /// instead.
+    async fn write_cache_stream<Payload: StreamCacheable>(
+        &self,
+        cache_entry: &CacheEntry,
+        cache_policy: Option<&CachePolicy>,
+        payload: Payload,
+        length_check: Option<&ContentLengthCheck>,
+    ) -> Result<StreamedPayload, Error> {
+        if self.is_encrypted() {
+            let mut data = Vec::new();
+            write_payload(payload, &mut data, length_check).await?;
+            if let Some(cache_policy) = cache_policy {
+                let bytes = DataWithCachePolicy::serialize(
+                    cache_policy,
+                    &self.seal_payload(cache_entry, &data)?,
+                )?;
+                self.write_cache(cache_entry, bytes).await?;
+            }
+            return Ok(StreamedPayload::Memory(data));
+        }
+
+        let Some(cache_policy) = cache_policy else {
+            let file = tempfile::tempfile_in(cache_entry.dir()).map_err(ErrorKind::CacheWrite)?;
+            let mut wtr = BufWriter::new(tokio::fs::File::from_std(file));
+            write_payload(payload, &mut wtr, length_check).await?;
+            wtr.flush().await.map_err(ErrorKind::CacheWrite)?;
+            let file = wtr.into_inner();
+            let len = file.metadata().await.map_err(ErrorKind::CacheWrite)?.len();
+            return Ok(StreamedPayload::File(file.into_std().await, len));
+        };
+
+        let temp_file = self.temp_file_for(cache_entry)?;
+        let file = temp_file.reopen().map_err(ErrorKind::CacheWrite)?;
+        let mut wtr = ChecksumWriter::new(BufWriter::new(tokio::fs::File::from_std(file)));
+        write_payload(payload, &mut wtr, length_check).await?;
+        let (len, checksum) = (wtr.len, wtr.hasher.finish());
+        let mut wtr = wtr.inner;
+
+        if self.sidecar_payloads {
+            // Move the sidecar into place before the entry that references it.
+            wtr.flush().await.map_err(ErrorKind::CacheWrite)?;
+            drop(wtr);
+            let file = temp_file
+                .persist(sidecar::sidecar_path(cache_entry.path()))
+                .map_err(|err| ErrorKind::CacheWrite(err.error))?;
+            self.stats.written(len);
+            let sidecar = SidecarRef { len, checksum };
+            let bytes = DataWithCachePolicy::serialize(cache_policy, &sidecar.encode())?;
+            self.write_cache(cache_entry, bytes).await?;
+            return Ok(StreamedPayload::Sidecar(file, sidecar));
+        }
+
+        let mut trailer = vec![];
+        DataWithCachePolicy::serialize_trailer(cache_policy, checksum, &mut trailer)?;
+        self.reserve_entry(cache_entry).await?;
+        self.stats.written(len + trailer.len() as u64);
+        wtr.write_all(&trailer)
+            .await
+            .map_err(ErrorKind::CacheWrite)?;
+        wtr.flush().await.map_err(ErrorKind::CacheWrite)?;
+        drop(wtr);
+        let file = temp_file
+            .persist(cache_entry.path())
+            .map_err(|err| ErrorKind::CacheWrite(err.error))?;
+        Ok(StreamedPayload::File(file, len))
+    }
+
+    /// Atomically replace the cache policy of a cache entry for a [`StreamCacheable`] payload,
+    /// copying the payload to the new entry without holding it in memory (unless encryption is
+    /// enabled).
+    async fn rewrite_cache_policy(
+        &self,
+        cache_entry: &CacheEntry,
+        streamed: StreamedPayload,
+        cache_policy: &CachePolicy,
+    ) -> Result<StreamedPayload, Error> {
+        let (file, len) = match streamed {
+            StreamedPayload::File(file, len) => (file, len),
+            StreamedPayload::Sidecar(file, sidecar) => {
+                // Only the entry itself needs to be rewritten.
+                let bytes = DataWithCachePolicy::serialize(cache_policy, &sidecar.encode())?;
+                self.write_cache(cache_entry, bytes).await?;
+                return Ok(StreamedPayload::Sidecar(file, sidecar));
+            }
+            StreamedPayload::Memory(data) => {
+                let bytes = DataWithCachePolicy::serialize(
+                    cache_policy,
+                    &self.seal_payload(cache_entry, &data)?,
+                )?;
+                self.write_cache(cache_entry, bytes).await?;
+                return Ok(StreamedPayload::Memory(data));
+            }
+        };
+        let temp_file = self.temp_file_for(cache_entry)?;
+        let mut wtr = BufWriter::new(tokio::fs::File::from_std(
+            temp_file.reopen().map_err(ErrorKind::CacheWrite)?,
+        ));
+        // Copy the payload along with its checksum, which are unchanged.
+        let mut rdr = tokio::fs::File::from_std(file);
+        rdr.rewind().await.map_err(ErrorKind::Io)?;
+        tokio::io::copy(&mut (&mut rdr).take(len + 8), &mut wtr)
+            .await
+            .map_err(ErrorKind::CacheWrite)?;
+        let mut trailer = vec![];
+        DataWithCachePolicy::serialize_cache_policy(cache_policy, &mut trailer)?;
+        self.stats.written(len + 8 + trailer.len() as u64);
+        wtr.write_all(&trailer)
+            .await
+            .map_err(ErrorKind::CacheWrite)?;
+        wtr.flush().await.map_err(ErrorKind::CacheWrite)?;
+        drop(wtr);
+        let file = temp_file
+            .persist(cache_entry.path())
+            .map_err(|err| ErrorKind::CacheWrite(err.error))?;
+        Ok(StreamedPayload::File(file, len))
+    }
+
+    /// Create a temporary file from which to atomically write the given cache entry.
+    fn temp_file_for(&self, cache_entry: &CacheEntry) -> Result<NamedTempFile, Error> {
+        NamedTempFile::new_in(self.temp_dir.as_deref().unwrap_or(cache_entry.dir()))
+            .map_err(|err| ErrorKind::CacheWrite(err).into())
+    }
+
+    /// Make a cached request, returning the payload along with the cache policy that was stored
+    /// alongside it, and how it was obtained.
+    ///
+    /// Concurrent requests for the same cache entry with [`CacheControl::None`] share a single
+    /// request: the first one goes through as usual, while the others wait for it to finish and
+    /// deserialize the payload it obtained. If the first request fails, the next one to wake up
+    /// leads a new flight that the others wait on. Requests with any other cache control are
+    /// always made on their own, as they can't make do with a payload obtained under another.
+    async fn get_cacheable_inner<Payload: Cacheable, CallBackError, Callback, CallbackReturn>(
+        &self,
+        req: Request,
+        cache_entry: &CacheEntry,
+        cache_control: CacheControl,
+        response_callback: Callback,
+    ) -> Result<CachedPayload<Payload::Target>, CachedClientError<CallBackError>>
+    where
+        Callback: FnOnce(Response) -> CallbackReturn,
+        CallbackReturn: Future<Output = Result<Payload, CallBackError>> + Send,
+    {
+        // A streaming body can't be cloned, so the request couldn't be replayed to revalidate
+        // (or retry) it. Send it once, and leave the cache entry alone.
+        if req.try_clone().is_none() {
+            debug!("Bypassing the cache for streaming request: {}", req.url());
+            let cached_response = self.fresh_request(req).await?.uncacheable();
+            return self
+                .handle_cached_response(cached_response, cache_entry, None, response_callback)
+                .await;
+        }
+
+        let flight = if matches!(
+            self.policy_overrides.apply(req.url(), cache_control),
+            CacheControl::None
+        ) {
+            loop {
+                match self.single_flight.join(cache_entry.path()) {
+                    Join::Leader(leader) => break Some(leader),
+                    Join::Follower(flight) => {
+                        if let Ok(shared) = flight.await {
+                            trace!(
+                                "Sharing the response to a concurrent request for: {}",
+                                req.url()
+                            );
+                            self.stats.shared();
+                            shared.outcome.record();
+                            return Ok(CachedPayload {
+                                payload: Payload::from_aligned_bytes(shared.bytes.clone())?,
+                                cache_policy: shared.cache_policy.clone(),
+                                outcome: shared.outcome,
+                                filename: shared.filename.clone(),
+                            });
+                        }
+                        // Rejoin, such that one of the waiting requests leads the next flight
+                        // rather than all of them going to the network at once.
+                        debug!("Concurrent request failed, rejoining: {}", req.url());
+                    }
+                }
+            }
+        } else {
+            None
+        };
+
+        let _active_entry = self.active_entries.acquire(cache_entry.path());
+        let cached = self.read_cache(cache_entry).await;
+        let cached = self.check_not_found(&req, cache_control, cached)?;
+        let url = req.url().clone();
+        let cached_response = match cached {
+            Some(cached) if self.can_serve_stale_while_revalidate(&req, cache_control, &cached) => {
+                debug!("Found stale (but revalidating) response for: {}", req.url());
+                self.spawn_revalidation(req, cache_entry, Payload::compression_hint());
+                Ok(CachedResponse::FreshCache(cached))
+            }
+            Some(cached) => self.send_cached(req, cache_control, cached).boxed().await,
+            None => self.send_uncached(req, cache_control).await,
+        };
+        let cached_response = self
+            .cache_not_found(cached_response, &url, cache_entry)
+            .await?;
+        self.handle_cached_response(cached_response, cache_entry, flight, response_callback)
+            .await
+    }
+
+    /// Returns `true` if the cached response is stale, but may be served while it's revalidated in
+    /// the background, as per its `stale-while-revalidate` directive.
+    ///
+    /// This only applies to requests that would otherwise revalidate the response as usual (i.e.,
+    /// with [`CacheControl::None`]), and that can be replayed in the background.
+    fn can_serve_stale_while_revalidate(
+        &self,
+        req: &Request,
+        cache_control: CacheControl,
+        cached: &DataWithCachePolicy,
+    ) -> bool {
+        let cache_control = self.policy_overrides.apply(req.url(), cache_control);
+        if !matches!(cache_control, CacheControl::None) {
+            return false;
+        }
+        let Some(mut req) = req.try_clone() else {
+            return false;
+        };
+        matches!(
+            Self::before_request(&cached.cache_policy, &mut req, cache_control),
+            BeforeRequest::Stale(_)
+        ) && cached
+            .cache_policy
+            .is_within_stale_while_revalidate(SystemTime::now())
+    }
+
+    /// Revalidate a cache entry in the background, unless that's already underway.
+    ///
+    /// If the server confirms that the cached response is still valid, the entry's cache policy is
+    /// refreshed. Otherwise, the new response can't be turned into a payload without the caller's
+    /// `response_callback`, so the entry is removed instead, such that the next request fetches
+    /// it in the foreground. The payload is rewritten as per the `compression_hint` of its type.
+    fn spawn_revalidation(
+        &self,
+        req: Request,
+        cache_entry: &CacheEntry,
+        compression_hint: CompressionHint,
+    ) {
+        let path = cache_entry.path().to_path_buf();
+        if !self.revalidating.lock().unwrap().insert(path.clone()) {
+            trace!("Already revalidating in the background: {}", req.url());
+            return;
+        }
+        let client = self.clone();
+        let cache_entry = cache_entry.clone();
+        tokio::spawn(
+            async move {
+                if let Err(err) = client
+                    .revalidate_in_background(req, &cache_entry, compression_hint)
+                    .await
+                {
+                    warn!(
+                        "Failed to revalidate the cache entry at {} in the background: {err}",
+                        cache_entry.path().display()
+                    );
+                }
+                client.revalidating.lock().unwrap().remove(&path);
+            }
+            .instrument(info_span!("background_revalidation")),
+        );
+    }
+
+    /// See [`CachedClient::spawn_revalidation`].
+    async fn revalidate_in_background(
+        &self,
+        req: Request,
+        cache_entry: &CacheEntry,
+        compression_hint: CompressionHint,
+    ) -> Result<(), Error> {
+        let _active_entry = self.active_entries.acquire(cache_entry.path());
+        // The entry may have been refreshed (or removed) in the meantime.
+        let Some(cached) = self.read_cache(cache_entry).await else {
+            return Ok(());
+        };
+        match self.send_cached(req, CacheControl::None, cached).await? {
+            CachedResponse::FreshCache(_) => {}
+            CachedResponse::NotModified { cached, new_policy } => {
+                let data_with_cache_policy_bytes = DataWithCachePolicy::serialize(
+                    &new_policy,
+                    &self.pack_payload(cache_entry, &cached.data, compression_hint)?,
+                )?;
+                self.write_cache(cache_entry, data_with_cache_policy_bytes)
+                    .await?;
+            }
+            CachedResponse::ModifiedOrNew { .. } => {
+                debug!(
+                    "Removing modified cache entry at: {}",
+                    cache_entry.path().display()
+                );
+                if let Some(weak_cache) = self.weak_cache.as_ref() {
+                    weak_cache.remove(cache_entry.path());
+                }
+                match fs_err::tokio::remove_file(cache_entry.path()).await {
+                    Ok(()) => {}
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(err) => return Err(ErrorKind::CacheWrite(err).into()),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Turn the outcome of a request into a payload, running the response through
+    /// `response_callback` and writing the cache entry as necessary.
+    ///
+    /// If the request leads a `flight`, the payload is shared with the requests waiting on it.
+    async fn handle_cached_response<Payload: Cacheable, CallBackError, Callback, CallbackReturn>(
+        &self,
+        cached_response: CachedResponse,
+        cache_entry: &CacheEntry,
+        flight: Option<FlightLeader>,
+        response_callback: Callback,
+    ) -> Result<CachedPayload<Payload::Target>, CachedClientError<CallBackError>>
+    where
+        Callback: FnOnce(Response) -> CallbackReturn,
+        CallbackReturn: Future<Output = Result<Payload, CallBackError>> + Send,
+    {
+        match cached_response {
+            CachedResponse::FreshCache(cached) => {
+                self.stats.fresh(cached.data.len() as u64);
+                CacheOutcome::Fresh.record();
+                let filename = cached.cache_policy.filename().map(ToString::to_string);
+                if let Some(flight) = flight {
+                    flight.land(|| {
+                        Some(SharedPayload {
+                            bytes: cached.data.clone(),
+                            cache_policy: Some(cached.cache_policy.clone()),
+                            outcome: CacheOutcome::Fresh,
+                            filename: filename.clone(),
+                        })
+                    });
+                }
+                Ok(CachedPayload {
+                    filename,
+                    payload: Payload::from_aligned_bytes(cached.data)?,
+                    cache_policy: Some(cached.cache_policy),
+                    outcome: CacheOutcome::Fresh,
+                })
+            }
+            CachedResponse::NotModified { cached, new_policy } => {
+                self.stats.revalidated(cached.data.len() as u64);
+                CacheOutcome::Revalidated.record();
+                let refresh_cache =
+                    info_span!("refresh_cache", file = %cache_entry.path().display());
+                async {
+                    let data_with_cache_policy_bytes = DataWithCachePolicy::serialize(
+                        &new_policy,
+                        &self.pack_payload(
+                            cache_entry,
+                            &cached.data,
+                            Payload::compression_hint(),
+                        )?,
+                    )?;
+                    self.write_cache(cache_entry, data_with_cache_policy_bytes)
+                        .await?;
+                    let cache_policy = new_policy.to_archived();
+                    let filename = cache_policy.filename().map(ToString::to_string);
+                    if let Some(flight) = flight {
+                        flight.land(|| {
+                            Some(SharedPayload {
+                                bytes: cached.data.clone(),
+                                cache_policy: Some(cache_policy.clone()),
+                                outcome: CacheOutcome::Revalidated,
+                                filename: filename.clone(),
+                            })
+                        });
+                    }
+                    Ok(CachedPayload {
+                        payload: Payload::from_aligned_bytes(cached.data)?,
+                        filename,
+                        cache_policy: Some(cache_policy),
+                        outcome: CacheOutcome::Revalidated,
+                    })
+                }
+                .instrument(refresh_cache)
+                .await
+            }
+            CachedResponse::ModifiedOrNew {
+                response,
+                cache_policy,
+                length_check,
+            } => {
+                let new_cache = info_span!("new_cache", file = %cache_entry.path().display());
+                // Read the filename before handing off the response, since non-storable
+                // responses have no cache policy to read it from.
+                let filename = content_disposition_filename(response.headers());
+                self.stats
+                    .fetched(fetched_len(&response, length_check.as_ref()));
+                CacheOutcome::Fetched.record();
+                let data = response_callback(response).boxed().await;
+                // Never cache a body of the wrong length, even if the callback accepted it.
+                if let Some(length_check) = length_check {
+                    length_check.verify()?;
+                }
+                let data = data.map_err(|err| CachedClientError::Callback(err))?;
+                let Some(cache_policy) = cache_policy else {
+                    if let Some(flight) = flight {
+                        flight.land(|| {
+                            Some(SharedPayload {
+                                bytes: aligned_bytes(&data.to_bytes().ok()?),
+                                cache_policy: None,
+                                outcome: CacheOutcome::Fetched,
+                                filename: filename.clone(),
+                            })
+                        });
+                    }
+                    return Ok(CachedPayload {
+                        payload: data.into_target(),
+                        cache_policy: None,
+                        outcome: CacheOutcome::Fetched,
+                        filename,
+                    });
+                };
+                async {
+                    fs_err::tokio::create_dir_all(cache_entry.dir())
+                        .await
+                        .map_err(ErrorKind::CacheWrite)?;
+                    let bytes = data.to_bytes()?;
+                    let data_with_cache_policy_bytes = DataWithCachePolicy::serialize(
+                        &cache_policy,
+                        &self.pack_payload(cache_entry, &bytes, Payload::compression_hint())?,
+                    )?;
+                    self.write_cache(cache_entry, data_with_cache_policy_bytes)
+                        .await?;
+                    let cache_policy = cache_policy.to_archived();
+                    if let Some(flight) = flight {
+                        flight.land(|| {
+                            Some(SharedPayload {
+                                bytes: aligned_bytes(&bytes),
+                                cache_policy: Some(cache_policy.clone()),
+                                outcome: CacheOutcome::Fetched,
+                                filename: filename.clone(),
+                            })
+                        });
+                    }
+                    drop(bytes);
+                    Ok(CachedPayload {
+                        payload: data.into_target(),
+                        cache_policy: Some(cache_policy),
+                        outcome: CacheOutcome::Fetched,
+                        filename,
+                    })
+                }
+                .instrument(new_cache)
+                .await
+            }
+        }
+    }
+
+    /// Returns the rate limit most recently advertised by the host of the given URL, if any.
+    ///
+    /// See [`CachedClientBuilder::rate_limit_headers`] for the headers that are recognized.
+    pub fn rate_limit_state(&self, url: &Url) -> Option<RateLimitState> {
+        self.rate_limiter.state(url)
+    }
+
+    /// Read the raw bytes of a cached response, bypassing any `Cacheable` transformation.
+    ///
+    /// The payload is decrypted and decompressed as far as it was encrypted or compressed, but
+    /// isn't checked for freshness. If there is no cache entry, `None` is returned.
+    ///
+    /// Unlike the other read paths, a broken cache entry is surfaced as an error rather than
+    /// being removed, since this is intended for inspecting the cache.
+    pub async fn read_raw(&self, cache_entry: &CacheEntry) -> Result<Option<Vec<u8>>, Error> {
+        match DataWithCachePolicy::from_path_async(cache_entry.path()).await {
+            Ok(cached) => Ok(Some(
+                self.unpack_payload(cache_entry, cached.data)?.into_vec(),
+            )),
+            Err(err) => match err.kind() {
+                ErrorKind::Io(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                _ => Err(err),
+            },
+        }
+    }
+
+    /// Read the bytes of a cached response exactly as they're stored in the cache, i.e., still
+    /// compressed or encrypted, if they were.
+    ///
+    /// As with [`CachedClient::read_raw`], the entry isn't checked for freshness, and a broken
+    /// cache entry is surfaced as an error. If there is no cache entry, `None` is returned.
+    pub async fn read_raw_stored(
+        &self,
+        cache_entry: &CacheEntry,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        match DataWithCachePolicy::from_path_async(cache_entry.path()).await {
+            Ok(cached) => Ok(Some(cached.data.into_vec())),
+            Err(err) => match err.kind() {
+                ErrorKind::Io(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                _ => Err(err),
+            },
+        }
+    }
+
+    /// Read the stored bytes of a cached response, like [`CachedClient::read_raw_stored`], but map
+    /// them into memory rather than copying them into a fresh allocation.
+    ///
+    /// Only the cache policy is read up front; the payload is paged in as it's accessed, and the
+    /// returned guard keeps the mapping alive. A range of the payload can be read by slicing the
+    /// guard, which only pages in that range. See the `mmap` module for when mapping an entry
+    /// is safe: in short, the entry must not be modified in place (as opposed to replaced or
+    /// removed, which is how the client itself writes entries) for as long as it's mapped.
+    ///
+    /// Only payloads that are stored as-is can be mapped: those that were compressed or
+    /// encrypted fail with [`ErrorKind::CacheNotMappable`], and should be read with
+    /// [`CachedClient::read_raw`] or [`CachedClient::read_stream`] instead.
+    #[cfg(feature = "mmap")]
+    pub async fn read_raw_mapped(
+        &self,
+        cache_entry: &CacheEntry,
+    ) -> Result<Option<MappedBytes>, Error> {
+        let path = cache_entry.path().to_path_buf();
+        let result = tokio::task::spawn_blocking(move || {
+            let (_, file, len) = DataWithCachePolicy::from_path_without_data(&path)?;
+            let mapped = MappedBytes::map(file.into_parts().0, len).map_err(ErrorKind::Io)?;
+            if compression::is_compressed(&mapped) || encryption::is_sealed(&mapped) {
+                return Err(ErrorKind::CacheNotMappable.into());
+            }
+            Ok::<_, Error>(mapped)
+        })
+        .await
+        // This just forwards panics from the closure.
+        .unwrap();
+        match result {
+            Ok(mapped) => Ok(Some(mapped)),
+            Err(err) => match err.kind() {
+                ErrorKind::Io(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                _ => Err(err),
+            },
+        }
+    }
+
+    /// Read the payload of a cached response as a stream, without checking whether it's fresh.
+    ///
+    /// Unlike [`CachedClient::read_raw`], a compressed payload is decompressed as it's read, such
+    /// that large payloads are never held in memory in full, let alone in both forms. Encrypted
+    /// payloads can't be opened incrementally, so they're read (and opened) up front; those that
+    /// can't be opened are surfaced as errors. If there is no cache entry, `None` is returned.
+    ///
+    /// As with [`CachedClient::read_raw_mapped`], the payload isn't checked against its
+    /// checksum, and a broken cache entry is surfaced as an error rather than being removed.
+    pub async fn read_stream(
+        &self,
+        cache_entry: &CacheEntry,
+    ) -> Result<Option<Box<dyn AsyncRead + Unpin + Send>>, Error> {
+        if self.is_encrypted() {
+            let cached = match DataWithCachePolicy::from_path_async(cache_entry.path()).await {
+                Ok(cached) => cached,
+                Err(err) => {
+                    return match err.kind() {
+                        ErrorKind::Io(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                            Ok(None)
+                        }
+                        _ => Err(err),
+                    }
+                }
+            };
+            let data = self.unpack_payload(cache_entry, cached.data)?.into_vec();
+            return Ok(Some(Box::new(std::io::Cursor::new(data))));
+        }
+
+        let path = cache_entry.path().to_path_buf();
+        let result = tokio::task::spawn_blocking(move || {
+            let (_, mut file, len) = DataWithCachePolicy::from_path_without_data(&path)?;
+            let mut prefix = Vec::with_capacity(compression::MAGIC_LEN);
+            file.rewind().map_err(ErrorKind::Io)?;
+            (&mut file)
+                .take(len.min(compression::MAGIC_LEN as u64))
+                .read_to_end(&mut prefix)
+                .map_err(ErrorKind::Io)?;
+            if encryption::is_sealed(&prefix) {
+                return Err(ErrorKind::CacheDecrypt.into());
+            }
+            file.rewind().map_err(ErrorKind::Io)?;
+            Ok::<_, Error>((
+                file.into_parts().0,
+                len,
+                compression::is_compressed(&prefix),
+            ))
+        })
+        .await
+        // This just forwards panics from the closure.
+        .unwrap();
+        let (file, len, compressed) = match result {
+            Ok(result) => result,
+            Err(err) => {
+                return match err.kind() {
+                    ErrorKind::Io(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                    _ => Err(err),
+                }
+            }
+        };
+        let rdr = BufReader::new(tokio::fs::File::from_std(file)).take(len);
+        if compressed {
+            Ok(Some(Box::new(compression::decompress_reader(rdr).await?)))
+        } else {
+            Ok(Some(Box::new(rdr)))
+        }
+    }
+
+    /// Mark a cache entry as recently used, without reading it, such that it's among the last to
+    /// be deleted under [`CachedClientBuilder::max_entries`].
+    ///
+    /// Entries are ranked by when they were last written, so this sets the entry's modification
+    /// time to now. Use it when a payload is served from memory (e.g., from a cache of the
+    /// caller's own), which would otherwise leave the entry looking unused. If there is no cache
+    /// entry, this does nothing.
+    pub async fn touch(&self, cache_entry: &CacheEntry) -> Result<(), Error> {
+        let path = cache_entry.path().to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let file = match fs_err::OpenOptions::new().write(true).open(path) {
+                Ok(file) => file,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+                Err(err) => return Err(ErrorKind::CacheWrite(err).into()),
+            };
+            file.file()
+                .set_modified(SystemTime::now())
+                .map_err(|err| ErrorKind::CacheWrite(err).into())
+        })
+        .await
+        // This just forwards panics from the closure.
+        .unwrap()
+    }
+
+    /// Read the freshness of a cache entry, without issuing any requests.
+    ///
+    /// If there is no cache entry, `None` is returned. As with [`CachedClient::read_raw`], a
+    /// broken cache entry is surfaced as an error rather than being removed.
+    pub async fn peek(&self, cache_entry: &CacheEntry) -> Result<Option<CacheStatus>, Error> {
+        match DataWithCachePolicy::from_path_async(cache_entry.path()).await {
+            Ok(cached) => {
+                let now = SystemTime::now();
+                Ok(Some(CacheStatus {
+                    age: cached.cache_policy.age(now),
+                    freshness_lifetime: cached.cache_policy.freshness_lifetime(),
+                    filename: cached.cache_policy.filename().map(ToString::to_string),
+                }))
+            }
+            Err(err) => match err.kind() {
+                ErrorKind::Io(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                _ => Err(err),
+            },
+        }
+    }
+
+    /// Read the raw bytes of a cached response, like [`CachedClient::read_raw`], but re-fetch the
+    /// entry with the given request if it's corrupt and [`CachedClientBuilder::repair_on_read`]
+    /// is enabled.
+    ///
+    /// The callback is used to rewrite the entry, as in [`CachedClient::get_cacheable`]. A
+    /// missing entry is not considered corrupt, and is not fetched.
+    pub async fn read_raw_or_repair<Payload: Cacheable, CallBackError, Callback, CallbackReturn>(
+        &self,
+        req: Request,
+        cache_entry: &CacheEntry,
+        response_callback: Callback,
+    ) -> Result<Option<Vec<u8>>, CachedClientError<CallBackError>>
+    where
+        Callback: FnOnce(Response) -> CallbackReturn,
+        CallbackReturn: Future<Output = Result<Payload, CallBackError>> + Send,
+    {
+        match self.read_raw(cache_entry).await {
+            Err(err) if self.repair_on_read && Self::is_corrupt(&err) => {
+                self.repair(err, req, cache_entry, response_callback)
+                    .await?;
+                Ok(self.read_raw(cache_entry).await?)
+            }
+            result => Ok(result?),
+        }
+    }
+
+    /// Read the freshness of a cache entry, like [`CachedClient::peek`], but re-fetch the entry
+    /// with the given request if it's corrupt and [`CachedClientBuilder::repair_on_read`] is
+    /// enabled.
+    ///
+    /// The callback is used to rewrite the entry, as in [`CachedClient::get_cacheable`]. A
+    /// missing entry is not considered corrupt, and is not fetched.
+    pub async fn peek_or_repair<Payload: Cacheable, CallBackError, Callback, CallbackReturn>(
+        &self,
+        req: Request,
+        cache_entry: &CacheEntry,
+        response_callback: Callback,
+    ) -> Result<Option<CacheStatus>, CachedClientError<CallBackError>>
+    where
+        Callback: FnOnce(Response) -> CallbackReturn,
+        CallbackReturn: Future<Output = Result<Payload, CallBackError>> + Send,
+    {
+        match self.peek(cache_entry).await {
+            Err(err) if self.repair_on_read && Self::is_corrupt(&err) => {
+                self.repair(err, req, cache_entry, response_callback)
+                    .await?;
+                Ok(self.peek(cache_entry).await?)
+            }
+            result => Ok(result?),
+        }
+    }
+
+    /// Returns `true` if an error from reading a cache entry indicates that the entry is corrupt,
+    /// as opposed to, e.g., unreadable.
+    fn is_corrupt(err: &Error) -> bool {
+        !matches!(err.kind(), ErrorKind::Io(_))
+    }
+
+    /// Replace a corrupt cache entry by re-fetching it.
+    async fn repair<Payload: Cacheable, CallBackError, Callback, CallbackReturn>(
+        &self,
+        err: Error,
+        req: Request,
+        cache_entry: &CacheEntry,
+        response_callback: Callback,
+    ) -> Result<(), CachedClientError<CallBackError>>
+    where
+        Callback: FnOnce(Response) -> CallbackReturn,
+        CallbackReturn: Future<Output = Result<Payload, CallBackError>> + Send,
+    {
+        warn!(
+            "Broken cache entry at {}, re-fetching: {err}",
+            cache_entry.path().display()
+        );
+        fs_err::tokio::remove_file(cache_entry.path())
+            .await
+            .map_err(ErrorKind::CacheWrite)?;
+        self.get_cacheable_inner(req, cache_entry, CacheControl::None, response_callback)
+            .await?;
+        Ok(())
+    }
+
+    /// Delete a cache entry (along with its sidecar, if any), returning the number of files
+    /// deleted.
+    ///
+    /// If there is no cache entry, this does nothing and returns zero.
+    pub async fn invalidate(&self, cache_entry: &CacheEntry) -> Result<usize, Error> {
+        let _active_entry = self.active_entries.acquire(cache_entry.path());
+        if let Some(weak_cache) = self.weak_cache.as_ref() {
+            weak_cache.remove(cache_entry.path());
+        }
+        let mut removed = 0;
+        for path in [
+            cache_entry.path().to_path_buf(),
+            sidecar::sidecar_path(cache_entry.path()),
+        ] {
+            match fs_err::tokio::remove_file(&path).await {
+                Ok(()) => removed += 1,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => return Err(ErrorKind::CacheWrite(err).into()),
+            }
+        }
+        if removed > 0 {
+            debug!("Removed cache entry at: {}", cache_entry.path().display());
+            if let Some(audit_log) = self.audit_log.as_ref() {
+                audit_log.evict(cache_entry.path(), None, None);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Delete every cache entry under `dir` for which the predicate returns `true`, returning the
+    /// number of entries deleted.
+    ///
+    /// Files that can't be read as cache entries written by a [`CachedClient`] (e.g., unzipped
+    /// wheels stored alongside them) are left untouched, as are entries in use by requests made
+    /// through this client (or its clones).
+    pub async fn invalidate_where(
+        &self,
+        dir: impl Into<PathBuf>,
+        predicate: impl Fn(&CacheEntryMeta) -> bool + Send + 'static,
+    ) -> Result<usize, Error> {
+        let dir = dir.into();
+        let active_entries = self.active_entries.clone();
+        let removed = tokio::task::spawn_blocking(move || {
+            Self::invalidate_where_sync(&dir, predicate, Some(&active_entries))
+        })
+        .await
+        // This just forwards panics from the closure.
+        .unwrap()?;
+        for meta in &removed {
+            if let Some(weak_cache) = self.weak_cache.as_ref() {
+                weak_cache.remove(&meta.path);
+            }
+            if let Some(audit_log) = self.audit_log.as_ref() {
+                audit_log.evict(&meta.path, meta.url.as_ref(), Some(meta.size));
+            }
+        }
+        Ok(removed.len())
+    }
+
+    /// List the cache entries that [`CachedClient::invalidate_where`] would delete, without
+    /// deleting anything.
+    ///
+    /// Entries are selected exactly as for the real operation, so the preview matches it, unless
+    /// the cache changes in the meantime. The real operation skips entries that are in use when
+    /// it gets to them, which the preview can't anticipate, so it may list entries that are left
+    /// in place.
+    pub async fn invalidate_where_preview(
+        &self,
+        dir: impl Into<PathBuf>,
+        predicate: impl Fn(&CacheEntryMeta) -> bool + Send + 'static,
+    ) -> Result<EvictionPreview, Error> {
+        let dir = dir.into();
+        let entries =
+            tokio::task::spawn_blocking(move || Self::invalidate_where_sync(&dir, predicate, None))
+                .await
+                // This just forwards panics from the closure.
+                .unwrap()?;
+        Ok(EvictionPreview { entries })
+    }
+
+    /// Delete every cache entry under `dir` that is older than the given age.
+    ///
+    /// See [`CachedClient::invalidate_where`].
+    pub async fn prune_older_than(
+        &self,
+        dir: impl Into<PathBuf>,
+        age: Duration,
+    ) -> Result<usize, Error> {
+        self.invalidate_where(dir, evictor::older_than(age)).await
+    }
+
+    /// List the cache entries that [`CachedClient::prune_older_than`] would delete, without
+    /// deleting anything.
+    ///
+    /// See [`CachedClient::invalidate_where_preview`].
+    pub async fn prune_older_than_preview(
+        &self,
+        dir: impl Into<PathBuf>,
+        age: Duration,
+    ) -> Result<EvictionPreview, Error> {
+        self.invalidate_where_preview(dir, evictor::older_than(age))
+            .await
+    }
+
+    /// Delete every cache entry under `dir` that was fetched from the given host.
+    ///
+    /// See [`CachedClient::invalidate_where`].
+    pub async fn clean_host(&self, dir: impl Into<PathBuf>, host: &str) -> Result<usize, Error> {
+        self.invalidate_where(dir, evictor::from_host(host)).await
+    }
+
+    /// List the cache entries that [`CachedClient::clean_host`] would delete, without deleting
+    /// anything.
+    ///
+    /// See [`CachedClient::invalidate_where_preview`].
+    pub async fn clean_host_preview(
+        &self,
+        dir: impl Into<PathBuf>,
+        host: &str,
+    ) -> Result<EvictionPreview, Error> {
+        self.invalidate_where_preview(dir, evictor::from_host(host))
+            .await
+    }
+
+    /// List the cache entries that a sweep of an evictor started with the given policy (see
+    /// [`CachedClient::spawn_evictor`]) would delete, without deleting anything.
+    ///
+    /// The evictor skips entries that are in use when it gets to them, which the preview can't
+    /// anticipate, so it may list entries that the evictor only deletes on a later sweep.
+    pub async fn preview_evictor(&self, policy: &EvictionPolicy) -> Result<EvictionPreview, Error> {
+        let dir = policy.dir().to_path_buf();
+        let policy = policy.clone();
+        self.invalidate_where_preview(dir, move |entry| policy.matches(entry))
+            .await
+    }
+
+    /// Start deleting every cache entry that matches the given policy in the background.
+    ///
+    /// Unlike [`CachedClient::invalidate_where`], which deletes all matching entries in a single
+    /// sweep, the evictor examines entries incrementally, at the rate set by the policy, and
+    /// pauses while the client is busy. It sweeps the directory repeatedly until stopped via the
+    /// returned handle. Entries in use by requests made through this client (or its clones) are
+    /// never deleted; they're reconsidered on the next sweep.
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn spawn_evictor(&self, policy: EvictionPolicy) -> EvictorHandle {
+        evictor::spawn(
+            policy,
+            self.active_entries.clone(),
+            self.weak_cache.clone(),
+            self.audit_log.clone(),
+        )
+    }
+
+    /// Read the metadata of the cache entry at the given path, such as the URL it was fetched from
+    /// and the package it belongs to.
+    ///
+    /// Returns `None` if there's no file at the path, or it can't be read as a cache entry
+    /// written by a [`CachedClient`] (e.g., an unzipped wheel stored alongside one).
+    pub async fn inspect(&self, path: impl Into<PathBuf>) -> Result<Option<CacheEntryMeta>, Error> {
+        let path = path.into();
+        tokio::task::spawn_blocking(move || {
+            if !path.is_file() {
+                return Ok(None);
+            }
+            CacheEntryMeta::from_path(&path, SystemTime::now())
+        })
+        .await
+        // This just forwards panics from the closure.
+        .unwrap()
+    }
+
+    /// Returns the distinct names of the packages with entries under `dir`, in order.
+    ///
+    /// Names are recovered from the cached responses, as described on
+    /// [`CacheEntryMeta::package_name`]; entries for which no name can be recovered are skipped.
+    pub async fn cached_package_names(
+        &self,
+        dir: impl Into<PathBuf>,
+    ) -> Result<BTreeSet<PackageName>, Error> {
+        let dir = dir.into();
+        tokio::task::spawn_blocking(move || {
+            let mut names = BTreeSet::new();
+            Self::for_each_entry(&dir, |meta| {
+                names.extend(meta.package_name);
+                Ok(())
+            })?;
+            Ok(names)
+        })
+        .await
+        // This just forwards panics from the closure.
+        .unwrap()
+    }
+
+    /// Delete the broken cache entries and abandoned temporary files under `dir`, returning what
+    /// was deleted.
+    ///
+    /// An entry is broken if it fails to load, e.g., because it was truncated or written by an
+    /// earlier version of the format; such entries are deleted (along with their sidecars) just
+    /// as when they're read. Temporary files left behind by interrupted atomic writes are deleted
+    /// once they've gone unmodified for an hour.
+    ///
+    /// Only files with the extensions used for cache entries (`.rkyv`, `.msgpack`, and `.http`)
+    /// are examined. Since other processes may use the cache concurrently, only files that are
+    /// known to belong to this version of the client are deleted: entries written by a later
+    /// version of the format, and files that aren't cache entries at all (e.g., the metadata of
+    /// built wheels stored alongside them), are left untouched, as are entries in use by this
+    /// client, and entries that are rewritten while they're examined.
+    pub async fn gc(&self, dir: impl Into<PathBuf>) -> Result<GcReport, Error> {
+        let dir = dir.into();
+        let active_entries = self.active_entries.clone();
+        let (report, removed) =
+            tokio::task::spawn_blocking(move || Self::gc_sync(&dir, &active_entries))
+                .await
+                // This just forwards panics from the closure.
+                .unwrap()?;
+        for path in &removed {
+            if let Some(weak_cache) = self.weak_cache.as_ref() {
+                weak_cache.remove(path);
+            }
+            if let Some(audit_log) = self.audit_log.as_ref() {
+                audit_log.evict(path, None, None);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Delete the broken cache entries and abandoned temporary files under `dir`, as per
+    /// [`CachedClient::gc`], returning what was deleted along with the paths of the deleted
+    /// entries.
+    fn gc_sync(
+        dir: &Path,
+        active_entries: &ActiveEntries,
+    ) -> Result<(GcReport, Vec<PathBuf>), Error> {
+        let mut report = GcReport::default();
+        let mut removed = vec![];
+        let now = SystemTime::now();
+        for entry in walkdir::WalkDir::new(dir) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err)
+                    if err
+                        .io_error()
+                        .is_some_and(|err| err.kind() == std::io::ErrorKind::NotFound) =>
+                {
+                    continue;
+                }
+                Err(err) => return Err(ErrorKind::Io(err.into()).into()),
+            };
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let path = entry.path();
+            let metadata = match fs_err::metadata(path) {
+                Ok(metadata) => metadata,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(ErrorKind::Io(err).into()),
+            };
+
+            // The temporary files of atomic writes (see `uv_fs::write_atomic`).
+            if entry.file_name().to_string_lossy().starts_with(".tmp") {
+                let modified = metadata.modified().map_err(ErrorKind::Io)?;
+                if now.duration_since(modified).unwrap_or_default() < ABANDONED_TEMP_FILE_AGE {
+                    continue;
+                }
+                match fs_err::remove_file(path) {
+                    Ok(()) => {
+                        debug!("Removed abandoned temporary file at: {}", path.display());
+                        report.removed_temp_files += 1;
+                        report.bytes_reclaimed += metadata.len();
+                    }
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(err) => return Err(ErrorKind::CacheWrite(err).into()),
+                }
+                continue;
+            }
+
+            // Only files named as cache entries are examined; anything else (e.g., sidecars, which
+            // are only removed along with their entries) is left alone.
+            if !path
+                .extension()
+                .is_some_and(|ext| GC_EXTENSIONS.iter().any(|gc_ext| ext == *gc_ext))
+            {
+                continue;
+            }
+
+            // Check the trailer first, such that files that aren't cache entries are never read
+            // in full.
+            match DataWithCachePolicy::has_entry_trailer(path) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(ErrorKind::Io(err).into()),
+            }
+            let Err(err) = DataWithCachePolicy::from_path_sync(path) else {
+                continue;
+            };
+            // If the entry was rewritten in the meantime (e.g., by another process fetching it
+            // anew), it's no longer the one that failed to load.
+            let unchanged = fs_err::metadata(path).is_ok_and(|current| {
+                current.len() == metadata.len()
+                    && current.modified().ok() == metadata.modified().ok()
+            });
+            if !unchanged {
+                continue;
+            }
+            let sidecar_len = fs_err::metadata(sidecar::sidecar_path(path))
+                .map(|metadata| metadata.len())
+                .unwrap_or(0);
+            if active_entries.remove_unless_active(path)? {
+                debug!("Removed broken cache entry at {}: {err}", path.display());
+                report.removed_entries += 1;
+                report.bytes_reclaimed += metadata.len() + sidecar_len;
+                removed.push(path.to_path_buf());
+            }
+        }
+        Ok((report, removed))
+    }
+
+    /// Select the cache entries under `dir` for which the predicate returns `true`, deleting them
+    /// unless they're in use if given the client's active entries (or not at all, for a dry run),
+    /// and return them.
+    ///
+    /// Both [`CachedClient::invalidate_where`] and its preview go through here, such that they
+    /// always agree on which entries are selected.
+    fn invalidate_where_sync(
+        dir: &Path,
+        predicate: impl Fn(&CacheEntryMeta) -> bool,
+        active_entries: Option<&ActiveEntries>,
+    ) -> Result<Vec<CacheEntryMeta>, Error> {
+        let mut selected = vec![];
+        Self::for_each_entry(dir, |meta| {
+            if !predicate(&meta) {
+                return Ok(());
+            }
+            // Entries that are in use, or were removed in the meantime (e.g., by another
+            // process), aren't counted as removed here.
+            if let Some(active_entries) = active_entries {
+                if !active_entries.remove_unless_active(&meta.path)? {
+                    return Ok(());
+                }
+            }
+            selected.push(meta);
+            Ok(())
+        })?;
+        Ok(selected)
+    }
+
+    /// Call `f` with the metadata of every cache entry under `dir`, skipping files that can't be
+    /// read as cache entries.
+    fn for_each_entry(
+        dir: &Path,
+        mut f: impl FnMut(CacheEntryMeta) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let now = SystemTime::now();
+        for entry in walkdir::WalkDir::new(dir) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err)
+                    if err
+                        .io_error()
+                        .is_some_and(|err| err.kind() == std::io::ErrorKind::NotFound) =>
+                {
+                    continue;
+                }
+                Err(err) => return Err(ErrorKind::Io(err.into()).into()),
+            };
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let Some(meta) = CacheEntryMeta::from_path(entry.path(), now)? else {
+                continue;
+            };
+            f(meta)?;
+        }
+        Ok(())
+    }
+
+    /// Determine whether a request would be served from the cache, revalidated, or fetched anew,
+    /// without sending anything over the network.
+    ///
+    /// This mirrors the decisions made by [`CachedClient::get_cacheable`]. As there, a broken
+    /// cache entry is removed and treated as missing. If the cached response would be
+    /// revalidated, the plan includes why, which [`CachedClient::get_cacheable`] itself doesn't
+    /// compute.
+    pub async fn plan(
+        &self,
+        mut req: Request,
+        cache_entry: &CacheEntry,
+        cache_control: CacheControl,
+    ) -> CachePlan {
+        let cache_control = self.policy_overrides.apply(req.url(), cache_control);
+        let Some(cached) = self.read_cache(cache_entry).await else {
+            return match cache_control {
+                CacheControl::Offline => CachePlan::Unavailable,
+                _ => CachePlan::WouldFetch,
+            };
+        };
+        if matches!(cache_control, CacheControl::Offline) {
+            return CachePlan::FreshCache;
+        }
+        Self::apply_cache_control(&mut req, cache_control);
+        match Self::before_request(&cached.cache_policy, &mut req, cache_control) {
+            BeforeRequest::Fresh => CachePlan::FreshCache,
+            BeforeRequest::Stale(_) => match cache_control {
+                CacheControl::None
+                | CacheControl::MustRevalidate
+                | CacheControl::NoCache
+                | CacheControl::MaxAge(_) => {
+                    // Only `CacheControl::NoCache` revalidates a response that's otherwise fresh.
+                    let stale_reason = cached.cache_policy.stale_reason(&req);
+                    CachePlan::WouldRevalidate(stale_reason.unwrap_or(StaleReason::Forced))
+                }
+                CacheControl::AllowStale | CacheControl::Offline => CachePlan::FreshCache,
+            },
+            BeforeRequest::VaryMismatch | BeforeRequest::NoMatch => CachePlan::WouldFetch,
+        }
+    }
+
+    /// Apply the cache control header to the request, if necessary.
+    fn apply_cache_control(req: &mut Request, cache_control: CacheControl) {
+        match cache_control {
+            CacheControl::None | CacheControl::AllowStale | CacheControl::Offline => {}
+            CacheControl::MustRevalidate | CacheControl::NoCache => {
+                req.headers_mut().insert(
+                    http::header::CACHE_CONTROL,
+                    http::HeaderValue::from_static("no-cache"),
+                );
+            }
+            CacheControl::MaxAge(max_age) => {
+                req.headers_mut().insert(
+                    http::header::CACHE_CONTROL,
+                    http::HeaderValue::from_str(&format!("max-age={}", max_age.as_secs()))
+                        .expect("`max-age` directive is a valid header value"),
+                );
+            }
+        }
+    }
+
+    async fn read_cache(&self, cache_entry: &CacheEntry) -> Option<DataWithCachePolicy> {
+        let span = info_span!("read_and_parse_cache", file = %cache_entry.path().display());
+        match span
+            .in_scope(|| DataWithCachePolicy::from_path_async(cache_entry.path()))
+            .await
+        {
+            Ok(mut cached) => match self.unpack_payload(cache_entry, cached.data) {
+                Ok(data) => {
+                    cached.data = data;
+                    Some(cached)
+                }
+                Err(err) => {
+                    warn!(
+                        "Ignoring unreadable cache entry at {}: {err}",
+                        cache_entry.path().display()
+                    );
+                    None
+                }
+            },
+            Err(err) => {
+                warn!(
+                    "Broken cache entry at {}, removing: {err}",
+                    cache_entry.path().display()
+                );
+                let _ = fs_err::tokio::remove_file(&cache_entry.path()).await;
+                None
+            }
+        }
+    }
+
+    /// Check whether a cached entry remembers a "not found" response (see
+    /// [`CachedClientBuilder::cache_not_found`]).
+    ///
+    /// Fails with [`ErrorKind::NotFoundCached`] if the remembered response may be used for the
+    /// request. Otherwise, an entry that remembers a "not found" response is treated as missing,
+    /// and any other entry is returned as-is.
+    fn check_not_found(
+        &self,
+        req: &Request,
+        cache_control: CacheControl,
+        cached: Option<DataWithCachePolicy>,
+    ) -> Result<Option<DataWithCachePolicy>, Error> {
+        let Some(not_found) = cached
+            .as_ref()
+            .and_then(|cached| NotFound::decode(&cached.data))
+        else {
+            return Ok(cached);
+        };
+        let ignored = self.not_found_ttl.is_none()
+            || not_found.is_expired(SystemTime::now())
+            || matches!(
+                self.policy_overrides.apply(req.url(), cache_control),
+                CacheControl::MustRevalidate | CacheControl::NoCache
+            );
+        if ignored {
+            debug!("Ignoring cached `{}` for: {}", not_found.status, req.url());
+            return Ok(None);
+        }
+        debug!("Found cached `{}` for: {}", not_found.status, req.url());
+        self.stats.fresh(0);
+        CacheOutcome::Fresh.record();
+        Err(ErrorKind::NotFoundCached(req.url().clone()).into())
+    }
+
+    /// Remember a "not found" response to a request for the given cache entry, if enabled (see
+    /// [`CachedClientBuilder::cache_not_found`]).
+    ///
+    /// The result of the request is passed through as-is; failing to write the entry is only
+    /// logged.
+    async fn cache_not_found(
+        &self,
+        result: Result<CachedResponse, Error>,
+        url: &Url,
+        cache_entry: &CacheEntry,
+    ) -> Result<CachedResponse, Error> {
+        let Err(err) = result else {
+            return result;
+        };
+        let (Some(ttl), ErrorKind::RequestError(request_err)) = (self.not_found_ttl, err.kind())
+        else {
+            return Err(err);
+        };
+        let Some(not_found) = request_err
+            .status()
+            .and_then(|status| NotFound::new(status, ttl, SystemTime::now()))
+        else {
+            return Err(err);
+        };
+        debug!("Caching `{}` for: {url}", not_found.status);
+        if let Err(write_err) = self.write_not_found(not_found, url, cache_entry).await {
+            warn!(
+                "Failed to cache `{}` at {}: {write_err}",
+                not_found.status,
+                cache_entry.path().display()
+            );
+        }
+        Err(err)
+    }
+
+    /// Write a marker for a "not found" response in place of the given cache entry.
+    async fn write_not_found(
+        &self,
+        not_found: NotFound,
+        url: &Url,
+        cache_entry: &CacheEntry,
+    ) -> Result<(), Error> {
+        // The marker carries its own expiry, so the policy only needs to match the request.
+        let request = Request::new(http::Method::GET, url.clone());
+        let response = http::Response::builder()
+            .status(not_found.status)
+            .url(url.clone())
+            .body("")
+            .expect("the parts of a valid response are valid");
+        let cache_policy = CachePolicyBuilder::new(&request).build(&Response::from(response));
+        fs_err::tokio::create_dir_all(cache_entry.dir())
+            .await
+            .map_err(ErrorKind::CacheWrite)?;
+        let data = DataWithCachePolicy::serialize(
+            &cache_policy,
+            &self.pack_payload(
+                cache_entry,
+                &not_found.encode(),
+                CompressionHint::Compressible,
+            )?,
+        )?;
+        self.write_cache(cache_entry, data).await
+    }
+
+    /// Atomically write a serialized entry to the cache.
+    async fn write_cache(&self, cache_entry: &CacheEntry, data: Vec<u8>) -> Result<(), Error> {
+        self.reserve_entry(cache_entry).await?;
+        self.stats.written(data.len() as u64);
+        match self.temp_dir.as_deref() {
+            Some(temp_dir) => write_atomic_in(cache_entry.path(), data, temp_dir).await,
+            None => write_atomic(cache_entry.path(), data).await,
+        }
+        .map_err(ErrorKind::CacheWrite)?;
+        Ok(())
+    }
+
+    /// Make room for a new cache entry, as per [`CachedClientBuilder::max_entries`].
+    async fn reserve_entry(&self, cache_entry: &CacheEntry) -> Result<(), Error> {
+        let Some(entry_limit) = self.entry_limit.clone() else {
+            return Ok(());
+        };
+        let active_entries = self.active_entries.clone();
+        let path = cache_entry.path().to_path_buf();
+        let evicted =
+            tokio::task::spawn_blocking(move || entry_limit.reserve(&path, &active_entries))
+                .await
+                // This just forwards panics from the closure.
+                .unwrap()?;
+        for path in &evicted {
+            if let Some(weak_cache) = self.weak_cache.as_ref() {
+                weak_cache.remove(path);
+            }
+            if let Some(audit_log) = self.audit_log.as_ref() {
+                audit_log.evict(path, None, None);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if cache entries are encrypted at rest.
+    fn is_encrypted(&self) -> bool {
+        #[cfg(feature = "encryption")]
+        if self.encryption_key.is_some() {
+            return true;
+        }
+        false
+    }
+
+    /// Compress and then encrypt a [`Cacheable`] payload before it's written to the cache, as far
+    /// as either is enabled.
+    ///
+    /// Payloads that are already compressed, as per their [`CompressionHint`], are left as-is.
+    /// [`StreamCacheable`] payloads are never compressed, as they're read back from the entry
+    /// as-is (unless encrypted), and tend to be compressed already (e.g., wheels).
+    fn pack_payload<'data>(
+        &self,
+        cache_entry: &CacheEntry,
+        data: &'data [u8],
+        compression_hint: CompressionHint,
+    ) -> Result<Cow<'data, [u8]>, Error> {
+        let Some(compressed) = self
+            .compression_level
+            .filter(|_| compression_hint == CompressionHint::Compressible)
+            .and_then(|level| compression::compress(data, level))
+        else {
+            return self.seal_payload(cache_entry, data);
+        };
+        if self.is_encrypted() {
+            Ok(Cow::Owned(
+                self.seal_payload(cache_entry, &compressed)?.into_owned(),
+            ))
+        } else {
+            Ok(Cow::Owned(compressed))
+        }
+    }
+
+    /// Decrypt and then decompress a payload read from the cache, as far as it was encrypted or
+    /// compressed.
+    ///
+    /// Compressed payloads are decompressed regardless of whether compression is enabled.
+    fn unpack_payload(
+        &self,
+        cache_entry: &CacheEntry,
+        data: AlignedVec,
+    ) -> Result<AlignedVec, Error> {
+        let data = self.open_payload(cache_entry, data)?;
+        if compression::is_compressed(&data) {
+            compression::decompress(&data)
+        } else {
+            Ok(data)
+        }
+    }
+
+    /// Encrypt a payload before it's written to the given cache entry, if encryption is enabled.
+    ///
+    /// The payload is bound to the path of the entry, such that it can't be opened from any
+    /// other entry (see [`CachedClient::open_payload`]). As such, entries don't survive the cache
+    /// directory being moved; they're refetched instead.
+    fn seal_payload<'data>(
+        &self,
+        cache_entry: &CacheEntry,
+        data: &'data [u8],
+    ) -> Result<Cow<'data, [u8]>, Error> {
+        #[cfg(feature = "encryption")]
+        if let Some(encryption_key) = self.encryption_key.as_ref() {
+            let aad = cache_entry.path().as_os_str().as_encoded_bytes();
+            return Ok(Cow::Owned(encryption_key.seal(data, aad)?));
+        }
+        #[cfg(not(feature = "encryption"))]
+        let _ = cache_entry;
+        Ok(Cow::Borrowed(data))
+    }
+
+    /// Decrypt a payload read from the cache, if it was encrypted.
+    ///
+    /// Fails if the payload can't be decrypted (including if it was sealed for another entry), or
+    /// if encryption is enabled but the payload was written in the clear.
+    fn open_payload(
+        &self,
+        cache_entry: &CacheEntry,
+        data: AlignedVec,
+    ) -> Result<AlignedVec, Error> {
+        #[cfg(feature = "encryption")]
+        if let Some(encryption_key) = self.encryption_key.as_ref() {
+            let aad = cache_entry.path().as_os_str().as_encoded_bytes();
+            return encryption_key.open(&data, aad);
+        }
+        #[cfg(not(feature = "encryption"))]
+        let _ = cache_entry;
+        if encryption::is_sealed(&data) {
+            return Err(ErrorKind::CacheDecrypt.into());
+        }
+        Ok(data)
+    }
+
+    /// Determine whether the cached response can be used for the request, given the cache
+    /// control.
+    fn before_request(
+        cache_policy: &OwnedArchive<CachePolicy>,
+        req: &mut Request,
+        cache_control: CacheControl,
+    ) -> BeforeRequest {
+        match cache_control {
+            CacheControl::NoCache => cache_policy.before_revalidation(req),
+            CacheControl::MaxAge(max_age) => {
+                // `immutable` responses short-circuit, as with the `max-age` request directive.
+                if cache_policy.is_immutable() {
+                    return cache_policy.before_request(req);
+                }
+                if cache_policy.age(SystemTime::now()) > max_age {
+                    return cache_policy.before_revalidation(req);
+                }
+                // Within the given age, the server's freshness lifetime doesn't apply.
+                let stale_reason = cache_policy.stale_reason(req);
+                match cache_policy.before_request(req) {
+                    BeforeRequest::Stale(_)
+                        if matches!(
+                            stale_reason,
+                            Some(StaleReason::Expired | StaleReason::MustRevalidate)
+                        ) =>
+                    {
+                        BeforeRequest::Fresh
+                    }
+                    before_request => before_request,
+                }
+            }
+            CacheControl::None
+            | CacheControl::MustRevalidate
+            | CacheControl::AllowStale
+            | CacheControl::Offline => cache_policy.before_request(req),
+        }
+    }
+
+    /// Send a request given that we have a (possibly) stale cached response.
+    ///
+    /// If the cached response is valid but stale, then this will attempt a
+    /// revalidation request.
+    ///
+    /// If the cached response varies (as per its `Vary` header) on request headers that differ
+    /// for this request, it's for another variant of the resource, so a fresh request is sent
+    /// instead. Only the headers named by the cached response's `Vary` header are considered,
+    /// and `Vary: *` never matches. The fresh response isn't cached, such that it doesn't
+    /// overwrite the cached variant, which keeps serving the requests it does match.
+    #[instrument(
+        skip_all,
+        fields(url = req.url().as_str(), correlation_id = self.correlation_id_of(&req))
+    )]
+    async fn send_cached(
+        &self,
+        mut req: Request,
+        cache_control: CacheControl,
+        cached: DataWithCachePolicy,
+    ) -> Result<CachedResponse, Error> {
+        let cache_control = self.policy_overrides.apply(req.url(), cache_control);
+        if matches!(cache_control, CacheControl::Offline) {
+            debug!("Found cached response (while offline) for: {}", req.url());
+            return Ok(CachedResponse::FreshCache(cached));
+        }
+        Self::apply_cache_control(&mut req, cache_control);
+        Ok(
+            match Self::before_request(&cached.cache_policy, &mut req, cache_control) {
+                BeforeRequest::Fresh => {
+                    debug!("Found fresh response for: {}", req.url());
+                    CachedResponse::FreshCache(cached)
+                }
+                BeforeRequest::Stale(new_cache_policy_builder) => match cache_control {
+                    CacheControl::None
+                    | CacheControl::MustRevalidate
+                    | CacheControl::NoCache
+                    | CacheControl::MaxAge(_) => {
+                        debug!("Found stale response for: {}", req.url());
+                        self.send_cached_handle_stale(req, cached, new_cache_policy_builder)
+                            .await?
+                    }
+                    CacheControl::AllowStale | CacheControl::Offline => {
+                        debug!("Found stale (but allowed) response for: {}", req.url());
+                        CachedResponse::FreshCache(cached)
+                    }
+                },
+                BeforeRequest::VaryMismatch => {
+                    debug!("Cached response is for another variant of: {}", req.url());
+                    self.fresh_request(req).await?.uncacheable()
+                }
+                BeforeRequest::NoMatch => {
+                    // This shouldn't happen; if it does, we'll override the cache.
+                    warn!(
+                        "Cached request doesn't match current request for: {}",
+                        req.url()
+                    );
+                    self.fresh_request(req).await?
+                }
+            },
+        )
+    }
+
+    /// Send a request given that we have no cached response, unless the request is offline (see
+    /// [`CacheControl::Offline`]).
+    async fn send_uncached(
+        &self,
+        req: Request,
+        cache_control: CacheControl,
+    ) -> Result<CachedResponse, Error> {
+        debug!("No cache entry for: {}", req.url());
+        match self.policy_overrides.apply(req.url(), cache_control) {
+            CacheControl::Offline => Err(ErrorKind::Offline(req.url().to_string()).into()),
+            _ => self.fresh_request(req).await,
+        }
+    }
+
+    /// Send a request given that we have a (possibly) stale cached response, checking whether
+    /// it's still current with a `HEAD` request, as per [`CachedClient::get_cacheable_probed`].
+    async fn send_probed(
+        &self,
+        req: Request,
+        cached: DataWithCachePolicy,
+    ) -> Result<CachedResponse, Error> {
+        let cache_control = self.policy_overrides.apply(req.url(), CacheControl::None);
+        // Anything but a stale response that needs revalidating takes the usual path.
+        let stale = req.try_clone().is_some_and(|mut req| {
+            matches!(
+                Self::before_request(&cached.cache_policy, &mut req, cache_control),
+                BeforeRequest::Stale(_)
+            )
+        });
+        if !stale
+            || matches!(
+                cache_control,
+                CacheControl::AllowStale | CacheControl::Offline
+            )
+        {
+            return self.send_cached(req, CacheControl::None, cached).await;
+        }
+
+        let url = req.url().clone();
+        let mut probe = Request::new(http::Method::HEAD, url.clone());
+        *probe.headers_mut() = req.headers().clone();
+        Self::apply_cache_control(&mut probe, cache_control);
+        Self::redirect_to_final_url(&mut probe, &cached.cache_policy);
+        debug!("Sending probe request for: {url}");
+        let cache_policy_builder = CachePolicyBuilder::new(&req);
+        let response = match self
+            .execute(probe)
+            .instrument(info_span!("probe_request", url = url.as_str()))
+            .await
+            .and_then(|response| {
+                response
+                    .error_for_status()
+                    .map_err(|err| ErrorKind::RequestError(err).into())
+            }) {
+            Ok(response) => response,
+            Err(err) if self.can_serve_stale_on_error(&cached, &err) => {
+                warn!("Failed to probe the cached response for {url}, serving it stale: {err}");
+                return Ok(CachedResponse::FreshCache(cached));
+            }
+            Err(err) => return Err(err),
+        };
+        match cached
+            .cache_policy
+            .after_probe(cache_policy_builder, &response)
+        {
+            Some(mut new_policy) => {
+                debug!("Found unchanged validators for: {url}");
+                self.audit_revalidate(&url, &response);
+                self.apply_default_max_age(&mut new_policy);
+                Ok(CachedResponse::NotModified {
+                    cached,
+                    new_policy: Box::new(new_policy),
+                })
+            }
+            None => {
+                debug!("Found changed validators for: {url}");
+                self.fresh_request(req).await
+            }
+        }
+    }
+
+    async fn send_cached_handle_stale(
+        &self,
+        mut req: Request,
+        cached: DataWithCachePolicy,
+        new_cache_policy_builder: CachePolicyBuilder,
+    ) -> Result<CachedResponse, Error> {
+        Self::redirect_to_final_url(&mut req, &cached.cache_policy);
+        let url = req.url().clone();
+        let method = req.method().clone();
+        debug!("Sending revalidation request for: {url}");
+        let response = match self
+            .execute(req)
+            .instrument(info_span!("revalidation_request", url = url.as_str()))
+            .await
+            .and_then(|response| {
+                response
+                    .error_for_status()
+                    .map_err(|err| ErrorKind::RequestError(err).into())
+            }) {
+            Ok(response) => response,
+            Err(err) if self.can_serve_stale_on_error(&cached, &err) => {
+                warn!(
+                    "Failed to revalidate the cached response for {url}, serving it stale: {err}"
+                );
+                return Ok(CachedResponse::FreshCache(cached));
+            }
+            Err(err) => return Err(err),
+        };
+        match cached
+            .cache_policy
+            .after_response(new_cache_policy_builder, &response)
+        {
+            AfterResponse::NotModified(mut new_policy) => {
+                debug!("Found not-modified response for: {url}");
+                self.audit_revalidate(&url, &response);
+                self.apply_default_max_age(&mut new_policy);
+                Ok(CachedResponse::NotModified {
+                    cached,
+                    new_policy: Box::new(new_policy),
+                })
+            }
+            AfterResponse::Modified(mut new_policy) => {
+                debug!("Found modified response for: {url}");
+                self.apply_default_max_age(&mut new_policy);
+                let response = self.audit_fetch(response);
+                let (response, length_check) = self.check_content_length(&method, response);
+                Ok(CachedResponse::ModifiedOrNew {
+                    response,
+                    cache_policy: new_policy
+                        .to_archived()
+                        .is_storable()
+                        .then(|| Box::new(new_policy)),
+                    length_check,
+                })
+            }
+        }
+    }
+
+    /// Send a revalidation request to the URL the cached response was served from, if it was
+    /// redirected there, rather than to the URL it was requested from.
+    ///
+    /// As when `reqwest` follows a redirect, credentials aren't sent to another origin.
+    fn redirect_to_final_url(req: &mut Request, cache_policy: &OwnedArchive<CachePolicy>) {
+        let Ok(mut final_url) = Url::parse(cache_policy.final_url()) else {
+            return;
+        };
+        if final_url.origin() == req.url().origin() {
+            // Keep any credentials from the request's URL, which the response's URL may lack.
+            if final_url.username().is_empty() && final_url.password().is_none() {
+                let _ = final_url.set_username(req.url().username());
+                let _ = final_url.set_password(req.url().password());
+            }
+        } else {
+            remove_credentials(req);
+        }
+        if final_url == *req.url() {
+            return;
+        }
+        debug!(
+            "Revalidating {} at its redirect target: {final_url}",
+            req.url()
+        );
+        *req.url_mut() = final_url;
+    }
+
+    /// Record a new response in the audit log, if enabled, once its body has been read.
+    fn audit_fetch(&self, response: Response) -> Response {
+        match self.audit_log.as_ref() {
+            Some(audit_log) => audit_log.fetch(response),
+            None => response,
+        }
+    }
+
+    /// Record the revalidation of a cached response in the audit log, if enabled.
+    fn audit_revalidate(&self, url: &Url, response: &Response) {
+        if let Some(audit_log) = self.audit_log.as_ref() {
+            audit_log.revalidate(url, response.status().as_u16());
+        }
+    }
+
+    /// Prepare to check the body of a new response against its declared `Content-Length`, as per
+    /// [`CachedClientBuilder::verify_content_length`].
+    fn check_content_length(
+        &self,
+        method: &http::Method,
+        response: Response,
+    ) -> (Response, Option<ContentLengthCheck>) {
+        if self.verify_content_length {
+            ContentLengthCheck::wrap(method, response)
+        } else {
+            (response, None)
+        }
+    }
+
+    /// Apply [`CachedClientBuilder::default_max_age`] to the cache policy of a new response.
+    fn apply_default_max_age(&self, cache_policy: &mut CachePolicy) {
+        if let Some(max_age) = self.default_max_age {
+            cache_policy.set_default_max_age(max_age);
+        }
+    }
+
+    /// Returns `true` if the cached response should be served, stale, in place of a failed
+    /// revalidation, as per [`CachedClientBuilder::stale_on_error`].
+    fn can_serve_stale_on_error(&self, cached: &DataWithCachePolicy, err: &Error) -> bool {
+        let Some(max_age) = self.stale_on_error else {
+            return false;
+        };
+        let transient = match err.kind() {
+            ErrorKind::RequestError(err) => err.status().map_or(true, |status| {
+                status.is_server_error() || status == http::StatusCode::TOO_MANY_REQUESTS
+            }),
+            _ => true,
+        };
+        transient
+            && (200..300).contains(&cached.cache_policy.status())
+            && cached.cache_policy.age(SystemTime::now()) <= max_age
+    }
+
+    /// Returns the correlation ID of a request: the one it carries, if any, or the client's.
+    fn correlation_id_of<'a>(&'a self, req: &'a Request) -> &'a str {
+        req.headers()
+            .get(CORRELATION_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or(&self.correlation_id)
+    }
+
+    /// Send a request, recording any rate limit advertised in the response and, if throttling is
+    /// enabled, first waiting out the host's cooldown.
+    ///
+    /// If the response asks to retry the request later (see [`CachedClientBuilder::retry_after`]),
+    /// the request is retried once the indicated time has passed.
+    ///
+    /// The request is identified with the client's correlation ID, unless it carries its own, and
+    /// carries the headers configured for its host (see [`CachedClientBuilder::host_headers`]).
+    #[instrument(
+        skip_all,
+        fields(url = req.url().as_str(), correlation_id = self.correlation_id_of(&req))
+    )]
+    async fn execute(&self, mut req: Request) -> Result<Response, Error> {
+        if let Some(mirror) = self.mirror.as_ref() {
+            mirror.apply(&mut req);
+        }
+        self.host_headers.apply(&mut req);
+        if !req.headers().contains_key(CORRELATION_ID_HEADER) {
+            if let Ok(value) = http::HeaderValue::from_str(&self.correlation_id) {
+                req.headers_mut().insert(CORRELATION_ID_HEADER, value);
+            } else {
+                warn!(
+                    "Not sending invalid correlation ID: {:?}",
+                    self.correlation_id
+                );
+            }
+        }
+        let url = req.url().clone();
+        let mut retries = 0;
+        loop {
+            if let Some(cooldown) = self.rate_limiter.cooldown(&url) {
+                tokio::time::sleep(cooldown).await;
+            }
+            let retry = if retries < self.retry_after.max_retries {
+                req.try_clone()
+            } else {
+                None
+            };
+            let permit = match self.concurrency_limit.as_ref() {
+                // The semaphore is never closed.
+                Some(semaphore) => Some(semaphore.clone().acquire_owned().await.unwrap()),
+                None => None,
+            };
+            let response = self
+                .client
+                .execute(req)
+                .await
+                .map_err(ErrorKind::from_middleware)?;
+            self.rate_limiter.observe(&url, response.headers());
+            let Some((retry, delay)) = retry.zip(rate_limit::retry_after(
+                response.status(),
+                response.headers(),
+                SystemTime::now(),
+            )) else {
+                // The request is in flight until its body has been read, so the permit is held
+                // until then (or until the body is dropped).
+                return Ok(match permit {
+                    Some(permit) => observe_body(response, ConcurrencyPermit(Some(permit))),
+                    None => response,
+                });
+            };
+            let delay = delay.min(self.retry_after.max_delay);
+            debug!(
+                "Received {} for {url}; retrying in {:.2}s",
+                response.status(),
+                delay.as_secs_f32()
+            );
+            drop(response);
+            drop(permit);
+            tokio::time::sleep(delay).await;
+            req = retry;
+            retries += 1;
+        }
+    }
+
+    #[instrument(
+        skip_all,
+        fields(url = req.url().as_str(), correlation_id = self.correlation_id_of(&req))
+    )]
+    async fn fresh_request(&self, req: Request) -> Result<CachedResponse, Error> {
+        trace!("Sending fresh {} request for {}", req.method(), req.url());
+        let method = req.method().clone();
+        let cache_policy_builder = CachePolicyBuilder::new(&req);
+        let response = self
+            .execute(req)
+            .await?
+            .error_for_status()
+            .map_err(ErrorKind::RequestError)?;
+        let mut cache_policy = cache_policy_builder.build(&response);
+        self.apply_default_max_age(&mut cache_policy);
+        let response = self.audit_fetch(response);
+        let (response, length_check) = self.check_content_length(&method, response);
+        Ok(CachedResponse::ModifiedOrNew {
+            response,
+            cache_policy: cache_policy
+                .to_archived()
+                .is_storable()
+                .then(|| Box::new(cache_policy)),
+            length_check,
+        })
+    }
+}
+
+/// Remove the credentials from the headers of a request that's being sent to another origin than
+/// the one it was meant for.
+pub(crate) fn remove_credentials(req: &mut Request) {
+    for header in [
+        http::header::AUTHORIZATION,
+        http::header::COOKIE,
+        http::header::PROXY_AUTHORIZATION,
+        http::header::WWW_AUTHENTICATE,
+    ] {
+        req.headers_mut().remove(header);
+    }
+}
+
+/// Returns the number of bytes to record as downloaded for a new response.
+///
+/// Wrapping a body for a [`ContentLengthCheck`] hides its length from [`Response::content_length`],
+/// so it's taken from the check instead.
+fn fetched_len(response: &Response, length_check: Option<&ContentLengthCheck>) -> Option<u64> {
+    length_check
+        .map(ContentLengthCheck::declared)
+        .or_else(|| response.content_length())
+}
+
+/// Copy serialized payload bytes into an aligned buffer, as [`Cacheable::from_aligned_bytes`]
+/// expects.
+fn aligned_bytes(bytes: &[u8]) -> AlignedVec {
+    let mut aligned = AlignedVec::with_capacity(bytes.len());
+    aligned.extend_from_slice(bytes);
+    aligned
+}
+
+/// Write a [`StreamCacheable`] payload that may stream the body of a response, and then check
+/// the body against its declared `Content-Length`.
+///
+/// A body of the wrong length takes precedence over any error it caused while writing.
+async fn write_payload<Payload: StreamCacheable, W: AsyncWrite + Unpin + Send>(
+    payload: Payload,
+    wtr: &mut W,
+    length_check: Option<&ContentLengthCheck>,
+) -> Result<(), Error> {
+    let result = payload.write_to(wtr).await;
+    if let Some(length_check) = length_check {
+        length_check.verify()?;
+    }
+    result
+}
+
+/// A [`StreamCacheable`] payload that was read from or written to the cache.
+#[derive(Debug)]
+enum StreamedPayload {
+    /// The payload occupies the first `len` bytes of the given file.
+    File(std::fs::File, u64),
+    /// The payload occupies the given sidecar file in its entirety.
+    Sidecar(std::fs::File, SidecarRef),
+    /// The payload is held in memory, e.g., because the cache entry is encrypted.
+    Memory(Vec<u8>),
+}
+
+impl StreamedPayload {
+    /// The length of the payload, in bytes.
+    fn len(&self) -> u64 {
+        match self {
+            StreamedPayload::File(_, len) | StreamedPayload::Sidecar(_, SidecarRef { len, .. }) => {
+                *len
+            }
+            StreamedPayload::Memory(data) => data.len() as u64,
+        }
+    }
+
+    /// Read the payload back via [`StreamCacheable::read_from`].
+    async fn read<Payload: StreamCacheable>(self) -> Result<Payload::Target, Error> {
+        match self {
+            StreamedPayload::File(file, len)
+            | StreamedPayload::Sidecar(file, SidecarRef { len, .. }) => {
+                let mut file = tokio::fs::File::from_std(file);
+                file.rewind().await.map_err(ErrorKind::Io)?;
+                Payload::read_from(BufReader::new(file).take(len)).await
+            }
+            StreamedPayload::Memory(data) => Payload::read_from(std::io::Cursor::new(data)).await,
+        }
+    }
+}
+
+/// A writer that computes the length and checksum of everything written through it.
+struct ChecksumWriter<W> {
+    inner: W,
+    hasher: SeaHasher,
+    len: u64,
+}
+
+impl<W> ChecksumWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: SeaHasher::new(),
+            len: 0,
+        }
+    }
+
+    fn update(&mut self, buf: &[u8]) {
+        self.hasher.write(buf);
+        self.len += buf.len() as u64;
+    }
+}
+
+impl<W: std::io::Write> std::io::Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for ChecksumWriter<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let n = ready!(Pin::new(&mut self.inner).poll_write(cx, buf))?;
+        self.update(&buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[derive(Debug)]
+enum CachedResponse {
+    /// The cached response is fresh without an HTTP request (e.g. age < max-age).
+    FreshCache(DataWithCachePolicy),
+    /// The cached response is fresh after an HTTP request (e.g. 304 not modified)
+    NotModified {
+        /// The cached response (with its old cache policy).
+        cached: DataWithCachePolicy,
+        /// The new [`CachePolicy`] is used to determine if the response
+        /// is fresh or stale when making subsequent requests for the same
+        /// resource. This policy should overwrite the old policy associated
+        /// with the cached response. In particular, this new policy is derived
+        /// from data received in a revalidation response, which might change
+        /// the parameters of cache behavior.
+        ///
+        /// The policy is large (352 bytes at time of writing), so we reduce
+        /// the stack size by boxing it.
+        new_policy: Box<CachePolicy>,
+    },
+    /// There was no prior cached response or the cache was outdated
+    ///
+    /// The cache policy is `None` if it isn't storable
+    ModifiedOrNew {
+        /// The response received from the server.
+        response: Response,
+        /// The [`CachePolicy`] is used to determine if the response is fresh or
+        /// stale when making subsequent requests for the same resource.
+        ///
+        /// The policy is large (352 bytes at time of writing), so we reduce
+        /// the stack size by boxing it.
+        cache_policy: Option<Box<CachePolicy>>,
+        /// The check of the response body against its declared `Content-Length`, if any.
+        length_check: Option<ContentLengthCheck>,
+    },
+}
+
+impl CachedResponse {
+    /// Prevent a new response from being written to the cache entry.
+    fn uncacheable(self) -> Self {
+        match self {
+            CachedResponse::ModifiedOrNew {
+                response,
+                length_check,
+                ..
+            } => CachedResponse::ModifiedOrNew {
+                response,
+                cache_policy: None,
+                length_check,
+            },
+            cached_response => cached_response,
+        }
+    }
+}
+
+/// The version of the format of cache entries, including the payloads stored in them.
+///
+/// Bump this whenever a change would cause existing entries to be misread, e.g., a change to the
+/// fields of a cached payload.
+pub(crate) const CACHE_VERSION: u16 = 3;
+
+/// Represents an arbitrary data blob with an associated HTTP cache policy.
+///
+/// The cache policy is used to determine whether the data blob is stale or
+/// not.
+///
+/// # Format
+///
+/// This type encapsulates the format for how blobs of data are stored on
+/// disk. The format is very simple. First, the blob of data is written as-is,
+/// followed by its checksum as a 64-bit little endian integer. Second, the
+/// archived representation of a `CachePolicy` is written. Thirdly, the length,
+/// in bytes, of the archived `CachePolicy` is written as a 64-bit little
+/// endian integer. Finally, the version of the format (i.e., [`CACHE_VERSION`])
+/// is written as a 16-bit little endian integer.
+///
+/// Entries written with any other version (including those from before the
+/// version was written, which read as version 0) fail to load, and so are
+/// treated like broken entries: removed, and fetched anew.
+///
+/// The checksum is verified whenever the data is read into memory, such
+/// that a truncated or otherwise corrupt entry is treated like any other
+/// broken entry, rather than being decoded. Payloads that are streamed from
+/// the entry are only verified on request (see
+/// [`CachedClientBuilder::verify_immutable`]).
+///
+/// Reading the format is done via an `AlignedVec` so that `rkyv` can correctly
+/// read the archived representation of the data blob. The cache policy is
+/// split into its own `AlignedVec` allocation.
+///
+/// # Future ideas
+///
+/// This format was also chosen because it should in theory permit rewriting
+/// the cache policy without needing to rewrite the data blob if the blob has
+/// not changed. For example, this case occurs when a revalidation request
+/// responds with HTTP 304 NOT MODIFIED. At time of writing, this is not yet
+/// implemented because 1) the synchronization specifics of mutating a cache
+/// file have not been worked out and 2) it's not clear if it's a win.
+///
+/// An alternative format would be to write the cache policy and the
+/// blob in two distinct files. This would avoid needing to worry about
+/// synchronization, but it means reading two files instead of one for every
+/// cached response in the fast path. It's unclear whether it's worth it.
+/// (Experiments have not yet been done.)
+///
+/// Another approach here would be to memory map the file and rejigger
+/// `OwnedArchive` (or create a new type) that works with a memory map instead
+/// of an `AlignedVec`. This will require care to ensure alignment is handled
+/// correctly. This approach has not been litigated yet. I did not start with
+/// it because experiments with ripgrep have tended to show that (on Linux)
+/// memory mapping a bunch of small files ends up being quite a bit slower than
+/// just reading them on to the heap.
+#[derive(Debug)]
+pub struct DataWithCachePolicy {
+    pub data: AlignedVec,
+    cache_policy: OwnedArchive<CachePolicy>,
+    checksum: u64,
+}
+
+impl DataWithCachePolicy {
+    /// Loads cached data and its associated HTTP cache policy from the given
+    /// file path in an asynchronous fashion (via `spawn_blocking`).
+    ///
+    /// # Errors
+    ///
+    /// If the given byte buffer is not in a valid format or if reading the
+    /// file given fails, then this returns an error.
+    async fn from_path_async(path: &Path) -> Result<DataWithCachePolicy, Error> {
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || DataWithCachePolicy::from_path_sync(&path))
+            .await
+            // This just forwards panics from the closure.
+            .unwrap()
+    }
+
+    /// Loads cached data and its associated HTTP cache policy from the given
+    /// file path in a synchronous fashion.
+    ///
+    /// # Errors
+    ///
+    /// If the given byte buffer is not in a valid format or if reading the
+    /// file given fails, then this returns an error.
+    fn from_path_sync(path: &Path) -> Result<DataWithCachePolicy, Error> {
+        let file = fs_err::File::open(path).map_err(ErrorKind::Io)?;
+        // Note that we don't wrap our file in a buffer because it will just
+        // get passed to AlignedVec::extend_from_reader, which doesn't benefit
+        // from an intermediary buffer. In effect, the AlignedVec acts as the
+        // buffer.
+        DataWithCachePolicy::from_reader(file)
+    }
+
+    /// Loads the HTTP cache policy associated with cached data from the given
+    /// file path, without reading the data itself.
+    ///
+    /// Returns the cache policy (with empty data), along with the open file
+    /// and the length of the data at its start.
+    ///
+    /// # Errors
+    ///
+    /// If the file is not in a valid format or if reading it fails, then this
+    /// returns an error.
+    pub(crate) fn from_path_without_data(
+        path: &Path,
+    ) -> Result<(DataWithCachePolicy, fs_err::File, u64), Error> {
+        let invalid = |total: u64| {
+            let msg = format!(
+                "invalid cache entry: data-with-cache-policy file of {total} bytes \
+                 is too short for its cache policy",
+            );
+            Error::from(ErrorKind::ArchiveRead(msg))
+        };
+        let mut file = fs_err::File::open(path).map_err(ErrorKind::Io)?;
+        let total = file.seek(SeekFrom::End(0)).map_err(ErrorKind::Io)?;
+        let mut buf = [0; 8];
+        if total < 18 {
+            return Err(invalid(total));
+        }
+        file.seek(SeekFrom::End(-10)).map_err(ErrorKind::Io)?;
+        file.read_exact(&mut buf).map_err(ErrorKind::Io)?;
+        let cache_policy_len = u64::from_le_bytes(buf);
+        let mut version = [0; 2];
+        file.read_exact(&mut version).map_err(ErrorKind::Io)?;
+        DataWithCachePolicy::check_version(u16::from_le_bytes(version))?;
+        let Some(data_len) = (total - 18).checked_sub(cache_policy_len) else {
+            return Err(invalid(total));
+        };
+        file.seek(SeekFrom::Start(data_len))
+            .map_err(ErrorKind::Io)?;
+        file.read_exact(&mut buf).map_err(ErrorKind::Io)?;
+        let checksum = u64::from_le_bytes(buf);
+        let mut cache_policy_bytes = AlignedVec::new();
+        cache_policy_bytes
+            .extend_from_reader(&mut (&mut file).take(cache_policy_len))
+            .map_err(ErrorKind::Io)?;
+        let cached = DataWithCachePolicy {
+            data: AlignedVec::new(),
+            cache_policy: OwnedArchive::new(cache_policy_bytes)?,
+            checksum,
+        };
+        Ok((cached, file, data_len))
+    }
+
+    /// Returns `true` if the file at the given path ends like a cache entry
+    /// written with this or an earlier version of the format, i.e., with the
+    /// length of a cache policy that fits in the file, followed by a version
+    /// no later than [`CACHE_VERSION`].
+    ///
+    /// This doesn't mean that the entry can be loaded; it's used to tell
+    /// broken entries apart from files that aren't cache entries at all.
+    fn has_entry_trailer(path: &Path) -> std::io::Result<bool> {
+        let mut file = fs_err::File::open(path)?;
+        let total = file.seek(SeekFrom::End(0))?;
+        if total < 18 {
+            return Ok(false);
+        }
+        file.seek(SeekFrom::End(-10))?;
+        let mut trailer = [0; 10];
+        file.read_exact(&mut trailer)?;
+        let cache_policy_len =
+            u64::from_le_bytes(trailer[..8].try_into().expect("length is 8 bytes"));
+        let version = u16::from_le_bytes(trailer[8..].try_into().expect("version is 2 bytes"));
+        Ok((1..=CACHE_VERSION).contains(&version)
+            && cache_policy_len > 0
+            && cache_policy_len <= total - 18)
+    }
+
+    /// Loads cached data and its associated HTTP cache policy from the given
+    /// reader.
+    ///
+    /// # Errors
+    ///
+    /// If the given byte buffer is not in a valid format or if the reader
+    /// fails, then this returns an error.
+    pub fn from_reader(mut rdr: impl std::io::Read) -> Result<DataWithCachePolicy, Error> {
+        let mut aligned_bytes = rkyv::util::AlignedVec::new();
+        aligned_bytes
+            .extend_from_reader(&mut rdr)
+            .map_err(ErrorKind::Io)?;
+        DataWithCachePolicy::from_aligned_bytes(aligned_bytes)
+    }
+
+    /// Loads cached data and its associated HTTP cache policy form an in
+    /// memory byte buffer.
+    ///
+    /// # Errors
+    ///
+    /// If the given byte buffer is not in a valid format, then this
+    /// returns an error.
+    fn from_aligned_bytes(mut bytes: AlignedVec) -> Result<DataWithCachePolicy, Error> {
+        DataWithCachePolicy::deserialize_version(&mut bytes)?;
+        let cache_policy = DataWithCachePolicy::deserialize_cache_policy(&mut bytes)?;
+        let checksum = DataWithCachePolicy::deserialize_checksum(&mut bytes)?;
+        let cached = DataWithCachePolicy {
+            data: bytes,
+            cache_policy,
+            checksum,
+        };
+        if !cached.checksum_matches() {
+            return Err(
+                ErrorKind::ArchiveRead("data doesn't match its checksum".to_string()).into(),
+            );
+        }
+        Ok(cached)
+    }
+
+    /// Returns `true` if the data matches the checksum it was written with,
+    /// i.e., if it hasn't been corrupted since.
+    fn checksum_matches(&self) -> bool {
+        seahash::hash(&self.data) == self.checksum
+    }
+
+    /// Serializes the given cache policy and arbitrary data blob to an in
+    /// memory byte buffer.
+    ///
+    /// # Errors
+    ///
+    /// If there was a problem converting the given cache policy to its
+    /// serialized representation, then this routine will return an error.
+    pub(crate) fn serialize(cache_policy: &CachePolicy, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut buf = vec![];
+        DataWithCachePolicy::serialize_to_writer(cache_policy, data, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Serializes the given cache policy and arbitrary data blob to the given
+    /// writer.
+    ///
+    /// # Errors
+    ///
+    /// If there was a problem converting the given cache policy to its
+    /// serialized representation or if the writer returns an error, then
+    /// this routine will return an error.
+    fn serialize_to_writer(
+        cache_policy: &CachePolicy,
+        data: &[u8],
+        mut wtr: impl std::io::Write,
+    ) -> Result<(), Error> {
+        wtr.write_all(data).map_err(ErrorKind::Io)?;
+        DataWithCachePolicy::serialize_trailer(cache_policy, seahash::hash(data), wtr)
+    }
+
+    /// Serializes everything that follows the data blob, i.e., the given
+    /// checksum of the data and the cache policy, to the given writer.
+    ///
+    /// # Errors
+    ///
+    /// See [`DataWithCachePolicy::serialize_to_writer`].
+    fn serialize_trailer(
+        cache_policy: &CachePolicy,
+        checksum: u64,
+        mut wtr: impl std::io::Write,
+    ) -> Result<(), Error> {
+        wtr.write_all(&checksum.to_le_bytes())
+            .map_err(ErrorKind::Io)?;
+        DataWithCachePolicy::serialize_cache_policy(cache_policy, wtr)
+    }
+
+    /// Serializes the given cache policy, followed by its length and the
+    /// version of the format, to the given writer.
+    ///
+    /// # Errors
+    ///
+    /// See [`DataWithCachePolicy::serialize_to_writer`].
+    fn serialize_cache_policy(
+        cache_policy: &CachePolicy,
+        mut wtr: impl std::io::Write,
+    ) -> Result<(), Error> {
+        let cache_policy_archived = OwnedArchive::from_unarchived(cache_policy)?;
+        let cache_policy_bytes = OwnedArchive::as_bytes(&cache_policy_archived);
+        wtr.write_all(cache_policy_bytes).map_err(ErrorKind::Io)?;
+        let len = u64::try_from(cache_policy_bytes.len()).map_err(|_| {
+            let msg = format!(
+                "failed to represent {} (length of cache policy) in a u64",
+                cache_policy_bytes.len()
+            );
+            ErrorKind::Io(std::io::Error::other(msg))
+        })?;
+        wtr.write_all(&len.to_le_bytes()).map_err(ErrorKind::Io)?;
+        wtr.write_all(&CACHE_VERSION.to_le_bytes())
+            .map_err(ErrorKind::Io)?;
+        Ok(())
+    }
+
+    /// Deserializes the version of the format off the end of the given
+    /// bytes, and checks that it's the current version. Upon success, the
+    /// given bytes will no longer contain the version.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the bytes are too short to contain a version,
+    /// or if the version doesn't match [`CACHE_VERSION`].
+    fn deserialize_version(bytes: &mut AlignedVec) -> Result<(), Error> {
+        let Some(version_start) = bytes.len().checked_sub(2) else {
+            let msg = format!(
+                "data-with-cache-policy buffer should be at least 2 bytes \
+                 in length, but is {} bytes",
+                bytes.len(),
+            );
+            return Err(ErrorKind::ArchiveRead(msg).into());
+        };
+        let version_bytes =
+            <[u8; 2]>::try_from(&bytes[version_start..]).expect("version is 2 bytes");
+        DataWithCachePolicy::check_version(u16::from_le_bytes(version_bytes))?;
+        bytes.resize(version_start, 0);
+        Ok(())
+    }
+
+    /// Returns an error if the given version of the format isn't the
+    /// current one.
+    fn check_version(version: u16) -> Result<(), Error> {
+        if version == CACHE_VERSION {
+            Ok(())
+        } else {
+            Err(ErrorKind::CacheVersionMismatch {
+                found: version,
+                expected: CACHE_VERSION,
+            }
+            .into())
+        }
+    }
+
+    /// Deserializes a `OwnedArchive<CachePolicy>` off the end of the given
+    /// aligned bytes. Upon success, the given bytes will only contain the
+    /// data itself. The bytes representing the cached policy will have been
+    /// removed.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the cache policy could not be deserialized
+    /// from the end of the given bytes.
+    fn deserialize_cache_policy(
+        bytes: &mut AlignedVec,
+    ) -> Result<OwnedArchive<CachePolicy>, Error> {
+        let len = DataWithCachePolicy::deserialize_cache_policy_len(bytes)?;
+        let cache_policy_bytes_start = bytes.len() - (len + 8);
+        let cache_policy_bytes = &bytes[cache_policy_bytes_start..][..len];
+        let mut cache_policy_bytes_aligned = AlignedVec::with_capacity(len);
+        cache_policy_bytes_aligned.extend_from_slice(cache_policy_bytes);
+        assert!(
+            cache_policy_bytes_start <= bytes.len(),
+            "slicing cache policy should result in a truncation"
+        );
+        // Technically this will keep the extra capacity used to store the
+        // cache policy around. But it should be pretty small, and it saves a
+        // realloc. (It's unclear whether that matters more or less than the
+        // extra memory usage.)
+        bytes.resize(cache_policy_bytes_start, 0);
+        OwnedArchive::new(cache_policy_bytes_aligned)
+    }
+
+    /// Deserializes the checksum off the end of the given bytes, which must
+    /// have already had the cache policy removed. Upon success, the given
+    /// bytes will only contain the data itself.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the bytes are too short to contain a
+    /// checksum.
+    fn deserialize_checksum(bytes: &mut AlignedVec) -> Result<u64, Error> {
+        let Some(checksum_start) = bytes.len().checked_sub(8) else {
+            let msg = format!(
+                "data-with-cache-policy data should be at least 8 bytes \
+                 in length, but is {} bytes",
+                bytes.len(),
+            );
+            return Err(ErrorKind::ArchiveRead(msg).into());
+        };
+        let checksum_bytes =
+            <[u8; 8]>::try_from(&bytes[checksum_start..]).expect("checksum is 8 bytes");
+        bytes.resize(checksum_start, 0);
+        Ok(u64::from_le_bytes(checksum_bytes))
+    }
+
+    /// Deserializes the length, in bytes, of the cache policy given a complete
+    /// serialized byte buffer of a `DataWithCachePolicy`.
+    ///
+    /// Upon success, callers are guaranteed that
+    /// `&bytes[bytes.len() - (len + 8)..][..len]` will not panic.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the length could not be read as a `usize` or is
+    /// otherwise known to be invalid. (For example, it is a length that is bigger
+    /// than `bytes.len()`.)
+    fn deserialize_cache_policy_len(bytes: &[u8]) -> Result<usize, Error> {
+        let Some(cache_policy_len_start) = bytes.len().checked_sub(8) else {
+            let msg = format!(
+                "data-with-cache-policy buffer should be at least 8 bytes \
+                 in length, but is {} bytes",
+                bytes.len(),
+            );
+            return Err(ErrorKind::ArchiveRead(msg).into());
+        };
+        let cache_policy_len_bytes = <[u8; 8]>::try_from(&bytes[cache_policy_len_start..])
+            .expect("cache policy length is 8 bytes");
+        let len_u64 = u64::from_le_bytes(cache_policy_len_bytes);
+        let Ok(len_usize) = usize::try_from(len_u64) else {
+            let msg = format!(
+                "data-with-cache-policy has cache policy length of {}, \
+                 but overflows usize",
+                len_u64,
+            );
+            return Err(ErrorKind::ArchiveRead(msg).into());
+        };
+        // Use a checked addition, since arbitrary (non-entry) files may claim any length.
+        if len_usize
+            .checked_add(8)
+            .map_or(true, |total| bytes.len() < total)
+        {
+            let msg = format!(
+                "invalid cache entry: data-with-cache-policy has cache policy length of {}, \
+                 but total buffer size is {}",
+                len_usize,
+                bytes.len(),
+            );
+            return Err(ErrorKind::ArchiveRead(msg).into());
+        }
+        Ok(len_usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::collections::HashMap;
+    use std::io::{Read, Write};
+    use std::num::NonZeroUsize;
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant, SystemTime};
+
+    use anyhow::Result;
+    use reqwest::ResponseBuilderExt;
+    use reqwest_middleware::ClientBuilder;
+    use rkyv::util::AlignedVec;
+    use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _};
+    use url::Url;
+
+    use uv_cache::{CacheEntry, Freshness};
+    use uv_normalize::PackageName;
+
+    use crate::httpcache::{BeforeRequest, CachePolicy, CachePolicyBuilder, StaleReason};
+    use crate::sidecar::sidecar_path;
+    use crate::{CompressionHint, ErrorKind, EvictionPolicy, HostPort};
+
+    use super::{
+        CacheCodec, CacheControl, CacheOutcome, CachePlan, Cacheable, CachedClient,
+        CachedClientBuilder, CachedClientError, DataWithCachePolicy, GcReport, RevalidationOutcome,
+        SerdeCacheable, SidecarRef, StreamCacheable, WarmRequest, CACHE_VERSION,
+    };
+
+    impl<T> SerdeCacheable<T> {
+        fn new(inner: T) -> Self {
+            Self {
+                inner,
+                codec: std::marker::PhantomData,
+            }
+        }
+    }
+
+    fn client() -> CachedClient {
+        CachedClient::new(ClientBuilder::new(reqwest::Client::new()).build())
+    }
+
+    /// Build a cache policy for a `GET` of `url` that received a response with the given headers.
+    fn cache_policy(url: &str, headers: &[(&str, &str)]) -> CachePolicy {
+        let request = reqwest::Request::new(http::Method::GET, Url::parse(url).unwrap());
+        let mut response = http::Response::builder()
+            .status(200)
+            .url(Url::parse(url).unwrap());
+        for (name, value) in headers {
+            response = response.header(*name, *value);
+        }
+        let response = reqwest::Response::from(response.body("").unwrap());
+        CachePolicyBuilder::new(&request).build(&response)
+    }
+
+    /// Read an HTTP request from a connection, up to the end of its headers, returning what was
+    /// read (including any part of the body that arrived along with the headers).
+    fn read_request(stream: &mut std::net::TcpStream) -> String {
+        let mut request = Vec::new();
+        let mut buf = [0; 1024];
+        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+            let len = stream.read(&mut buf).unwrap();
+            assert_ne!(len, 0, "Expected a complete request");
+            request.extend_from_slice(&buf[..len]);
+        }
+        String::from_utf8_lossy(&request).into_owned()
+    }
+
+    /// Returns the value of the named header in a request read by [`read_request`], if any.
+    fn header<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+        request.lines().skip(1).find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+    }
+
+    /// Start a server that answers `count` requests, each on a connection of its own, with the
+    /// response returned by `respond` for the request (as read by [`read_request`]).
+    ///
+    /// Returns the URL of the server, and a handle to the thread serving it, which yields the
+    /// requests it received once it has answered them all.
+    fn serve_with(
+        count: usize,
+        mut respond: impl FnMut(&str) -> Vec<u8> + Send + 'static,
+    ) -> Result<(Url, std::thread::JoinHandle<Vec<String>>)> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let url = Url::parse(&format!("http://{}/", listener.local_addr()?))?;
+        let server = std::thread::spawn(move || {
+            (0..count)
+                .map(|_| {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let request = read_request(&mut stream);
+                    stream.write_all(&respond(&request)).unwrap();
+                    request
+                })
+                .collect()
+        });
+        Ok((url, server))
+    }
+
+    /// Start a server that answers each request with the next of the given responses, as per
+    /// [`serve_with`].
+    fn serve<R: Into<Vec<u8>>>(
+        responses: impl IntoIterator<Item = R>,
+    ) -> Result<(Url, std::thread::JoinHandle<Vec<String>>)> {
+        let responses = responses.into_iter().map(Into::into).collect::<Vec<_>>();
+        let count = responses.len();
+        let mut responses = responses.into_iter();
+        serve_with(count, move |_| responses.next().unwrap())
+    }
+
+    #[tokio::test]
+    async fn read_raw() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+        let client = client();
+
+        // A missing entry is not an error.
+        assert!(client.read_raw(&cache_entry).await?.is_none());
+
+        // The raw data is returned without the cache policy.
+        let policy = cache_policy("https://example.com/", &[("cache-control", "max-age=60")]);
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, b"hello")?,
+        )?;
+        assert_eq!(
+            client.read_raw(&cache_entry).await?.as_deref(),
+            Some(&b"hello"[..])
+        );
+
+        // A broken entry is an error, and is left in place.
+        fs_err::write(cache_entry.path(), b"broken")?;
+        assert!(client.read_raw(&cache_entry).await.is_err());
+        assert!(cache_entry.path().exists());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "mmap")]
+    #[tokio::test]
+    async fn read_raw_mapped() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+        let client = client();
+
+        assert!(client.read_raw_mapped(&cache_entry).await?.is_none());
+
+        let policy = cache_policy("https://example.com/", &[("cache-control", "max-age=60")]);
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, b"hello")?,
+        )?;
+        let mapped = client.read_raw_mapped(&cache_entry).await?.unwrap();
+        assert_eq!(&*mapped, b"hello");
+
+        // Replacing the entry, as the client does, leaves the mapping intact.
+        uv_fs::write_atomic(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, b"world")?,
+        )
+        .await?;
+        assert_eq!(&*mapped, b"hello");
+        assert_eq!(
+            &*client.read_raw_mapped(&cache_entry).await?.unwrap(),
+            b"world"
+        );
+
+        // Compressed payloads can't be mapped.
+        let data = "hello\n".repeat(1000);
+        let compressed = crate::compression::compress(
+            data.as_bytes(),
+            crate::compression::DEFAULT_COMPRESSION_LEVEL,
+        )
+        .unwrap();
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, &compressed)?,
+        )?;
+        let err = client.read_raw_mapped(&cache_entry).await.unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::CacheNotMappable));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn touch() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+        let client = client();
+
+        // A missing entry is left missing.
+        client.touch(&cache_entry).await?;
+        assert!(!cache_entry.path().exists());
+
+        let policy = cache_policy("https://example.com/", &[("cache-control", "max-age=60")]);
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, b"hello")?,
+        )?;
+        let an_hour_ago = SystemTime::now() - Duration::from_secs(60 * 60);
+        std::fs::File::options()
+            .write(true)
+            .open(cache_entry.path())?
+            .set_modified(an_hour_ago)?;
+
+        client.touch(&cache_entry).await?;
+        let modified = fs_err::metadata(cache_entry.path())?.modified()?;
+        assert!(modified > an_hour_ago + Duration::from_secs(60));
+        assert_eq!(client.read_raw(&cache_entry).await?.unwrap(), b"hello");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn peek_age() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+        let client = client();
+
+        assert!(client.peek(&cache_entry).await?.is_none());
+
+        // An upstream `Age` shortens the remaining time to live.
+        let policy = cache_policy(
+            "https://example.com/",
+            &[("cache-control", "max-age=600"), ("age", "590")],
+        );
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, b"hello")?,
+        )?;
+        let status = client.peek(&cache_entry).await?.unwrap();
+        assert_eq!(status.freshness_lifetime, Duration::from_secs(600));
+        assert!(status.age >= Duration::from_secs(590));
+        assert!(status.time_to_live() <= Duration::from_secs(10));
+        assert!(status.is_fresh());
+
+        Ok(())
+    }
+
+    #[test]
+    fn fresh_in_memory() {
+        let url = "https://example.com/";
+        let request = || reqwest::Request::new(http::Method::GET, Url::parse(url).unwrap());
+        let fresh = cache_policy(url, &[("cache-control", "max-age=600")]).to_archived();
+        let stale =
+            cache_policy(url, &[("cache-control", "max-age=0"), ("age", "60")]).to_archived();
+
+        assert!(CachedClient::is_fresh_in_memory(
+            &request(),
+            CacheControl::None,
+            &fresh
+        ));
+        assert!(!CachedClient::is_fresh_in_memory(
+            &request(),
+            CacheControl::MustRevalidate,
+            &fresh
+        ));
+        assert!(!CachedClient::is_fresh_in_memory(
+            &request(),
+            CacheControl::None,
+            &stale
+        ));
+        assert!(CachedClient::is_fresh_in_memory(
+            &request(),
+            CacheControl::AllowStale,
+            &stale
+        ));
+    }
+
+    #[tokio::test]
+    async fn plan() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+        let client = client();
+        let url = "https://example.com/";
+        let request = || reqwest::Request::new(http::Method::GET, Url::parse(url).unwrap());
+
+        // Without a cache entry, the request would be sent.
+        assert_eq!(
+            client
+                .plan(request(), &cache_entry, CacheControl::None)
+                .await,
+            CachePlan::WouldFetch
+        );
+
+        // A fresh entry is served from the cache, unless revalidation is forced.
+        let policy = cache_policy(url, &[("cache-control", "max-age=600")]);
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, b"hello")?,
+        )?;
+        assert_eq!(
+            client
+                .plan(request(), &cache_entry, CacheControl::None)
+                .await,
+            CachePlan::FreshCache
+        );
+        assert_eq!(
+            client
+                .plan(request(), &cache_entry, CacheControl::MustRevalidate)
+                .await,
+            CachePlan::WouldRevalidate(StaleReason::RequestNoCache)
+        );
+
+        // A stale entry is revalidated, unless stale responses are allowed.
+        let policy = cache_policy(url, &[("cache-control", "max-age=0"), ("age", "60")]);
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, b"hello")?,
+        )?;
+        assert_eq!(
+            client
+                .plan(request(), &cache_entry, CacheControl::None)
+                .await,
+            CachePlan::WouldRevalidate(StaleReason::Expired)
+        );
+        assert_eq!(
+            client
+                .plan(request(), &cache_entry, CacheControl::AllowStale)
+                .await,
+            CachePlan::FreshCache
+        );
+
+        // A broken entry is removed, and the request would be sent.
+        fs_err::write(cache_entry.path(), b"broken")?;
+        assert_eq!(
+            client
+                .plan(request(), &cache_entry, CacheControl::None)
+                .await,
+            CachePlan::WouldFetch
+        );
+        assert!(!cache_entry.path().exists());
+
+        Ok(())
+    }
+
+    /// Unlike `MustRevalidate`, `NoCache` revalidates even responses marked `immutable`.
+    #[tokio::test]
+    async fn no_cache() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+        let client = client();
+        let url = "https://example.com/";
+        let request = || reqwest::Request::new(http::Method::GET, Url::parse(url).unwrap());
+
+        let policy = cache_policy(
+            url,
+            &[
+                ("cache-control", "max-age=600, immutable"),
+                ("etag", "\"abc\""),
+            ],
+        );
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, b"hello")?,
+        )?;
+        assert_eq!(
+            client
+                .plan(request(), &cache_entry, CacheControl::MustRevalidate)
+                .await,
+            CachePlan::FreshCache
+        );
+        assert_eq!(
+            client
+                .plan(request(), &cache_entry, CacheControl::NoCache)
+                .await,
+            CachePlan::WouldRevalidate(StaleReason::Forced)
+        );
+
+        // The revalidation request is conditional.
+        let mut req = request();
+        CachedClient::apply_cache_control(&mut req, CacheControl::NoCache);
+        let cached = DataWithCachePolicy::from_path_async(cache_entry.path()).await?;
+        assert!(matches!(
+            CachedClient::before_request(&cached.cache_policy, &mut req, CacheControl::NoCache),
+            BeforeRequest::Stale(_)
+        ));
+        assert_eq!(
+            req.headers().get(http::header::IF_NONE_MATCH),
+            Some(&http::HeaderValue::from_static("\"abc\""))
+        );
+
+        // Both revalidate fresh responses that aren't `immutable`.
+        let policy = cache_policy(url, &[("cache-control", "max-age=600")]);
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, b"hello")?,
+        )?;
+        for cache_control in [CacheControl::MustRevalidate, CacheControl::NoCache] {
+            assert_eq!(
+                client.plan(request(), &cache_entry, cache_control).await,
+                CachePlan::WouldRevalidate(StaleReason::RequestNoCache)
+            );
+        }
+
+        Ok(())
+    }
+
+    /// `MaxAge` revalidates based on the age of the cached response, rather than its freshness
+    /// lifetime, except for responses marked `immutable`.
+    #[tokio::test]
+    async fn max_age() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+        let client = client();
+        let url = "https://example.com/";
+        let request = || reqwest::Request::new(http::Method::GET, Url::parse(url).unwrap());
+        let write = |headers: &[(&str, &str)]| {
+            fs_err::write(
+                cache_entry.path(),
+                DataWithCachePolicy::serialize(&cache_policy(url, headers), b"hello").unwrap(),
+            )
+        };
+        let max_age = CacheControl::MaxAge(Duration::from_secs(60));
+
+        // A response younger than the given age is served from the cache.
+        write(&[("cache-control", "max-age=600")])?;
+        assert_eq!(
+            client.plan(request(), &cache_entry, max_age).await,
+            CachePlan::FreshCache
+        );
+
+        // An older response is revalidated, even if the server considers it fresh.
+        write(&[("cache-control", "max-age=600"), ("age", "120")])?;
+        assert_eq!(
+            client.plan(request(), &cache_entry, max_age).await,
+            CachePlan::WouldRevalidate(StaleReason::RequestMaxAge)
+        );
+        let mut req = request();
+        CachedClient::apply_cache_control(&mut req, max_age);
+        assert_eq!(
+            req.headers().get(http::header::CACHE_CONTROL),
+            Some(&http::HeaderValue::from_static("max-age=60"))
+        );
+
+        // A younger response is served even if the server considers it stale...
+        write(&[("cache-control", "max-age=0"), ("age", "30")])?;
+        assert_eq!(
+            client.plan(request(), &cache_entry, max_age).await,
+            CachePlan::FreshCache
+        );
+
+        // ...unless the server requires it to be revalidated on every use.
+        write(&[("cache-control", "no-cache")])?;
+        assert_eq!(
+            client.plan(request(), &cache_entry, max_age).await,
+            CachePlan::WouldRevalidate(StaleReason::ResponseNoCache)
+        );
+
+        // `immutable` responses are used for as long as they're fresh, regardless of their age.
+        write(&[("cache-control", "max-age=600, immutable"), ("age", "120")])?;
+        assert_eq!(
+            client.plan(request(), &cache_entry, max_age).await,
+            CachePlan::FreshCache
+        );
+        write(&[("cache-control", "max-age=60, immutable"), ("age", "120")])?;
+        assert_eq!(
+            client.plan(request(), &cache_entry, max_age).await,
+            CachePlan::WouldRevalidate(StaleReason::Expired)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sealed_entry_without_key() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+        let url = "https://example.com/";
+        let request = reqwest::Request::new(http::Method::GET, Url::parse(url).unwrap());
+
+        // An entry that was encrypted by another client is treated as a miss, but left in place.
+        let policy = cache_policy(url, &[("cache-control", "max-age=600")]);
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, b"uv-aead1\0\0\0\0")?,
+        )?;
+        assert_eq!(
+            client()
+                .plan(request, &cache_entry, CacheControl::None)
+                .await,
+            CachePlan::WouldFetch
+        );
+        assert!(cache_entry.path().exists());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn corrupt_entry_without_repair() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+        let request = reqwest::Request::new(
+            http::Method::GET,
+            Url::parse("https://example.com/").unwrap(),
+        );
+        fs_err::write(cache_entry.path(), b"broken")?;
+
+        // Without `repair_on_read`, the corrupt entry is surfaced as an error, and left in place.
+        let result = client()
+            .read_raw_or_repair(request, &cache_entry, |_| async {
+                Ok::<_, std::convert::Infallible>(SerdeCacheable::new(()))
+            })
+            .await;
+        assert!(matches!(result, Err(CachedClientError::Client(_))));
+        assert!(cache_entry.path().exists());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn corrupt_entry_with_repair() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+        let (url, server) = serve([
+            "HTTP/1.1 200 OK\r\ncache-control: max-age=600\r\ncontent-length: 5\r\n\
+             connection: close\r\n\r\nhello",
+            "HTTP/1.1 200 OK\r\ncache-control: max-age=600\r\ncontent-length: 5\r\n\
+             connection: close\r\n\r\nhello",
+        ])?;
+        let client = CachedClientBuilder::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        )
+        .repair_on_read(true)
+        .build();
+        let request = || reqwest::Request::new(http::Method::GET, url.clone());
+        let callback = |response: reqwest::Response| async {
+            Ok::<_, reqwest::Error>(SerdeCacheable::new(response.text().await?))
+        };
+
+        // With `repair_on_read`, a corrupt entry is fetched anew when its freshness is read...
+        fs_err::write(cache_entry.path(), b"broken")?;
+        let status = client
+            .peek_or_repair(request(), &cache_entry, callback)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(status.freshness_lifetime, Duration::from_secs(600));
+
+        // ...or when its raw bytes are.
+        fs_err::write(cache_entry.path(), b"broken")?;
+        let raw = client
+            .read_raw_or_repair(request(), &cache_entry, callback)
+            .await
+            .unwrap();
+        assert_eq!(raw, Some(rmp_serde::to_vec("hello")?));
+
+        assert_eq!(server.join().unwrap().len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn verify_checksum() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+        let url = "https://example.com/";
+        let request = || reqwest::Request::new(http::Method::GET, Url::parse(url).unwrap());
+
+        // Corrupt the payload of an immutable entry, leaving its cache policy intact.
+        let policy = cache_policy(url, &[("cache-control", "max-age=600, immutable")]);
+        let mut bytes = DataWithCachePolicy::serialize(&policy, b"hello")?;
+        bytes[0] = b'j';
+        fs_err::write(cache_entry.path(), bytes)?;
+
+        // The corrupt entry is removed, and would be fetched anew.
+        assert_eq!(
+            client()
+                .plan(request(), &cache_entry, CacheControl::None)
+                .await,
+            CachePlan::WouldFetch
+        );
+        assert!(!cache_entry.path().exists());
+
+        // An intact entry is still served from the cache.
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, b"hello")?,
+        )?;
+        assert_eq!(
+            client()
+                .plan(request(), &cache_entry, CacheControl::None)
+                .await,
+            CachePlan::FreshCache
+        );
+
+        Ok(())
+    }
+
+    /// The request headers nominated by `Vary` must survive a round-trip through the cache, such
+    /// that a later process can tell whether the cached response applies to its request.
+    #[tokio::test]
+    async fn vary_round_trip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+        let url = Url::parse("https://example.com/simple/flask/")?;
+        let request = |accept: Option<&'static str>| {
+            let mut request = reqwest::Request::new(http::Method::GET, url.clone());
+            if let Some(accept) = accept {
+                request
+                    .headers_mut()
+                    .insert(http::header::ACCEPT, http::HeaderValue::from_static(accept));
+            }
+            request
+        };
+
+        let response = http::Response::builder()
+            .status(200)
+            .header("cache-control", "max-age=600")
+            .header("vary", "Accept, Accept-Encoding")
+            .body("")?;
+        let policy = CachePolicyBuilder::new(&request(Some("application/vnd.pypi.simple.v1+json")))
+            .build(&reqwest::Response::from(response));
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, b"hello")?,
+        )?;
+
+        // Read the entry back from disk, as a fresh process would.
+        let cached = DataWithCachePolicy::from_path_async(cache_entry.path()).await?;
+        assert!(matches!(
+            cached
+                .cache_policy
+                .before_request(&mut request(Some("application/vnd.pypi.simple.v1+json"))),
+            BeforeRequest::Fresh
+        ));
+
+        // A request with different values for the nominated headers is for another variant.
+        assert!(matches!(
+            cached
+                .cache_policy
+                .before_request(&mut request(Some("text/html"))),
+            BeforeRequest::VaryMismatch
+        ));
+        assert!(matches!(
+            cached.cache_policy.before_request(&mut request(None)),
+            BeforeRequest::VaryMismatch
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn clone_shares_weak_cache() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+        let url = "https://example.com/";
+        let policy = cache_policy(url, &[("cache-control", "max-age=600")]);
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, &rmp_serde::to_vec("hello")?)?,
+        )?;
+
+        let client = CachedClientBuilder::new(ClientBuilder::new(reqwest::Client::new()).build())
+            .weak_cache(4)
+            .build();
+        let unexpected =
+            |_| async { Err::<SerdeCacheable<String>, _>(anyhow::anyhow!("Unexpected request")) };
+        let payload = client
+            .get_cacheable_memoized(
+                reqwest::Request::new(http::Method::GET, Url::parse(url)?),
+                &cache_entry,
+                CacheControl::None,
+                unexpected,
+            )
+            .await
+            .unwrap();
+        assert_eq!(*payload, "hello");
+
+        // With the entry gone from disk, a clone can only return the payload from memory.
+        fs_err::remove_file(cache_entry.path())?;
+        let payload = client
+            .clone()
+            .get_cacheable_memoized(
+                reqwest::Request::new(http::Method::GET, Url::parse(url)?),
+                &cache_entry,
+                CacheControl::None,
+                unexpected,
+            )
+            .await
+            .unwrap();
+        assert_eq!(*payload, "hello");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_fresh_or_refresh_fresh() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+        let url = "https://example.com/";
+        let policy = cache_policy(
+            url,
+            &[
+                ("cache-control", "max-age=600"),
+                (
+                    "content-disposition",
+                    "attachment; filename*=UTF-8''flask-3.0.0-py3-none-any.whl",
+                ),
+            ],
+        );
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, &rmp_serde::to_vec("hello")?)?,
+        )?;
+
+        // A fresh entry is returned without invoking the callback, along with the filename that
+        // was stored alongside it.
+        let request = reqwest::Request::new(http::Method::GET, Url::parse(url)?);
+        let response = client()
+            .get_fresh_or_refresh(request, &cache_entry, |_| async {
+                Err::<SerdeCacheable<String>, _>(anyhow::anyhow!("Unexpected request"))
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.payload, "hello");
+        assert_eq!(response.outcome, CacheOutcome::Fresh);
+        assert_eq!(
+            response.filename.as_deref(),
+            Some("flask-3.0.0-py3-none-any.whl")
+        );
+
+        // The filename is also reported when peeking at the entry.
+        let status = client().peek(&cache_entry).await?.unwrap();
+        assert_eq!(
+            status.filename.as_deref(),
+            Some("flask-3.0.0-py3-none-any.whl")
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn deadline() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+
+        // A server that accepts connections, but never responds.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let url = Url::parse(&format!("http://{}/", listener.local_addr()?))?;
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+
+        let start = Instant::now();
+        let result = client
+            .get_cacheable_with_deadline(
+                reqwest::Request::new(http::Method::GET, url.clone()),
+                &cache_entry,
+                CacheControl::None,
+                start + Duration::from_millis(200),
+                |_| async { Ok::<_, std::convert::Infallible>(SerdeCacheable::new(())) },
+            )
+            .await;
+        let Err(CachedClientError::Client(err)) = result else {
+            panic!("Expected the deadline to be exceeded");
+        };
+        assert!(matches!(err.kind(), ErrorKind::DeadlineExceeded(_)));
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(!cache_entry.path().exists());
+
+        // With a deadline in the past, even a fresh cache entry isn't read.
+        let policy = cache_policy(url.as_str(), &[("cache-control", "max-age=600")]);
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, &rmp_serde::to_vec(&())?)?,
+        )?;
+        let result = client
+            .get_cacheable_with_deadline(
+                reqwest::Request::new(http::Method::GET, url.clone()),
+                &cache_entry,
+                CacheControl::None,
+                start,
+                |_| async { Ok::<_, std::convert::Infallible>(SerdeCacheable::new(())) },
+            )
+            .await;
+        assert!(matches!(result, Err(CachedClientError::Client(_))));
+
+        // Otherwise, it's served as usual.
+        client
+            .get_cacheable_with_deadline(
+                reqwest::Request::new(http::Method::GET, url),
+                &cache_entry,
+                CacheControl::None,
+                Instant::now() + Duration::from_secs(60),
+                |_| async { Err::<SerdeCacheable<()>, _>(anyhow::anyhow!("Unexpected request")) },
+            )
+            .await
+            .unwrap();
+
+        drop(listener);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn streaming_body() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+
+        // A server that reads a chunked request body and responds with a cacheable response.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let url = Url::parse(&format!("http://{}/", listener.local_addr()?))?;
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = read_request(&mut stream).into_bytes();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"\r\n0\r\n\r\n") {
+                let len = stream.read(&mut buf).unwrap();
+                assert_ne!(len, 0, "Expected a chunked request body");
+                request.extend_from_slice(&buf[..len]);
+            }
+            let body = rmp_serde::to_vec("uploaded").unwrap();
+            stream
+                .write_all(
+                    format!(
+                        "HTTP/1.1 200 OK\r\ncache-control: max-age=600\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                        body.len()
+                    )
+                    .as_bytes(),
+                )
+                .unwrap();
+            stream.write_all(&body).unwrap();
+        });
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+
+        // Even a fresh cache entry isn't served for a request with a streaming body...
+        let policy = cache_policy(url.as_str(), &[("cache-control", "max-age=600")]);
+        let cached = DataWithCachePolicy::serialize(&policy, &rmp_serde::to_vec("cached")?)?;
+        fs_err::write(cache_entry.path(), &cached)?;
+        let mut req = reqwest::Request::new(http::Method::GET, url);
+        let body = futures::stream::iter([Ok::<_, std::io::Error>("hello")]);
+        *req.body_mut() = Some(reqwest::Body::wrap_stream(body));
+        assert!(req.try_clone().is_none());
+        let response = client
+            .get_serde(req, &cache_entry, CacheControl::None, |response| async {
+                Ok::<_, reqwest::Error>(
+                    rmp_serde::from_slice::<String>(&response.bytes().await?).unwrap(),
+                )
+            })
+            .await
+            .unwrap();
+        assert_eq!(response, "uploaded");
+        server.join().unwrap();
+
+        // ...nor is the response written to it.
+        assert_eq!(fs_err::read(cache_entry.path())?, cached);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn correlation_id() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        // A server that echoes the correlation ID of each request.
+        let (url, server) = serve_with(3, |request| {
+            let id = header(request, "x-request-id").unwrap_or_default();
+            let body = rmp_serde::to_vec(id).unwrap();
+            let mut response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                body.len()
+            )
+            .into_bytes();
+            response.extend_from_slice(&body);
+            response
+        })?;
+        let configured = CachedClientBuilder::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        )
+        .correlation_id("install-1234")
+        .build();
+        let echo = |client: CachedClient, req: reqwest::Request, name: &'static str| {
+            let cache_entry = CacheEntry::new(dir.path(), name);
+            async move {
+                client
+                    .get_serde(req, &cache_entry, CacheControl::None, |response| async {
+                        Ok::<_, reqwest::Error>(
+                            rmp_serde::from_slice::<String>(&response.bytes().await?).unwrap(),
+                        )
+                    })
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let req = reqwest::Request::new(http::Method::GET, url.clone());
+        assert_eq!(
+            echo(configured.clone(), req, "a.msgpack").await,
+            "install-1234"
+        );
+
+        // The ID can be set per operation...
+        let req = reqwest::Request::new(http::Method::GET, url.clone());
+        let scoped = configured.with_correlation_id("lock-5678");
+        assert_eq!(scoped.correlation_id(), "lock-5678");
+        assert_eq!(echo(scoped, req, "b.msgpack").await, "lock-5678");
+
+        // ...or per request.
+        let mut req = reqwest::Request::new(http::Method::GET, url);
+        req.headers_mut()
+            .insert("x-request-id", http::HeaderValue::from_static("sync-9012"));
+        assert_eq!(echo(configured, req, "c.msgpack").await, "sync-9012");
+        server.join().unwrap();
+
+        // Without a configured ID, each client generates its own.
+        assert!(!client().correlation_id().is_empty());
+        assert_ne!(client().correlation_id(), client().correlation_id());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn warm() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        // A server that doesn't know one of the paths.
+        let (url, server) = serve_with(4, |request| {
+            let status = if request.starts_with("GET /missing ") {
+                "404 Not Found"
+            } else {
+                "200 OK"
+            };
+            format!(
+                "HTTP/1.1 {status}\r\ncache-control: max-age=600\r\n\
+                 content-length: 0\r\nconnection: close\r\n\r\n"
+            )
+            .into_bytes()
+        })?;
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let request = |path: &str, priority| {
+            WarmRequest::new(
+                reqwest::Request::new(http::Method::GET, url.join(path).unwrap()),
+                CacheEntry::new(dir.path(), format!("{path}.msgpack")),
+            )
+            .with_priority(priority)
+        };
+        let results = client
+            .warm(
+                [
+                    request("leaf", 0),
+                    request("missing", 0),
+                    request("root", 10),
+                    request("branch", 5),
+                ],
+                NonZeroUsize::new(1).unwrap(),
+                |response| async move {
+                    Ok::<_, reqwest::Error>(SerdeCacheable::new(response.url().path().to_string()))
+                },
+            )
+            .await;
+
+        // Higher priorities are fetched first, and ties are fetched in order.
+        let paths = server
+            .join()
+            .unwrap()
+            .iter()
+            .map(|request| request.split(' ').nth(1).unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["/root", "/branch", "/leaf", "/missing"]);
+        // Results are returned in the order given.
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+        assert!(results[2..].iter().all(Result::is_ok));
+        assert!(CacheEntry::new(dir.path(), "root.msgpack").path().exists());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn redirect() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+
+        // A server that redirects to a stale response, and then confirms that it's still valid.
+        let (url, server) = serve([
+            &b"HTTP/1.1 302 Found\r\nlocation: /cdn/simple/\r\ncontent-length: 0\r\n\
+               connection: close\r\n\r\n"[..],
+            b"HTTP/1.1 200 OK\r\netag: \"v1\"\r\ncache-control: max-age=0\r\nage: 60\r\n\
+              content-length: 5\r\nconnection: close\r\n\r\nhello",
+            b"HTTP/1.1 304 Not Modified\r\netag: \"v1\"\r\nconnection: close\r\n\r\n",
+        ])?;
+        let url = url.join("/simple/")?;
+        let target = url.join("/cdn/simple/")?;
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let get = || {
+            client.get_fresh_or_refresh(
+                reqwest::Request::new(http::Method::GET, url.clone()),
+                &cache_entry,
+                |response| async {
+                    Ok::<SerdeCacheable<String>, reqwest::Error>(SerdeCacheable::new(
+                        response.text().await?,
+                    ))
+                },
+            )
+        };
+
+        // The redirect target is recorded...
+        let response = get().await.unwrap();
+        assert_eq!(response.payload, "hello");
+        assert_eq!(response.outcome, CacheOutcome::Fetched);
+        assert_eq!(response.url.as_ref(), Some(&target));
+
+        // ...and the cached response is revalidated there, rather than at the original URL.
+        let response = get().await.unwrap();
+        assert_eq!(response.payload, "hello");
+        assert_eq!(response.outcome, CacheOutcome::Revalidated);
+        assert_eq!(response.url.as_ref(), Some(&target));
+        let paths = server
+            .join()
+            .unwrap()
+            .iter()
+            .map(|request| {
+                let path = request.split(' ').nth(1).unwrap().to_string();
+                (path, header(request, "if-none-match").is_some())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                ("/simple/".to_string(), false),
+                ("/cdn/simple/".to_string(), false),
+                ("/cdn/simple/".to_string(), true),
+            ]
+        );
+
+        Ok(())
+    }
+
+    /// Responses with a `Last-Modified` header (but no `ETag`) are revalidated with
+    /// `If-Modified-Since`, even if the `304` doesn't repeat the `Last-Modified` header.
+    #[tokio::test]
+    async fn last_modified() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+
+        let (url, server) = serve([
+            &b"HTTP/1.1 200 OK\r\nlast-modified: Wed, 21 Oct 2015 07:28:00 GMT\r\n\
+               cache-control: max-age=0\r\ncontent-length: 5\r\nconnection: close\r\n\r\nhello"[..],
+            b"HTTP/1.1 304 Not Modified\r\ncache-control: max-age=0\r\nconnection: close\r\n\r\n",
+            b"HTTP/1.1 304 Not Modified\r\ncache-control: max-age=0\r\nconnection: close\r\n\r\n",
+        ])?;
+        let url = url.join("/simple/")?;
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let get = || {
+            client.get_fresh_or_refresh(
+                reqwest::Request::new(http::Method::GET, url.clone()),
+                &cache_entry,
+                |response| async {
+                    Ok::<SerdeCacheable<String>, reqwest::Error>(SerdeCacheable::new(
+                        response.text().await?,
+                    ))
+                },
+            )
+        };
+
+        let response = get().await.unwrap();
+        assert_eq!(response.outcome, CacheOutcome::Fetched);
+        // Both revalidations are conditional, since the validator outlives the first `304`.
+        for _ in 0..2 {
+            let response = get().await.unwrap();
+            assert_eq!(response.payload, "hello");
+            assert_eq!(response.outcome, CacheOutcome::Revalidated);
+        }
+        let requests = server.join().unwrap();
+        let if_modified_since = Some("Wed, 21 Oct 2015 07:28:00 GMT");
+        assert_eq!(
+            requests
+                .iter()
+                .map(|request| header(request, "if-modified-since"))
+                .collect::<Vec<_>>(),
+            [None, if_modified_since, if_modified_since]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mirror_fallback() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "flask.msgpack");
+
+        // A mirror that fails, then confirms the response from upstream, then doesn't know the
+        // package; and an upstream index that answers once.
+        let (mirror, mirror_server) = serve([
+            &b"HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                [..],
+            b"HTTP/1.1 304 Not Modified\r\netag: \"v1\"\r\ncache-control: max-age=0\r\n\
+              connection: close\r\n\r\n",
+            b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+        ])?;
+        let (upstream, upstream_server) = serve([
+            b"HTTP/1.1 200 OK\r\netag: \"v1\"\r\ncache-control: max-age=0\r\n\
+              content-length: 5\r\nconnection: close\r\n\r\nhello",
+        ])?;
+
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let url = upstream.join("/simple/flask/")?;
+        let get = || {
+            client.get_cacheable_with_fallback(
+                reqwest::Request::new(http::Method::GET, url.clone()),
+                &[mirror.clone(), upstream.clone()],
+                &cache_entry,
+                CacheControl::None,
+                |response| async {
+                    Ok::<SerdeCacheable<String>, reqwest::Error>(SerdeCacheable::new(
+                        response.text().await?,
+                    ))
+                },
+            )
+        };
+
+        // The mirror fails, so upstream answers...
+        assert_eq!(get().await.unwrap(), "hello");
+        // ...but the mirror can revalidate the entry just the same...
+        assert_eq!(get().await.unwrap(), "hello");
+        // ...and its `404` is final.
+        let Err(CachedClientError::Client(err)) = get().await else {
+            panic!("Expected a client error");
+        };
+        let ErrorKind::RequestError(err) = err.kind() else {
+            panic!("Expected a request error, got: {err:?}");
+        };
+        assert_eq!(err.status(), Some(http::StatusCode::NOT_FOUND));
+
+        let requests = mirror_server.join().unwrap();
+        assert!(requests
+            .iter()
+            .all(|request| request.starts_with("GET /simple/flask/ ")));
+        assert_eq!(header(&requests[1], "if-none-match"), Some("\"v1\""));
+        assert_eq!(upstream_server.join().unwrap().len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn max_concurrency() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        // A server that answers each request after a delay, recording the most requests it was
+        // answering at once.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let url = Url::parse(&format!("http://{}/simple/", listener.local_addr()?))?;
+        let server = std::thread::spawn(move || {
+            let in_flight = std::sync::Arc::new(AtomicUsize::new(0));
+            let max_in_flight = std::sync::Arc::new(AtomicUsize::new(0));
+            let handlers = (0..6)
+                .map(|_| {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let in_flight = in_flight.clone();
+                    let max_in_flight = max_in_flight.clone();
+                    std::thread::spawn(move || {
+                        let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                        max_in_flight.fetch_max(current, Ordering::SeqCst);
+                        read_request(&mut stream);
+                        std::thread::sleep(Duration::from_millis(100));
+                        // Before responding, since the client may send the next request as soon
+                        // as it has the response.
+                        in_flight.fetch_sub(1, Ordering::SeqCst);
+                        stream
+                            .write_all(
+                                b"HTTP/1.1 200 OK\r\ncache-control: max-age=600\r\n\
+                                  content-length: 5\r\nconnection: close\r\n\r\nhello",
+                            )
+                            .unwrap();
+                    })
+                })
+                .collect::<Vec<_>>();
+            for handler in handlers {
+                handler.join().unwrap();
+            }
+            max_in_flight.load(Ordering::SeqCst)
+        });
+
+        let client = CachedClientBuilder::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        )
+        .max_concurrency(NonZeroUsize::new(2).unwrap())
+        .build();
+        let cache_entries = (0..6)
+            .map(|i| CacheEntry::new(dir.path(), format!("{i}.msgpack")))
+            .collect::<Vec<_>>();
+        let payloads = futures::future::join_all(cache_entries.iter().map(|cache_entry| {
+            client.get_serde(
+                reqwest::Request::new(http::Method::GET, url.clone()),
+                cache_entry,
+                CacheControl::None,
+                |response| async { Ok::<_, reqwest::Error>(response.text().await?) },
+            )
+        }))
+        .await;
+        assert!(payloads
+            .into_iter()
+            .all(|payload| payload.unwrap() == "hello"));
+        assert!(server.join().unwrap() <= 2);
+
+        // Fresh cache entries are served without sending (or waiting to send) any requests.
+        let payloads = futures::future::join_all(cache_entries.iter().map(|cache_entry| {
+            client.get_serde(
+                reqwest::Request::new(http::Method::GET, url.clone()),
+                cache_entry,
+                CacheControl::None,
+                |_| async { Err::<String, _>(anyhow::anyhow!("Unexpected request")) },
+            )
+        }))
+        .await;
+        assert_eq!(payloads.into_iter().filter(Result::is_ok).count(), 6);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn max_concurrency_body() -> Result<()> {
+        // A server that sends the first half of its first response and holds the connection
+        // open, until it has answered a second request in full.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let url = Url::parse(&format!("http://{}/simple/", listener.local_addr()?))?;
+        let server = std::thread::spawn(move || {
+            let (mut first, _) = listener.accept().unwrap();
+            read_request(&mut first);
+            first
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 10\r\n\r\nhello")
+                .unwrap();
+            let (mut second, _) = listener.accept().unwrap();
+            read_request(&mut second);
+            second
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\ncontent-length: 5\r\nconnection: close\r\n\r\nworld",
+                )
+                .unwrap();
+            drop(first);
+        });
+
+        let client = CachedClientBuilder::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        )
+        .max_concurrency(NonZeroUsize::new(1).unwrap())
+        .build();
+
+        // While the body of the first response is unread, the second request has to wait.
+        let first = client
+            .execute(reqwest::Request::new(http::Method::GET, url.clone()))
+            .await?;
+        let second = tokio::time::timeout(
+            Duration::from_millis(200),
+            client.execute(reqwest::Request::new(http::Method::GET, url.clone())),
+        )
+        .await;
+        assert!(second.is_err());
+
+        // Once the first body is dropped, the second request is sent.
+        drop(first);
+        let second = client
+            .execute(reqwest::Request::new(http::Method::GET, url.clone()))
+            .await?;
+        assert_eq!(second.text().await?, "world");
+        server.join().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn host_headers() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        // Two servers that report whether each request was authorized, in responses that vary on
+        // the `Authorization` header.
+        let report_authorization = || {
+            serve_with(1, |request| {
+                let authorized = header(request, "authorization") == Some("Bearer secret-token");
+                let body = rmp_serde::to_vec(&authorized).unwrap();
+                let mut response = format!(
+                    "HTTP/1.1 200 OK\r\ncache-control: max-age=600\r\nvary: authorization\r\n\
+                     content-length: {}\r\nconnection: close\r\n\r\n",
+                    body.len()
+                )
+                .into_bytes();
+                response.extend_from_slice(&body);
+                response
+            })
+        };
+        let (private, private_server) = report_authorization()?;
+        let (public, public_server) = report_authorization()?;
+
+        let mut headers = http::HeaderMap::new();
+        headers.insert(
+            http::header::AUTHORIZATION,
+            http::HeaderValue::from_static("Bearer secret-token"),
+        );
+        let client = CachedClientBuilder::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        )
+        .host_headers(HashMap::from([(
+            HostPort::from_url(&private).unwrap(),
+            headers,
+        )]))
+        .build();
+        let get = |url: Url, name: &'static str| {
+            let client = client.clone();
+            let cache_entry = CacheEntry::new(dir.path(), name);
+            async move {
+                let authorized = client
+                    .get_serde(
+                        reqwest::Request::new(http::Method::GET, url),
+                        &cache_entry,
+                        CacheControl::None,
+                        |response| async {
+                            Ok::<_, reqwest::Error>(
+                                rmp_serde::from_slice::<bool>(&response.bytes().await?).unwrap(),
+                            )
+                        },
+                    )
+                    .await
+                    .unwrap();
+                (authorized, fs_err::read(cache_entry.path()).unwrap())
+            }
+        };
+
+        // The headers are only sent to the matching host...
+        let (authorized, _) = get(public, "public.msgpack").await;
+        assert!(!authorized);
+        public_server.join().unwrap();
+        let (authorized, entry) = get(private.clone(), "private.msgpack").await;
+        assert!(authorized);
+        private_server.join().unwrap();
+
+        // ...and never written to the cache entry, not even as a `Vary` value.
+        assert!(!String::from_utf8_lossy(&entry).contains("secret-token"));
+
+        // Since the cached response varies on a header the request itself doesn't carry, it's
+        // served for the next request (which couldn't reach the server anymore).
+        let (authorized, _) = get(private, "private.msgpack").await;
+        assert!(authorized);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn audit_log() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+        let audit_log = dir.path().join("audit.jsonl");
+
+        // A server that serves a stale response, and then confirms that it's still valid.
+        let (mut url, server) = serve([
+            &b"HTTP/1.1 200 OK\r\netag: \"v1\"\r\ncache-control: max-age=0\r\nage: 60\r\n\
+               content-length: 5\r\nconnection: close\r\n\r\nhello"[..],
+            b"HTTP/1.1 304 Not Modified\r\netag: \"v1\"\r\nconnection: close\r\n\r\n",
+        ])?;
+        url.set_username("user").unwrap();
+        url.set_password(Some("secret")).unwrap();
+        let url = url.join("hello")?;
+        let client = CachedClientBuilder::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        )
+        .audit_log(&audit_log)
+        .build();
+        for _ in 0..2 {
+            let payload = client
+                .get_serde(
+                    reqwest::Request::new(http::Method::GET, url.clone()),
+                    &cache_entry,
+                    CacheControl::None,
+                    |response| async { response.text().await },
+                )
+                .await
+                .unwrap();
+            assert_eq!(payload, "hello");
+        }
+        server.join().unwrap();
+        assert_eq!(client.invalidate_where(dir.path(), |_| true).await?, 1);
+
+        let records = fs_err::read_to_string(&audit_log)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<serde_json::Value>, _>>()?;
+        let field = |record: &serde_json::Value, name| record[name].clone();
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| field(record, "operation"))
+                .collect::<Vec<_>>(),
+            ["fetch", "revalidate", "evict"]
+        );
+        let redacted = url.as_str().replace("user:secret@", "");
+        assert!(records
+            .iter()
+            .all(|record| field(record, "url") == redacted.as_str()));
+        assert_eq!(field(&records[0], "status"), 200);
+        assert_eq!(field(&records[0], "bytes"), 5);
+        assert_eq!(
+            field(&records[0], "sha256"),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(field(&records[1], "status"), 304);
+        assert_eq!(
+            field(&records[2], "path"),
+            cache_entry.path().to_str().unwrap()
+        );
+        assert!(records.iter().all(|record| record["timestamp"].is_string()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stale_while_revalidate() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+
+        // A server that confirms that the cached response is still valid, once.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let url = Url::parse(&format!("http://{}/", listener.local_addr()?))?;
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            read_request(&mut stream);
+            stream
+                .write_all(
+                    b"HTTP/1.1 304 Not Modified\r\netag: \"v1\"\r\n\
+                      cache-control: max-age=600\r\nconnection: close\r\n\r\n",
+                )
+                .unwrap();
+            drop(stream);
+            // Any further revalidation would be a duplicate.
+            listener.set_nonblocking(true).unwrap();
+            std::thread::sleep(Duration::from_millis(200));
+            listener.accept().is_err()
+        });
+
+        let policy = cache_policy(
+            url.as_str(),
+            &[
+                ("cache-control", "max-age=60, stale-while-revalidate=600"),
+                ("etag", "\"v1\""),
+                ("age", "120"),
+            ],
+        );
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, &rmp_serde::to_vec("flask")?)?,
+        )?;
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let unexpected = |_| async { Err::<String, _>(anyhow::anyhow!("Unexpected request")) };
+
+        // Concurrent requests are all served the stale response right away, and trigger a single
+        // revalidation.
+        let payloads = futures::future::join_all((0..5).map(|_| {
+            client.get_serde(
+                reqwest::Request::new(http::Method::GET, url.clone()),
+                &cache_entry,
+                CacheControl::None,
+                unexpected,
+            )
+        }))
+        .await;
+        assert!(payloads
+            .into_iter()
+            .all(|payload| payload.unwrap() == "flask"));
+        while !client.revalidating.lock().unwrap().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(server.join().unwrap(), "Expected a single revalidation");
+
+        // The entry was refreshed by the revalidation.
+        assert_eq!(
+            client
+                .plan(
+                    reqwest::Request::new(http::Method::GET, url),
+                    &cache_entry,
+                    CacheControl::None
+                )
+                .await,
+            CachePlan::FreshCache
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn offline() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let client = client();
+        // Nothing listens here, so any request would fail.
+        let url = Url::parse("http://127.0.0.1:1/simple/flask/")?;
+        let unexpected = |_| async { Err::<String, _>(anyhow::anyhow!("Unexpected request")) };
+
+        // A stale entry is served as-is.
+        let cache_entry = CacheEntry::new(dir.path(), "stale.msgpack");
+        let policy = cache_policy(
+            url.as_str(),
+            &[("cache-control", "max-age=0"), ("age", "60")],
+        );
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, &rmp_serde::to_vec("flask")?)?,
+        )?;
+        let req = || reqwest::Request::new(http::Method::GET, url.clone());
+        assert_eq!(
+            client
+                .plan(req(), &cache_entry, CacheControl::Offline)
+                .await,
+            CachePlan::FreshCache
+        );
+        let payload = client
+            .get_serde(req(), &cache_entry, CacheControl::Offline, unexpected)
+            .await
+            .unwrap();
+        assert_eq!(payload, "flask");
+
+        // A missing entry is an error, rather than being fetched.
+        let cache_entry = CacheEntry::new(dir.path(), "missing.msgpack");
+        assert_eq!(
+            client
+                .plan(req(), &cache_entry, CacheControl::Offline)
+                .await,
+            CachePlan::Unavailable
+        );
+        let result = client
+            .get_serde(req(), &cache_entry, CacheControl::Offline, unexpected)
+            .await;
+        let Err(CachedClientError::Client(err)) = result else {
+            panic!("Expected an offline error");
+        };
+        assert!(matches!(err.kind(), ErrorKind::Offline(_)));
+
+        // The offline mode is never derived from the freshness of a cache entry.
+        for freshness in [Freshness::Fresh, Freshness::Stale, Freshness::Missing] {
+            assert!(!matches!(
+                CacheControl::from(freshness),
+                CacheControl::Offline
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_serde_validated() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+        let dask = PackageName::from_str("dask")?;
+        let name_of = |name: &String| PackageName::from_str(name).unwrap();
+
+        // A mirror that serves `flask` in place of `dask`.
+        let (url, server) = serve([b"HTTP/1.1 200 OK\r\ncache-control: max-age=600\r\n\
+              content-length: 5\r\nconnection: close\r\n\r\nflask"])?;
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let result = client
+            .get_serde_validated(
+                reqwest::Request::new(http::Method::GET, url.clone()),
+                &cache_entry,
+                CacheControl::None,
+                &dask,
+                name_of,
+                |response| async { response.text().await },
+            )
+            .await;
+        server.join().unwrap();
+        let Err(CachedClientError::Client(err)) = result else {
+            panic!("Expected a name mismatch");
+        };
+        assert!(matches!(err.kind(), ErrorKind::NameMismatch { .. }));
+        assert!(!cache_entry.path().exists());
+
+        // A poisoned entry that's already in the cache is removed.
+        let policy = cache_policy(url.as_str(), &[("cache-control", "max-age=600")]);
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, &rmp_serde::to_vec("flask")?)?,
+        )?;
+        let unexpected = |_| async { Err::<String, _>(anyhow::anyhow!("Unexpected request")) };
+        let flask = PackageName::from_str("flask")?;
+        assert_eq!(
+            client
+                .get_serde_validated(
+                    reqwest::Request::new(http::Method::GET, url.clone()),
+                    &cache_entry,
+                    CacheControl::None,
+                    &flask,
+                    name_of,
+                    unexpected,
+                )
+                .await
+                .unwrap(),
+            "flask"
+        );
+        let result = client
+            .get_serde_validated(
+                reqwest::Request::new(http::Method::GET, url),
+                &cache_entry,
+                CacheControl::None,
+                &dask,
+                name_of,
+                unexpected,
+            )
+            .await;
+        assert!(matches!(result, Err(CachedClientError::Client(_))));
+        assert!(!cache_entry.path().exists());
+
+        Ok(())
+    }
+
+    /// A [`StreamCacheable`] payload of raw bytes.
+    struct Bytes(Vec<u8>);
+
+    impl StreamCacheable for Bytes {
+        type Target = Vec<u8>;
+
+        async fn write_to<W: AsyncWrite + Unpin + Send>(
+            self,
+            wtr: &mut W,
+        ) -> Result<(), crate::Error> {
+            // Write in chunks, as a streaming payload would.
+            for chunk in self.0.chunks(3) {
+                wtr.write_all(chunk).await.map_err(ErrorKind::Io)?;
+            }
+            Ok(())
+        }
+
+        async fn read_from<R: AsyncRead + Unpin + Send + 'static>(
+            mut rdr: R,
+        ) -> Result<Vec<u8>, crate::Error> {
+            let mut data = Vec::new();
+            rdr.read_to_end(&mut data).await.map_err(ErrorKind::Io)?;
+            Ok(data)
+        }
+    }
+
+    #[tokio::test]
+    async fn get_stream_cacheable() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.bin");
+
+        let (url, server) = serve([b"HTTP/1.1 200 OK\r\ncache-control: max-age=600\r\n\
+              content-length: 11\r\nconnection: close\r\n\r\nhello world"])?;
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let data = client
+            .get_stream_cacheable(
+                reqwest::Request::new(http::Method::GET, url.clone()),
+                &cache_entry,
+                CacheControl::None,
+                |response| async {
+                    Ok::<_, reqwest::Error>(Bytes(response.bytes().await?.to_vec()))
+                },
+            )
+            .await
+            .unwrap();
+        server.join().unwrap();
+        assert_eq!(data, b"hello world");
+
+        // The entry is in the same format as those written by `get_cacheable`.
+        let cached = DataWithCachePolicy::from_path_sync(cache_entry.path())?;
+        assert_eq!(cached.data.as_slice(), b"hello world");
+        assert!(cached.checksum_matches());
+        let (without_data, _, len) =
+            DataWithCachePolicy::from_path_without_data(cache_entry.path())?;
+        assert_eq!(len, 11);
+        assert_eq!(without_data.checksum, cached.checksum);
+
+        // A fresh entry is read back without a request.
+        let data = client
+            .get_stream_cacheable(
+                reqwest::Request::new(http::Method::GET, url),
+                &cache_entry,
+                CacheControl::None,
+                |_| async { Err::<Bytes, _>(anyhow::anyhow!("Unexpected request")) },
+            )
+            .await
+            .unwrap();
+        assert_eq!(data, b"hello world");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_stream_cacheable_sidecar() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.bin");
+        let sidecar = dir.path().join("entry.bin.data");
+
+        let response = b"HTTP/1.1 200 OK\r\ncache-control: max-age=600\r\n\
+              content-length: 11\r\nconnection: close\r\n\r\nhello world";
+        let (url, server) = serve([response; 2])?;
+        let client = CachedClientBuilder::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        )
+        .sidecar_payloads(true)
+        .build();
+        let fetch = |response: reqwest::Response| async {
+            Ok::<_, reqwest::Error>(Bytes(response.bytes().await?.to_vec()))
+        };
+        let unexpected = |_| async { Err::<Bytes, _>(anyhow::anyhow!("Unexpected request")) };
+
+        // The payload is stored as-is in the sidecar, rather than in the entry.
+        let data = client
+            .get_stream_cacheable(
+                reqwest::Request::new(http::Method::GET, url.clone()),
+                &cache_entry,
+                CacheControl::None,
+                fetch,
+            )
+            .await
+            .unwrap();
+        assert_eq!(data, b"hello world");
+        assert_eq!(fs_err::read(&sidecar)?, b"hello world");
+        let cached = DataWithCachePolicy::from_path_sync(cache_entry.path())?;
+        assert_eq!(cached.data.len(), SidecarRef::ENCODED_LEN);
+
+        // A fresh entry is read back from the sidecar without a request.
+        let data = client
+            .get_stream_cacheable(
+                reqwest::Request::new(http::Method::GET, url.clone()),
+                &cache_entry,
+                CacheControl::None,
+                unexpected,
+            )
+            .await
+            .unwrap();
+        assert_eq!(data, b"hello world");
+
+        // A truncated sidecar is a cache miss.
+        fs_err::write(&sidecar, b"hello")?;
+        let data = client
+            .get_stream_cacheable(
+                reqwest::Request::new(http::Method::GET, url),
+                &cache_entry,
+                CacheControl::None,
+                fetch,
+            )
+            .await
+            .unwrap();
+        server.join().unwrap();
+        assert_eq!(data, b"hello world");
+        assert_eq!(fs_err::read(&sidecar)?, b"hello world");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stale_on_error() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+
+        // A server that fails every revalidation.
+        let response = b"HTTP/1.1 503 Service Unavailable\r\n\
+              content-length: 0\r\nconnection: close\r\n\r\n";
+        let (url, server) = serve([response; 2])?;
+        let policy = cache_policy(
+            url.as_str(),
+            &[("cache-control", "max-age=0"), ("age", "60")],
+        );
+        let write_entry = || {
+            fs_err::write(
+                cache_entry.path(),
+                DataWithCachePolicy::serialize(&policy, &rmp_serde::to_vec("hello").unwrap())
+                    .unwrap(),
+            )
+        };
+        let unexpected =
+            |_| async { Err::<SerdeCacheable<String>, _>(anyhow::anyhow!("Unexpected response")) };
+        let reqwest = || reqwest::Client::builder().no_proxy().build().unwrap();
+
+        // By default, the failure is surfaced.
+        write_entry()?;
+        let result = CachedClient::new(ClientBuilder::new(reqwest()).build())
+            .get_cacheable(
+                reqwest::Request::new(http::Method::GET, url.clone()),
+                &cache_entry,
+                CacheControl::None,
+                unexpected,
+            )
+            .await;
+        assert!(matches!(result, Err(CachedClientError::Client(_))));
+
+        // With the policy, the stale response is served instead.
+        write_entry()?;
+        let client = CachedClientBuilder::new(ClientBuilder::new(reqwest()).build())
+            .stale_on_error(Duration::from_secs(3600))
+            .build();
+        let payload = client
+            .get_cacheable(
+                reqwest::Request::new(http::Method::GET, url),
+                &cache_entry,
+                CacheControl::None,
+                unexpected,
+            )
+            .await
+            .unwrap();
+        server.join().unwrap();
+        assert_eq!(payload, "hello");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn revalidate() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+
+        // A server that confirms the first revalidation, and then returns new content.
+        let (url, server) = serve([
+            &b"HTTP/1.1 304 Not Modified\r\n\
+               etag: \"v1\"\r\ncache-control: max-age=600, immutable\r\n\
+               content-length: 0\r\nconnection: close\r\n\r\n"[..],
+            &b"HTTP/1.1 200 OK\r\n\
+               etag: \"v2\"\r\ncache-control: max-age=600, immutable\r\n\
+               content-length: 5\r\nconnection: close\r\n\r\nworld"[..],
+        ])?;
+
+        // The entry is fresh (and immutable), but is revalidated regardless.
+        let policy = cache_policy(
+            url.as_str(),
+            &[
+                ("cache-control", "max-age=600, immutable"),
+                ("etag", "\"v1\""),
+            ],
+        );
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, &rmp_serde::to_vec("hello")?)?,
+        )?;
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let fetch = |response: reqwest::Response| async {
+            Ok::<_, reqwest::Error>(SerdeCacheable::new(response.text().await?))
+        };
+
+        let outcome = client
+            .revalidate(
+                reqwest::Request::new(http::Method::GET, url.clone()),
+                &cache_entry,
+                fetch,
+            )
+            .await
+            .unwrap();
+        assert_eq!(outcome, RevalidationOutcome::NotModified);
+
+        let outcome = client
+            .revalidate(
+                reqwest::Request::new(http::Method::GET, url.clone()),
+                &cache_entry,
+                fetch,
+            )
+            .await
+            .unwrap();
+        assert_eq!(outcome, RevalidationOutcome::Updated);
+        server.join().unwrap();
+
+        // The new content was written to the entry.
+        let cached = DataWithCachePolicy::from_path_sync(cache_entry.path())?;
+        assert_eq!(rmp_serde::from_slice::<String>(&cached.data)?, "world");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_cacheable_probed() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+
+        // A server that ignores conditional requests, but reports validators on `HEAD` requests:
+        // first unchanged, and then changed, which is followed by a `GET` for the new content.
+        let (url, server) = serve([
+            &b"HTTP/1.1 200 OK\r\n\
+               etag: \"v1\"\r\ncache-control: max-age=0\r\nage: 60\r\n\
+               content-length: 5\r\nconnection: close\r\n\r\n"[..],
+            &b"HTTP/1.1 200 OK\r\n\
+               etag: \"v2\"\r\ncache-control: max-age=0\r\nage: 60\r\n\
+               content-length: 5\r\nconnection: close\r\n\r\n"[..],
+            &b"HTTP/1.1 200 OK\r\n\
+               etag: \"v2\"\r\ncache-control: max-age=0\r\nage: 60\r\n\
+               content-length: 5\r\nconnection: close\r\n\r\nworld"[..],
+        ])?;
+
+        let policy = cache_policy(
+            url.as_str(),
+            &[
+                ("cache-control", "max-age=0"),
+                ("age", "60"),
+                ("etag", "\"v1\""),
+                ("content-length", "5"),
+            ],
+        );
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, &rmp_serde::to_vec("hello")?)?,
+        )?;
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let fetch = |response: reqwest::Response| async {
+            Ok::<_, reqwest::Error>(SerdeCacheable::new(response.text().await?))
+        };
+
+        // The validators are unchanged, so the cached payload is served.
+        let response = client
+            .get_cacheable_probed(
+                reqwest::Request::new(http::Method::GET, url.clone()),
+                &cache_entry,
+                fetch,
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.outcome, CacheOutcome::Revalidated);
+        assert_eq!(response.payload, "hello");
+
+        // The validators changed, so the new content is fetched and written to the entry.
+        let response = client
+            .get_cacheable_probed(
+                reqwest::Request::new(http::Method::GET, url.clone()),
+                &cache_entry,
+                fetch,
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.outcome, CacheOutcome::Fetched);
+        assert_eq!(response.payload, "world");
+        let methods = server
+            .join()
+            .unwrap()
+            .iter()
+            .map(|request| request.split(' ').next().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(methods, ["HEAD", "HEAD", "GET"]);
+
+        let cached = DataWithCachePolicy::from_path_sync(cache_entry.path())?;
+        assert_eq!(rmp_serde::from_slice::<String>(&cached.data)?, "world");
+
+        Ok(())
+    }
+
+    /// Partial responses are cached for the range they were requested for, and never reused for
+    /// another range, or for the complete resource.
+    #[tokio::test]
+    async fn partial_content() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "flask.whl.bin");
+
+        let (url, server) = serve([
+            &b"HTTP/1.1 206 Partial Content\r\n\
+               cache-control: max-age=600\r\ncontent-range: bytes 0-4/10\r\n\
+               content-length: 5\r\nconnection: close\r\n\r\nhello"[..],
+            &b"HTTP/1.1 206 Partial Content\r\n\
+               cache-control: max-age=600\r\ncontent-range: bytes 5-9/10\r\n\
+               content-length: 5\r\nconnection: close\r\n\r\nworld"[..],
+        ])?;
+        let url = url.join("flask.whl")?;
+
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let read = |range: &'static str| {
+            let mut req = reqwest::Request::new(http::Method::GET, url.clone());
+            req.headers_mut()
+                .insert(http::header::RANGE, http::HeaderValue::from_static(range));
+            let client = &client;
+            let cache_entry = &cache_entry;
+            async move {
+                client
+                    .get_cacheable_with_info(
+                        req,
+                        cache_entry,
+                        CacheControl::None,
+                        |response| async {
+                            Ok::<_, reqwest::Error>(response.bytes().await?.to_vec())
+                        },
+                    )
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let (payload, info) = read("bytes=0-4").await;
+        assert_eq!(
+            (payload.as_slice(), info.outcome),
+            (&b"hello"[..], CacheOutcome::Fetched)
+        );
+        let (payload, info) = read("bytes=0-4").await;
+        assert_eq!(
+            (payload.as_slice(), info.outcome),
+            (&b"hello"[..], CacheOutcome::Fresh)
+        );
+        let (payload, info) = read("bytes=5-9").await;
+        assert_eq!(
+            (payload.as_slice(), info.outcome),
+            (&b"world"[..], CacheOutcome::Fetched)
+        );
+
+        let requests = server.join().unwrap();
+        assert_eq!(
+            requests
+                .iter()
+                .map(|request| header(request, "range").unwrap())
+                .collect::<Vec<_>>(),
+            ["bytes=0-4", "bytes=5-9"]
+        );
+
+        // A request for the complete resource doesn't match the cached range.
+        let cached = DataWithCachePolicy::from_path_sync(cache_entry.path())?;
+        assert_eq!(cached.cache_policy.content_range(), Some("bytes 5-9/10"));
+        let mut req = reqwest::Request::new(http::Method::GET, url);
+        assert!(matches!(
+            cached.cache_policy.before_request(&mut req),
+            BeforeRequest::NoMatch
+        ));
+
+        Ok(())
+    }
+
+    /// As for the central directories of remote wheels, what's read after a `HEAD` request is
+    /// cached under its policy, such that fresh `immutable` entries skip the request entirely.
+    #[tokio::test]
+    async fn head_immutable() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        let (url, server) = serve([
+            &b"HTTP/1.1 200 OK\r\n\
+               etag: \"v1\"\r\ncache-control: max-age=600, immutable\r\n\
+               content-length: 5\r\nconnection: close\r\n\r\n"[..],
+            &b"HTTP/1.1 200 OK\r\n\
+               etag: \"v1\"\r\ncache-control: max-age=600\r\n\
+               content-length: 5\r\nconnection: close\r\n\r\n"[..],
+            &b"HTTP/1.1 304 Not Modified\r\n\
+               etag: \"v1\"\r\ncache-control: max-age=600\r\n\
+               connection: close\r\n\r\n"[..],
+        ])?;
+
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let read = |path: &str, cache_control: CacheControl| {
+            let req = reqwest::Request::new(http::Method::HEAD, url.join(path).unwrap());
+            let cache_entry = CacheEntry::new(dir.path(), format!("{path}.msgpack"));
+            let client = &client;
+            async move {
+                let (payload, info) = client
+                    .get_cacheable_with_info(req, &cache_entry, cache_control, |_| async {
+                        Ok::<_, reqwest::Error>(SerdeCacheable::new("index".to_string()))
+                    })
+                    .await
+                    .unwrap();
+                assert_eq!(payload, "index");
+                info.outcome
+            }
+        };
+
+        // An `immutable` file is only requested once, even if revalidation is requested.
+        assert_eq!(
+            read("immutable.whl", CacheControl::None).await,
+            CacheOutcome::Fetched
+        );
+        assert_eq!(
+            read("immutable.whl", CacheControl::MustRevalidate).await,
+            CacheOutcome::Fresh
+        );
+
+        // Any other file is revalidated, if requested.
+        assert_eq!(
+            read("mutable.whl", CacheControl::None).await,
+            CacheOutcome::Fetched
+        );
+        assert_eq!(
+            read("mutable.whl", CacheControl::None).await,
+            CacheOutcome::Fresh
+        );
+        assert_eq!(
+            read("mutable.whl", CacheControl::MustRevalidate).await,
+            CacheOutcome::Revalidated
+        );
+
+        let requests = server
+            .join()
+            .unwrap()
+            .iter()
+            .map(|request| request.split(' ').take(2).collect::<Vec<_>>().join(" "))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            requests,
+            [
+                "HEAD /immutable.whl",
+                "HEAD /mutable.whl",
+                "HEAD /mutable.whl"
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn content_length_mismatch() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.msgpack");
+
+        // A server that declares a longer body than it sends, and then closes the connection.
+        let (url, server) = serve([b"HTTP/1.1 200 OK\r\n\
+              cache-control: max-age=600\r\n\
+              content-length: 10\r\nconnection: close\r\n\r\nhello"])?;
+
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let err = client
+            .get_cacheable(
+                reqwest::Request::new(http::Method::GET, url.clone()),
+                &cache_entry,
+                CacheControl::None,
+                |response: reqwest::Response| async {
+                    Ok::<_, reqwest::Error>(SerdeCacheable::new(response.text().await?))
+                },
+            )
+            .await
+            .unwrap_err();
+        server.join().unwrap();
+
+        let CachedClientError::Client(err) = err else {
+            panic!("expected a client error, got: {err:?}");
+        };
+        assert!(
+            matches!(
+                err.kind(),
+                ErrorKind::ContentLengthMismatch {
+                    declared: 10,
+                    actual: 5,
+                    ..
+                }
+            ),
+            "{err:?}"
+        );
+        assert!(!cache_entry.path().exists());
+
+        Ok(())
+    }
+
+    /// The body read by `get_cached` itself is checked too.
+    #[tokio::test]
+    async fn content_length_mismatch_raw() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "entry.bin");
+
+        let (url, server) = serve([b"HTTP/1.1 200 OK\r\n\
+              cache-control: max-age=600\r\n\
+              content-length: 10\r\nconnection: close\r\n\r\nhello"])?;
+
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let err = client
+            .get_cached(
+                reqwest::Request::new(http::Method::GET, url),
+                &cache_entry,
+                CacheControl::None,
+            )
+            .await
+            .unwrap_err();
+        server.join().unwrap();
+
+        assert!(
+            matches!(
+                err.kind(),
+                ErrorKind::ContentLengthMismatch {
+                    declared: 10,
+                    actual: 5,
+                    ..
+                }
+            ),
+            "{err:?}"
+        );
+        assert!(!cache_entry.path().exists());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_cacheable_with_info() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_entry = CacheEntry::new(dir.path(), "flask.msgpack");
+        let url = "https://example.com/simple/flask/";
+        let policy = cache_policy(url, &[("cache-control", "max-age=600"), ("age", "60")]);
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, &rmp_serde::to_vec("flask")?)?,
+        )?;
+
+        let (payload, info) = client()
+            .get_cacheable_with_info(
+                reqwest::Request::new(http::Method::GET, Url::parse(url)?),
+                &cache_entry,
+                CacheControl::None,
+                |_| async {
+                    Err::<SerdeCacheable<String>, _>(anyhow::anyhow!("Unexpected request"))
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(payload, "flask");
+        assert_eq!(info.outcome, CacheOutcome::Fresh);
+        assert!(info
+            .age
+            .is_some_and(|age| age >= Duration::from_secs(60) && age < Duration::from_secs(600)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn invalidate() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let client = client();
+        let cache_entry = CacheEntry::new(dir.path(), "flask.msgpack");
+        let policy = cache_policy(
+            "https://pypi.org/simple/flask/",
+            &[("cache-control", "max-age=600")],
+        );
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, b"hello")?,
+        )?;
+        fs_err::write(sidecar_path(cache_entry.path()), b"hello")?;
+        // Other entries in the same directory are left alone.
+        fs_err::write(dir.path().join("django.msgpack"), b"hello")?;
+
+        assert_eq!(client.invalidate(&cache_entry).await?, 2);
+        assert!(!cache_entry.path().exists());
+        assert!(!sidecar_path(cache_entry.path()).exists());
+        assert!(dir.path().join("django.msgpack").exists());
+
+        // Removing a missing entry is a no-op.
+        assert_eq!(client.invalidate(&cache_entry).await?, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn gc() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let client = client();
+        let policy = cache_policy(
+            "https://pypi.org/simple/flask/",
+            &[("cache-control", "max-age=600")],
+        );
+        let entry = DataWithCachePolicy::serialize(&policy, b"hello")?;
+        let with_version = |version: u16| {
+            let mut bytes = entry.clone();
+            let start = bytes.len() - 2;
+            bytes[start..].copy_from_slice(&version.to_le_bytes());
+            bytes
+        };
+        let an_hour_ago = SystemTime::now() - Duration::from_secs(60 * 60);
+
+        // Kept: a valid entry, an entry (and its sidecar) from a later version of the format, a
+        // file that isn't an entry, the temporary file of an ongoing write, and a broken entry
+        // that isn't named as one.
+        fs_err::create_dir_all(dir.path().join("shard"))?;
+        fs_err::write(dir.path().join("valid.msgpack"), &entry)?;
+        let future = dir.path().join("shard").join("future.msgpack");
+        fs_err::write(&future, with_version(CACHE_VERSION + 1))?;
+        fs_err::write(sidecar_path(&future), b"hello")?;
+        fs_err::write(
+            dir.path().join("metadata.msgpack"),
+            rmp_serde::to_vec("flask")?,
+        )?;
+        fs_err::write(dir.path().join(".tmpongoing"), b"hello")?;
+        fs_err::write(
+            dir.path().join("corrupt.json"),
+            with_version(CACHE_VERSION - 1),
+        )?;
+
+        // Removed: a corrupt entry (and its sidecar), an entry from an earlier version of the
+        // format, and the temporary file of an abandoned write.
+        let mut corrupt = entry.clone();
+        corrupt[0] ^= 0xff;
+        let corrupt_path = dir.path().join("shard").join("corrupt.msgpack");
+        fs_err::write(&corrupt_path, &corrupt)?;
+        fs_err::write(sidecar_path(&corrupt_path), b"hello")?;
+        fs_err::write(
+            dir.path().join("old.msgpack"),
+            with_version(CACHE_VERSION - 1),
+        )?;
+        let abandoned = dir.path().join("shard").join(".tmpabandoned");
+        fs_err::write(&abandoned, b"hello")?;
+        std::fs::File::options()
+            .write(true)
+            .open(&abandoned)?
+            .set_modified(an_hour_ago)?;
+
+        let report = client.gc(dir.path()).await?;
+        assert_eq!(report.removed_entries, 2);
+        assert_eq!(report.removed_temp_files, 1);
+        assert_eq!(
+            report.bytes_reclaimed,
+            2 * entry.len() as u64 + 2 * b"hello".len() as u64
+        );
+
+        let mut remaining = walkdir::WalkDir::new(dir.path())
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        remaining.sort();
+        assert_eq!(
+            remaining,
+            [
+                ".tmpongoing",
+                "corrupt.json",
+                "future.msgpack",
+                "future.msgpack.data",
+                "metadata.msgpack",
+                "valid.msgpack",
+            ]
+        );
+
+        // Nothing is left to collect.
+        assert_eq!(client.gc(dir.path()).await?, GcReport::default());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn invalidate_where() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let client = client();
+        for (name, url) in [
+            ("pypi.msgpack", "https://pypi.org/simple/flask/"),
+            ("private.msgpack", "https://example.com/simple/flask/"),
+        ] {
+            let policy = cache_policy(url, &[("cache-control", "max-age=600")]);
+            fs_err::write(
+                dir.path().join(name),
+                DataWithCachePolicy::serialize(&policy, b"hello")?,
+            )?;
+        }
+        // Files that aren't cache entries are never touched.
+        fs_err::write(dir.path().join("other.txt"), b"hello")?;
+
+        assert_eq!(
+            client
+                .prune_older_than(dir.path(), Duration::from_secs(3600))
+                .await?,
+            0
+        );
+        assert_eq!(client.clean_host(dir.path(), "example.com").await?, 1);
+        assert!(!dir.path().join("private.msgpack").exists());
+        assert!(dir.path().join("pypi.msgpack").exists());
+
+        assert_eq!(
+            client
+                .invalidate_where(dir.path(), |entry| entry.status_code == 200)
+                .await?,
+            1
+        );
+        assert!(!dir.path().join("pypi.msgpack").exists());
+        assert!(dir.path().join("other.txt").exists());
+
+        // Entries in use are left in place.
+        let policy = cache_policy("https://pypi.org/simple/flask/", &[]);
+        fs_err::write(
+            dir.path().join("pypi.msgpack"),
+            DataWithCachePolicy::serialize(&policy, b"hello")?,
+        )?;
+        let in_use = client
+            .active_entries
+            .acquire(&dir.path().join("pypi.msgpack"));
+        assert_eq!(client.invalidate_where(dir.path(), |_| true).await?, 0);
+        assert!(dir.path().join("pypi.msgpack").exists());
+        drop(in_use);
+        assert_eq!(client.invalidate_where(dir.path(), |_| true).await?, 1);
+
+        // A missing directory is empty.
+        assert_eq!(
+            client
+                .invalidate_where(dir.path().join("missing"), |_| true)
+                .await?,
+            0
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn invalidate_where_preview() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let client = client();
+        for (name, url) in [
+            ("pypi.msgpack", "https://pypi.org/simple/flask/"),
+            ("private.msgpack", "https://example.com/simple/flask/"),
+            ("private-too.msgpack", "https://example.com/simple/django/"),
+        ] {
+            let policy = cache_policy(url, &[("cache-control", "max-age=600")]);
+            fs_err::write(
+                dir.path().join(name),
+                DataWithCachePolicy::serialize(&policy, b"hello")?,
+            )?;
+        }
+
+        let preview = client.clean_host_preview(dir.path(), "example.com").await?;
+        let mut paths = preview
+            .entries
+            .iter()
+            .map(|entry| entry.path.clone())
+            .collect::<Vec<_>>();
+        paths.sort();
+        assert_eq!(
+            paths,
+            [
+                dir.path().join("private-too.msgpack"),
+                dir.path().join("private.msgpack"),
+            ]
+        );
+        assert_eq!(
+            preview.bytes(),
+            2 * fs_err::metadata(dir.path().join("private.msgpack"))?.len()
+        );
+        // Nothing was deleted.
+        assert!(dir.path().join("private.msgpack").exists());
+        assert!(client
+            .prune_older_than_preview(dir.path(), Duration::from_secs(3600))
+            .await?
+            .entries
+            .is_empty());
+
+        // The real operation deletes the previewed entries.
+        assert_eq!(
+            client.clean_host(dir.path(), "example.com").await?,
+            preview.entries.len()
+        );
+        assert!(paths.iter().all(|path| !path.exists()));
+        assert!(dir.path().join("pypi.msgpack").exists());
+
+        // Evictor policies can be previewed, too.
+        let policy = EvictionPolicy::new(dir.path(), |entry| entry.status_code == 200);
+        assert_eq!(client.preview_evictor(&policy).await?.entries.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cached_package_names() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let client = client();
+        for (name, url, headers) in [
+            ("simple.msgpack", "https://pypi.org/simple/Flask/", vec![]),
+            (
+                "wheel.http",
+                "https://files.pythonhosted.org/packages/ab/cd/zope.interface-6.1-cp312-cp312-manylinux_2_17_x86_64.whl",
+                vec![],
+            ),
+            (
+                "metadata.msgpack",
+                "https://files.pythonhosted.org/packages/ab/cd/Django-5.0-py3-none-any.whl.metadata",
+                vec![],
+            ),
+            (
+                "download.http",
+                "https://example.com/download?id=42",
+                vec![(
+                    "content-disposition",
+                    "attachment; filename=anyio-4.2.0.tar.gz",
+                )],
+            ),
+            ("unknown.msgpack", "https://example.com/", vec![]),
+        ] {
+            let policy = cache_policy(url, &headers);
+            fs_err::write(
+                dir.path().join(name),
+                DataWithCachePolicy::serialize(&policy, b"hello")?,
+            )?;
+        }
+
+        let names = client.cached_package_names(dir.path()).await?;
+        let names: Vec<&str> = names.iter().map(AsRef::as_ref).collect();
+        assert_eq!(names, ["anyio", "django", "flask", "zope-interface"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn single_flight() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        // A server that's slow to respond, such that the requests overlap.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let url = Url::parse(&format!("http://{}/", listener.local_addr()?))?;
+        let server = {
+            let listener = listener.try_clone()?;
+            std::thread::spawn(move || {
+                let (mut stream, _) = listener.accept().unwrap();
+                read_request(&mut stream);
+                std::thread::sleep(Duration::from_millis(200));
+                stream
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\ncache-control: max-age=600\r\n\
+                          content-length: 5\r\nconnection: close\r\n\r\nflask",
+                    )
+                    .unwrap();
+            })
+        };
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let cache_entry = CacheEntry::new(dir.path(), "flask.msgpack");
+        let callbacks = AtomicUsize::new(0);
+        let requests = (0..50).map(|_| {
+            let req = reqwest::Request::new(http::Method::GET, url.clone());
+            client.get_serde(req, &cache_entry, CacheControl::None, |response| async {
+                callbacks.fetch_add(1, Ordering::Relaxed);
+                response.text().await
+            })
+        });
+        let payloads =
+            tokio::time::timeout(Duration::from_secs(10), futures::future::join_all(requests))
+                .await?;
+        server.join().unwrap();
+
+        // Every request got the payload, but only one of them went to the network.
+        assert!(payloads
+            .into_iter()
+            .all(|payload| payload.unwrap() == "flask"));
+        assert_eq!(callbacks.load(Ordering::Relaxed), 1);
+        listener.set_nonblocking(true)?;
+        assert!(listener.accept().is_err());
+        assert_eq!(client.stats().fetched, 1);
+        assert_eq!(client.stats().shared, 49);
+        assert_eq!(client.stats().fresh, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn single_flight_rejoin() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        // A server that's slow to respond, and drops the first connection without responding.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let url = Url::parse(&format!("http://{}/", listener.local_addr()?))?;
+        let server = {
+            let listener = listener.try_clone()?;
+            std::thread::spawn(move || {
+                for attempt in 0..2 {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    read_request(&mut stream);
+                    std::thread::sleep(Duration::from_millis(200));
+                    if attempt == 0 {
+                        continue;
+                    }
+                    stream
+                        .write_all(
+                            b"HTTP/1.1 200 OK\r\ncache-control: max-age=600\r\n\
+                              content-length: 5\r\nconnection: close\r\n\r\nflask",
+                        )
+                        .unwrap();
+                }
+            })
+        };
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let cache_entry = CacheEntry::new(dir.path(), "flask.msgpack");
+        let requests = (0..50).map(|_| {
+            let req = reqwest::Request::new(http::Method::GET, url.clone());
+            client.get_serde(req, &cache_entry, CacheControl::None, |response| async {
+                response.text().await
+            })
+        });
+        let payloads =
+            tokio::time::timeout(Duration::from_secs(10), futures::future::join_all(requests))
+                .await?;
+        server.join().unwrap();
+
+        // Only the first request failed; the others waited on a single retry.
+        let (ok, err): (Vec<_>, Vec<_>) = payloads.into_iter().partition(Result::is_ok);
+        assert_eq!(err.len(), 1);
+        assert!(ok.into_iter().all(|payload| payload.unwrap() == "flask"));
+        listener.set_nonblocking(true)?;
+        assert!(listener.accept().is_err());
+        assert_eq!(client.stats().fetched, 1);
+        assert_eq!(client.stats().shared, 48);
+
+        Ok(())
+    }
+
+    /// A codec that stores payloads as JSON.
+    struct JsonCodec;
+
+    impl CacheCodec for JsonCodec {
+        fn encode<T: serde::Serialize>(value: &T) -> Result<Vec<u8>, crate::Error> {
+            Ok(serde_json::to_vec(value).map_err(|err| ErrorKind::Io(err.into()))?)
+        }
+
+        fn decode<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<T, crate::Error> {
+            Ok(serde_json::from_slice(bytes).map_err(|err| ErrorKind::Io(err.into()))?)
+        }
+    }
+
+    #[tokio::test]
+    async fn codec() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (url, server) = serve([b"HTTP/1.1 200 OK\r\ncache-control: max-age=600\r\n\
+              content-length: 5\r\nconnection: close\r\n\r\nflask"])?;
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let cache_entry = CacheEntry::new(dir.path(), "flask.json");
+        let req = || reqwest::Request::new(http::Method::GET, url.clone());
 
-impl DataWithCachePolicy {
-    /// Loads cached data and its associated HTTP cache policy from the given
-    /// file path in an asynchronous fashion (via `spawn_blocking`).
-    ///
-    /// # Errors
-    ///
-    /// If the given byte buffer is not in a valid format or if reading the
-    /// file given fails, then this returns an error.
-    async fn from_path_async(path: &Path) -> Result<DataWithCachePolicy, Error> {
-        let path = path.to_path_buf();
-        tokio::task::spawn_blocking(move || DataWithCachePolicy::from_path_sync(&path))
+        let payload = client
+            .get_serde_with_codec::<JsonCodec, _, _, _, _>(
+                req(),
+                &cache_entry,
+                CacheControl::None,
+                |response| async { response.text().await },
+            )
             .await
-            // This just forwards panics from the closure.
-            .unwrap()
+            .unwrap();
+        assert_eq!(payload, "flask");
+        server.join().unwrap();
+        assert_eq!(client.read_raw(&cache_entry).await?.unwrap(), b"\"flask\"");
+
+        // The cached payload is decoded with the same codec...
+        let unexpected = |_| async { Err::<String, _>(anyhow::anyhow!("Unexpected request")) };
+        let payload = client
+            .get_serde_with_codec::<JsonCodec, _, _, _, _>(
+                req(),
+                &cache_entry,
+                CacheControl::None,
+                unexpected,
+            )
+            .await
+            .unwrap();
+        assert_eq!(payload, "flask");
+
+        // ...and can't be decoded with another.
+        let result = client
+            .get_serde(req(), &cache_entry, CacheControl::None, unexpected)
+            .await;
+        assert!(matches!(result, Err(CachedClientError::Client(_))));
+
+        Ok(())
     }
 
-    /// Loads cached data and its associated HTTP cache policy from the given
-    /// file path in a synchronous fashion.
-    ///
-    /// # Errors
-    ///
-    /// If the given byte buffer is not in a valid format or if reading the
-    /// file given fails, then this returns an error.
-    fn from_path_sync(path: &Path) -> Result<DataWithCachePolicy, Error> {
-        let file = fs_err::File::open(path).map_err(ErrorKind::Io)?;
-        // Note that we don't wrap our file in a buffer because it will just
-        // get passed to AlignedVec::extend_from_reader, which doesn't benefit
-        // from an intermediary buffer. In effect, the AlignedVec acts as the
-        // buffer.
-        DataWithCachePolicy::from_reader(file)
+    #[tokio::test]
+    async fn compression() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        // A large, repetitive payload, like a Simple API page.
+        let body = "<a href=\"flask-3.0.0-py3-none-any.whl\">flask</a>\n".repeat(20_000);
+        let (url, server) = serve([format!(
+            "HTTP/1.1 200 OK\r\ncache-control: max-age=600\r\n\
+             content-length: {}\r\nconnection: close\r\n\r\n{body}",
+            body.len()
+        )])?;
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let cache_entry = CacheEntry::new(dir.path(), "flask.msgpack");
+        let req = || reqwest::Request::new(http::Method::GET, url.clone());
+
+        let payload = client
+            .get_serde(req(), &cache_entry, CacheControl::None, |response| async {
+                response.text().await
+            })
+            .await
+            .unwrap();
+        assert_eq!(payload, body);
+        server.join().unwrap();
+
+        // The payload is stored compressed...
+        let stored = client.read_raw_stored(&cache_entry).await?.unwrap();
+        assert!(crate::compression::is_compressed(&stored));
+        assert!(stored.len() < body.len() / 10);
+
+        // ...is decompressed when read raw...
+        let raw = client.read_raw(&cache_entry).await?.unwrap();
+        assert_eq!(raw, rmp_serde::to_vec(&body)?);
+
+        // ...and read back in full, even with compression disabled.
+        let uncompressed =
+            CachedClientBuilder::new(ClientBuilder::new(reqwest::Client::new()).build())
+                .compression_level(None)
+                .build();
+        let unexpected = |_| async { Err::<String, _>(anyhow::anyhow!("Unexpected request")) };
+        let payload = uncompressed
+            .get_serde(req(), &cache_entry, CacheControl::None, unexpected)
+            .await
+            .unwrap();
+        assert_eq!(payload, body);
+
+        Ok(())
     }
 
-    /// Loads cached data and its associated HTTP cache policy from the given
-    /// reader.
-    ///
-    /// # Errors
-    ///
-    /// If the given byte buffer is not in a valid format or if the reader
-    /// fails, then this returns an error.
-    pub fn from_reader(mut rdr: impl std::io::Read) -> Result<DataWithCachePolicy, Error> {
-        let mut aligned_bytes = rkyv::util::AlignedVec::new();
-        aligned_bytes
-            .extend_from_reader(&mut rdr)
-            .map_err(ErrorKind::Io)?;
-        DataWithCachePolicy::from_aligned_bytes(aligned_bytes)
+    #[tokio::test]
+    async fn read_stream() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let client = client();
+
+        // A missing entry is not an error.
+        let cache_entry = CacheEntry::new(dir.path(), "missing.msgpack");
+        assert!(client.read_stream(&cache_entry).await?.is_none());
+
+        // A large, repetitive payload, like a Simple API page.
+        let mut data = Vec::new();
+        for i in 0..50_000 {
+            writeln!(data, "<a href=\"flask-{i}.0.0-py3-none-any.whl\">flask</a>")?;
+        }
+        let policy = cache_policy("https://example.com/", &[("cache-control", "max-age=60")]);
+        let compressed =
+            crate::compression::compress(&data, crate::compression::DEFAULT_COMPRESSION_LEVEL)
+                .unwrap();
+        for (name, payload) in [
+            ("compressed.msgpack", &compressed),
+            ("plain.msgpack", &data),
+        ] {
+            let cache_entry = CacheEntry::new(dir.path(), name);
+            fs_err::write(
+                cache_entry.path(),
+                DataWithCachePolicy::serialize(&policy, payload)?,
+            )?;
+
+            // The payload is decompressed as it's read, in chunks.
+            let mut stream = client.read_stream(&cache_entry).await?.unwrap();
+            let mut streamed = Vec::new();
+            let mut chunk = [0; 4096];
+            loop {
+                let n = stream.read(&mut chunk).await?;
+                if n == 0 {
+                    break;
+                }
+                streamed.extend_from_slice(&chunk[..n]);
+            }
+            assert_eq!(streamed, data, "{name}");
+        }
+
+        // A truncated payload fails to read.
+        let cache_entry = CacheEntry::new(dir.path(), "truncated.msgpack");
+        fs_err::write(
+            cache_entry.path(),
+            DataWithCachePolicy::serialize(&policy, &compressed[..compressed.len() / 2])?,
+        )?;
+        let mut stream = client.read_stream(&cache_entry).await?.unwrap();
+        assert!(stream.read_to_end(&mut Vec::new()).await.is_err());
+
+        Ok(())
     }
 
-    /// Loads cached data and its associated HTTP cache policy form an in
-    /// memory byte buffer.
-    ///
-    /// # Errors
-    ///
-    /// If the given byte buffer is not in a valid format, then this
-    /// returns an error.
-    fn from_aligned_bytes(mut bytes: AlignedVec) -> Result<DataWithCachePolicy, Error> {
-        let cache_policy = DataWithCachePolicy::deserialize_cache_policy(&mut bytes)?;
-        Ok(DataWithCachePolicy {
-            data: bytes,
-            cache_policy,
-        })
+    /// Raw bytes that declare themselves already compressed.
+    struct Precompressed(Vec<u8>);
+
+    impl Cacheable for Precompressed {
+        type Target = Vec<u8>;
+
+        fn from_aligned_bytes(bytes: AlignedVec) -> Result<Vec<u8>, crate::Error> {
+            Ok(bytes.into_vec())
+        }
+
+        fn to_bytes(&self) -> Result<Cow<'_, [u8]>, crate::Error> {
+            Ok(Cow::Borrowed(&self.0))
+        }
+
+        fn into_target(self) -> Self::Target {
+            self.0
+        }
+
+        fn compression_hint() -> CompressionHint {
+            CompressionHint::AlreadyCompressed
+        }
     }
 
-    /// Serializes the given cache policy and arbitrary data blob to an in
-    /// memory byte buffer.
-    ///
-    /// # Errors
-    ///
-    /// If there was a problem converting the given cache policy to its
-    /// serialized representation, then this routine will return an error.
-    fn serialize(cache_policy: &CachePolicy, data: &[u8]) -> Result<Vec<u8>, Error> {
-        let mut buf = vec![];
-        DataWithCachePolicy::serialize_to_writer(cache_policy, data, &mut buf)?;
-        Ok(buf)
+    #[tokio::test]
+    async fn compression_hint() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        // A payload that would compress well, were it not declared as already compressed.
+        let body = "flask\n".repeat(20_000);
+        let (url, server) = serve([format!(
+            "HTTP/1.1 200 OK\r\ncache-control: max-age=600\r\n\
+             content-length: {}\r\nconnection: close\r\n\r\n{body}",
+            body.len()
+        )])?;
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let cache_entry = CacheEntry::new(dir.path(), "flask.http");
+        let req = reqwest::Request::new(http::Method::GET, url.clone());
+
+        let payload = client
+            .get_cacheable(req, &cache_entry, CacheControl::None, |response| async {
+                Ok::<_, reqwest::Error>(Precompressed(response.bytes().await?.to_vec()))
+            })
+            .await
+            .unwrap();
+        assert_eq!(payload, body.as_bytes());
+        server.join().unwrap();
+
+        // The payload is stored as-is.
+        let stored = client.read_raw_stored(&cache_entry).await?.unwrap();
+        assert!(!crate::compression::is_compressed(&stored));
+        assert_eq!(stored, body.as_bytes());
+
+        Ok(())
     }
 
-    /// Serializes the given cache policy and arbitrary data blob to the given
-    /// writer.
-    ///
-    /// # Errors
-    ///
-    /// If there was a problem converting the given cache policy to its
-    /// serialized representation or if the writer returns an error, then
-    /// this routine will return an error.
-    fn serialize_to_writer(
-        cache_policy: &CachePolicy,
-        data: &[u8],
-        mut wtr: impl std::io::Write,
-    ) -> Result<(), Error> {
-        let cache_policy_archived = OwnedArchive::from_unarchived(cache_policy)?;
-        let cache_policy_bytes = OwnedArchive::as_bytes(&cache_policy_archived);
-        wtr.write_all(data).map_err(ErrorKind::Io)?;
-        wtr.write_all(cache_policy_bytes).map_err(ErrorKind::Io)?;
-        let len = u64::try_from(cache_policy_bytes.len()).map_err(|_| {
-            let msg = format!(
-                "failed to represent {} (length of cache policy) in a u64",
-                cache_policy_bytes.len()
-            );
-            ErrorKind::Io(std::io::Error::other(msg))
-        })?;
-        wtr.write_all(&len.to_le_bytes()).map_err(ErrorKind::Io)?;
+    #[tokio::test]
+    async fn cache_version() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (url, server) = serve([b"HTTP/1.1 200 OK\r\ncache-control: max-age=600\r\n\
+              content-length: 5\r\nconnection: close\r\n\r\nflask"])?;
+
+        // A fresh entry, but written with another version of the format.
+        let cache_entry = CacheEntry::new(dir.path(), "flask.msgpack");
+        let policy = cache_policy(url.as_str(), &[("cache-control", "max-age=600")]);
+        let mut bytes = DataWithCachePolicy::serialize(&policy, &rmp_serde::to_vec("django")?)?;
+        let version = bytes.len() - 2;
+        bytes[version..].copy_from_slice(&(CACHE_VERSION + 1).to_le_bytes());
+        fs_err::write(cache_entry.path(), &bytes)?;
+        let err = DataWithCachePolicy::from_path_async(cache_entry.path())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::CacheVersionMismatch { found, expected: CACHE_VERSION }
+                if *found == CACHE_VERSION + 1
+        ));
+
+        // It's replaced by a fresh response, rather than misread.
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let req = reqwest::Request::new(http::Method::GET, url);
+        let payload = client
+            .get_serde(req, &cache_entry, CacheControl::None, |response| async {
+                response.text().await
+            })
+            .await
+            .unwrap();
+        assert_eq!(payload, "flask");
+        server.join().unwrap();
+        assert!(DataWithCachePolicy::from_path_async(cache_entry.path())
+            .await
+            .is_ok());
+
         Ok(())
     }
 
-    /// Deserializes a `OwnedArchive<CachePolicy>` off the end of the given
-    /// aligned bytes. Upon success, the given bytes will only contain the
-    /// data itself. The bytes representing the cached policy will have been
-    /// removed.
-    ///
-    /// # Errors
-    ///
-    /// This returns an error if the cache policy could not be deserialized
-    /// from the end of the given bytes.
-    fn deserialize_cache_policy(
-        bytes: &mut AlignedVec,
-    ) -> Result<OwnedArchive<CachePolicy>, Error> {
-        let len = DataWithCachePolicy::deserialize_cache_policy_len(bytes)?;
-        let cache_policy_bytes_start = bytes.len() - (len + 8);
-        let cache_policy_bytes = &bytes[cache_policy_bytes_start..][..len];
-        let mut cache_policy_bytes_aligned = AlignedVec::with_capacity(len);
-        cache_policy_bytes_aligned.extend_from_slice(cache_policy_bytes);
-        assert!(
-            cache_policy_bytes_start <= bytes.len(),
-            "slicing cache policy should result in a truncation"
+    #[tokio::test]
+    async fn corrupt_entry() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (url, server) = serve(
+            [b"HTTP/1.1 200 OK\r\ncache-control: max-age=600\r\n\
+              content-length: 5\r\nconnection: close\r\n\r\nflask"; 2],
+        )?;
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
         );
-        // Technically this will keep the extra capacity used to store the
-        // cache policy around. But it should be pretty small, and it saves a
-        // realloc. (It's unclear whether that matters more or less than the
-        // extra memory usage.)
-        bytes.resize(cache_policy_bytes_start, 0);
-        OwnedArchive::new(cache_policy_bytes_aligned)
+        let cache_entry = CacheEntry::new(dir.path(), "flask.msgpack");
+        let get = || {
+            let req = reqwest::Request::new(http::Method::GET, url.clone());
+            client.get_serde(req, &cache_entry, CacheControl::None, |response| async {
+                response.text().await
+            })
+        };
+        assert_eq!(get().await.unwrap(), "flask");
+
+        // Flip a byte in the middle of the payload, as a partial write might.
+        let mut bytes = fs_err::read(cache_entry.path())?;
+        bytes[3] ^= 0xff;
+        fs_err::write(cache_entry.path(), &bytes)?;
+
+        // The entry is fetched anew, rather than surfaced as a decoding error.
+        assert_eq!(get().await.unwrap(), "flask");
+        server.join().unwrap();
+        assert_eq!(client.stats().fetched, 2);
+
+        Ok(())
     }
 
-    /// Deserializes the length, in bytes, of the cache policy given a complete
-    /// serialized byte buffer of a `DataWithCachePolicy`.
-    ///
-    /// Upon success, callers are guaranteed that
-    /// `&bytes[bytes.len() - (len + 8)..][..len]` will not panic.
-    ///
-    /// # Errors
-    ///
-    /// This returns an error if the length could not be read as a `usize` or is
-    /// otherwise known to be invalid. (For example, it is a length that is bigger
-    /// than `bytes.len()`.)
-    fn deserialize_cache_policy_len(bytes: &[u8]) -> Result<usize, Error> {
-        let Some(cache_policy_len_start) = bytes.len().checked_sub(8) else {
-            let msg = format!(
-                "data-with-cache-policy buffer should be at least 8 bytes \
-                 in length, but is {} bytes",
-                bytes.len(),
+    #[tokio::test]
+    async fn retry_after() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (url, server) = serve([
+            "HTTP/1.1 503 Service Unavailable\r\nretry-after: 1\r\n\
+             content-length: 0\r\nconnection: close\r\n\r\n",
+            "HTTP/1.1 200 OK\r\ncontent-length: 5\r\nconnection: close\r\n\r\nflask",
+        ])?;
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let cache_entry = CacheEntry::new(dir.path(), "flask.msgpack");
+
+        let start = Instant::now();
+        let req = reqwest::Request::new(http::Method::GET, url);
+        let payload = client
+            .get_serde(req, &cache_entry, CacheControl::None, |response| async {
+                response.text().await
+            })
+            .await
+            .unwrap();
+        assert_eq!(payload, "flask");
+        assert!(start.elapsed() >= Duration::from_secs(1));
+        server.join().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn vary_mismatch() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        // A server that responds with the encoding it was asked for.
+        let (url, server) = serve_with(2, |request| {
+            let encoding = header(request, "accept-encoding").unwrap();
+            format!(
+                "HTTP/1.1 200 OK\r\ncache-control: max-age=600\r\n\
+                 vary: Accept-Encoding\r\ncontent-length: {}\r\n\
+                 connection: close\r\n\r\n{encoding}",
+                encoding.len()
+            )
+            .into_bytes()
+        })?;
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let cache_entry = CacheEntry::new(dir.path(), "flask.msgpack");
+        let get = |encoding: &'static str| {
+            let mut req = reqwest::Request::new(http::Method::GET, url.clone());
+            req.headers_mut().insert(
+                http::header::ACCEPT_ENCODING,
+                http::HeaderValue::from_static(encoding),
             );
-            return Err(ErrorKind::ArchiveRead(msg).into());
+            client.get_serde(req, &cache_entry, CacheControl::None, |response| async {
+                response.text().await
+            })
         };
-        let cache_policy_len_bytes = <[u8; 8]>::try_from(&bytes[cache_policy_len_start..])
-            .expect("cache policy length is 8 bytes");
-        let len_u64 = u64::from_le_bytes(cache_policy_len_bytes);
-        let Ok(len_usize) = usize::try_from(len_u64) else {
-            let msg = format!(
-                "data-with-cache-policy has cache policy length of {}, \
-                 but overflows usize",
-                len_u64,
-            );
-            return Err(ErrorKind::ArchiveRead(msg).into());
+
+        // The first variant is fetched and cached.
+        assert_eq!(get("gzip").await.unwrap(), "gzip");
+        // Another variant is fetched, rather than being served the cached one...
+        assert_eq!(get("identity").await.unwrap(), "identity");
+        server.join().unwrap();
+        // ...without overwriting the cached variant, which is still served from the cache.
+        assert_eq!(get("gzip").await.unwrap(), "gzip");
+        assert_eq!(client.stats().fetched, 2);
+        assert_eq!(client.stats().fresh, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_cached() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (url, server) = serve([b"HTTP/1.1 200 OK\r\ncache-control: max-age=600\r\n\
+              content-length: 5\r\nconnection: close\r\n\r\nflask"])?;
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let cache_entry = CacheEntry::new(dir.path(), "flask.html");
+        let req = || reqwest::Request::new(http::Method::GET, url.clone());
+
+        let body = client
+            .get_cached(req(), &cache_entry, CacheControl::None)
+            .await?;
+        assert_eq!(body, b"flask");
+        server.join().unwrap();
+
+        // The body is cached as-is, and served from the cache from here on.
+        assert_eq!(client.read_raw(&cache_entry).await?.unwrap(), b"flask");
+        let body = client
+            .get_cached(req(), &cache_entry, CacheControl::None)
+            .await?;
+        assert_eq!(body, b"flask");
+
+        Ok(())
+    }
+
+    /// Raw bytes are stored without any encoding, and revalidated like any other payload.
+    #[tokio::test]
+    async fn raw_bytes() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (url, server) = serve([
+            &b"HTTP/1.1 200 OK\r\netag: \"v1\"\r\ncache-control: max-age=0\r\n\
+               content-length: 5\r\nconnection: close\r\n\r\nflask"[..],
+            b"HTTP/1.1 304 Not Modified\r\netag: \"v1\"\r\ncache-control: max-age=0\r\n\
+              connection: close\r\n\r\n",
+        ])?;
+        let client = CachedClient::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        );
+        let cache_entry = CacheEntry::new(dir.path(), "flask.bin");
+        // A payload that isn't the body, and isn't valid MessagePack (or UTF-8) either.
+        let payload = vec![0xc1, 0xff, 0x00];
+        let get = || {
+            client.get_fresh_or_refresh(
+                reqwest::Request::new(http::Method::GET, url.clone()),
+                &cache_entry,
+                |_| async { Ok::<_, anyhow::Error>(payload.clone()) },
+            )
         };
-        if bytes.len() < len_usize + 8 {
-            let msg = format!(
-                "invalid cache entry: data-with-cache-policy has cache policy length of {}, \
-                 but total buffer size is {}",
-                len_usize,
-                bytes.len(),
-            );
-            return Err(ErrorKind::ArchiveRead(msg).into());
-        }
-        Ok(len_usize)
+
+        let response = get().await.unwrap();
+        assert_eq!(response.payload, payload);
+        assert_eq!(response.outcome, CacheOutcome::Fetched);
+        assert_eq!(client.read_raw(&cache_entry).await?.unwrap(), payload);
+
+        let response = get().await.unwrap();
+        assert_eq!(response.payload, payload);
+        assert_eq!(response.outcome, CacheOutcome::Revalidated);
+        server.join().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn not_found() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (url, server) = serve([
+            &b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"[..],
+            b"HTTP/1.1 200 OK\r\ncache-control: max-age=600\r\n\
+              content-length: 5\r\nconnection: close\r\n\r\nflask",
+        ])?;
+        let client = CachedClientBuilder::new(
+            ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?).build(),
+        )
+        .cache_not_found(Duration::from_millis(200))
+        .build();
+        let cache_entry = CacheEntry::new(dir.path(), "flask.html");
+        let req = || reqwest::Request::new(http::Method::GET, url.clone());
+
+        // The first request goes to the network, and fails.
+        let err = client
+            .get_cached(req(), &cache_entry, CacheControl::None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::RequestError(_)));
+
+        // Until it expires, the "not found" response is served from the cache.
+        let err = client
+            .get_cached(req(), &cache_entry, CacheControl::None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::NotFoundCached(_)));
+
+        // Once it expires, the next response replaces it.
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        let body = client
+            .get_cached(req(), &cache_entry, CacheControl::None)
+            .await?;
+        assert_eq!(body, b"flask");
+        server.join().unwrap();
+
+        let body = client
+            .get_cached(req(), &cache_entry, CacheControl::None)
+            .await?;
+        assert_eq!(body, b"flask");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn inspect() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let client = client();
+        let url = "https://files.pythonhosted.org/packages/ab/cd/flask-3.0.0-py3-none-any.whl";
+        let path = dir.path().join("flask-3.0.0-py3-none-any.http");
+        fs_err::write(
+            &path,
+            DataWithCachePolicy::serialize(&cache_policy(url, &[]), b"hello")?,
+        )?;
+
+        let meta = client.inspect(&path).await?.unwrap();
+        assert_eq!(meta.url.unwrap().as_str(), url);
+        assert_eq!(meta.package_name.unwrap().as_ref(), "flask");
+
+        // Neither unzipped wheels nor missing files are cache entries.
+        let unzipped = dir.path().join("flask-3.0.0-py3-none-any");
+        fs_err::create_dir(&unzipped)?;
+        assert!(client.inspect(&unzipped).await?.is_none());
+        assert!(client
+            .inspect(dir.path().join("missing.http"))
+            .await?
+            .is_none());
+
+        Ok(())
     }
 }
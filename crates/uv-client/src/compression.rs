@@ -0,0 +1,138 @@
+//! Compression of cached payloads.
+//!
+//! Unless disabled with [`crate::CachedClientBuilder::compression_level`], the payload of every
+//! cache entry written by the [`crate::CachedClient`] is compressed with zstd, provided that makes
+//! it smaller. As with encryption, the cache policy itself is left as-is.
+//!
+//! Compressed payloads are tagged with a magic prefix, so that uncompressed payloads (e.g., those
+//! written with compression disabled, or that didn't compress) can be read alongside them.
+//! The same prefix tells [`crate::CachedClient::read_stream`] whether to decompress a payload
+//! as it's read.
+
+use std::io::{Read, Write};
+
+use async_compression::tokio::bufread::ZstdDecoder as AsyncZstdDecoder;
+use rkyv::util::AlignedVec;
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncReadExt};
+use zstd::stream::read::Decoder as ZstdDecoder;
+use zstd::stream::write::Encoder as ZstdEncoder;
+
+use crate::{Error, ErrorKind};
+
+/// The prefix of every compressed payload, followed by the uncompressed length (as a
+/// little-endian `u64`) and then the zstd frame.
+const COMPRESSED_MAGIC: &[u8; 8] = b"uv-zstd1";
+
+/// The length of the magic prefix, i.e., how much of a payload [`is_compressed`] needs to see.
+pub(crate) const MAGIC_LEN: usize = COMPRESSED_MAGIC.len();
+
+/// The default compression level, which favors speed over size (and matches zstd's own default).
+pub(crate) const DEFAULT_COMPRESSION_LEVEL: u32 = 3;
+
+/// Whether a type of payload is worth compressing, as declared by
+/// [`crate::Cacheable::compression_hint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompressionHint {
+    /// The payload may compress well (e.g., JSON or MessagePack metadata), so it's compressed
+    /// if compression is enabled.
+    #[default]
+    Compressible,
+    /// The payload is compressed already (e.g., the bytes of a wheel), so compressing it again
+    /// would only waste time.
+    AlreadyCompressed,
+}
+
+/// Returns `true` if the given cached payload was compressed with [`compress`].
+pub(crate) fn is_compressed(data: &[u8]) -> bool {
+    data.starts_with(COMPRESSED_MAGIC)
+}
+
+/// Compress a payload at the given level (from 1 to 22), unless that doesn't make it any smaller.
+pub(crate) fn compress(data: &[u8], level: u32) -> Option<Vec<u8>> {
+    let mut compressed = Vec::with_capacity(COMPRESSED_MAGIC.len() + 8 + data.len() / 2);
+    compressed.extend_from_slice(COMPRESSED_MAGIC);
+    compressed.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    let level = i32::try_from(level.clamp(1, 22)).expect("level is at most 22");
+    let mut encoder = ZstdEncoder::new(compressed, level).ok()?;
+    encoder.write_all(data).ok()?;
+    let compressed = encoder.finish().ok()?;
+    (compressed.len() < data.len()).then_some(compressed)
+}
+
+/// Decompress a payload previously compressed with [`compress`].
+///
+/// Fails if the payload is not compressed, or is truncated or otherwise corrupt.
+pub(crate) fn decompress(data: &[u8]) -> Result<AlignedVec, Error> {
+    let rest = data
+        .strip_prefix(COMPRESSED_MAGIC.as_slice())
+        .ok_or(ErrorKind::CacheDecompress)?;
+    if rest.len() < 8 {
+        return Err(ErrorKind::CacheDecompress.into());
+    }
+    let (len, stream) = rest.split_at(8);
+    let len = u64::from_le_bytes(len.try_into().expect("length is 8 bytes"));
+
+    // Don't trust the length enough to allocate it up front, but don't read past it either.
+    let mut decompressed = Vec::new();
+    ZstdDecoder::new(stream)
+        .map_err(|_| ErrorKind::CacheDecompress)?
+        .take(len.saturating_add(1))
+        .read_to_end(&mut decompressed)
+        .map_err(|_| ErrorKind::CacheDecompress)?;
+    if decompressed.len() as u64 != len {
+        return Err(ErrorKind::CacheDecompress.into());
+    }
+
+    let mut aligned = AlignedVec::with_capacity(decompressed.len());
+    aligned.extend_from_slice(&decompressed);
+    Ok(aligned)
+}
+
+/// Decompress a payload previously compressed with [`compress`] as it's read from `rdr`, which
+/// must be positioned at the start of the payload.
+///
+/// Unlike [`decompress`], the payload is never held in memory in full. As there, nothing past
+/// the uncompressed length is read, and a truncated or otherwise corrupt payload fails to read
+/// (though only once the reader gets to the corruption).
+pub(crate) async fn decompress_reader<R: AsyncBufRead + Unpin>(
+    mut rdr: R,
+) -> Result<impl AsyncRead + Unpin, Error> {
+    let mut header = [0; MAGIC_LEN + 8];
+    rdr.read_exact(&mut header)
+        .await
+        .map_err(|_| ErrorKind::CacheDecompress)?;
+    if !is_compressed(&header) {
+        return Err(ErrorKind::CacheDecompress.into());
+    }
+    let len = u64::from_le_bytes(header[MAGIC_LEN..].try_into().expect("length is 8 bytes"));
+    Ok(AsyncZstdDecoder::new(rdr).take(len))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write;
+
+    use super::{compress, decompress, is_compressed, DEFAULT_COMPRESSION_LEVEL};
+
+    #[test]
+    fn round_trip() {
+        // A large, repetitive payload, like a Simple API page.
+        let mut data = String::new();
+        for i in 0..50_000 {
+            writeln!(data, "<a href=\"flask-{i}.0.0-py3-none-any.whl\">flask</a>").unwrap();
+        }
+        let compressed = compress(data.as_bytes(), DEFAULT_COMPRESSION_LEVEL).unwrap();
+        assert!(is_compressed(&compressed));
+        assert!(compressed.len() < data.len() / 4);
+        assert_eq!(decompress(&compressed).unwrap().as_slice(), data.as_bytes());
+
+        // A truncated payload fails to decompress.
+        assert!(decompress(&compressed[..compressed.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn incompressible() {
+        assert!(compress(b"flask", DEFAULT_COMPRESSION_LEVEL).is_none());
+        assert!(decompress(b"flask").is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::{Arc, Mutex};
+
+use reqwest::Response;
+use url::Url;
+
+use crate::observed_body::{observe_body, BodyObserver};
+use crate::{Error, ErrorKind};
+
+/// A check of a response body against its declared `Content-Length`, as enabled by
+/// [`crate::CachedClientBuilder::verify_content_length`].
+///
+/// The body is wrapped such that reading it to the end (or until it fails) records how many
+/// bytes were received. If the body isn't read to the end, e.g., because the caller only needs
+/// its first few bytes, nothing is recorded, and the check passes.
+#[derive(Debug)]
+pub(crate) struct ContentLengthCheck {
+    url: Url,
+    declared: u64,
+    /// The length of the body, if it was read to the end and differs from the declared length.
+    mismatch: Arc<Mutex<Option<u64>>>,
+}
+
+impl ContentLengthCheck {
+    /// Wrap the body of a response to a request with the given method, if it declares a
+    /// `Content-Length`.
+    ///
+    /// Responses to `HEAD` requests declare the length of the body that a `GET` would return,
+    /// and so aren't checked; nor are responses without a declared length, like chunked ones.
+    /// `206 Partial Content` responses are checked, since they declare the length of the part
+    /// they contain.
+    pub(crate) fn wrap(method: &http::Method, response: Response) -> (Response, Option<Self>) {
+        if method == http::Method::HEAD {
+            return (response, None);
+        }
+        let Some(declared) = response
+            .headers()
+            .get(http::header::CONTENT_LENGTH)
+            .and_then(|header| header.to_str().ok())
+            .and_then(|header| header.parse::<u64>().ok())
+        else {
+            return (response, None);
+        };
+
+        let url = response.url().clone();
+        let mismatch = Arc::new(Mutex::new(None));
+        let response = observe_body(
+            response,
+            LengthCounter {
+                declared,
+                received: 0,
+                mismatch: mismatch.clone(),
+            },
+        );
+        (
+            response,
+            Some(Self {
+                url,
+                declared,
+                mismatch,
+            }),
+        )
+    }
+
+    /// Returns the length declared by the response.
+    pub(crate) fn declared(&self) -> u64 {
+        self.declared
+    }
+
+    /// Fail with [`ErrorKind::ContentLengthMismatch`] if the body was read to the end, and its
+    /// length differs from the declared length.
+    pub(crate) fn verify(&self) -> Result<(), Error> {
+        match *self.mismatch.lock().unwrap() {
+            Some(actual) => Err(ErrorKind::ContentLengthMismatch {
+                url: self.url.clone(),
+                declared: self.declared,
+                actual,
+            }
+            .into()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Counts the bytes of a response body, as per [`ContentLengthCheck`].
+struct LengthCounter {
+    declared: u64,
+    received: u64,
+    mismatch: Arc<Mutex<Option<u64>>>,
+}
+
+impl BodyObserver for LengthCounter {
+    fn chunk(&mut self, chunk: &[u8]) {
+        self.received += chunk.len() as u64;
+    }
+
+    fn end(&mut self, _complete: bool) {
+        if self.received != self.declared {
+            *self.mismatch.lock().unwrap() = Some(self.received);
+        }
+    }
+}
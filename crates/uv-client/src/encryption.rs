@@ -0,0 +1,148 @@
+//! At-rest encryption of cached payloads.
+//!
+//! When a [`CacheEncryptionKey`] is configured, the payload of every cache entry written by the
+//! [`crate::CachedClient`] is sealed with ChaCha20-Poly1305 under a fresh random nonce, which is
+//! stored alongside the ciphertext. The cache policy itself is left in the clear, such that
+//! freshness can be determined without the key.
+//!
+//! Each payload is bound to the path of its cache entry, which is authenticated along with it,
+//! such that a sealed payload copied (or moved) to another entry fails to open there.
+//!
+//! Sealed payloads are tagged with a magic prefix, so that entries that can't be opened (e.g.,
+//! because no key or a different key is configured) can be treated as cache misses rather than as
+//! corrupt data.
+
+/// The prefix of every sealed payload, followed by the nonce and then the ciphertext.
+const SEALED_MAGIC: &[u8; 8] = b"uv-aead1";
+
+/// Returns `true` if the given cached payload was sealed with a [`CacheEncryptionKey`].
+pub(crate) fn is_sealed(data: &[u8]) -> bool {
+    data.starts_with(SEALED_MAGIC)
+}
+
+#[cfg(feature = "encryption")]
+pub use key::CacheEncryptionKey;
+
+#[cfg(feature = "encryption")]
+mod key {
+    use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+    use ring::rand::{SecureRandom, SystemRandom};
+    use rkyv::util::AlignedVec;
+
+    use super::SEALED_MAGIC;
+    use crate::{Error, ErrorKind};
+
+    /// A key used to encrypt cached payloads at rest.
+    pub struct CacheEncryptionKey {
+        key: LessSafeKey,
+        rng: SystemRandom,
+    }
+
+    impl CacheEncryptionKey {
+        /// Create a key from 32 bytes of secret key material.
+        pub fn new(key: &[u8; 32]) -> Self {
+            let key = UnboundKey::new(&CHACHA20_POLY1305, key)
+                .expect("ChaCha20-Poly1305 keys are 32 bytes");
+            Self {
+                key: LessSafeKey::new(key),
+                rng: SystemRandom::new(),
+            }
+        }
+
+        /// Seal a payload under a fresh random nonce, binding it to the given associated data
+        /// (i.e., the path of its cache entry), which must be passed again to open it.
+        pub(crate) fn seal(&self, data: &[u8], aad: &[u8]) -> Result<Vec<u8>, Error> {
+            let mut nonce = [0u8; NONCE_LEN];
+            self.rng
+                .fill(&mut nonce)
+                .map_err(|_| ErrorKind::CacheEncrypt)?;
+
+            let mut in_out = data.to_vec();
+            self.key
+                .seal_in_place_append_tag(
+                    Nonce::assume_unique_for_key(nonce),
+                    Aad::from(aad),
+                    &mut in_out,
+                )
+                .map_err(|_| ErrorKind::CacheEncrypt)?;
+
+            let mut sealed = Vec::with_capacity(SEALED_MAGIC.len() + NONCE_LEN + in_out.len());
+            sealed.extend_from_slice(SEALED_MAGIC);
+            sealed.extend_from_slice(&nonce);
+            sealed.extend_from_slice(&in_out);
+            Ok(sealed)
+        }
+
+        /// Open a payload previously sealed with [`CacheEncryptionKey::seal`].
+        ///
+        /// Fails if the payload is not sealed, or was sealed with a different key or different
+        /// associated data.
+        pub(crate) fn open(&self, sealed: &[u8], aad: &[u8]) -> Result<AlignedVec, Error> {
+            let rest = sealed
+                .strip_prefix(SEALED_MAGIC.as_slice())
+                .ok_or(ErrorKind::CacheDecrypt)?;
+            if rest.len() < NONCE_LEN {
+                return Err(ErrorKind::CacheDecrypt.into());
+            }
+            let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+            let nonce =
+                Nonce::try_assume_unique_for_key(nonce).map_err(|_| ErrorKind::CacheDecrypt)?;
+
+            let mut in_out = ciphertext.to_vec();
+            let data = self
+                .key
+                .open_in_place(nonce, Aad::from(aad), &mut in_out)
+                .map_err(|_| ErrorKind::CacheDecrypt)?;
+
+            let mut aligned = AlignedVec::with_capacity(data.len());
+            aligned.extend_from_slice(data);
+            Ok(aligned)
+        }
+    }
+
+    impl std::fmt::Debug for CacheEncryptionKey {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            // Never print the key material.
+            f.debug_struct("CacheEncryptionKey").finish_non_exhaustive()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::CacheEncryptionKey;
+        use crate::encryption::is_sealed;
+
+        #[test]
+        fn round_trip() {
+            let key = CacheEncryptionKey::new(&[7; 32]);
+            let sealed = key.seal(b"hello", b"a.msgpack").unwrap();
+            assert!(is_sealed(&sealed));
+            assert!(!sealed.windows(5).any(|window| window == b"hello"));
+            assert_eq!(
+                key.open(&sealed, b"a.msgpack").unwrap().as_slice(),
+                b"hello"
+            );
+
+            // Every seal uses a fresh nonce.
+            assert_ne!(key.seal(b"hello", b"a.msgpack").unwrap(), sealed);
+        }
+
+        #[test]
+        fn wrong_key() {
+            let sealed = CacheEncryptionKey::new(&[7; 32])
+                .seal(b"hello", b"a.msgpack")
+                .unwrap();
+            let other = CacheEncryptionKey::new(&[8; 32]);
+            assert!(other.open(&sealed, b"a.msgpack").is_err());
+            assert!(other.open(b"hello", b"a.msgpack").is_err());
+            assert!(other.open(b"uv-aead1", b"a.msgpack").is_err());
+        }
+
+        #[test]
+        fn wrong_entry() {
+            let key = CacheEncryptionKey::new(&[7; 32]);
+            let sealed = key.seal(b"hello", b"a.msgpack").unwrap();
+            assert!(key.open(&sealed, b"b.msgpack").is_err());
+        }
+    }
+}
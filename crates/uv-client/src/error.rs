@@ -106,6 +106,9 @@ pub enum ErrorKind {
     #[error("The wheel {0} is not a valid zip file")]
     Zip(WheelFilename, #[source] ZipError),
 
+    #[error("Failed to read `{1}` from the wheel {0}: {2}")]
+    RemoteZipEntry(WheelFilename, String, String),
+
     #[error("Failed to write to the client cache")]
     CacheWrite(#[source] std::io::Error),
 
@@ -118,6 +121,18 @@ pub enum ErrorKind {
     #[error("Cache serialization failed")]
     Encode(#[source] rmp_serde::encode::Error),
 
+    #[error("Failed to encrypt cache entry")]
+    CacheEncrypt,
+
+    #[error("Failed to decrypt cache entry; it may have been written with a different key")]
+    CacheDecrypt,
+
+    #[error("Failed to decompress cache entry")]
+    CacheDecompress,
+
+    #[error("Cache entry is stored compressed or encrypted, so it can't be memory-mapped")]
+    CacheNotMappable,
+
     /// An [`io::Error`] with a filename attached
     #[error(transparent)]
     Persist(#[from] tempfile::PersistError),
@@ -137,6 +152,34 @@ pub enum ErrorKind {
     #[error("Writing to cache archive failed: {0}")]
     ArchiveWrite(#[source] crate::rkyvutil::SerializerError),
 
+    #[error("Cache entry was written with version {found} of the cache format, but version {expected} is required")]
+    CacheVersionMismatch { found: u16, expected: u16 },
+
+    #[error("The response from {url} declared a `Content-Length` of {declared} bytes, but {actual} bytes were received")]
+    ContentLengthMismatch {
+        url: Url,
+        declared: u64,
+        actual: u64,
+    },
+
+    #[error(
+        "Hash mismatch for {url}: expected `{expected}`, but the download hashed to `{actual}`"
+    )]
+    HashMismatch {
+        url: Url,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("Request to {0} did not complete before its deadline")]
+    DeadlineExceeded(Url),
+
+    #[error("{0} was not found, as remembered by the cache")]
+    NotFoundCached(Url),
+
+    #[error("Failed to parse the cache policy overrides at {}: {1}", .0.display())]
+    PolicyOverrides(std::path::PathBuf, String),
+
     #[error("Network connectivity is disabled, but the requested data wasn't found in the cache for: `{0}`")]
     Offline(String),
 }
@@ -0,0 +1,597 @@
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+
+use rustc_hash::FxHashMap;
+use tokio::task::JoinHandle;
+use tokio_util::sync::{CancellationToken, DropGuard};
+use tracing::{debug, warn};
+
+use crate::audit::AuditLog;
+use crate::sidecar;
+use crate::weak_cache::WeakCache;
+use crate::{CacheEntryMeta, DataWithCachePolicy, Error, ErrorKind};
+
+/// How long the evictor waits before checking again whether the client is still busy.
+const BUSY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// The cache entries currently in use by requests made through a [`crate::CachedClient`] (and
+/// its clones).
+///
+/// Entries are reference counted by path, since concurrent requests may share an entry. The
+/// evictor never deletes an entry that's in use, and, conversely, no request can start using an
+/// entry while it's being deleted.
+#[derive(Debug, Default)]
+pub(crate) struct ActiveEntries {
+    entries: Mutex<FxHashMap<PathBuf, usize>>,
+}
+
+impl ActiveEntries {
+    /// Mark the entry at the given path as in use, until the returned guard is dropped.
+    pub(crate) fn acquire(self: &Arc<Self>, path: &Path) -> ActiveEntry {
+        *self
+            .entries
+            .lock()
+            .unwrap()
+            .entry(path.to_path_buf())
+            .or_default() += 1;
+        ActiveEntry {
+            entries: self.clone(),
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// Returns the number of entries currently in use.
+    fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Delete the entry at the given path, unless it's in use, returning whether it was deleted.
+    pub(crate) fn remove_unless_active(&self, path: &Path) -> Result<bool, Error> {
+        // Hold the lock while deleting, such that no request can start using the entry (and, e.g.,
+        // read half of it) in the meantime.
+        let entries = self.entries.lock().unwrap();
+        if entries.contains_key(path) {
+            return Ok(false);
+        }
+        match fs_err::remove_file(path) {
+            Ok(()) => {
+                sidecar::remove_sidecar(path).map_err(ErrorKind::CacheWrite)?;
+                Ok(true)
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(ErrorKind::CacheWrite(err).into()),
+        }
+    }
+}
+
+/// Marks a cache entry as in use, as returned by [`ActiveEntries::acquire`].
+#[derive(Debug)]
+pub(crate) struct ActiveEntry {
+    entries: Arc<ActiveEntries>,
+    path: PathBuf,
+}
+
+impl Drop for ActiveEntry {
+    fn drop(&mut self) {
+        let mut entries = self.entries.entries.lock().unwrap();
+        if let Some(count) = entries.get_mut(&self.path) {
+            *count -= 1;
+            if *count == 0 {
+                entries.remove(&self.path);
+            }
+        }
+    }
+}
+
+/// Which cache entries are deleted by [`crate::CachedClient::spawn_evictor`], and how quickly.
+#[derive(Clone)]
+pub struct EvictionPolicy {
+    dir: PathBuf,
+    predicate: Arc<dyn Fn(&CacheEntryMeta) -> bool + Send + Sync>,
+    entries_per_second: NonZeroU32,
+    max_active_entries: usize,
+    sweep_interval: Duration,
+}
+
+impl EvictionPolicy {
+    /// Delete every cache entry under `dir` for which the predicate returns `true`, as in
+    /// [`crate::CachedClient::invalidate_where`].
+    ///
+    /// By default, at most 100 entries are examined per second, the evictor pauses while more
+    /// than 32 cache entries are in use, and `dir` is swept again every 10 minutes.
+    pub fn new(
+        dir: impl Into<PathBuf>,
+        predicate: impl Fn(&CacheEntryMeta) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            dir: dir.into(),
+            predicate: Arc::new(predicate),
+            entries_per_second: NonZeroU32::new(100).unwrap(),
+            max_active_entries: 32,
+            sweep_interval: Duration::from_secs(10 * 60),
+        }
+    }
+
+    /// Delete every cache entry under `dir` that is older than the given age, as in
+    /// [`crate::CachedClient::prune_older_than`].
+    pub fn older_than(dir: impl Into<PathBuf>, age: Duration) -> Self {
+        Self::new(dir, older_than(age))
+    }
+
+    /// Examine at most the given number of cache entries per second, such that the evictor
+    /// doesn't saturate disk I/O.
+    #[must_use]
+    pub fn entries_per_second(mut self, entries_per_second: NonZeroU32) -> Self {
+        self.entries_per_second = entries_per_second;
+        self
+    }
+
+    /// Pause while more than the given number of cache entries are in use by requests, such that
+    /// the evictor yields to the client while it's busy.
+    #[must_use]
+    pub fn max_active_entries(mut self, max_active_entries: usize) -> Self {
+        self.max_active_entries = max_active_entries;
+        self
+    }
+
+    /// Wait for the given duration between sweeps of the directory.
+    #[must_use]
+    pub fn sweep_interval(mut self, sweep_interval: Duration) -> Self {
+        self.sweep_interval = sweep_interval;
+        self
+    }
+}
+
+impl EvictionPolicy {
+    /// Returns the directory swept by the evictor.
+    pub(crate) fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns `true` if the evictor would delete the given cache entry (unless it's in use).
+    pub(crate) fn matches(&self, entry: &CacheEntryMeta) -> bool {
+        (self.predicate)(entry)
+    }
+}
+
+impl std::fmt::Debug for EvictionPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EvictionPolicy")
+            .field("dir", &self.dir)
+            .field("entries_per_second", &self.entries_per_second)
+            .field("max_active_entries", &self.max_active_entries)
+            .field("sweep_interval", &self.sweep_interval)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Select the cache entries older than the given age, as for
+/// [`crate::CachedClient::prune_older_than`].
+pub(crate) fn older_than(
+    age: Duration,
+) -> impl Fn(&CacheEntryMeta) -> bool + Send + Sync + 'static {
+    move |entry| entry.freshness.age > age
+}
+
+/// Select the cache entries fetched from the given host, as for
+/// [`crate::CachedClient::clean_host`].
+pub(crate) fn from_host(host: &str) -> impl Fn(&CacheEntryMeta) -> bool + Send + Sync + 'static {
+    let host = host.to_string();
+    move |entry| entry.host.as_deref() == Some(host.as_str())
+}
+
+/// A cap on the number of cache entries under a directory, as set by
+/// [`crate::CachedClientBuilder::max_entries`].
+///
+/// The number of entries is counted once, on the first write, and then maintained as a running
+/// count of new entries. Once the count reaches the cap, the directory is scanned again, which
+/// corrects the count for entries deleted in the meantime (e.g., by another process), and the
+/// least recently written entries are deleted down to a low-water mark (see
+/// [`EntryLimit::low_water_mark`]), such that the directory is only scanned once every so many
+/// new entries, rather than on every write at the cap.
+#[derive(Debug)]
+pub(crate) struct EntryLimit {
+    dir: PathBuf,
+    max_entries: NonZeroUsize,
+    count: OnceLock<AtomicUsize>,
+}
+
+impl EntryLimit {
+    pub(crate) fn new(dir: PathBuf, max_entries: NonZeroUsize) -> Self {
+        Self {
+            dir,
+            max_entries,
+            count: OnceLock::new(),
+        }
+    }
+
+    /// Account for writing the cache entry at the given path, first deleting the least recently
+    /// written entries if the directory is at capacity. Returns the paths of the deleted entries.
+    ///
+    /// Rewriting an existing entry, or writing an entry outside the directory, doesn't count
+    /// towards the cap. Entries in use by requests are never deleted, so the cap can be exceeded
+    /// while the client is busy.
+    pub(crate) fn reserve(
+        &self,
+        path: &Path,
+        active_entries: &ActiveEntries,
+    ) -> Result<Vec<PathBuf>, Error> {
+        if !path.starts_with(&self.dir) || path.exists() {
+            return Ok(Vec::new());
+        }
+        let count = self.count.get_or_init(|| {
+            let count = list_entries(&self.dir).map_or_else(
+                |err| {
+                    warn!(
+                        "Failed to count cache entries in {}: {err}",
+                        self.dir.display()
+                    );
+                    0
+                },
+                |entries| entries.len(),
+            );
+            AtomicUsize::new(count)
+        });
+        if count.fetch_add(1, Ordering::Relaxed) < self.max_entries.get() {
+            return Ok(Vec::new());
+        }
+
+        let mut entries = list_entries(&self.dir)?;
+        entries.sort_unstable_by_key(|(_, modified)| *modified);
+        let mut len = entries.len();
+        let mut evicted = Vec::new();
+        for (entry, _) in entries {
+            // Leave room for the entry that's about to be written.
+            if len < self.low_water_mark() {
+                break;
+            }
+            if entry != path && active_entries.remove_unless_active(&entry)? {
+                debug!(
+                    "Evicted cache entry to stay under the cap: {}",
+                    entry.display()
+                );
+                len -= 1;
+                evicted.push(entry);
+            }
+        }
+        // Include the entry that's about to be written.
+        count.store(len + 1, Ordering::Relaxed);
+        Ok(evicted)
+    }
+
+    /// Returns the number of entries (including the one about to be written) left once the cap
+    /// is reached: 90% of the cap, leaving room for at least one more entry before the next scan.
+    fn low_water_mark(&self) -> usize {
+        let max_entries = self.max_entries.get();
+        max_entries - (max_entries / 10).max(1)
+    }
+}
+
+/// List the cache entries under `dir`, recursively, along with when each was last written.
+///
+/// Access times would be a better measure of use, but they aren't tracked reliably (e.g., with
+/// `relatime`), and reading the entries here would update them anyway. Instead, entries used
+/// without being read are marked by [`crate::CachedClient::touch`]. Files that can't be read
+/// as cache entries (like sidecars) are skipped.
+fn list_entries(dir: &Path) -> Result<Vec<(PathBuf, SystemTime)>, Error> {
+    let mut entries = vec![];
+    for path in list_files(dir)? {
+        if DataWithCachePolicy::from_path_without_data(&path).is_err() {
+            continue;
+        }
+        let Ok(metadata) = fs_err::metadata(&path) else {
+            continue;
+        };
+        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        entries.push((path, modified));
+    }
+    Ok(entries)
+}
+
+/// A handle to an evictor started by [`crate::CachedClient::spawn_evictor`].
+///
+/// Dropping the handle stops the evictor once it's done with the entry it's working on; use
+/// [`EvictorHandle::stop`] to wait for that to happen.
+#[derive(Debug)]
+pub struct EvictorHandle {
+    guard: DropGuard,
+    task: JoinHandle<()>,
+    evicted: Arc<AtomicUsize>,
+}
+
+impl EvictorHandle {
+    /// Returns the number of cache entries deleted so far.
+    pub fn evicted(&self) -> usize {
+        self.evicted.load(Ordering::Relaxed)
+    }
+
+    /// Stop the evictor, waiting for it to finish with the entry it's working on, and return the
+    /// number of cache entries it deleted.
+    pub async fn stop(self) -> usize {
+        let Self {
+            guard,
+            task,
+            evicted,
+        } = self;
+        drop(guard);
+        // This just forwards panics from the evictor.
+        task.await.unwrap();
+        evicted.load(Ordering::Relaxed)
+    }
+}
+
+/// Start an evictor on the current Tokio runtime.
+pub(crate) fn spawn(
+    policy: EvictionPolicy,
+    active_entries: Arc<ActiveEntries>,
+    weak_cache: Option<Arc<WeakCache>>,
+    audit_log: Option<Arc<AuditLog>>,
+) -> EvictorHandle {
+    let cancel = CancellationToken::new();
+    let evicted = Arc::new(AtomicUsize::new(0));
+    let task = tokio::spawn(run(
+        policy,
+        active_entries,
+        weak_cache,
+        audit_log,
+        cancel.clone(),
+        evicted.clone(),
+    ));
+    EvictorHandle {
+        guard: cancel.drop_guard(),
+        task,
+        evicted,
+    }
+}
+
+async fn run(
+    policy: EvictionPolicy,
+    active_entries: Arc<ActiveEntries>,
+    weak_cache: Option<Arc<WeakCache>>,
+    audit_log: Option<Arc<AuditLog>>,
+    cancel: CancellationToken,
+    evicted: Arc<AtomicUsize>,
+) {
+    let period = Duration::from_secs(1) / policy.entries_per_second.get();
+    loop {
+        let dir = policy.dir.clone();
+        let paths = match tokio::task::spawn_blocking(move || list_files(&dir))
+            .await
+            // This just forwards panics from the closure.
+            .unwrap()
+        {
+            Ok(paths) => paths,
+            Err(err) => {
+                warn!(
+                    "Failed to list cache entries in {}: {err}",
+                    policy.dir.display()
+                );
+                Vec::new()
+            }
+        };
+        for path in paths {
+            while active_entries.len() > policy.max_active_entries {
+                if sleep_or_cancel(&cancel, BUSY_BACKOFF).await {
+                    return;
+                }
+            }
+            if sleep_or_cancel(&cancel, period).await {
+                return;
+            }
+            let active_entries = active_entries.clone();
+            let predicate = policy.predicate.clone();
+            let result = tokio::task::spawn_blocking({
+                let path = path.clone();
+                move || evict(&active_entries, &path, &*predicate)
+            })
+            .await
+            // This just forwards panics from the closure.
+            .unwrap();
+            match result {
+                Ok(Some(meta)) => {
+                    debug!("Evicted cache entry: {}", path.display());
+                    if let Some(weak_cache) = weak_cache.as_ref() {
+                        weak_cache.remove(&path);
+                    }
+                    if let Some(audit_log) = audit_log.as_ref() {
+                        audit_log.evict(&path, meta.url.as_ref(), Some(meta.size));
+                    }
+                    evicted.fetch_add(1, Ordering::Relaxed);
+                }
+                Ok(None) => {}
+                Err(err) => warn!("Failed to evict cache entry at {}: {err}", path.display()),
+            }
+        }
+        if sleep_or_cancel(&cancel, policy.sweep_interval).await {
+            return;
+        }
+    }
+}
+
+/// Wait for the given duration, returning `true` if the evictor was stopped in the meantime.
+async fn sleep_or_cancel(cancel: &CancellationToken, duration: Duration) -> bool {
+    tokio::time::timeout(duration, cancel.cancelled())
+        .await
+        .is_ok()
+}
+
+/// List the files under `dir`, recursively.
+fn list_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut paths = vec![];
+    for entry in walkdir::WalkDir::new(dir) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err)
+                if err
+                    .io_error()
+                    .is_some_and(|err| err.kind() == std::io::ErrorKind::NotFound) =>
+            {
+                continue;
+            }
+            Err(err) => return Err(ErrorKind::Io(err.into()).into()),
+        };
+        if entry.file_type().is_file() {
+            paths.push(entry.into_path());
+        }
+    }
+    Ok(paths)
+}
+
+/// Delete the cache entry at the given path if it matches the predicate and isn't in use,
+/// returning its metadata if it was deleted.
+///
+/// If the entry is rewritten between checking the predicate and deleting it, the new entry is
+/// deleted regardless; at worst, it's fetched again.
+fn evict(
+    active_entries: &ActiveEntries,
+    path: &Path,
+    predicate: &(dyn Fn(&CacheEntryMeta) -> bool + Send + Sync),
+) -> Result<Option<CacheEntryMeta>, Error> {
+    let Some(meta) = CacheEntryMeta::from_path(path, SystemTime::now())? else {
+        return Ok(None);
+    };
+    if !predicate(&meta) {
+        return Ok(None);
+    }
+    Ok(active_entries.remove_unless_active(path)?.then_some(meta))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::{NonZeroU32, NonZeroUsize};
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    use anyhow::Result;
+    use reqwest_middleware::ClientBuilder;
+    use url::Url;
+
+    use crate::httpcache::{CachePolicy, CachePolicyBuilder};
+    use crate::{CachedClient, DataWithCachePolicy};
+
+    use super::{ActiveEntries, EntryLimit, EvictionPolicy};
+
+    fn cache_policy() -> CachePolicy {
+        let request = reqwest::Request::new(
+            http::Method::GET,
+            Url::parse("https://example.com/").unwrap(),
+        );
+        let response = http::Response::builder()
+            .status(200)
+            .header("cache-control", "max-age=60")
+            .body("")
+            .unwrap();
+        CachePolicyBuilder::new(&request).build(&reqwest::Response::from(response))
+    }
+
+    #[test]
+    fn active_entries() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("entry.msgpack");
+        fs_err::write(&path, "hello")?;
+
+        let active_entries = Arc::new(ActiveEntries::default());
+        let first = active_entries.acquire(&path);
+        let second = active_entries.acquire(&path);
+        assert_eq!(active_entries.len(), 1);
+
+        // An entry is kept for as long as any request is using it.
+        assert!(!active_entries.remove_unless_active(&path)?);
+        drop(first);
+        assert!(!active_entries.remove_unless_active(&path)?);
+        drop(second);
+        assert_eq!(active_entries.len(), 0);
+        assert!(active_entries.remove_unless_active(&path)?);
+        assert!(!path.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn entry_limit() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let data = DataWithCachePolicy::serialize(&cache_policy(), b"hello")?;
+        for (name, age) in [("a.msgpack", 30), ("b.msgpack", 10), ("c.msgpack", 20)] {
+            let path = dir.path().join(name);
+            fs_err::write(&path, &data)?;
+            let time = SystemTime::now() - Duration::from_secs(age);
+            std::fs::File::options()
+                .write(true)
+                .open(&path)?
+                .set_modified(time)?;
+        }
+        // Files that aren't cache entries are neither counted nor evicted.
+        fs_err::write(dir.path().join("d.whl"), b"wheel")?;
+
+        let active_entries = ActiveEntries::default();
+        let limit = EntryLimit::new(dir.path().to_path_buf(), NonZeroUsize::new(3).unwrap());
+
+        // Rewriting an existing entry doesn't require any room.
+        assert!(limit
+            .reserve(&dir.path().join("b.msgpack"), &active_entries)?
+            .is_empty());
+
+        // A new entry at the cap evicts the least recently written entries, down to the
+        // low-water mark.
+        let path = dir.path().join("e.msgpack");
+        assert_eq!(
+            limit.reserve(&path, &active_entries)?,
+            vec![dir.path().join("a.msgpack"), dir.path().join("c.msgpack")]
+        );
+        fs_err::write(&path, &data)?;
+
+        // Which leaves room for the next entry, without another scan.
+        assert!(limit
+            .reserve(&dir.path().join("f.msgpack"), &active_entries)?
+            .is_empty());
+        assert!(dir.path().join("b.msgpack").exists());
+        assert!(dir.path().join("d.whl").exists());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn spawn_evictor() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let client = CachedClient::new(ClientBuilder::new(reqwest::Client::new()).build());
+        let data = DataWithCachePolicy::serialize(&cache_policy(), b"hello")?;
+        for name in ["a.msgpack", "b.msgpack", "c.msgpack"] {
+            fs_err::write(dir.path().join(name), &data)?;
+        }
+        // Files that aren't cache entries are left alone.
+        fs_err::write(dir.path().join("d.whl"), b"wheel")?;
+
+        let in_use = client.active_entries.acquire(&dir.path().join("a.msgpack"));
+        let handle = client.spawn_evictor(
+            EvictionPolicy::new(dir.path(), |entry| entry.size > 0)
+                .entries_per_second(NonZeroU32::new(1000).unwrap())
+                .sweep_interval(Duration::from_millis(10)),
+        );
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while handle.evicted() < 2 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await?;
+        assert!(dir.path().join("a.msgpack").exists());
+        assert!(!dir.path().join("b.msgpack").exists());
+        assert!(!dir.path().join("c.msgpack").exists());
+        assert!(dir.path().join("d.whl").exists());
+
+        // Once no longer in use, the entry is evicted on the next sweep.
+        drop(in_use);
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while handle.evicted() < 3 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await?;
+        assert_eq!(handle.stop().await, 3);
+        assert!(!dir.path().join("a.msgpack").exists());
+
+        Ok(())
+    }
+}
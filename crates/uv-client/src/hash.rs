@@ -0,0 +1,126 @@
+//! Verification of downloads against the hash in their URL's fragment, as in
+//! `https://example.com/flask-3.0.0-py3-none-any.whl#sha256=...`.
+
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use url::Url;
+
+use crate::{Error, ErrorKind};
+
+/// Hashes a download as it's read, to check it against the hash in its URL's fragment.
+#[derive(Debug)]
+pub struct HashVerifier {
+    url: Url,
+    hasher: Hasher,
+    /// The expected digest, as lowercase hex.
+    expected: String,
+}
+
+#[derive(Debug)]
+enum Hasher {
+    Sha256(Sha256),
+    Sha384(Sha384),
+    Sha512(Sha512),
+}
+
+impl HashVerifier {
+    /// Returns a verifier for the given URL, if its fragment carries a `sha256`, `sha384`, or
+    /// `sha512` hash.
+    ///
+    /// Any other fragment (e.g., `#egg=flask`) is ignored.
+    pub fn from_url(url: &Url) -> Option<Self> {
+        let (algorithm, digest) = url.fragment()?.split_once('=')?;
+        let hasher = match algorithm {
+            "sha256" => Hasher::Sha256(Sha256::new()),
+            "sha384" => Hasher::Sha384(Sha384::new()),
+            "sha512" => Hasher::Sha512(Sha512::new()),
+            _ => return None,
+        };
+        Some(Self {
+            url: url.clone(),
+            hasher,
+            expected: digest.to_ascii_lowercase(),
+        })
+    }
+
+    /// Hash the next chunk of the download.
+    pub fn update(&mut self, chunk: &[u8]) {
+        match &mut self.hasher {
+            Hasher::Sha256(hasher) => hasher.update(chunk),
+            Hasher::Sha384(hasher) => hasher.update(chunk),
+            Hasher::Sha512(hasher) => hasher.update(chunk),
+        }
+    }
+
+    /// Check the download, once read in full, against the expected hash.
+    ///
+    /// Fails with [`ErrorKind::HashMismatch`] if it doesn't match.
+    pub fn verify(self) -> Result<(), Error> {
+        let (algorithm, actual) = match self.hasher {
+            Hasher::Sha256(hasher) => ("sha256", hex::encode(hasher.finalize())),
+            Hasher::Sha384(hasher) => ("sha384", hex::encode(hasher.finalize())),
+            Hasher::Sha512(hasher) => ("sha512", hex::encode(hasher.finalize())),
+        };
+        if actual == self.expected {
+            return Ok(());
+        }
+        Err(ErrorKind::HashMismatch {
+            url: self.url,
+            expected: format!("{algorithm}:{}", self.expected),
+            actual: format!("{algorithm}:{actual}"),
+        }
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use super::HashVerifier;
+    use crate::ErrorKind;
+
+    #[test]
+    fn verify() {
+        let url = Url::parse(
+            "https://example.com/flask.whl#sha256=\
+             CA978112CA1BBDCAFAC231B39A23DC4DA786EFF8147C4E72B9807785AFEE48BB",
+        )
+        .unwrap();
+        let mut verifier = HashVerifier::from_url(&url).unwrap();
+        verifier.update(b"a");
+        verifier.verify().unwrap();
+
+        let mut verifier = HashVerifier::from_url(&url).unwrap();
+        verifier.update(b"b");
+        let err = verifier.verify().unwrap_err();
+        let ErrorKind::HashMismatch {
+            expected, actual, ..
+        } = err.kind()
+        else {
+            panic!("{err}");
+        };
+        assert_eq!(
+            expected,
+            "sha256:ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb"
+        );
+        assert_eq!(
+            actual,
+            "sha256:3e23e8160039594a33894f6564e1b1348bbd7a0088d42c4acb73eeaed59c009d"
+        );
+    }
+
+    #[test]
+    fn from_url() {
+        for algorithm in ["sha256", "sha384", "sha512"] {
+            let url = Url::parse(&format!("https://example.com/flask.whl#{algorithm}=00")).unwrap();
+            assert!(HashVerifier::from_url(&url).is_some());
+        }
+        for url in [
+            "https://example.com/flask.whl",
+            "https://example.com/flask.whl#md5=00",
+            "https://example.com/flask.tar.gz#egg=flask",
+        ] {
+            assert!(HashVerifier::from_url(&Url::parse(url).unwrap()).is_none());
+        }
+    }
+}
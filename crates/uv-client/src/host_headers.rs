@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use http::HeaderMap;
+use reqwest::Request;
+use rustc_hash::FxHashMap;
+use url::Url;
+
+/// A host and port to which requests are sent, as matched by
+/// [`CachedClientBuilder::host_headers`](crate::CachedClientBuilder::host_headers).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HostPort {
+    host: String,
+    port: u16,
+}
+
+impl HostPort {
+    /// A host (as a domain or IP address) and port, e.g., `pypi.example.com` and `443`.
+    pub fn new(host: impl Into<String>, port: u16) -> Self {
+        Self {
+            host: host.into().to_ascii_lowercase(),
+            port,
+        }
+    }
+
+    /// The host and port of the given URL, where the port defaults to that of the scheme.
+    ///
+    /// Returns `None` if the URL has no host, or no port is known for its scheme.
+    pub fn from_url(url: &Url) -> Option<Self> {
+        Some(Self::new(url.host_str()?, url.port_or_known_default()?))
+    }
+}
+
+/// The default headers to send with requests to each host, e.g., the credentials of a private
+/// index.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HostHeaders(FxHashMap<HostPort, HeaderMap>);
+
+impl HostHeaders {
+    pub(crate) fn new(host_headers: HashMap<HostPort, HeaderMap>) -> Self {
+        Self(host_headers.into_iter().collect())
+    }
+
+    /// Add the headers for the request's host to the request, unless it already sets them.
+    ///
+    /// Only the request's own host is considered, so headers are never sent to another host
+    /// (and `reqwest` drops credentials when following a redirect to another host).
+    pub(crate) fn apply(&self, req: &mut Request) {
+        if self.0.is_empty() {
+            return;
+        }
+        let Some(headers) = HostPort::from_url(req.url()).and_then(|host| self.0.get(&host)) else {
+            return;
+        };
+        for name in headers.keys() {
+            if req.headers().contains_key(name) {
+                continue;
+            }
+            for value in headers.get_all(name) {
+                req.headers_mut().append(name, value.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use http::{HeaderMap, HeaderValue};
+    use reqwest::Request;
+    use url::Url;
+
+    use super::{HostHeaders, HostPort};
+
+    #[test]
+    fn from_url() {
+        let host = |url: &str| HostPort::from_url(&Url::parse(url).unwrap());
+        assert_eq!(
+            host("https://Example.com/simple/"),
+            Some(HostPort::new("example.com", 443))
+        );
+        assert_eq!(
+            host("http://example.com:8080/"),
+            Some(HostPort::new("example.com", 8080))
+        );
+        assert_eq!(host("file:///tmp/simple/"), None);
+    }
+
+    #[test]
+    fn apply() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            http::header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer secret"),
+        );
+        let host_headers = HostHeaders::new(HashMap::from([(
+            HostPort::new("example.com", 443),
+            headers,
+        )]));
+        let request = |url: &str| {
+            let mut req = Request::new(http::Method::GET, Url::parse(url).unwrap());
+            host_headers.apply(&mut req);
+            req.headers()
+                .get(http::header::AUTHORIZATION)
+                .map(|value| value.to_str().unwrap().to_string())
+        };
+
+        assert_eq!(
+            request("https://example.com/simple/").as_deref(),
+            Some("Bearer secret")
+        );
+        // Other hosts, and other ports on the same host, don't get the headers.
+        assert_eq!(request("https://example.org/simple/"), None);
+        assert_eq!(request("https://files.example.com/simple/"), None);
+        assert_eq!(request("http://example.com/simple/"), None);
+
+        // Headers set on the request itself take precedence.
+        let mut req = Request::new(
+            http::Method::GET,
+            Url::parse("https://example.com/simple/").unwrap(),
+        );
+        req.headers_mut().insert(
+            http::header::AUTHORIZATION,
+            HeaderValue::from_static("Basic other"),
+        );
+        host_headers.apply(&mut req);
+        assert_eq!(
+            req.headers().get(http::header::AUTHORIZATION),
+            Some(&HeaderValue::from_static("Basic other"))
+        );
+    }
+}
@@ -60,6 +60,8 @@ pub struct CacheControl {
     pub s_maxage_seconds: Option<u64>,
     /// https://httpwg.org/specs/rfc8246.html
     pub immutable: bool,
+    /// https://www.rfc-editor.org/rfc/rfc5861.html#section-3
+    pub stale_while_revalidate_seconds: Option<u64>,
 }
 
 impl CacheControl {
@@ -134,6 +136,11 @@ impl FromIterator<CacheControlDirective> for CacheControl {
                     Some(seconds) => cc.s_maxage_seconds = Some(seconds),
                 },
                 "immutable" => cc.immutable = true,
+                // Unlike the directives above, this one only ever allows a stale response to be
+                // used, so an invalid value is ignored, rather than making the response stale.
+                "stale-while-revalidate" => {
+                    cc.stale_while_revalidate_seconds = parse_int(&ccd.value);
+                }
                 _ => {}
             }
         }
@@ -471,6 +478,19 @@ mod tests {
     // support parsing it that way anyway.
     //
     // [RFC 9111 S5.2.1.1]: https://www.rfc-editor.org/rfc/rfc9111.html#section-5.2.1.1
+    #[test]
+    fn cache_control_stale_while_revalidate() {
+        let cc: CacheControl =
+            CacheControlParser::new(["max-age=60, stale-while-revalidate=3600"]).collect();
+        assert_eq!(Some(3600), cc.stale_while_revalidate_seconds);
+        assert!(!cc.must_revalidate);
+
+        let cc: CacheControl =
+            CacheControlParser::new(["max-age=60, stale-while-revalidate=soon"]).collect();
+        assert_eq!(None, cc.stale_while_revalidate_seconds);
+        assert!(!cc.must_revalidate);
+    }
+
     #[test]
     fn cache_control_max_age_quoted() {
         let cc: CacheControl = CacheControlParser::new([r#"max-age="60""#]).collect();
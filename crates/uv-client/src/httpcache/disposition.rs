@@ -0,0 +1,229 @@
+/// Extracts the filename from the `Content-Disposition` header of a response,
+/// as per [RFC 6266].
+///
+/// The extended `filename*` parameter (encoded as per [RFC 8187]) takes
+/// precedence over the plain `filename` parameter, since servers send the
+/// latter as a fallback for clients that don't support the former.
+///
+/// Only the final path component of the filename is returned, such that the
+/// result can never be used to escape the directory it's written to. If no
+/// usable filename is present, this returns `None`.
+///
+/// [RFC 6266]: https://www.rfc-editor.org/rfc/rfc6266
+/// [RFC 8187]: https://www.rfc-editor.org/rfc/rfc8187
+pub(crate) fn content_disposition_filename(headers: &http::HeaderMap) -> Option<String> {
+    let header = headers.get(http::header::CONTENT_DISPOSITION)?;
+    parse_filename(header.as_bytes())
+}
+
+/// Extracts the filename from a `Content-Disposition` header value.
+fn parse_filename(value: &[u8]) -> Option<String> {
+    // Header values are nominally ASCII, but servers do send raw UTF-8 (and,
+    // historically, ISO-8859-1) in the plain `filename` parameter.
+    let value = match std::str::from_utf8(value) {
+        Ok(value) => value.to_string(),
+        Err(_) => value.iter().map(|&byte| char::from(byte)).collect(),
+    };
+
+    let mut filename = None;
+    let mut filename_ext = None;
+    for (name, value) in DispositionParams::new(&value) {
+        if name.eq_ignore_ascii_case("filename*") {
+            filename_ext = filename_ext.or_else(|| decode_ext_value(&value));
+        } else if name.eq_ignore_ascii_case("filename") {
+            filename = filename.or(Some(value));
+        }
+    }
+    sanitize(&filename_ext.or(filename)?)
+}
+
+/// Reduces a filename to its final path component, rejecting anything that
+/// doesn't name a file.
+fn sanitize(filename: &str) -> Option<String> {
+    let filename = filename
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or(filename)
+        .trim();
+    if filename.is_empty() || filename == "." || filename == ".." {
+        return None;
+    }
+    Some(filename.to_string())
+}
+
+/// Decodes an extended parameter value, as per [RFC 8187 S3.2].
+///
+/// The value is of the form `charset'language'percent-encoded-value`. Only
+/// the `UTF-8` and `ISO-8859-1` charsets are supported, as required by the
+/// RFC.
+///
+/// [RFC 8187 S3.2]: https://www.rfc-editor.org/rfc/rfc8187#section-3.2
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next()?;
+    let _language = parts.next()?;
+    let bytes = percent_decode(parts.next()?)?;
+    if charset.eq_ignore_ascii_case("utf-8") {
+        String::from_utf8(bytes).ok()
+    } else if charset.eq_ignore_ascii_case("iso-8859-1") {
+        Some(bytes.into_iter().map(char::from).collect())
+    } else {
+        None
+    }
+}
+
+/// Decodes `%XX` escapes, returning `None` if any escape is malformed.
+fn percent_decode(value: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut iter = value.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hi = char::from(iter.next()?).to_digit(16)?;
+            let lo = char::from(iter.next()?).to_digit(16)?;
+            bytes.push(u8::try_from(hi * 16 + lo).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    Some(bytes)
+}
+
+/// An iterator over the `name=value` parameters of a `Content-Disposition`
+/// header value, skipping the leading disposition type.
+///
+/// Values may be tokens or quoted strings (with backslash escapes), as per
+/// [RFC 6266 S4.1].
+///
+/// [RFC 6266 S4.1]: https://www.rfc-editor.org/rfc/rfc6266#section-4.1
+struct DispositionParams<'a> {
+    rest: &'a str,
+}
+
+impl<'a> DispositionParams<'a> {
+    fn new(value: &'a str) -> DispositionParams<'a> {
+        // The disposition type is a token, so it can't contain a `;`.
+        let rest = value.split_once(';').map_or("", |(_, rest)| rest);
+        DispositionParams { rest }
+    }
+}
+
+impl<'a> Iterator for DispositionParams<'a> {
+    type Item = (&'a str, String);
+
+    fn next(&mut self) -> Option<(&'a str, String)> {
+        loop {
+            self.rest = self
+                .rest
+                .trim_start_matches(|c: char| c == ';' || c.is_whitespace());
+            if self.rest.is_empty() {
+                return None;
+            }
+            let name_end = self.rest.find(['=', ';']).unwrap_or(self.rest.len());
+            let name = self.rest[..name_end].trim();
+            self.rest = &self.rest[name_end..];
+            let Some(rest) = self.rest.strip_prefix('=') else {
+                // A parameter without a value; skip it.
+                continue;
+            };
+            let rest = rest.trim_start();
+            if let Some(quoted) = rest.strip_prefix('"') {
+                let mut value = String::new();
+                let mut chars = quoted.char_indices();
+                let mut end = quoted.len();
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            if let Some((_, escaped)) = chars.next() {
+                                value.push(escaped);
+                            }
+                        }
+                        '"' => {
+                            end = i + 1;
+                            break;
+                        }
+                        c => value.push(c),
+                    }
+                }
+                self.rest = &quoted[end..];
+                return Some((name, value));
+            }
+            let value_end = rest.find(';').unwrap_or(rest.len());
+            let value = rest[..value_end].trim().to_string();
+            self.rest = &rest[value_end..];
+            return Some((name, value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_filename;
+
+    #[test]
+    fn plain() {
+        let parse = |value: &str| parse_filename(value.as_bytes());
+        assert_eq!(
+            parse("attachment; filename=flask-3.0.0-py3-none-any.whl").as_deref(),
+            Some("flask-3.0.0-py3-none-any.whl")
+        );
+        assert_eq!(
+            parse(r#"attachment; filename="EURO rates.txt""#).as_deref(),
+            Some("EURO rates.txt")
+        );
+        assert_eq!(
+            parse(r#"Attachment; FileName="a\"b;c.whl"; size=10"#).as_deref(),
+            Some(r#"a"b;c.whl"#)
+        );
+        assert_eq!(parse("attachment"), None);
+        assert_eq!(parse("attachment; size=10"), None);
+        assert_eq!(parse(r#"attachment; filename="""#), None);
+    }
+
+    #[test]
+    fn extended() {
+        let parse = |value: &str| parse_filename(value.as_bytes());
+        assert_eq!(
+            parse("attachment; filename*=UTF-8''%e2%82%ac%20rates").as_deref(),
+            Some("€ rates")
+        );
+        assert_eq!(
+            parse("attachment; filename*=iso-8859-1'en'%A3%20rates").as_deref(),
+            Some("£ rates")
+        );
+        // The extended form takes precedence, regardless of order.
+        assert_eq!(
+            parse(r#"attachment; filename="EURO rates"; filename*=utf-8''%e2%82%ac%20rates"#)
+                .as_deref(),
+            Some("€ rates")
+        );
+        assert_eq!(
+            parse(r#"attachment; filename*=utf-8''%e2%82%ac%20rates; filename="EURO rates""#)
+                .as_deref(),
+            Some("€ rates")
+        );
+        // An undecodable extended form falls back to the plain form.
+        assert_eq!(
+            parse(r#"attachment; filename*=koi8-r''%C1; filename="fallback.whl""#).as_deref(),
+            Some("fallback.whl")
+        );
+        assert_eq!(
+            parse(r#"attachment; filename*=utf-8''%zz; filename="fallback.whl""#).as_deref(),
+            Some("fallback.whl")
+        );
+    }
+
+    #[test]
+    fn path_components() {
+        let parse = |value: &str| parse_filename(value.as_bytes());
+        assert_eq!(
+            parse(r#"attachment; filename="../../etc/passwd""#).as_deref(),
+            Some("passwd")
+        );
+        assert_eq!(
+            parse(r#"attachment; filename="C:\\Users\\flask.whl""#).as_deref(),
+            Some("flask.whl")
+        );
+        assert_eq!(parse(r#"attachment; filename="..""#), None);
+        assert_eq!(parse(r#"attachment; filename="dir/""#), None);
+    }
+}
@@ -142,8 +142,10 @@ use {http::header::HeaderValue, rkyv::bytecheck};
 use crate::rkyvutil::OwnedArchive;
 
 use self::control::CacheControl;
+pub(crate) use self::disposition::content_disposition_filename;
 
 mod control;
+mod disposition;
 
 /// Knobs to configure uv's cache behavior.
 ///
@@ -280,6 +282,27 @@ impl CachePolicy {
         // There's no way (other than OOM) for serializing this type to fail.
         OwnedArchive::from_unarchived(self).expect("all possible values can be archived")
     }
+
+    /// Use the given freshness lifetime, as if the response had a `max-age`
+    /// directive, if the response specifies neither a freshness lifetime (via
+    /// `max-age`, `s-maxage`, or `Expires`) nor a validator (via `ETag` or
+    /// `Last-Modified`).
+    ///
+    /// Such a response is otherwise stale as soon as it's received (see
+    /// [`ArchivedCachePolicy::freshness_lifetime`]), and can't even be
+    /// revalidated. Since the directive is stored in the policy, it carries
+    /// over to future reads of the cached response.
+    pub fn set_default_max_age(&mut self, max_age: Duration) {
+        let headers = &mut self.response.headers;
+        if headers.cc.max_age_seconds.is_none()
+            && headers.cc.s_maxage_seconds.is_none()
+            && headers.expires_unix_timestamp.is_none()
+            && headers.etag.is_none()
+            && headers.last_modified_unix_timestamp.is_none()
+        {
+            headers.cc.max_age_seconds = Some(max_age.as_secs());
+        }
+    }
 }
 
 impl ArchivedCachePolicy {
@@ -343,37 +366,105 @@ impl ArchivedCachePolicy {
             );
             return BeforeRequest::NoMatch;
         }
+        // A stored response is only reused for requests for the same range
+        // (or for the complete resource, if it was requested without one),
+        // such that a partial response is never served as the complete
+        // resource, or vice versa. RFC 9111 S3.3 would permit serving a part
+        // of a stored response, but the payloads we store can't be sliced.
+        let range = request
+            .headers()
+            .get("range")
+            .and_then(|header| header.to_str().ok());
+        let cached_range = self
+            .request
+            .headers
+            .range
+            .as_ref()
+            .map(|range| range.as_str());
+        if cached_range != range {
+            tracing::trace!(
+                "request {} does not match cached request because it's for \
+                 another range",
+                request.url(),
+            );
+            return BeforeRequest::NoMatch;
+        }
+        match self.stale_reason_at(now, request) {
+            None => return BeforeRequest::Fresh,
+            Some(StaleReason::Vary) => return BeforeRequest::VaryMismatch,
+            Some(_) => {}
+        }
+        // "successfully validated."
+        //
+        // In this case, callers will need to send a revalidation request.
+        self.set_revalidation_headers(request);
+        BeforeRequest::Stale(self.new_cache_policy_builder(request))
+    }
+
+    /// Returns why the cached response must be revalidated before it can be
+    /// used for the given request, or `None` if it may be used as-is.
+    ///
+    /// This mirrors the decision made by
+    /// [`ArchivedCachePolicy::before_request`] for a request that matches the
+    /// cache policy, without modifying the request. It's intended for
+    /// explaining that decision (e.g., when debugging unexpected revalidation
+    /// requests), and needn't be called otherwise.
+    pub fn stale_reason(&self, request: &reqwest::Request) -> Option<StaleReason> {
+        self.stale_reason_at(SystemTime::now(), request)
+    }
+
+    fn stale_reason_at(&self, now: SystemTime, request: &reqwest::Request) -> Option<StaleReason> {
         // "request header fields nominated by the stored response (if any)
         // match those presented, and..."
         //
-        // We don't support the `Vary` header, so if it was set, we
-        // conservatively require revalidation.
+        // The values of the request headers nominated by `Vary` are stored in
+        // the policy itself, so this check holds across process restarts. On
+        // a mismatch, the cached response is for another variant, which
+        // `before_request` reports as a `VaryMismatch`.
         if !self.vary.matches(request.headers()) {
             tracing::trace!(
                 "request {} does not match cached request because of the 'Vary' header",
                 request.url(),
             );
-            self.set_revalidation_headers(request);
-            return BeforeRequest::Stale(self.new_cache_policy_builder(request));
+            return Some(StaleReason::Vary);
         }
         // "the stored response does not contain the no-cache directive, unless
         // it is successfully validated, and..."
         if self.response.headers.cc.no_cache {
-            self.set_revalidation_headers(request);
-            return BeforeRequest::Stale(self.new_cache_policy_builder(request));
+            tracing::trace!(
+                "cached request {} must be revalidated because its response has \
+                 a 'no-cache' cache-control directive",
+                self.request.uri,
+            );
+            return Some(StaleReason::ResponseNoCache);
         }
         // "the stored response is one of the following: ..."
         //
         // "fresh, or..."
         // "allowed to be served stale, or..."
-        if self.is_fresh(now, request) {
-            return BeforeRequest::Fresh;
+        self.staleness(now, request)
+    }
+
+    /// Like [`ArchivedCachePolicy::before_request`], but never considers the
+    /// cached response fresh, such that it's always revalidated with the
+    /// origin server before use.
+    ///
+    /// This implements the `no-cache` request directive as per [RFC 9111
+    /// S5.2.1.4], which a cache must honor even for responses marked
+    /// `immutable`. (In contrast, `before_request` lets `immutable` override
+    /// a `no-cache` request directive, as per [RFC 8246].)
+    ///
+    /// [RFC 9111 S5.2.1.4]: https://www.rfc-editor.org/rfc/rfc9111.html#section-5.2.1.4
+    /// [RFC 8246]: https://httpwg.org/specs/rfc8246.html
+    pub fn before_revalidation(&self, request: &mut reqwest::Request) -> BeforeRequest {
+        match self.before_request(request) {
+            // See `StaleReason::Forced`.
+            BeforeRequest::Fresh => {
+                self.set_revalidation_headers(request);
+                BeforeRequest::Stale(self.new_cache_policy_builder(request))
+            }
+            before_request => before_request,
         }
-        // "successfully validated."
-        //
-        // In this case, callers will need to send a revalidation request.
-        self.set_revalidation_headers(request);
-        BeforeRequest::Stale(self.new_cache_policy_builder(request))
     }
 
     /// This implements the logic for handling the response to a request that
@@ -407,10 +498,120 @@ impl ArchivedCachePolicy {
             AfterResponse::Modified(new_policy)
         } else {
             new_policy.response.status = self.response.status;
+            // A `304` needn't repeat the validators of the response it
+            // validates either, so keep those we already had, such that the
+            // next revalidation request is still conditional.
+            if new_policy.response.headers.etag.is_none() {
+                new_policy.response.headers.etag =
+                    self.response.headers.etag.as_ref().map(|etag| ETag {
+                        value: etag.value.to_vec(),
+                        weak: etag.weak,
+                    });
+            }
+            if new_policy
+                .response
+                .headers
+                .last_modified_unix_timestamp
+                .is_none()
+            {
+                new_policy.response.headers.last_modified_unix_timestamp = self
+                    .response
+                    .headers
+                    .last_modified_unix_timestamp
+                    .as_ref()
+                    .copied();
+            }
+            // A `304` needn't repeat the `Content-Disposition` header of the
+            // response it validates, so keep the filename we already had.
+            if new_policy.response.headers.filename.is_none() {
+                new_policy.response.headers.filename = self
+                    .response
+                    .headers
+                    .filename
+                    .as_ref()
+                    .map(|filename| filename.as_str().to_string());
+            }
+            // Nor does it repeat the `Content-Range` of a partial response.
+            new_policy.response.headers.content_range = self.content_range().map(str::to_string);
             AfterResponse::NotModified(new_policy)
         }
     }
 
+    /// Determines whether the cached response is still current given the
+    /// response to a `HEAD` request for the same resource, for servers that
+    /// report validators on `HEAD` requests but don't honor conditional `GET`
+    /// requests.
+    ///
+    /// The cached response is considered unchanged if at least one strong
+    /// validator (a strong `ETag` or a `Last-Modified` date) is present on both
+    /// responses, all such validators match, and the `Content-Length` (if
+    /// present on both) matches. A `Content-Length` on its own is not enough,
+    /// since content can change without changing length.
+    ///
+    /// If the cached response is unchanged, this returns the new policy with
+    /// which to update the cache, as for `AfterResponse::NotModified`. The
+    /// policy is derived from the `HEAD` response, but it's recorded against
+    /// the request that the given builder was created with (i.e., the `GET`
+    /// request that the cached response answered). Otherwise, this returns
+    /// `None`, and the caller should send that request to fetch the new
+    /// response.
+    pub fn after_probe(
+        &self,
+        cache_policy_builder: CachePolicyBuilder,
+        response: &reqwest::Response,
+    ) -> Option<CachePolicy> {
+        if !response.status().is_success() {
+            return None;
+        }
+        let mut new_policy = cache_policy_builder.build(response);
+        if !self.validators_match(&new_policy) {
+            return None;
+        }
+        new_policy.response.status = self.response.status;
+        if new_policy.response.headers.filename.is_none() {
+            new_policy.response.headers.filename = self
+                .response
+                .headers
+                .filename
+                .as_ref()
+                .map(|filename| filename.as_str().to_string());
+        }
+        new_policy.response.headers.content_range = self.content_range().map(str::to_string);
+        Some(new_policy)
+    }
+
+    /// Returns `true` if the validators of the cached response match those of
+    /// the new policy, as per [`ArchivedCachePolicy::after_probe`].
+    fn validators_match(&self, new_policy: &CachePolicy) -> bool {
+        let old = &self.response.headers;
+        let new = &new_policy.response.headers;
+        let mut validated = false;
+        if let Some(old_etag) = old.etag.as_ref().filter(|etag| !etag.weak) {
+            match new.etag.as_ref().filter(|etag| !etag.weak) {
+                Some(new_etag) if old_etag.value == new_etag.value => validated = true,
+                Some(_) => return false,
+                None => {}
+            }
+        }
+        if let Some(old_last_modified) = old.last_modified_unix_timestamp.as_ref() {
+            match new.last_modified_unix_timestamp {
+                Some(new_last_modified) if *old_last_modified == new_last_modified => {
+                    validated = true;
+                }
+                Some(_) => return false,
+                None => {}
+            }
+        }
+        if let (Some(old_length), Some(new_length)) =
+            (old.content_length.as_ref(), new.content_length)
+        {
+            if *old_length != new_length {
+                return false;
+            }
+        }
+        validated
+    }
+
     fn is_modified(&self, new_policy: &CachePolicy) -> bool {
         // From [RFC 9111 S4.3.3],
         //
@@ -454,14 +655,15 @@ impl ArchivedCachePolicy {
                 }
             }
         }
-        // As per [RFC 9111 S4.3.4], if we have no validators anywhere, then
-        // we can just rely on the HTTP 304 status code and reuse the cached
-        // response.
+        // As per [RFC 9111 S4.3.4], if the 304 response has no validators,
+        // then we can just rely on the HTTP 304 status code and reuse the
+        // cached response (since it's the only one we have). Notably, a 304
+        // response needn't repeat the `Last-Modified` header as per [RFC 9110
+        // S15.4.5], so this is common for servers that don't send `ETag`s.
         //
         // [RFC 9111 S4.3.4]: https://www.rfc-editor.org/rfc/rfc9111.html#section-4.3.4
-        if self.response.headers.etag.is_none()
-            && new_policy.response.headers.etag.is_none()
-            && self.response.headers.last_modified_unix_timestamp.is_none()
+        // [RFC 9110 S15.4.5]: https://www.rfc-editor.org/rfc/rfc9110#section-15.4.5
+        if new_policy.response.headers.etag.is_none()
             && new_policy
                 .response
                 .headers
@@ -577,10 +779,13 @@ impl ArchivedCachePolicy {
         // cache directive (see Section 5.2.2.3) is present: the cache
         // understands the response status code"
         //
-        // We don't currently support `must-understand`. We also don't support
-        // partial content (206). And 304 not modified shouldn't be cached
+        // We don't currently support `must-understand`. We only support
+        // partial content (206) in response to a request for a single range,
+        // which is stored as-is, and only reused for requests for the same
+        // range (see `before_request`), rather than combined with other
+        // parts of the resource. And 304 not modified shouldn't be cached
         // itself.
-        if self.response.status == 206 || self.response.status == 304 {
+        if (self.response.status == 206 && !self.is_single_range()) || self.response.status == 304 {
             tracing::trace!(
                 "cached request {} is not storable because its response has \
                  unsupported status code {:?}",
@@ -722,12 +927,12 @@ impl ArchivedCachePolicy {
             || self.response.headers.cc.s_maxage_seconds.is_some()
     }
 
-    /// Returns true if the response is considered fresh as per [RFC 9111
+    /// Returns `None` if the response is considered fresh as per [RFC 9111
     /// S4.2]. If the response is not fresh, then it considered stale and ought
-    /// to be revalidated with the origin server.
+    /// to be revalidated with the origin server, for the returned reason.
     ///
     /// [RFC 9111 S4.2]: https://www.rfc-editor.org/rfc/rfc9111.html#section-4.2
-    fn is_fresh(&self, now: SystemTime, request: &reqwest::Request) -> bool {
+    fn staleness(&self, now: SystemTime, request: &reqwest::Request) -> Option<StaleReason> {
         let freshness_lifetime = self.freshness_lifetime().as_secs();
         let age = self.age(now).as_secs();
 
@@ -761,7 +966,7 @@ impl ArchivedCachePolicy {
                  it has a 'no-cache' cache-control directive",
                     request.url(),
                 );
-                return false;
+                return Some(StaleReason::RequestNoCache);
             }
 
             // If the request has a max-age directive, then we should respect that
@@ -778,7 +983,7 @@ impl ArchivedCachePolicy {
                         age,
                         max_age,
                     );
-                    return false;
+                    return Some(StaleReason::RequestMaxAge);
                 }
             }
 
@@ -788,7 +993,7 @@ impl ArchivedCachePolicy {
             //
             // [RFC 9111 S5.2.1.3]: https://www.rfc-editor.org/rfc/rfc9111.html#section-5.2.1.3
             if let Some(&min_fresh) = reqcc.min_fresh_seconds.as_ref() {
-                let time_to_live = freshness_lifetime.saturating_sub(unix_timestamp(now));
+                let time_to_live = self.time_to_live(now).as_secs();
                 if time_to_live < min_fresh {
                     tracing::trace!(
                         "request {} does not have a fresh cache because \
@@ -801,7 +1006,7 @@ impl ArchivedCachePolicy {
                     );
                     // Note that S5.2.1.3 does not say that max-stale overrides
                     // this, so we ignore it here.
-                    return false;
+                    return Some(StaleReason::MinFresh);
                 }
             }
         }
@@ -817,10 +1022,13 @@ impl ArchivedCachePolicy {
                     age,
                     freshness_lifetime,
                 );
-                return false;
+                if self.response.headers.cc.must_revalidate {
+                    return Some(StaleReason::MustRevalidate);
+                }
+                return Some(StaleReason::Expired);
             }
         }
-        true
+        None
     }
 
     /// Returns true if we're allowed to serve a stale response, as per [RFC
@@ -882,6 +1090,19 @@ impl ArchivedCachePolicy {
         false
     }
 
+    /// Returns how much longer the cached response will be considered fresh,
+    /// as per [RFC 9111 S4.2]. Once the response is stale, this returns `0`.
+    ///
+    /// Note that this is not simply the freshness lifetime (e.g., `max-age`)
+    /// minus the time since the response was received. The age of a response
+    /// includes any `Age` reported by upstream caches, so a response served
+    /// by a CDN near the end of its life will only be fresh for a short time.
+    ///
+    /// [RFC 9111 S4.2]: https://www.rfc-editor.org/rfc/rfc9111.html#section-4.2
+    pub fn time_to_live(&self, now: SystemTime) -> Duration {
+        self.freshness_lifetime().saturating_sub(self.age(now))
+    }
+
     /// Returns the age of the HTTP response as per [RFC 9111 S4.2.3].
     ///
     /// The age of a response, essentially, refers to how long it has been
@@ -890,7 +1111,7 @@ impl ArchivedCachePolicy {
     /// whether the response is fresh or stale.
     ///
     /// [RFC 9111 S4.2.3]: https://www.rfc-editor.org/rfc/rfc9111.html#name-calculating-age
-    fn age(&self, now: SystemTime) -> Duration {
+    pub fn age(&self, now: SystemTime) -> Duration {
         // RFC 9111 S4.2.3
         let apparent_age = self
             .response
@@ -916,7 +1137,7 @@ impl ArchivedCachePolicy {
     /// cases.
     ///
     /// [RFC 9111 S4.2.1]: https://www.rfc-editor.org/rfc/rfc9111.html#section-4.2.1
-    fn freshness_lifetime(&self) -> Duration {
+    pub fn freshness_lifetime(&self) -> Duration {
         if self.config.shared {
             if let Some(&s_maxage) = self.response.headers.cc.s_maxage_seconds.as_ref() {
                 return Duration::from_secs(s_maxage);
@@ -939,6 +1160,96 @@ impl ArchivedCachePolicy {
         Duration::ZERO
     }
 
+    /// Returns the window after the response becomes stale during which it
+    /// may still be served while it's revalidated in the background, as per
+    /// the `stale-while-revalidate` directive of [RFC 5861 S3].
+    ///
+    /// Returns `None` if the response doesn't have the directive, or if it
+    /// must not be served stale without revalidating it first (i.e., it has a
+    /// `must-revalidate` or `no-cache` directive).
+    ///
+    /// [RFC 5861 S3]: https://www.rfc-editor.org/rfc/rfc5861.html#section-3
+    pub fn stale_while_revalidate(&self) -> Option<Duration> {
+        let cc = &self.response.headers.cc;
+        if cc.must_revalidate || cc.no_cache {
+            return None;
+        }
+        cc.stale_while_revalidate_seconds
+            .as_ref()
+            .map(|&seconds| Duration::from_secs(seconds))
+    }
+
+    /// Returns `true` if the response is stale, but within its
+    /// [`ArchivedCachePolicy::stale_while_revalidate`] window.
+    pub fn is_within_stale_while_revalidate(&self, now: SystemTime) -> bool {
+        let Some(window) = self.stale_while_revalidate() else {
+            return false;
+        };
+        let lifetime = self.freshness_lifetime();
+        let age = self.age(now);
+        age > lifetime && age - lifetime <= window
+    }
+
+    /// Returns the URL of the request that produced the cached response.
+    pub fn url(&self) -> &str {
+        self.request.uri.as_str()
+    }
+
+    /// Returns the URL the cached response was served from, after following
+    /// any redirects from [`ArchivedCachePolicy::url`].
+    ///
+    /// Revalidation requests ought to be sent here, rather than to the
+    /// original URL, such that they reach the server that produced the
+    /// response (e.g., a CDN) without being redirected again.
+    pub fn final_url(&self) -> &str {
+        self.response.url.as_str()
+    }
+
+    /// Returns the HTTP status code of the cached response.
+    pub fn status(&self) -> u16 {
+        self.response.status
+    }
+
+    /// Returns `true` if the cached response was marked as `immutable`, as
+    /// per [RFC 8246].
+    ///
+    /// [RFC 8246]: https://httpwg.org/specs/rfc8246.html
+    pub fn is_immutable(&self) -> bool {
+        self.response.headers.cc.immutable
+    }
+
+    /// Returns the filename advertised by the `Content-Disposition` header of
+    /// the cached response, if any.
+    pub fn filename(&self) -> Option<&str> {
+        self.response
+            .headers
+            .filename
+            .as_ref()
+            .map(|filename| filename.as_str())
+    }
+
+    /// Returns the `Content-Range` header of the cached response (e.g.,
+    /// `bytes 0-99/1000`), if any, i.e., the part of the resource it holds if
+    /// it's a `206 Partial Content` response.
+    pub fn content_range(&self) -> Option<&str> {
+        self.response
+            .headers
+            .content_range
+            .as_ref()
+            .map(|content_range| content_range.as_str())
+    }
+
+    /// Returns `true` if the cached response holds a single range of the
+    /// resource, as requested, rather than (e.g.) a `multipart/byteranges`
+    /// response to a request for several ranges, or a partial response to a
+    /// request for the complete resource.
+    fn is_single_range(&self) -> bool {
+        self.request.headers.range.is_some()
+            && self
+                .content_range()
+                .is_some_and(|content_range| content_range.starts_with("bytes "))
+    }
+
     fn new_cache_policy_builder(&self, request: &reqwest::Request) -> CachePolicyBuilder {
         let request_headers = request.headers().clone();
         CachePolicyBuilder {
@@ -963,12 +1274,51 @@ pub enum BeforeRequest {
     /// whether the cached response is actually fresh, or if it's stale and
     /// needs to be updated.
     Stale(CachePolicyBuilder),
+    /// The request headers nominated by the `Vary` header of the cached
+    /// response differ from those of the given request, so the cached
+    /// response is for another variant of the resource.
+    ///
+    /// The cached response can't be used (nor revalidated) for the request,
+    /// but it's still valid for the requests it does match, so the caller
+    /// should send a fresh request without overwriting it.
+    VaryMismatch,
     /// The given request does not match the cache policy identification.
     /// Generally speaking, this is usually implies a bug with the cache in
     /// that it loaded a cache policy that does not match the request.
     NoMatch,
 }
 
+/// Why a cached response must be revalidated before it's used, as determined
+/// by [`ArchivedCachePolicy::stale_reason`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StaleReason {
+    /// The request headers nominated by the `Vary` header of the cached
+    /// response differ from those of the request it was cached for.
+    Vary,
+    /// The cached response has a `no-cache` directive, so it must always be
+    /// revalidated.
+    ResponseNoCache,
+    /// The request has a `no-cache` directive, e.g., as set by
+    /// `CacheControl::MustRevalidate`.
+    RequestNoCache,
+    /// The age of the cached response exceeds the `max-age` directive of the
+    /// request.
+    RequestMaxAge,
+    /// The cached response won't stay fresh for as long as the `min-fresh`
+    /// directive of the request requires.
+    MinFresh,
+    /// The cached response has outlived its freshness lifetime (e.g., as set
+    /// by `max-age`), and serving it stale isn't allowed.
+    Expired,
+    /// The cached response has outlived its freshness lifetime, and its
+    /// `must-revalidate` directive forbids serving it stale.
+    MustRevalidate,
+    /// The cached response is fresh, but the caller asked for it to be
+    /// revalidated regardless, as in
+    /// [`ArchivedCachePolicy::before_revalidation`].
+    Forced,
+}
+
 /// The result of called [`CachePolicy::after_response`].
 ///
 /// This is meant to report whether a revalidation request was successful or
@@ -1014,6 +1364,11 @@ struct RequestHeaders {
     /// This is set to `true` only when an `Authorization` header is present.
     /// We don't need to record the value.
     authorization: bool,
+    /// The value of the `Range` header as per [RFC 9110 S14.2], if any. A
+    /// stored response is only reused for requests for the same range.
+    ///
+    /// [RFC 9110 S14.2]: https://www.rfc-editor.org/rfc/rfc9110#section-14.2
+    range: Option<String>,
 }
 
 impl<'a> From<&'a http::HeaderMap> for RequestHeaders {
@@ -1021,6 +1376,10 @@ impl<'a> From<&'a http::HeaderMap> for RequestHeaders {
         RequestHeaders {
             cc: from.get_all("cache-control").iter().collect(),
             authorization: from.contains_key("authorization"),
+            range: from
+                .get("range")
+                .and_then(|header| header.to_str().ok())
+                .map(ToString::to_string),
         }
     }
 }
@@ -1059,6 +1418,9 @@ struct Response {
     status: u16,
     headers: ResponseHeaders,
     unix_timestamp: u64,
+    /// The URL the response was served from. This differs from the URL of
+    /// the request if any redirects were followed.
+    url: String,
 }
 
 impl ArchivedResponse {
@@ -1099,6 +1461,7 @@ impl<'a> From<&'a reqwest::Response> for Response {
             status: from.status().as_u16(),
             headers: ResponseHeaders::from(from.headers()),
             unix_timestamp: unix_timestamp(SystemTime::now()),
+            url: from.url().to_string(),
         }
     }
 }
@@ -1146,6 +1509,22 @@ struct ResponseHeaders {
     ///
     /// [RFC 9110 S8.8.3]: https://www.rfc-editor.org/rfc/rfc9110#section-8.8.3
     etag: Option<ETag>,
+    /// The length of the response body from the `Content-Length` header as
+    /// per [RFC 9110 S8.6], which is compared (along with the validators)
+    /// when probing whether a cached response changed with a `HEAD` request.
+    ///
+    /// [RFC 9110 S8.6]: https://www.rfc-editor.org/rfc/rfc9110#section-8.6
+    content_length: Option<u64>,
+    /// The filename from the `Content-Disposition` header as per [RFC 6266],
+    /// reduced to its final path component.
+    ///
+    /// [RFC 6266]: https://www.rfc-editor.org/rfc/rfc6266
+    filename: Option<String>,
+    /// The value of the `Content-Range` header as per [RFC 9110 S14.4], i.e.,
+    /// the part of the resource that a `206 Partial Content` response holds.
+    ///
+    /// [RFC 9110 S14.4]: https://www.rfc-editor.org/rfc/rfc9110#section-14.4
+    content_range: Option<String>,
 }
 
 impl<'a> From<&'a http::HeaderMap> for ResponseHeaders {
@@ -1170,6 +1549,15 @@ impl<'a> From<&'a http::HeaderMap> for ResponseHeaders {
             etag: from
                 .get("etag")
                 .map(|header| ETag::parse(header.as_bytes())),
+            content_length: from
+                .get("content-length")
+                .and_then(|header| header.to_str().ok())
+                .and_then(|header| header.parse().ok()),
+            filename: content_disposition_filename(from),
+            content_range: from
+                .get("content-range")
+                .and_then(|header| header.to_str().ok())
+                .map(ToString::to_string),
         }
     }
 }
@@ -1332,18 +1720,25 @@ fn rfc2822_to_unix_timestamp(s: &str) -> Option<u64> {
     rfc2822_to_datetime(s).and_then(|dt| u64::try_from(dt.timestamp()).ok())
 }
 
-fn rfc2822_to_datetime(s: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+pub(crate) fn rfc2822_to_datetime(s: &str) -> Option<chrono::DateTime<chrono::Utc>> {
     chrono::DateTime::parse_from_rfc2822(s)
         .ok()
         .map(|dt| dt.to_utc())
 }
 
 fn unix_timestamp_to_header(seconds: u64) -> Option<HeaderValue> {
-    unix_timestamp_to_rfc2822(seconds).and_then(|string| HeaderValue::from_str(&string).ok())
+    unix_timestamp_to_http_date(seconds).and_then(|string| HeaderValue::from_str(&string).ok())
 }
 
-fn unix_timestamp_to_rfc2822(seconds: u64) -> Option<String> {
-    unix_timestamp_to_datetime(seconds).map(|dt| dt.to_rfc2822())
+/// Formats a timestamp as an HTTP date as per [RFC 9110 S5.6.7], e.g., `Wed, 21 Oct 2015
+/// 07:28:00 GMT`.
+///
+/// This is the fixed-length subset of RFC 2822 dates that servers must accept; notably, the zone
+/// is always `GMT` (rather than `+0000`).
+///
+/// [RFC 9110 S5.6.7]: https://www.rfc-editor.org/rfc/rfc9110#section-5.6.7
+fn unix_timestamp_to_http_date(seconds: u64) -> Option<String> {
+    unix_timestamp_to_datetime(seconds).map(|dt| dt.format("%a, %d %b %Y %H:%M:%S GMT").to_string())
 }
 
 fn unix_timestamp_to_datetime(seconds: u64) -> Option<chrono::DateTime<chrono::Utc>> {
@@ -1356,3 +1751,373 @@ fn parse_seconds(value: &[u8]) -> Option<u64> {
     }
     std::str::from_utf8(value).ok()?.parse().ok()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use url::Url;
+
+    use super::{AfterResponse, BeforeRequest, CachePolicy, CachePolicyBuilder, StaleReason};
+
+    fn request(url: &str) -> reqwest::Request {
+        reqwest::Request::new(http::Method::GET, Url::parse(url).unwrap())
+    }
+
+    fn cache_policy(url: &str, headers: &[(&str, &str)]) -> CachePolicy {
+        let mut response = http::Response::builder().status(200);
+        for (name, value) in headers {
+            response = response.header(*name, *value);
+        }
+        let response = reqwest::Response::from(response.body("").unwrap());
+        CachePolicyBuilder::new(&request(url)).build(&response)
+    }
+
+    #[test]
+    fn age_header_shortens_freshness() {
+        let url = "https://example.com/simple/flask/";
+        let now = SystemTime::now();
+
+        // Without an `Age` header, the response is fresh for the full `max-age`.
+        let policy = cache_policy(url, &[("cache-control", "max-age=600")]).to_archived();
+        assert!(policy.time_to_live(now) > Duration::from_secs(590));
+
+        // With a large `Age` header (e.g., from a CDN), the response is only fresh for the
+        // remainder of its lifetime.
+        let policy =
+            cache_policy(url, &[("cache-control", "max-age=600"), ("age", "590")]).to_archived();
+        assert!(policy.age(now) >= Duration::from_secs(590));
+        assert!(policy.time_to_live(now) <= Duration::from_secs(10));
+        assert_eq!(policy.staleness(now, &request(url)), None);
+        assert!(matches!(
+            policy.before_request(&mut request(url)),
+            BeforeRequest::Fresh
+        ));
+
+        // Twenty seconds later, the response is stale, even though `max-age` alone would imply
+        // that it's fresh for another nine minutes.
+        let later = now + Duration::from_secs(20);
+        assert_eq!(policy.time_to_live(later), Duration::ZERO);
+        assert_eq!(
+            policy.staleness(later, &request(url)),
+            Some(StaleReason::Expired)
+        );
+    }
+
+    #[test]
+    fn age_header_exceeds_max_age() {
+        let url = "https://example.com/simple/flask/";
+        let policy =
+            cache_policy(url, &[("cache-control", "max-age=600"), ("age", "1200")]).to_archived();
+        assert_eq!(policy.time_to_live(SystemTime::now()), Duration::ZERO);
+        assert!(matches!(
+            policy.before_request(&mut request(url)),
+            BeforeRequest::Stale(_)
+        ));
+    }
+
+    #[test]
+    fn min_fresh_accounts_for_age() {
+        let url = "https://example.com/simple/flask/";
+        let policy =
+            cache_policy(url, &[("cache-control", "max-age=600"), ("age", "500")]).to_archived();
+
+        // The response has ~100 seconds left to live, which satisfies a small `min-fresh`...
+        let mut req = request(url);
+        req.headers_mut().insert(
+            "cache-control",
+            http::HeaderValue::from_static("min-fresh=60"),
+        );
+        assert_eq!(policy.staleness(SystemTime::now(), &req), None);
+
+        // ...but not a large one.
+        let mut req = request(url);
+        req.headers_mut().insert(
+            "cache-control",
+            http::HeaderValue::from_static("min-fresh=300"),
+        );
+        assert_eq!(
+            policy.staleness(SystemTime::now(), &req),
+            Some(StaleReason::MinFresh)
+        );
+    }
+
+    #[test]
+    fn stale_while_revalidate() {
+        let now = SystemTime::now();
+        let policy = cache_policy(
+            "https://example.com/",
+            &[
+                ("cache-control", "max-age=60, stale-while-revalidate=600"),
+                ("age", "120"),
+            ],
+        );
+        let policy = policy.to_archived();
+        assert_eq!(
+            policy.stale_while_revalidate(),
+            Some(Duration::from_secs(600))
+        );
+        assert!(policy.is_within_stale_while_revalidate(now));
+        // Once the window has passed, the response has to be revalidated first.
+        assert!(!policy.is_within_stale_while_revalidate(now + Duration::from_secs(600)));
+
+        // A fresh response isn't within the window, since it isn't stale.
+        let policy = cache_policy(
+            "https://example.com/",
+            &[("cache-control", "max-age=60, stale-while-revalidate=600")],
+        );
+        assert!(!policy.to_archived().is_within_stale_while_revalidate(now));
+
+        // `must-revalidate` takes precedence.
+        let policy = cache_policy(
+            "https://example.com/",
+            &[
+                (
+                    "cache-control",
+                    "max-age=60, must-revalidate, stale-while-revalidate=600",
+                ),
+                ("age", "120"),
+            ],
+        );
+        assert_eq!(policy.to_archived().stale_while_revalidate(), None);
+    }
+
+    #[test]
+    fn default_max_age() {
+        let url = "https://example.com/simple/flask/";
+
+        let mut policy = cache_policy(url, &[]);
+        assert_eq!(policy.to_archived().freshness_lifetime(), Duration::ZERO);
+        policy.set_default_max_age(Duration::from_secs(600));
+        assert_eq!(
+            policy.to_archived().freshness_lifetime(),
+            Duration::from_secs(600)
+        );
+
+        // Responses with a freshness lifetime or a validator are left alone.
+        for headers in [
+            &[("cache-control", "max-age=60")][..],
+            &[("cache-control", "no-cache, max-age=0")][..],
+            &[("expires", "Thu, 01 Jan 1970 00:00:00 GMT")][..],
+            &[("etag", "\"v1\"")][..],
+        ] {
+            let mut policy = cache_policy(url, headers);
+            let before = policy.to_archived().freshness_lifetime();
+            policy.set_default_max_age(Duration::from_secs(600));
+            assert_eq!(
+                policy.to_archived().freshness_lifetime(),
+                before,
+                "{headers:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn after_probe() {
+        let url = "https://example.com/files/torch.whl";
+        let head = |headers: &[(&str, &str)]| {
+            let mut response = http::Response::builder().status(200);
+            for (name, value) in headers {
+                response = response.header(*name, *value);
+            }
+            reqwest::Response::from(response.body("").unwrap())
+        };
+        let last_modified = "Wed, 21 Oct 2015 07:28:00 GMT";
+        let policy = cache_policy(
+            url,
+            &[
+                ("etag", "\"v1\""),
+                ("last-modified", last_modified),
+                ("content-length", "1024"),
+                ("content-disposition", "attachment; filename=torch.whl"),
+            ],
+        )
+        .to_archived();
+
+        // Matching validators (even if only some are reported) confirm the cached response.
+        for headers in [
+            &[("etag", "\"v1\""), ("content-length", "1024")][..],
+            &[("last-modified", last_modified)][..],
+        ] {
+            let new_policy = policy
+                .after_probe(CachePolicyBuilder::new(&request(url)), &head(headers))
+                .unwrap_or_else(|| panic!("{headers:?}"));
+            assert_eq!(new_policy.to_archived().filename(), Some("torch.whl"));
+        }
+
+        // Any mismatch, or the lack of a validator to compare, means the response changed.
+        for headers in [
+            &[("etag", "\"v2\""), ("content-length", "1024")][..],
+            &[("etag", "\"v1\""), ("content-length", "2048")][..],
+            &[("last-modified", "Thu, 22 Oct 2015 07:28:00 GMT")][..],
+            &[("etag", "W/\"v1\"")][..],
+            &[("content-length", "1024")][..],
+        ] {
+            assert!(
+                policy
+                    .after_probe(CachePolicyBuilder::new(&request(url)), &head(headers))
+                    .is_none(),
+                "{headers:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn stale_reason() {
+        let url = "https://example.com/simple/flask/";
+        let with_cache_control = |value: &'static str| {
+            let mut req = request(url);
+            req.headers_mut()
+                .insert("cache-control", http::HeaderValue::from_static(value));
+            req
+        };
+
+        let policy =
+            cache_policy(url, &[("cache-control", "max-age=600"), ("age", "300")]).to_archived();
+        assert_eq!(policy.stale_reason(&request(url)), None);
+        assert_eq!(
+            policy.stale_reason(&with_cache_control("no-cache")),
+            Some(StaleReason::RequestNoCache)
+        );
+        assert_eq!(
+            policy.stale_reason(&with_cache_control("max-age=60")),
+            Some(StaleReason::RequestMaxAge)
+        );
+        assert_eq!(
+            policy.stale_reason(&with_cache_control("min-fresh=600")),
+            Some(StaleReason::MinFresh)
+        );
+
+        // A response that's fresh, but must be revalidated, as requested by the response or the
+        // caller.
+        let policy = cache_policy(url, &[("cache-control", "max-age=600, no-cache")]).to_archived();
+        assert_eq!(
+            policy.stale_reason(&request(url)),
+            Some(StaleReason::ResponseNoCache)
+        );
+        let policy =
+            cache_policy(url, &[("cache-control", "max-age=600, immutable")]).to_archived();
+        assert_eq!(policy.stale_reason(&with_cache_control("no-cache")), None);
+
+        // A response that has outlived its freshness lifetime.
+        let policy =
+            cache_policy(url, &[("cache-control", "max-age=0"), ("age", "60")]).to_archived();
+        assert_eq!(
+            policy.stale_reason(&request(url)),
+            Some(StaleReason::Expired)
+        );
+        let policy = cache_policy(
+            url,
+            &[
+                ("cache-control", "max-age=0, must-revalidate"),
+                ("age", "60"),
+            ],
+        )
+        .to_archived();
+        assert_eq!(
+            policy.stale_reason(&request(url)),
+            Some(StaleReason::MustRevalidate)
+        );
+
+        // A response that varies on a request header.
+        let policy = cache_policy(url, &[("cache-control", "max-age=600"), ("vary", "accept")])
+            .to_archived();
+        assert_eq!(policy.stale_reason(&request(url)), None);
+        let mut req = request(url);
+        req.headers_mut()
+            .insert("accept", http::HeaderValue::from_static("text/html"));
+        assert_eq!(policy.stale_reason(&req), Some(StaleReason::Vary));
+    }
+
+    #[test]
+    fn filename_survives_revalidation() {
+        let url = "https://example.com/packages/flask.whl";
+        let policy = cache_policy(
+            url,
+            &[
+                ("etag", "\"abc\""),
+                ("content-disposition", "attachment; filename=\"flask.whl\""),
+            ],
+        )
+        .to_archived();
+        assert_eq!(policy.filename(), Some("flask.whl"));
+
+        // A `304` without a `Content-Disposition` header keeps the stored filename.
+        let response = http::Response::builder()
+            .status(304)
+            .header("etag", "\"abc\"")
+            .body("")
+            .unwrap();
+        let AfterResponse::NotModified(new_policy) = policy.after_response(
+            CachePolicyBuilder::new(&request(url)),
+            &reqwest::Response::from(response),
+        ) else {
+            panic!("Expected the cached response to be revalidated");
+        };
+        assert_eq!(new_policy.to_archived().filename(), Some("flask.whl"));
+    }
+
+    #[test]
+    fn partial_content() {
+        let url = "https://example.com/packages/flask.whl";
+        let ranged = |range: &str| {
+            let mut req = request(url);
+            req.headers_mut()
+                .insert("range", http::HeaderValue::from_str(range).unwrap());
+            req
+        };
+        let partial = |req: &reqwest::Request| {
+            let response = http::Response::builder()
+                .status(206)
+                .header("cache-control", "max-age=600")
+                .header("etag", "\"abc\"")
+                .header("content-range", "bytes 0-99/1000")
+                .body("")
+                .unwrap();
+            CachePolicyBuilder::new(req).build(&reqwest::Response::from(response))
+        };
+
+        // A partial response is stored along with its range, and only reused for that range.
+        let policy = partial(&ranged("bytes=0-99")).to_archived();
+        assert!(policy.is_storable());
+        assert_eq!(policy.content_range(), Some("bytes 0-99/1000"));
+        assert!(matches!(
+            policy.before_request(&mut ranged("bytes=0-99")),
+            BeforeRequest::Fresh
+        ));
+        assert!(matches!(
+            policy.before_request(&mut ranged("bytes=100-199")),
+            BeforeRequest::NoMatch
+        ));
+        assert!(matches!(
+            policy.before_request(&mut request(url)),
+            BeforeRequest::NoMatch
+        ));
+
+        // A `304` keeps the range of the partial response it validates.
+        let response = http::Response::builder()
+            .status(304)
+            .header("etag", "\"abc\"")
+            .body("")
+            .unwrap();
+        let AfterResponse::NotModified(new_policy) = policy.after_response(
+            CachePolicyBuilder::new(&ranged("bytes=0-99")),
+            &reqwest::Response::from(response),
+        ) else {
+            panic!("Expected the cached response to be revalidated");
+        };
+        let new_policy = new_policy.to_archived();
+        assert!(new_policy.is_storable());
+        assert_eq!(new_policy.content_range(), Some("bytes 0-99/1000"));
+
+        // A partial response to a request for the complete resource is never stored.
+        assert!(!partial(&request(url)).to_archived().is_storable());
+
+        // Nor is a complete response reused for a request for a range.
+        let policy = cache_policy(url, &[("cache-control", "max-age=600")]).to_archived();
+        assert!(matches!(
+            policy.before_request(&mut ranged("bytes=0-99")),
+            BeforeRequest::NoMatch
+        ));
+    }
+}
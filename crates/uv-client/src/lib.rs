@@ -1,18 +1,54 @@
-pub use cached_client::{CacheControl, CachedClient, CachedClientError, DataWithCachePolicy};
+pub use cached_client::{
+    CacheCodec, CacheControl, CacheEntryMeta, CacheInfo, CacheOutcome, CachePlan, CacheResponse,
+    CacheStatus, CachedClient, CachedClientBuilder, CachedClientError, DataWithCachePolicy,
+    EvictionPreview, GcReport, MsgPackCodec, RevalidationOutcome, StreamCacheable, WarmRequest,
+};
+pub use compression::CompressionHint;
+#[cfg(feature = "encryption")]
+pub use encryption::CacheEncryptionKey;
 pub use error::{Error, ErrorKind};
+pub use evictor::{EvictionPolicy, EvictorHandle};
 pub use flat_index::{FlatDistributions, FlatIndex, FlatIndexClient, FlatIndexError};
+pub use hash::HashVerifier;
+pub use host_headers::HostPort;
+pub use httpcache::StaleReason;
+#[cfg(feature = "mmap")]
+pub use mmap::MappedBytes;
+pub use policy_overrides::CachePolicyOverrides;
+pub use rate_limit::{RateLimitHeaders, RateLimitState};
 pub use registry_client::{
-    Connectivity, RegistryClient, RegistryClientBuilder, SimpleMetadata, SimpleMetadatum,
-    VersionFiles,
+    Connectivity, HttpVersion, RegistryClient, RegistryClientBuilder, SimpleMetadata,
+    SimpleMetadatum, VersionFiles,
 };
+pub use retry_budget::{BudgetedRetryPolicy, RetryBudget};
 pub use rkyvutil::OwnedArchive;
+pub use stats::CacheStats;
 
+mod audit;
 mod cached_client;
+mod compression;
+mod content_length;
+mod encryption;
 mod error;
+mod evictor;
 mod flat_index;
+mod hash;
+mod host_headers;
 mod html;
 mod httpcache;
 mod middleware;
+mod mirror;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod not_found;
+mod observed_body;
+mod policy_overrides;
+mod rate_limit;
 mod registry_client;
 mod remote_metadata;
+mod retry_budget;
 mod rkyvutil;
+mod sidecar;
+mod single_flight;
+mod stats;
+mod weak_cache;
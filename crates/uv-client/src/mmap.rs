@@ -0,0 +1,82 @@
+//! Memory-mapped reads of cache entries.
+//!
+//! Mapping a cache entry avoids copying its payload into a fresh allocation on every read, which
+//! adds up for large entries that are read repeatedly (e.g., wheels and remote ZIP metadata).
+//!
+//! A memory map is only sound for as long as the underlying file isn't modified. The
+//! [`crate::CachedClient`] never modifies a cache entry in place: entries are replaced atomically
+//! by renaming a new file over the old one, and removed by unlinking them. On Unix, neither
+//! affects an existing mapping, which keeps referring to the original file. Truncating or writing
+//! to an entry in place (e.g., by another tool) while it's mapped, however, can crash the process
+//! (with `SIGBUS`) or change the mapped bytes, which is why mapping is opt-in.
+//!
+//! On Windows, a mapped file can't be replaced or removed, so writes to an entry (and its
+//! eviction) fail for as long as a mapping of it is alive.
+//!
+//! To guard against other tools, a shared lock is held on the mapped file for as long as the
+//! mapping is alive. The lock is advisory on Unix: it only keeps out writers that take an
+//! exclusive lock first, and doesn't stop the client itself from replacing or removing the
+//! entry, which is safe anyway.
+//!
+//! Only payloads that are stored as-is are mapped. Compressed or encrypted payloads can't be
+//! read in place, and are rejected rather than decoded into a fresh allocation, which is what
+//! mapping them would be meant to avoid.
+
+use std::fs::File;
+use std::ops::Deref;
+
+use fs2::FileExt;
+use memmap2::Mmap;
+
+/// The payload of a cache entry, mapped into memory, as returned by
+/// [`crate::CachedClient::read_raw_mapped`].
+///
+/// The mapping, and a shared lock on the mapped file, are kept alive for as long as the guard is.
+#[derive(Debug)]
+pub struct MappedBytes {
+    mmap: Mmap,
+    /// The length of the payload at the start of the mapping.
+    len: usize,
+    /// The mapped file, which holds the shared lock until it's closed.
+    _file: File,
+}
+
+impl MappedBytes {
+    /// Map the first `len` bytes of the given file, waiting for a shared lock on it first.
+    ///
+    /// The caller must ensure that the file is not modified in place while it's mapped (see the
+    /// module documentation).
+    pub(crate) fn map(file: File, len: u64) -> std::io::Result<Self> {
+        let len = usize::try_from(len)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        file.lock_shared()?;
+        // SAFETY: Cache entries are only ever replaced or removed, never modified in place, and
+        // writers that take an exclusive lock are kept out while the file is mapped.
+        let mmap = unsafe { Mmap::map(&file)? };
+        if mmap.len() < len {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "cache entry was truncated while being mapped",
+            ));
+        }
+        Ok(Self {
+            mmap,
+            len,
+            _file: file,
+        })
+    }
+}
+
+impl Deref for MappedBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.mmap[..self.len]
+    }
+}
+
+impl AsRef<[u8]> for MappedBytes {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
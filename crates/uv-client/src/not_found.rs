@@ -0,0 +1,92 @@
+//! Cached "not found" responses.
+//!
+//! With [`crate::CachedClientBuilder::cache_not_found`], a `404 Not Found` or `410 Gone` response
+//! is remembered by writing a marker in place of the payload of its cache entry. The marker holds
+//! the status and when it expires, regardless of the cache policy stored alongside it, such that
+//! it's never used once expired.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use http::StatusCode;
+
+/// The prefix of every marker, followed by the status (as a little-endian `u16`) and the time at
+/// which it expires (in milliseconds since the Unix epoch, as a little-endian `u64`).
+const NOT_FOUND_MAGIC: &[u8; 8] = b"uv-miss1";
+
+/// The length of a marker, in bytes.
+const NOT_FOUND_LEN: usize = NOT_FOUND_MAGIC.len() + 2 + 8;
+
+/// A cached "not found" response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct NotFound {
+    pub(crate) status: StatusCode,
+    pub(crate) expires_at: SystemTime,
+}
+
+impl NotFound {
+    /// Returns the marker for the given response status, if it's a "not found" response.
+    pub(crate) fn new(status: StatusCode, ttl: Duration, now: SystemTime) -> Option<Self> {
+        matches!(status, StatusCode::NOT_FOUND | StatusCode::GONE).then(|| Self {
+            status,
+            expires_at: now + ttl,
+        })
+    }
+
+    /// Returns `true` if the marker may no longer be used.
+    pub(crate) fn is_expired(&self, now: SystemTime) -> bool {
+        now >= self.expires_at
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let expires_at = self
+            .expires_at
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| {
+                u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+            });
+        let mut encoded = Vec::with_capacity(NOT_FOUND_LEN);
+        encoded.extend_from_slice(NOT_FOUND_MAGIC);
+        encoded.extend_from_slice(&self.status.as_u16().to_le_bytes());
+        encoded.extend_from_slice(&expires_at.to_le_bytes());
+        encoded
+    }
+
+    /// Decode the marker from the payload of a cache entry, if it is one.
+    pub(crate) fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() != NOT_FOUND_LEN {
+            return None;
+        }
+        let rest = data.strip_prefix(NOT_FOUND_MAGIC.as_slice())?;
+        let (status, expires_at) = rest.split_at(2);
+        let status = u16::from_le_bytes(status.try_into().expect("status is 2 bytes"));
+        let expires_at = u64::from_le_bytes(expires_at.try_into().expect("expiry is 8 bytes"));
+        Some(Self {
+            status: StatusCode::from_u16(status).ok()?,
+            expires_at: UNIX_EPOCH + Duration::from_millis(expires_at),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use http::StatusCode;
+
+    use super::NotFound;
+
+    #[test]
+    fn round_trip() {
+        let now = SystemTime::now();
+        let ttl = Duration::from_secs(60);
+        let not_found = NotFound::new(StatusCode::GONE, ttl, now).unwrap();
+        let decoded = NotFound::decode(&not_found.encode()).unwrap();
+        assert_eq!(decoded.status, StatusCode::GONE);
+        assert!(!decoded.is_expired(now));
+        assert!(decoded.is_expired(now + ttl));
+
+        // Only "not found" responses are remembered, and other payloads aren't markers.
+        assert!(NotFound::new(StatusCode::INTERNAL_SERVER_ERROR, ttl, now).is_none());
+        assert!(NotFound::decode(b"flask").is_none());
+    }
+}
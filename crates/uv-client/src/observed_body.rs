@@ -0,0 +1,67 @@
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use futures::{Stream, StreamExt};
+use reqwest::{Response, ResponseBuilderExt};
+
+/// Something that watches the body of a response go by, as per [`observe_body`].
+pub(crate) trait BodyObserver: Send + Sync + Unpin + 'static {
+    /// Called for each chunk of the body, as it's read.
+    fn chunk(&mut self, chunk: &[u8]);
+
+    /// Called once the body has been read to the end (`complete`) or failed to be read (not
+    /// `complete`). Not called if the body is dropped before either happens.
+    fn end(&mut self, complete: bool);
+}
+
+/// Replace the body of a response with one that reports to the given observer as it's read,
+/// keeping the status, headers, and URL of the response.
+pub(crate) fn observe_body(response: Response, observer: impl BodyObserver) -> Response {
+    let mut builder = http::Response::builder()
+        .status(response.status())
+        .version(response.version())
+        .url(response.url().clone());
+    if let Some(headers) = builder.headers_mut() {
+        *headers = response.headers().clone();
+    }
+    let body = ObservedBody {
+        inner: Box::pin(response.bytes_stream()),
+        observer,
+        ended: false,
+    };
+    let response = builder
+        .body(reqwest::Body::wrap_stream(body))
+        .expect("the parts of a valid response are valid");
+    Response::from(response)
+}
+
+/// A response body that reports to a [`BodyObserver`].
+struct ObservedBody<S, O> {
+    inner: S,
+    observer: O,
+    ended: bool,
+}
+
+impl<S, T, E, O> Stream for ObservedBody<S, O>
+where
+    S: Stream<Item = Result<T, E>> + Unpin,
+    T: AsRef<[u8]>,
+    O: BodyObserver,
+{
+    type Item = Result<T, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let item = ready!(self.inner.poll_next_unpin(cx));
+        match &item {
+            Some(Ok(chunk)) => self.observer.chunk(chunk.as_ref()),
+            // A truncated body typically ends in an error, rather than a clean end of stream.
+            Some(Err(_)) | None => {
+                if !self.ended {
+                    self.ended = true;
+                    self.observer.end(item.is_none());
+                }
+            }
+        }
+        Poll::Ready(item)
+    }
+}
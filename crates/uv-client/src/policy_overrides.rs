@@ -0,0 +1,122 @@
+use std::path::Path;
+
+use serde::Deserialize;
+use url::Url;
+
+use crate::{CacheControl, Error, ErrorKind};
+
+/// Overrides of the [`CacheControl`] used for requests to URLs matching a pattern, as loaded
+/// from a policy file.
+///
+/// The file is a JSON array of rules, each of which maps a glob pattern over full URLs to a
+/// cache control, e.g.:
+///
+/// ```json
+/// [
+///   { "url": "https://example.com/dev-index/*", "cache-control": "must-revalidate" },
+///   { "url": "https://pypi.org/simple/*", "cache-control": "allow-stale" }
+/// ]
+/// ```
+///
+/// The cache control is one of `none`, `must-revalidate`, `no-cache`, `allow-stale`, or
+/// `offline` (see [`CacheControl`]). In the pattern, `*` matches any sequence of characters
+/// (including `/`), and `?` matches any single character. The first matching rule wins; requests
+/// that don't match any rule use the cache control requested by the caller.
+#[derive(Debug, Clone, Default)]
+pub struct CachePolicyOverrides {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    pattern: glob::Pattern,
+    cache_control: CacheControl,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct RawRule {
+    url: String,
+    cache_control: CacheControl,
+}
+
+impl CachePolicyOverrides {
+    /// Load the overrides from the policy file at the given path.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let contents = fs_err::read_to_string(path).map_err(ErrorKind::Io)?;
+        Self::from_json(&contents)
+            .map_err(|err| ErrorKind::PolicyOverrides(path.to_path_buf(), err).into())
+    }
+
+    /// Parse the overrides from the contents of a policy file.
+    fn from_json(contents: &str) -> Result<Self, String> {
+        let raw: Vec<RawRule> = serde_json::from_str(contents).map_err(|err| err.to_string())?;
+        let rules = raw
+            .into_iter()
+            .map(|rule| {
+                let pattern = glob::Pattern::new(&rule.url)
+                    .map_err(|err| format!("invalid pattern `{}`: {err}", rule.url))?;
+                Ok(Rule {
+                    pattern,
+                    cache_control: rule.cache_control,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Returns the cache control to use for a request to the given URL, given the cache control
+    /// requested by the caller.
+    pub(crate) fn apply(&self, url: &Url, cache_control: CacheControl) -> CacheControl {
+        self.rules
+            .iter()
+            .find(|rule| rule.pattern.matches(url.as_str()))
+            .map_or(cache_control, |rule| rule.cache_control)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use crate::CacheControl;
+
+    use super::CachePolicyOverrides;
+
+    #[test]
+    fn first_match_wins() {
+        let overrides = CachePolicyOverrides::from_json(
+            r#"[
+                { "url": "https://example.com/dev-index/*", "cache-control": "must-revalidate" },
+                { "url": "https://example.com/*", "cache-control": "allow-stale" }
+            ]"#,
+        )
+        .unwrap();
+        let apply = |url: &str| overrides.apply(&Url::parse(url).unwrap(), CacheControl::None);
+        assert!(matches!(
+            apply("https://example.com/dev-index/flask/"),
+            CacheControl::MustRevalidate
+        ));
+        assert!(matches!(
+            apply("https://example.com/simple/flask/"),
+            CacheControl::AllowStale
+        ));
+        assert!(matches!(
+            apply("https://pypi.org/simple/flask/"),
+            CacheControl::None
+        ));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(CachePolicyOverrides::from_json(
+            r#"[{ "url": "https://example.com/*", "cache-control": "sometimes" }]"#
+        )
+        .is_err());
+        assert!(CachePolicyOverrides::from_json(
+            r#"[{ "url": "https://example.com/[", "cache-control": "no-cache" }]"#
+        )
+        .is_err());
+    }
+}
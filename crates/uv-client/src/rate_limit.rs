@@ -0,0 +1,321 @@
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use http::header::RETRY_AFTER;
+use http::{HeaderMap, HeaderName, StatusCode};
+use rustc_hash::FxHashMap;
+use tracing::debug;
+use url::Url;
+
+use crate::httpcache::rfc2822_to_datetime;
+
+/// The headers from which rate limit information is read.
+///
+/// Indexes disagree on how to advertise their rate limits, so each field holds a list of header
+/// names that are tried in order. The first header that is present and parses as an integer wins.
+#[derive(Debug, Clone)]
+pub struct RateLimitHeaders {
+    /// Headers holding the number of requests remaining in the current window.
+    remaining: Vec<HeaderName>,
+    /// Headers holding when the current window resets.
+    ///
+    /// Values are interpreted as a number of seconds until the reset, unless they're large enough
+    /// to be a Unix timestamp (as sent by, e.g., GitHub), in which case they're interpreted as
+    /// the time of the reset.
+    reset: Vec<HeaderName>,
+}
+
+impl Default for RateLimitHeaders {
+    /// The `X-RateLimit-*` headers, along with their `RateLimit-*` counterparts from the IETF
+    /// draft.
+    fn default() -> Self {
+        Self {
+            remaining: vec![
+                HeaderName::from_static("x-ratelimit-remaining"),
+                HeaderName::from_static("ratelimit-remaining"),
+            ],
+            reset: vec![
+                HeaderName::from_static("x-ratelimit-reset"),
+                HeaderName::from_static("ratelimit-reset"),
+            ],
+        }
+    }
+}
+
+impl RateLimitHeaders {
+    /// Read rate limits from the given headers, in order of preference.
+    pub fn new(remaining: Vec<HeaderName>, reset: Vec<HeaderName>) -> Self {
+        Self { remaining, reset }
+    }
+
+    /// Parse the rate limit state from a response's headers, if present.
+    fn parse(&self, headers: &HeaderMap, now: SystemTime) -> Option<RateLimitState> {
+        let remaining = Self::find(headers, &self.remaining)?;
+        let reset_at = Self::find(headers, &self.reset).map(|reset| {
+            // Anything past 2001 is surely a timestamp rather than a delay.
+            if reset >= 1_000_000_000 {
+                UNIX_EPOCH + Duration::from_secs(reset)
+            } else {
+                now + Duration::from_secs(reset)
+            }
+        });
+        Some(RateLimitState {
+            remaining,
+            reset_at,
+            observed_at: now,
+        })
+    }
+
+    fn find(headers: &HeaderMap, names: &[HeaderName]) -> Option<u64> {
+        names
+            .iter()
+            .find_map(|name| headers.get(name)?.to_str().ok()?.trim().parse::<u64>().ok())
+    }
+}
+
+/// A snapshot of the rate limit advertised by a host, as of its most recent response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitState {
+    /// The number of requests remaining in the current window.
+    pub remaining: u64,
+    /// When the current window resets, if advertised.
+    pub reset_at: Option<SystemTime>,
+    /// When the response carrying this information was received.
+    pub observed_at: SystemTime,
+}
+
+impl RateLimitState {
+    /// Returns how long to wait before sending the next request, such that the remaining
+    /// requests are spread evenly until the window resets.
+    ///
+    /// Returns `None` if `remaining` is above `threshold`, or if the window has already reset.
+    fn cooldown(&self, threshold: u64, now: SystemTime) -> Option<Duration> {
+        if self.remaining > threshold {
+            return None;
+        }
+        let until_reset = self.reset_at?.duration_since(now).ok()?;
+        let requests = u32::try_from(self.remaining.saturating_add(1)).unwrap_or(u32::MAX);
+        Some(until_reset / requests)
+    }
+}
+
+/// Returns how long a response asks to wait before retrying the request, as per its
+/// `Retry-After` header, if it's a `429 Too Many Requests` or `503 Service Unavailable`.
+///
+/// Both forms of the header are supported: a number of seconds, or an HTTP date (of which any
+/// time in the past means to retry immediately).
+pub(crate) fn retry_after(
+    status: StatusCode,
+    headers: &HeaderMap,
+    now: SystemTime,
+) -> Option<Duration> {
+    if !matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+    ) {
+        return None;
+    }
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = SystemTime::from(rfc2822_to_datetime(value)?);
+    Some(at.duration_since(now).unwrap_or_default())
+}
+
+/// Tracks the rate limits advertised by each host, and optionally throttles requests as they
+/// approach the limit.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    headers: RateLimitHeaders,
+    /// Start delaying requests once this many (or fewer) requests remain, if set.
+    throttle_threshold: Option<u64>,
+    /// The most recent rate limit state, by host.
+    states: Mutex<FxHashMap<String, RateLimitState>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(headers: RateLimitHeaders, throttle_threshold: Option<u64>) -> Self {
+        Self {
+            headers,
+            throttle_threshold,
+            states: Mutex::default(),
+        }
+    }
+
+    /// Returns the most recent rate limit state for the host of the given URL.
+    pub(crate) fn state(&self, url: &Url) -> Option<RateLimitState> {
+        let host = url.host_str()?;
+        self.states.lock().unwrap().get(host).copied()
+    }
+
+    /// Record the rate limit state advertised by a response, if any.
+    pub(crate) fn observe(&self, url: &Url, headers: &HeaderMap) {
+        let Some(host) = url.host_str() else {
+            return;
+        };
+        if let Some(state) = self.headers.parse(headers, SystemTime::now()) {
+            self.states.lock().unwrap().insert(host.to_string(), state);
+        }
+    }
+
+    /// Returns how long to wait before sending a request to the given URL, if throttling is
+    /// enabled and the host's rate limit is nearly exhausted.
+    pub(crate) fn cooldown(&self, url: &Url) -> Option<Duration> {
+        let threshold = self.throttle_threshold?;
+        let cooldown = self.state(url)?.cooldown(threshold, SystemTime::now())?;
+        debug!(
+            "Approaching rate limit for {}; delaying request by {:.2}s",
+            url.host_str().unwrap_or_default(),
+            cooldown.as_secs_f32()
+        );
+        Some(cooldown)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+    use url::Url;
+
+    use super::{retry_after, RateLimitHeaders, RateLimitState, RateLimiter};
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| {
+                (
+                    HeaderName::from_static(name),
+                    HeaderValue::from_static(value),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_default_headers() {
+        let now = SystemTime::now();
+        let state = RateLimitHeaders::default()
+            .parse(
+                &headers(&[("x-ratelimit-remaining", "42"), ("x-ratelimit-reset", "30")]),
+                now,
+            )
+            .unwrap();
+        assert_eq!(state.remaining, 42);
+        assert_eq!(state.reset_at, Some(now + Duration::from_secs(30)));
+
+        // Resets may also be given as a Unix timestamp.
+        let state = RateLimitHeaders::default()
+            .parse(
+                &headers(&[
+                    ("ratelimit-remaining", "0"),
+                    ("ratelimit-reset", "1700000000"),
+                ]),
+                now,
+            )
+            .unwrap();
+        assert_eq!(state.remaining, 0);
+        assert_eq!(
+            state.reset_at,
+            Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+
+        // Without a remaining count, there's nothing to track.
+        assert!(RateLimitHeaders::default()
+            .parse(&headers(&[("x-ratelimit-reset", "30")]), now)
+            .is_none());
+    }
+
+    #[test]
+    fn parse_custom_headers() {
+        let custom =
+            RateLimitHeaders::new(vec![HeaderName::from_static("x-requests-left")], vec![]);
+        let state = custom
+            .parse(&headers(&[("x-requests-left", "7")]), SystemTime::now())
+            .unwrap();
+        assert_eq!(state.remaining, 7);
+        assert_eq!(state.reset_at, None);
+        assert!(custom
+            .parse(
+                &headers(&[("x-ratelimit-remaining", "7")]),
+                SystemTime::now()
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn cooldown() {
+        let now = SystemTime::now();
+        let state = |remaining| RateLimitState {
+            remaining,
+            reset_at: Some(now + Duration::from_secs(60)),
+            observed_at: now,
+        };
+        assert_eq!(state(100).cooldown(10, now), None);
+        assert_eq!(state(5).cooldown(10, now), Some(Duration::from_secs(10)));
+        assert_eq!(state(0).cooldown(10, now), Some(Duration::from_secs(60)));
+
+        // Once the window has reset, there's no need to wait.
+        let later = now + Duration::from_secs(120);
+        assert_eq!(state(0).cooldown(10, later), None);
+    }
+
+    #[test]
+    fn per_host() {
+        let limiter = RateLimiter::new(RateLimitHeaders::default(), Some(10));
+        let pypi = Url::parse("https://pypi.org/simple/flask/").unwrap();
+        let other = Url::parse("https://example.com/simple/flask/").unwrap();
+        limiter.observe(
+            &pypi,
+            &headers(&[("x-ratelimit-remaining", "0"), ("x-ratelimit-reset", "60")]),
+        );
+        assert_eq!(limiter.state(&pypi).unwrap().remaining, 0);
+        assert!(limiter.cooldown(&pypi).is_some());
+        assert!(limiter.state(&other).is_none());
+        assert!(limiter.cooldown(&other).is_none());
+    }
+
+    #[test]
+    fn parse_retry_after() {
+        // Wed, 21 Oct 2015 07:28:00 GMT
+        let now = UNIX_EPOCH + Duration::from_secs(1_445_412_480);
+        let unavailable = StatusCode::SERVICE_UNAVAILABLE;
+        assert_eq!(
+            retry_after(unavailable, &headers(&[("retry-after", "1")]), now),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            retry_after(
+                StatusCode::TOO_MANY_REQUESTS,
+                &headers(&[("retry-after", "Wed, 21 Oct 2015 07:30:00 GMT")]),
+                now
+            ),
+            Some(Duration::from_secs(120))
+        );
+        // A date in the past means to retry right away.
+        assert_eq!(
+            retry_after(
+                unavailable,
+                &headers(&[("retry-after", "Wed, 21 Oct 2015 07:00:00 GMT")]),
+                now
+            ),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(
+            retry_after(unavailable, &headers(&[("retry-after", "soon")]), now),
+            None
+        );
+        assert_eq!(retry_after(unavailable, &headers(&[]), now), None);
+        // Only throttling responses are retried.
+        assert_eq!(
+            retry_after(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &headers(&[("retry-after", "1")]),
+                now
+            ),
+            None
+        );
+    }
+}
@@ -1,7 +1,10 @@
+use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::fmt::Debug;
+use std::ops::Range;
 use std::path::Path;
 use std::str::FromStr;
+use std::time::Duration;
 
 use async_http_range_reader::{AsyncHttpRangeReader, AsyncHttpRangeReaderError};
 use async_zip::tokio::read::seek::ZipFileReader;
@@ -27,16 +30,24 @@ use uv_normalize::PackageName;
 use crate::cached_client::CacheControl;
 use crate::html::SimpleHtml;
 use crate::middleware::OfflineMiddleware;
-use crate::remote_metadata::wheel_metadata_from_remote_zip;
+use crate::remote_metadata::{
+    wheel_metadata_from_remote_zip, zip_index_from_remote_zip, RemoteZipIndex,
+};
+use crate::retry_budget::{BudgetedRetryPolicy, RetryAfterStrategy, RetryBudget};
 use crate::rkyvutil::OwnedArchive;
-use crate::{CachedClient, CachedClientError, Error, ErrorKind};
+use crate::{CachedClient, CachedClientBuilder, CachedClientError, Error, ErrorKind};
 
 /// A builder for an [`RegistryClient`].
 #[derive(Debug, Clone)]
 pub struct RegistryClientBuilder {
     index_urls: IndexUrls,
     retries: u32,
+    retry_budget: RetryBudget,
     connectivity: Connectivity,
+    http_version: HttpVersion,
+    pool_max_idle_per_host: usize,
+    pool_idle_timeout: Option<Duration>,
+    keepalive: Option<Duration>,
     cache: Cache,
 }
 
@@ -46,7 +57,12 @@ impl RegistryClientBuilder {
             index_urls: IndexUrls::default(),
             cache,
             connectivity: Connectivity::Online,
+            http_version: HttpVersion::default(),
+            pool_max_idle_per_host: 20,
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            keepalive: None,
             retries: 3,
+            retry_budget: RetryBudget::unlimited(),
         }
     }
 }
@@ -70,6 +86,56 @@ impl RegistryClientBuilder {
         self
     }
 
+    /// Cap the aggregate rate of retries across all requests sent through the client.
+    ///
+    /// Defaults to [`RetryBudget::unlimited`].
+    #[must_use]
+    pub fn retry_budget(mut self, retry_budget: RetryBudget) -> Self {
+        self.retry_budget = retry_budget;
+        self
+    }
+
+    /// Set the HTTP version(s) to use when talking to indexes.
+    ///
+    /// Defaults to [`HttpVersion::Negotiate`].
+    #[must_use]
+    pub fn http_version(mut self, http_version: HttpVersion) -> Self {
+        self.http_version = http_version;
+        self
+    }
+
+    /// Set the maximum number of idle connections to keep open per host, for reuse by subsequent
+    /// requests.
+    ///
+    /// Defaults to 20. Over HTTP/2, a single connection is shared by concurrent requests, so
+    /// fewer idle connections are needed.
+    #[must_use]
+    pub fn pool_max_idle_per_host(mut self, max_idle: usize) -> Self {
+        self.pool_max_idle_per_host = max_idle;
+        self
+    }
+
+    /// Set how long an idle connection is kept open for reuse, or `None` to keep idle connections
+    /// open indefinitely.
+    ///
+    /// Defaults to 90 seconds.
+    #[must_use]
+    pub fn pool_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.pool_idle_timeout = timeout;
+        self
+    }
+
+    /// Send keepalive probes on open connections at the given interval: TCP keepalives, and
+    /// `PING` frames on HTTP/2 connections.
+    ///
+    /// This keeps connections from being silently dropped by intermediaries during long
+    /// resolutions. Disabled by default.
+    #[must_use]
+    pub fn keepalive(mut self, interval: Option<Duration>) -> Self {
+        self.keepalive = interval;
+        self
+    }
+
     #[must_use]
     pub fn cache<T>(mut self, cache: Cache) -> Self {
         self.cache = cache;
@@ -81,8 +147,16 @@ impl RegistryClientBuilder {
             // Disallow any connections.
             let client_core = ClientBuilder::new()
                 .user_agent("uv")
-                .pool_max_idle_per_host(20)
-                .timeout(std::time::Duration::from_secs(60 * 5));
+                .pool_max_idle_per_host(self.pool_max_idle_per_host)
+                .pool_idle_timeout(self.pool_idle_timeout)
+                .tcp_keepalive(self.keepalive)
+                .http2_keep_alive_interval(self.keepalive)
+                .timeout(Duration::from_secs(60 * 5));
+            let client_core = match self.http_version {
+                HttpVersion::Negotiate => client_core,
+                HttpVersion::Http2 => client_core.http2_prior_knowledge(),
+                HttpVersion::Http1 => client_core.http1_only(),
+            };
 
             client_core.build().expect("Failed to build HTTP client.")
         };
@@ -91,7 +165,10 @@ impl RegistryClientBuilder {
             Connectivity::Online => {
                 let retry_policy =
                     ExponentialBackoff::builder().build_with_max_retries(self.retries);
-                let retry_strategy = RetryTransientMiddleware::new_with_policy(retry_policy);
+                let retry_strategy = RetryTransientMiddleware::new_with_policy_and_strategy(
+                    BudgetedRetryPolicy::new(retry_policy, self.retry_budget.clone()),
+                    RetryAfterStrategy,
+                );
                 reqwest_middleware::ClientBuilder::new(client_raw.clone())
                     .with(retry_strategy)
                     .build()
@@ -106,7 +183,7 @@ impl RegistryClientBuilder {
             cache: self.cache,
             connectivity: self.connectivity,
             client_raw: client_raw.clone(),
-            client: CachedClient::new(uncached_client.clone()),
+            client: CachedClientBuilder::new(uncached_client.clone()).build(),
         }
     }
 }
@@ -356,6 +433,7 @@ impl RegistryClient {
                 Connectivity::Offline => CacheControl::AllowStale,
             };
 
+            let expected = filename.name.clone();
             let response_callback = |response: Response| async {
                 let bytes = response.bytes().await.map_err(ErrorKind::RequestError)?;
 
@@ -377,7 +455,14 @@ impl RegistryClient {
                 .map_err(ErrorKind::RequestError)?;
             Ok(self
                 .client
-                .get_serde(req, &cache_entry, cache_control, response_callback)
+                .get_serde_validated(
+                    req,
+                    &cache_entry,
+                    cache_control,
+                    &expected,
+                    |metadata: &Metadata21| metadata.name.clone(),
+                    response_callback,
+                )
                 .await?)
         } else {
             // If we lack PEP 658 support, try using HTTP range requests to read only the
@@ -440,10 +525,12 @@ impl RegistryClient {
             .map_err(ErrorKind::RequestError)?;
         let result = self
             .client
-            .get_serde(
+            .get_serde_validated(
                 req,
                 &cache_entry,
                 cache_control,
+                &filename.name,
+                |metadata: &Metadata21| metadata.name.clone(),
                 read_metadata_range_request,
             )
             .await
@@ -464,8 +551,15 @@ impl RegistryClient {
         // way.
 
         debug!("Range requests not supported for {filename}; downloading wheel");
+        let reader = self.download_wheel(url).await?;
+        read_metadata_async(filename, url.to_string(), reader).await
+    }
+
+    /// Download a wheel into a temporary file in the cache, for when it can't be read via range
+    /// requests.
+    async fn download_wheel(&self, url: &Url) -> Result<tokio::fs::File, Error> {
         // TODO(konstin): Download the wheel into a cache shared with the installer instead
-        // Note that this branch is only hit when you're not using and the server where
+        // Note that this is only needed when you're not using and the server where
         // you host your wheels for some reasons doesn't support range requests
         // (tbh we should probably warn here and tell users to get a better registry because
         // their current one makes resolution unnecessary slow).
@@ -475,9 +569,208 @@ impl RegistryClient {
         tokio::io::copy(&mut reader, &mut writer)
             .await
             .map_err(ErrorKind::CacheWrite)?;
-        let reader = writer.into_inner();
+        Ok(writer.into_inner())
+    }
 
-        read_metadata_async(filename, url.to_string(), reader).await
+    /// Read a single file from a wheel, e.g., `flask-3.0.0.dist-info/RECORD`.
+    ///
+    /// For a remote wheel, only the file itself is downloaded, via a range request. To find it,
+    /// the wheel's central directory is read via range requests once, and cached alongside the
+    /// wheel's metadata. The cached central directory is keyed by the wheel's URL and
+    /// revalidated against its `ETag` and `Last-Modified` headers, such that each subsequent read
+    /// takes a single round-trip. If the server doesn't support range requests, the entire wheel
+    /// is downloaded instead.
+    ///
+    /// Returns `None` if the wheel doesn't contain the file.
+    #[instrument(skip_all, fields(% built_dist, path))]
+    pub async fn wheel_file(
+        &self,
+        built_dist: &BuiltDist,
+        path: &str,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        match &built_dist {
+            BuiltDist::Registry(wheel) => match &wheel.file.url {
+                FileLocation::RelativeUrl(base, url) => {
+                    let url = pypi_types::base_url_join_relative(base, url)
+                        .map_err(ErrorKind::JoinRelativeError)?;
+                    self.remote_wheel_file(
+                        &wheel.filename,
+                        &url,
+                        WheelCache::Index(&wheel.index),
+                        path,
+                    )
+                    .await
+                }
+                FileLocation::AbsoluteUrl(url) => {
+                    let url = Url::parse(url).map_err(ErrorKind::UrlParseError)?;
+                    self.remote_wheel_file(
+                        &wheel.filename,
+                        &url,
+                        WheelCache::Index(&wheel.index),
+                        path,
+                    )
+                    .await
+                }
+                FileLocation::Path(wheel_path) => {
+                    let file = fs_err::tokio::File::open(&wheel_path)
+                        .await
+                        .map_err(ErrorKind::Io)?;
+                    let reader = tokio::io::BufReader::new(file);
+                    read_file_async(&wheel.filename, reader, path).await
+                }
+            },
+            BuiltDist::DirectUrl(wheel) => {
+                self.remote_wheel_file(
+                    &wheel.filename,
+                    &wheel.url,
+                    WheelCache::Url(&wheel.url),
+                    path,
+                )
+                .await
+            }
+            BuiltDist::Path(wheel) => {
+                let file = fs_err::tokio::File::open(&wheel.path)
+                    .await
+                    .map_err(ErrorKind::Io)?;
+                let reader = tokio::io::BufReader::new(file);
+                read_file_async(&wheel.filename, reader, path).await
+            }
+        }
+    }
+
+    /// Read a single file from a remote wheel, preferably via range requests.
+    async fn remote_wheel_file(
+        &self,
+        filename: &WheelFilename,
+        url: &Url,
+        cache_shard: WheelCache<'_>,
+        path: &str,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        match self
+            .remote_wheel_file_by_range(filename, url, cache_shard, path)
+            .await
+        {
+            Ok(contents) => return Ok(contents),
+            Err(err) => match err.into_kind() {
+                ErrorKind::AsyncHttpRangeReader(
+                    AsyncHttpRangeReaderError::HttpRangeRequestUnsupported,
+                ) => {}
+                kind => return Err(kind.into()),
+            },
+        }
+
+        debug!("Range requests not supported for {filename}; downloading wheel");
+        let reader = self.download_wheel(url).await?;
+        read_file_async(filename, reader, path).await
+    }
+
+    /// Read a single file from a remote wheel via range requests, using the cached central
+    /// directory of the wheel to locate it.
+    async fn remote_wheel_file_by_range(
+        &self,
+        filename: &WheelFilename,
+        url: &Url,
+        cache_shard: WheelCache<'_>,
+        path: &str,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let index = self.remote_zip_index(filename, url, cache_shard).await?;
+        let Some(entry) = index.entry(path) else {
+            return Ok(None);
+        };
+
+        trace!("Reading {path} from {filename} by range request");
+        let bytes = self.fetch_range(url, entry.range()).await?;
+        let data_range = entry.data_range(filename, &bytes)?;
+        let within_bytes = usize::try_from(data_range.start - entry.header_offset)
+            .ok()
+            .zip(usize::try_from(data_range.end - entry.header_offset).ok())
+            .and_then(|(start, end)| bytes.get(start..end));
+        let data = match within_bytes {
+            Some(data) => Cow::Borrowed(data),
+            // The local file header had a larger extra field than we allowed for.
+            None => Cow::Owned(self.fetch_range(url, data_range).await?),
+        };
+        entry.decompress(filename, &data).map(Some)
+    }
+
+    /// Read the central directory of a remote wheel via range requests, or from the cache.
+    async fn remote_zip_index(
+        &self,
+        filename: &WheelFilename,
+        url: &Url,
+        cache_shard: WheelCache<'_>,
+    ) -> Result<RemoteZipIndex, Error> {
+        let cache_entry = self.cache.entry(
+            CacheBucket::Wheels,
+            cache_shard.remote_wheel_dir(filename.name.as_ref()),
+            format!("{}.zip-index.msgpack", filename.stem()),
+        );
+        let cache_control = match self.connectivity {
+            Connectivity::Online => CacheControl::from(
+                self.cache
+                    .freshness(&cache_entry, Some(&filename.name))
+                    .map_err(ErrorKind::Io)?,
+            ),
+            Connectivity::Offline => CacheControl::AllowStale,
+        };
+
+        let client = self.client_raw.clone();
+        let read_zip_index_range_request = |response: Response| {
+            async {
+                let mut reader = AsyncHttpRangeReader::from_head_response(client, response)
+                    .await
+                    .map_err(ErrorKind::AsyncHttpRangeReader)?;
+                trace!("Getting central directory for {filename} by range request");
+                let index = zip_index_from_remote_zip(filename, &mut reader).await?;
+                Ok::<RemoteZipIndex, CachedClientError<Error>>(index)
+            }
+            .boxed()
+            .instrument(info_span!("read_zip_index_range_request", wheel = %filename))
+        };
+
+        let req = self
+            .client
+            .uncached()
+            .head(url.clone())
+            .build()
+            .map_err(ErrorKind::RequestError)?;
+        Ok(self
+            .client
+            .get_serde(
+                req,
+                &cache_entry,
+                cache_control,
+                read_zip_index_range_request,
+            )
+            .await?)
+    }
+
+    /// Fetch the given range of bytes of a remote file.
+    ///
+    /// Fails with [`AsyncHttpRangeReaderError::HttpRangeRequestUnsupported`] if the server
+    /// responds with anything but the requested range.
+    async fn fetch_range(&self, url: &Url, range: Range<u64>) -> Result<Vec<u8>, Error> {
+        let response = self
+            .client
+            .uncached()
+            .get(url.clone())
+            .header(
+                http::header::RANGE,
+                format!("bytes={}-{}", range.start, range.end.saturating_sub(1)),
+            )
+            .send()
+            .await
+            .map_err(ErrorKind::RequestMiddlewareError)?
+            .error_for_status()
+            .map_err(ErrorKind::RequestError)?;
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            return Err(ErrorKind::AsyncHttpRangeReader(
+                AsyncHttpRangeReaderError::HttpRangeRequestUnsupported,
+            )
+            .into());
+        }
+        let bytes = response.bytes().await.map_err(ErrorKind::RequestError)?;
+        Ok(bytes.to_vec())
     }
 
     /// Stream a file from an external URL.
@@ -501,6 +794,36 @@ impl RegistryClient {
     }
 }
 
+/// Read a single file from a local wheel, if present.
+async fn read_file_async(
+    filename: &WheelFilename,
+    reader: impl tokio::io::AsyncRead + tokio::io::AsyncSeek + Unpin,
+    path: &str,
+) -> Result<Option<Vec<u8>>, Error> {
+    let mut zip_reader = ZipFileReader::with_tokio(reader)
+        .await
+        .map_err(|err| ErrorKind::Zip(filename.clone(), err))?;
+
+    let Some(idx) = zip_reader
+        .file()
+        .entries()
+        .iter()
+        .position(|e| e.filename().as_str().ok() == Some(path))
+    else {
+        return Ok(None);
+    };
+
+    let mut contents = Vec::new();
+    zip_reader
+        .reader_with_entry(idx)
+        .await
+        .map_err(|err| ErrorKind::Zip(filename.clone(), err))?
+        .read_to_end_checked(&mut contents)
+        .await
+        .map_err(|err| ErrorKind::Zip(filename.clone(), err))?;
+    Ok(Some(contents))
+}
+
 /// It doesn't really fit into `uv_client`, but it avoids cyclical crate dependencies.
 async fn read_metadata_async(
     filename: &WheelFilename,
@@ -692,6 +1015,26 @@ impl MediaType {
     }
 }
 
+/// The HTTP version(s) used by a [`RegistryClient`].
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum HttpVersion {
+    /// Negotiate the version with the server, preferring HTTP/2 (over TLS, via ALPN), and falling
+    /// back to HTTP/1.1.
+    #[default]
+    Negotiate,
+    /// Always use HTTP/2, without negotiation.
+    ///
+    /// HTTP/2 multiplexes concurrent requests over a single connection per host, which is far
+    /// cheaper than opening many HTTP/1.1 connections during large resolutions. Requests fail
+    /// against servers that don't support HTTP/2.
+    Http2,
+    /// Always use HTTP/1.1.
+    ///
+    /// Some corporate proxies advertise HTTP/2 support, but break HTTP/2 connections in
+    /// practice; this allows such environments to opt out.
+    Http1,
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Connectivity {
     /// Allow access to the network.
@@ -708,7 +1051,25 @@ mod tests {
     use pypi_types::SimpleJson;
     use uv_normalize::PackageName;
 
-    use crate::{SimpleMetadata, SimpleMetadatum};
+    use uv_cache::Cache;
+
+    use crate::{HttpVersion, RegistryClientBuilder, SimpleMetadata, SimpleMetadatum};
+
+    #[test]
+    fn build_with_http_versions() {
+        for http_version in [
+            HttpVersion::Negotiate,
+            HttpVersion::Http2,
+            HttpVersion::Http1,
+        ] {
+            RegistryClientBuilder::new(Cache::temp().unwrap())
+                .http_version(http_version)
+                .pool_max_idle_per_host(1)
+                .pool_idle_timeout(None)
+                .keepalive(Some(std::time::Duration::from_secs(30)))
+                .build();
+        }
+    }
 
     #[test]
     fn ignore_failing_files() {
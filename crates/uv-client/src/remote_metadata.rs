@@ -1,5 +1,9 @@
+use std::io::Read;
+
 use async_http_range_reader::AsyncHttpRangeReader;
 use async_zip::tokio::read::seek::ZipFileReader;
+use async_zip::Compression;
+use serde::{Deserialize, Serialize};
 use tokio_util::compat::TokioAsyncReadCompatExt;
 
 use distribution_filename::WheelFilename;
@@ -7,6 +11,152 @@ use install_wheel_rs::find_dist_info;
 
 use crate::{Error, ErrorKind};
 
+/// Best guess for the central directory size inside the zip.
+const CENTRAL_DIRECTORY_SIZE: u64 = 16384;
+
+/// The size of a zip local file header, excluding the variable-length filename and extra field.
+const LOCAL_FILE_HEADER_SIZE: u64 = 30;
+
+/// The signature at the start of every zip local file header.
+const LOCAL_FILE_HEADER_SIGNATURE: [u8; 4] = *b"PK\x03\x04";
+
+/// Headroom for the extra field of a local file header, which (unlike that of the filename) isn't
+/// recorded in the central directory.
+const EXTRA_FIELD_ALLOWANCE: u64 = 1024;
+
+/// The parsed central directory of a remote zip archive, i.e., where each member is stored.
+///
+/// Reading the central directory takes a round-trip of its own, so caching it means that reading
+/// a single member of a remote wheel only requires one range request for the member itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct RemoteZipIndex {
+    entries: Vec<RemoteZipEntry>,
+}
+
+/// The location of a member within a remote zip archive.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct RemoteZipEntry {
+    /// The path of the member within the archive.
+    filename: String,
+    /// The offset of the member's local file header from the start of the archive.
+    pub(crate) header_offset: u64,
+    /// The size of the member's (possibly compressed) data.
+    compressed_size: u64,
+    /// The size of the member's data once decompressed.
+    uncompressed_size: u64,
+    /// How the member's data is compressed.
+    compression: RemoteZipCompression,
+}
+
+/// The compression methods of zip members, as far as we support reading them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum RemoteZipCompression {
+    Stored,
+    Deflate,
+    /// Any other compression method, which we can't decompress.
+    Unsupported,
+}
+
+impl RemoteZipIndex {
+    /// Returns the member at the given path, if any.
+    pub(crate) fn entry(&self, filename: &str) -> Option<&RemoteZipEntry> {
+        self.entries.iter().find(|entry| entry.filename == filename)
+    }
+}
+
+impl RemoteZipEntry {
+    /// The range of bytes to request to read the member, assuming the extra field of its local
+    /// file header fits in [`EXTRA_FIELD_ALLOWANCE`].
+    pub(crate) fn range(&self) -> std::ops::Range<u64> {
+        let end = self.header_offset
+            + LOCAL_FILE_HEADER_SIZE
+            + self.filename.len() as u64
+            + EXTRA_FIELD_ALLOWANCE
+            + self.compressed_size;
+        self.header_offset..end
+    }
+
+    /// Parse the member's local file header from the given bytes, which must start at
+    /// [`RemoteZipEntry::header_offset`], returning the range of the member's data within the
+    /// archive.
+    pub(crate) fn data_range(
+        &self,
+        wheel: &WheelFilename,
+        bytes: &[u8],
+    ) -> Result<std::ops::Range<u64>, Error> {
+        let header = bytes
+            .get(..LOCAL_FILE_HEADER_SIZE as usize)
+            .ok_or_else(|| self.invalid(wheel, "truncated local file header"))?;
+        if header[..4] != LOCAL_FILE_HEADER_SIGNATURE {
+            return Err(self.invalid(wheel, "invalid local file header signature"));
+        }
+        let filename_len = u64::from(u16::from_le_bytes([header[26], header[27]]));
+        let extra_len = u64::from(u16::from_le_bytes([header[28], header[29]]));
+        let start = self.header_offset + LOCAL_FILE_HEADER_SIZE + filename_len + extra_len;
+        Ok(start..start + self.compressed_size)
+    }
+
+    /// Decompress the member's data.
+    pub(crate) fn decompress(&self, wheel: &WheelFilename, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut contents = Vec::with_capacity(usize::try_from(self.uncompressed_size).unwrap_or(0));
+        match self.compression {
+            RemoteZipCompression::Stored => contents.extend_from_slice(data),
+            RemoteZipCompression::Deflate => {
+                flate2::read::DeflateDecoder::new(data)
+                    .take(self.uncompressed_size)
+                    .read_to_end(&mut contents)
+                    .map_err(|_| self.invalid(wheel, "invalid deflate stream"))?;
+            }
+            RemoteZipCompression::Unsupported => {
+                return Err(self.invalid(wheel, "unsupported compression method"));
+            }
+        }
+        if contents.len() as u64 != self.uncompressed_size {
+            return Err(self.invalid(wheel, "size mismatch"));
+        }
+        Ok(contents)
+    }
+
+    fn invalid(&self, wheel: &WheelFilename, reason: &str) -> Error {
+        ErrorKind::RemoteZipEntry(wheel.clone(), self.filename.clone(), reason.to_string()).into()
+    }
+}
+
+/// Read the central directory of a remote zip archive, without downloading the rest of it.
+pub(crate) async fn zip_index_from_remote_zip(
+    filename: &WheelFilename,
+    reader: &mut AsyncHttpRangeReader,
+) -> Result<RemoteZipIndex, Error> {
+    // Because the zip index is at the back
+    reader
+        .prefetch(reader.len().saturating_sub(CENTRAL_DIRECTORY_SIZE)..reader.len())
+        .await;
+
+    let reader = ZipFileReader::new(reader.compat())
+        .await
+        .map_err(|err| ErrorKind::Zip(filename.clone(), err))?;
+
+    let entries = reader
+        .file()
+        .entries()
+        .iter()
+        .filter_map(|entry| {
+            Some(RemoteZipEntry {
+                filename: entry.filename().as_str().ok()?.to_string(),
+                header_offset: entry.header_offset(),
+                compressed_size: entry.compressed_size(),
+                uncompressed_size: entry.uncompressed_size(),
+                compression: match entry.compression() {
+                    Compression::Stored => RemoteZipCompression::Stored,
+                    Compression::Deflate => RemoteZipCompression::Deflate,
+                    _ => RemoteZipCompression::Unsupported,
+                },
+            })
+        })
+        .collect();
+    Ok(RemoteZipIndex { entries })
+}
+
 /// Read the `.dist-info/METADATA` file from a async remote zip reader, so we avoid downloading the
 /// entire wheel just for the one file.
 ///
@@ -53,8 +203,6 @@ pub(crate) async fn wheel_metadata_from_remote_zip(
     reader: &mut AsyncHttpRangeReader,
 ) -> Result<String, Error> {
     // Make sure we have the back part of the stream.
-    // Best guess for the central directory size inside the zip
-    const CENTRAL_DIRECTORY_SIZE: u64 = 16384;
     // Because the zip index is at the back
     reader
         .prefetch(reader.len().saturating_sub(CENTRAL_DIRECTORY_SIZE)..reader.len())
@@ -105,3 +253,105 @@ pub(crate) async fn wheel_metadata_from_remote_zip(
 
     Ok(contents)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::str::FromStr;
+
+    use distribution_filename::WheelFilename;
+
+    use super::{RemoteZipCompression, RemoteZipEntry, RemoteZipIndex};
+
+    /// Build a zip local file header, followed by the given data.
+    fn local_file(filename: &str, extra: &[u8], compression: u16, data: &[u8]) -> Vec<u8> {
+        let mut bytes = b"PK\x03\x04".to_vec();
+        bytes.extend_from_slice(&20u16.to_le_bytes()); // Version needed to extract
+        bytes.extend_from_slice(&0u16.to_le_bytes()); // Flags
+        bytes.extend_from_slice(&compression.to_le_bytes());
+        bytes.extend_from_slice(&[0; 8]); // Modification time, date, and CRC-32
+        bytes.extend_from_slice(&u32::try_from(data.len()).unwrap().to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]); // Uncompressed size
+        bytes.extend_from_slice(&u16::try_from(filename.len()).unwrap().to_le_bytes());
+        bytes.extend_from_slice(&u16::try_from(extra.len()).unwrap().to_le_bytes());
+        bytes.extend_from_slice(filename.as_bytes());
+        bytes.extend_from_slice(extra);
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn read_entries() {
+        let wheel = WheelFilename::from_str("flask-3.0.0-py3-none-any.whl").unwrap();
+        let contents = b"flask/__init__.py,sha256=abc,123\n".repeat(10);
+        let mut encoder =
+            flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&contents).unwrap();
+        let deflated = encoder.finish().unwrap();
+
+        // An archive with a stored member, followed by a deflated one with a large extra field.
+        let stored = local_file("flask/py.typed", b"", 0, b"typed");
+        let record = "flask-3.0.0.dist-info/RECORD";
+        let deflate = local_file(record, &[0; 2048], 8, &deflated);
+        let archive = [stored.as_slice(), deflate.as_slice()].concat();
+        let index = RemoteZipIndex {
+            entries: vec![
+                RemoteZipEntry {
+                    filename: "flask/py.typed".to_string(),
+                    header_offset: 0,
+                    compressed_size: 5,
+                    uncompressed_size: 5,
+                    compression: RemoteZipCompression::Stored,
+                },
+                RemoteZipEntry {
+                    filename: record.to_string(),
+                    header_offset: stored.len() as u64,
+                    compressed_size: deflated.len() as u64,
+                    uncompressed_size: contents.len() as u64,
+                    compression: RemoteZipCompression::Deflate,
+                },
+            ],
+        };
+        let read = |entry: &RemoteZipEntry| {
+            // Emulate a range request, which may be cut short by the end of the archive.
+            let range = entry.range();
+            let end = usize::try_from(range.end).unwrap().min(archive.len());
+            let bytes = &archive[usize::try_from(range.start).unwrap()..end];
+            let data_range = entry.data_range(&wheel, bytes).unwrap();
+            let data = &archive[usize::try_from(data_range.start).unwrap()
+                ..usize::try_from(data_range.end).unwrap()];
+            entry.decompress(&wheel, data).unwrap()
+        };
+
+        assert_eq!(read(index.entry("flask/py.typed").unwrap()), b"typed");
+        assert_eq!(read(index.entry(record).unwrap()), contents);
+        assert!(index.entry("flask/missing.py").is_none());
+
+        // The extra field of the deflated member doesn't fit in the initial range request.
+        let entry = index.entry(record).unwrap();
+        assert!(entry.data_range(&wheel, &deflate).unwrap().end > entry.range().end);
+    }
+
+    #[test]
+    fn invalid_entries() {
+        let wheel = WheelFilename::from_str("flask-3.0.0-py3-none-any.whl").unwrap();
+        let entry = RemoteZipEntry {
+            filename: "flask/py.typed".to_string(),
+            header_offset: 0,
+            compressed_size: 5,
+            uncompressed_size: 5,
+            compression: RemoteZipCompression::Stored,
+        };
+        assert!(entry.data_range(&wheel, b"PK\x03\x04").is_err());
+        assert!(entry
+            .data_range(&wheel, &local_file("flask/py.typed", b"", 0, b"typed")[1..])
+            .is_err());
+        assert!(entry.decompress(&wheel, b"type").is_err());
+
+        let entry = RemoteZipEntry {
+            compression: RemoteZipCompression::Unsupported,
+            ..entry
+        };
+        assert!(entry.decompress(&wheel, b"typed").is_err());
+    }
+}
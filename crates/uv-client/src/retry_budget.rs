@@ -0,0 +1,249 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use reqwest::Response;
+use reqwest_retry::{
+    default_on_request_failure, default_on_request_success, Retryable, RetryableStrategy,
+};
+use retry_policies::{RetryDecision, RetryPolicy};
+use tracing::debug;
+
+use crate::rate_limit;
+
+/// A budget for retries that is shared across every request sent through a client.
+///
+/// The retry middleware decides whether to retry each request in isolation. When an index is
+/// struggling, thousands of in-flight requests can each decide to retry independently, which
+/// multiplies the load on the index right when it can least afford it. A [`RetryBudget`] caps the
+/// aggregate retry rate: every retry must first take a token from a shared bucket, and once the
+/// bucket is empty, requests fail fast instead of retrying.
+///
+/// The bucket holds at most `capacity` tokens and regains `refill_per_second` tokens per second.
+/// By default, the budget is unlimited, which matches the behavior of the retry middleware on its
+/// own.
+#[derive(Debug, Clone, Default)]
+pub struct RetryBudget {
+    /// The token bucket, or `None` if the budget is unlimited.
+    bucket: Option<Arc<Mutex<TokenBucket>>>,
+}
+
+impl RetryBudget {
+    /// Create a budget that permits bursts of up to `capacity` retries, regaining
+    /// `refill_per_second` retries every second.
+    pub fn new(capacity: u32, refill_per_second: f64) -> Self {
+        let capacity = f64::from(capacity);
+        Self {
+            bucket: Some(Arc::new(Mutex::new(TokenBucket {
+                capacity,
+                tokens: capacity,
+                refill_per_second: refill_per_second.max(0.0),
+                last_refill: Instant::now(),
+            }))),
+        }
+    }
+
+    /// Create a budget that never limits retries.
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Returns `true` if this budget never limits retries.
+    pub fn is_unlimited(&self) -> bool {
+        self.bucket.is_none()
+    }
+
+    /// Attempt to withdraw a single retry from the budget.
+    ///
+    /// Returns `false` if the budget is exhausted, in which case the caller should not retry.
+    pub fn try_acquire(&self) -> bool {
+        self.try_acquire_at(Instant::now())
+    }
+
+    fn try_acquire_at(&self, now: Instant) -> bool {
+        let Some(bucket) = self.bucket.as_ref() else {
+            return true;
+        };
+        let mut bucket = bucket.lock().unwrap();
+        bucket.refill(now);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    /// The maximum number of tokens the bucket can hold.
+    capacity: f64,
+    /// The number of tokens currently available.
+    tokens: f64,
+    /// The number of tokens regained per second.
+    refill_per_second: f64,
+    /// The last time the bucket was refilled.
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        if elapsed == Duration::ZERO {
+            return;
+        }
+        self.tokens = elapsed
+            .as_secs_f64()
+            .mul_add(self.refill_per_second, self.tokens)
+            .min(self.capacity);
+        self.last_refill = now;
+    }
+}
+
+/// A [`RetryPolicy`] that defers to an underlying policy, but only retries if the shared
+/// [`RetryBudget`] has a token to spare.
+///
+/// The [`crate::RegistryClient`] applies its budget (see
+/// [`crate::RegistryClientBuilder::retry_budget`]) this way. To apply a budget to a
+/// [`crate::CachedClient`] built over other middleware, wrap the policy given to the retry
+/// middleware (e.g., with `RetryTransientMiddleware::new_with_policy`), sharing the same budget
+/// across every client that should draw from it.
+#[derive(Debug)]
+pub struct BudgetedRetryPolicy<P> {
+    policy: P,
+    budget: RetryBudget,
+}
+
+impl<P> BudgetedRetryPolicy<P> {
+    /// Wrap the given policy, such that each retry it permits is first withdrawn from `budget`.
+    pub fn new(policy: P, budget: RetryBudget) -> Self {
+        Self { policy, budget }
+    }
+}
+
+impl<P: RetryPolicy> RetryPolicy for BudgetedRetryPolicy<P> {
+    fn should_retry(&self, n_past_retries: u32) -> RetryDecision {
+        match self.policy.should_retry(n_past_retries) {
+            RetryDecision::Retry { execute_after } => {
+                if self.budget.try_acquire() {
+                    RetryDecision::Retry { execute_after }
+                } else {
+                    debug!("Retry budget exhausted; failing fast instead of retrying");
+                    RetryDecision::DoNotRetry
+                }
+            }
+            RetryDecision::DoNotRetry => RetryDecision::DoNotRetry,
+        }
+    }
+}
+
+/// A [`RetryableStrategy`] that defers to the retry middleware's default, except for throttled
+/// responses with a `Retry-After` header.
+///
+/// Those are left to [`crate::CachedClient`], which waits as long as the header asks before
+/// retrying (see [`crate::CachedClientBuilder::retry_after`]). Retrying them in the middleware
+/// too would back off on a schedule that ignores the header, and nest its retries within each of
+/// the client's.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct RetryAfterStrategy;
+
+impl RetryableStrategy for RetryAfterStrategy {
+    fn handle(&self, res: &Result<Response, reqwest_middleware::Error>) -> Option<Retryable> {
+        match res {
+            Ok(response)
+                if rate_limit::retry_after(
+                    response.status(),
+                    response.headers(),
+                    SystemTime::now(),
+                )
+                .is_some() =>
+            {
+                None
+            }
+            Ok(response) => default_on_request_success(response),
+            Err(err) => default_on_request_failure(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use reqwest_retry::{Retryable, RetryableStrategy};
+
+    use super::{RetryAfterStrategy, RetryBudget};
+
+    #[test]
+    fn unlimited() {
+        let budget = RetryBudget::unlimited();
+        assert!(budget.is_unlimited());
+        for _ in 0..10_000 {
+            assert!(budget.try_acquire());
+        }
+    }
+
+    #[test]
+    fn exhausted() {
+        let budget = RetryBudget::new(3, 0.0);
+        let now = Instant::now();
+        assert!(budget.try_acquire_at(now));
+        assert!(budget.try_acquire_at(now));
+        assert!(budget.try_acquire_at(now));
+        assert!(!budget.try_acquire_at(now));
+    }
+
+    #[test]
+    fn refill() {
+        let budget = RetryBudget::new(2, 1.0);
+        let now = Instant::now();
+        assert!(budget.try_acquire_at(now));
+        assert!(budget.try_acquire_at(now));
+        assert!(!budget.try_acquire_at(now));
+
+        // After a second, a single token has been regained.
+        let later = now + Duration::from_secs(1);
+        assert!(budget.try_acquire_at(later));
+        assert!(!budget.try_acquire_at(later));
+
+        // The bucket never refills beyond its capacity.
+        let much_later = later + Duration::from_secs(60);
+        assert!(budget.try_acquire_at(much_later));
+        assert!(budget.try_acquire_at(much_later));
+        assert!(!budget.try_acquire_at(much_later));
+    }
+
+    #[test]
+    fn shared_across_clones() {
+        let budget = RetryBudget::new(1, 0.0);
+        let clone = budget.clone();
+        assert!(clone.try_acquire());
+        assert!(!budget.try_acquire());
+    }
+
+    #[test]
+    fn retry_after_strategy() {
+        let handle = |status: u16, headers: &[(&str, &str)]| {
+            let mut response = http::Response::builder().status(status);
+            for (name, value) in headers {
+                response = response.header(*name, *value);
+            }
+            let response = reqwest::Response::from(response.body("").unwrap());
+            RetryAfterStrategy.handle(&Ok(response))
+        };
+
+        // Throttled responses with a `Retry-After` header are left to the client.
+        assert_eq!(handle(429, &[("retry-after", "1")]), None);
+        assert_eq!(handle(503, &[("retry-after", "1")]), None);
+
+        // Anything else is handled as by default.
+        assert_eq!(handle(429, &[]), Some(Retryable::Transient));
+        assert_eq!(handle(503, &[]), Some(Retryable::Transient));
+        assert_eq!(
+            handle(500, &[("retry-after", "1")]),
+            Some(Retryable::Transient)
+        );
+        assert_eq!(handle(404, &[]), Some(Retryable::Fatal));
+        assert_eq!(handle(200, &[]), None);
+    }
+}
@@ -160,6 +160,15 @@ where
     }
 }
 
+impl<A> Clone for OwnedArchive<A> {
+    fn clone(&self) -> Self {
+        OwnedArchive {
+            raw: self.raw.clone(),
+            archive: std::marker::PhantomData,
+        }
+    }
+}
+
 impl<A> std::ops::Deref for OwnedArchive<A>
 where
     A: Archive + Serialize<Serializer<4096>>,
@@ -0,0 +1,94 @@
+//! Cache entries whose payload is stored in a separate file.
+//!
+//! When enabled with [`crate::CachedClientBuilder::sidecar_payloads`], the payloads of
+//! [`crate::StreamCacheable`] entries are written as-is to a sidecar file next to the cache entry,
+//! and the entry itself only holds the cache policy and a reference to the sidecar. As such,
+//! the cache policy of an entry can be read and rewritten without touching its (potentially
+//! large) payload.
+//!
+//! References are tagged with a magic prefix, and record the length and checksum of the sidecar,
+//! such that a missing or truncated sidecar is detected and treated as a cache miss.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// The prefix of every sidecar reference, followed by the length and checksum of the sidecar.
+const SIDECAR_MAGIC: &[u8; 8] = b"uv-side1";
+
+/// A reference to the sidecar file holding the payload of a cache entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SidecarRef {
+    /// The length of the sidecar, in bytes.
+    pub(crate) len: u64,
+    /// The checksum of the sidecar, as computed by `seahash`.
+    pub(crate) checksum: u64,
+}
+
+impl SidecarRef {
+    /// The length of an encoded reference, in bytes.
+    pub(crate) const ENCODED_LEN: usize = 24;
+
+    /// Encode the reference, to be stored as the payload of the cache entry.
+    pub(crate) fn encode(self) -> [u8; Self::ENCODED_LEN] {
+        let mut encoded = [0; Self::ENCODED_LEN];
+        encoded[..8].copy_from_slice(SIDECAR_MAGIC);
+        encoded[8..16].copy_from_slice(&self.len.to_le_bytes());
+        encoded[16..].copy_from_slice(&self.checksum.to_le_bytes());
+        encoded
+    }
+
+    /// Decode the payload of a cache entry as a reference, returning `None` if the payload is
+    /// stored inline.
+    pub(crate) fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() != Self::ENCODED_LEN || !data.starts_with(SIDECAR_MAGIC) {
+            return None;
+        }
+        let len = u64::from_le_bytes(data[8..16].try_into().expect("length is 8 bytes"));
+        let checksum = u64::from_le_bytes(data[16..].try_into().expect("checksum is 8 bytes"));
+        Some(Self { len, checksum })
+    }
+}
+
+/// Returns the path of the sidecar for the cache entry at the given path, i.e., the path of the
+/// entry with `.data` appended.
+pub(crate) fn sidecar_path(entry: &Path) -> PathBuf {
+    let mut path = OsString::from(entry.as_os_str());
+    path.push(".data");
+    PathBuf::from(path)
+}
+
+/// Remove the sidecar of the cache entry at the given path, if any.
+pub(crate) fn remove_sidecar(entry: &Path) -> std::io::Result<()> {
+    match fs_err::remove_file(sidecar_path(entry)) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{sidecar_path, SidecarRef};
+
+    #[test]
+    fn round_trip() {
+        let sidecar = SidecarRef {
+            len: 42,
+            checksum: 0xdead_beef,
+        };
+        assert_eq!(SidecarRef::decode(&sidecar.encode()), Some(sidecar));
+
+        // Inline payloads aren't references, even if they happen to be of the same length.
+        assert_eq!(SidecarRef::decode(&[0; SidecarRef::ENCODED_LEN]), None);
+        assert_eq!(SidecarRef::decode(&sidecar.encode()[..16]), None);
+    }
+
+    #[test]
+    fn path() {
+        assert_eq!(
+            sidecar_path(Path::new("/cache/wheels-v0/flask.http")),
+            Path::new("/cache/wheels-v0/flask.http.data")
+        );
+    }
+}
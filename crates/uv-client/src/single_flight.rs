@@ -0,0 +1,113 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use futures::channel::oneshot;
+use futures::future::{FutureExt, Shared};
+use rkyv::util::AlignedVec;
+use rustc_hash::FxHashMap;
+
+use crate::cached_client::CacheOutcome;
+use crate::httpcache::CachePolicy;
+use crate::rkyvutil::OwnedArchive;
+
+/// The result of a request, as shared with the concurrent requests for the same cache entry.
+///
+/// The payload is shared as its raw bytes, such that each request deserializes its own copy.
+pub(crate) struct SharedPayload {
+    pub(crate) bytes: AlignedVec,
+    pub(crate) cache_policy: Option<OwnedArchive<CachePolicy>>,
+    pub(crate) outcome: CacheOutcome,
+    pub(crate) filename: Option<String>,
+}
+
+/// A future resolving to the result of the request that's in flight for a cache entry, or to an
+/// error if that request fails.
+pub(crate) type SharedFlight = Shared<oneshot::Receiver<Arc<SharedPayload>>>;
+
+/// The requests in flight, keyed by the path of their cache entry, such that concurrent requests
+/// for the same entry wait on the first one rather than each going to the network.
+#[derive(Debug, Default)]
+pub(crate) struct SingleFlight {
+    flights: Mutex<FxHashMap<PathBuf, Flight>>,
+}
+
+#[derive(Debug)]
+struct Flight {
+    /// The number of requests waiting on the result.
+    followers: usize,
+    result: SharedFlight,
+}
+
+/// Whether a request leads the flight for its cache entry, or follows one already in flight.
+pub(crate) enum Join {
+    Leader(FlightLeader),
+    Follower(SharedFlight),
+}
+
+impl SingleFlight {
+    /// Join the flight for the given cache entry, starting one if there's none in flight.
+    pub(crate) fn join(self: &Arc<Self>, path: &Path) -> Join {
+        let mut flights = self.flights.lock().unwrap();
+        if let Some(flight) = flights.get_mut(path) {
+            flight.followers += 1;
+            return Join::Follower(flight.result.clone());
+        }
+        let (sender, receiver) = oneshot::channel();
+        flights.insert(
+            path.to_path_buf(),
+            Flight {
+                followers: 0,
+                result: receiver.shared(),
+            },
+        );
+        Join::Leader(FlightLeader {
+            single_flight: self.clone(),
+            path: path.to_path_buf(),
+            sender: Some(sender),
+        })
+    }
+}
+
+/// The request that the others for the same cache entry are waiting on.
+///
+/// Dropping the leader without landing it (e.g., as the request failed) lets the followers know
+/// to join again, such that one of them leads a new flight.
+pub(crate) struct FlightLeader {
+    single_flight: Arc<SingleFlight>,
+    path: PathBuf,
+    sender: Option<oneshot::Sender<Arc<SharedPayload>>>,
+}
+
+impl FlightLeader {
+    /// End the flight, sharing the result built by `result` with any followers.
+    ///
+    /// The result is only built if anyone is waiting on it; if it can't be built, the followers
+    /// join again, as if the request had failed. Any request that starts from here on leads a new
+    /// flight, so this should only be called once the cache entry has been written.
+    pub(crate) fn land(mut self, result: impl FnOnce() -> Option<SharedPayload>) {
+        let sender = self.sender.take().expect("a flight lands only once");
+        if self.take_off() > 0 {
+            if let Some(result) = result() {
+                let _ = sender.send(Arc::new(result));
+            }
+        }
+    }
+
+    /// Remove the flight, returning the number of requests waiting on it.
+    fn take_off(&self) -> usize {
+        self.single_flight
+            .flights
+            .lock()
+            .unwrap()
+            .remove(&self.path)
+            .map_or(0, |flight| flight.followers)
+    }
+}
+
+impl Drop for FlightLeader {
+    fn drop(&mut self) {
+        if self.sender.is_some() {
+            self.take_off();
+        }
+    }
+}
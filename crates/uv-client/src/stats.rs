@@ -0,0 +1,153 @@
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A snapshot of how the requests made through a [`crate::CachedClient`] (and its clones) were
+/// served, as returned by [`crate::CachedClient::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// The number of cached requests made.
+    pub requests: u64,
+    /// The number of requests served from the cache without contacting the server, including
+    /// those served from memory.
+    pub fresh: u64,
+    /// The number of requests served from the cache after the server confirmed that the cached
+    /// response was still valid.
+    pub revalidated: u64,
+    /// The number of requests for which a new response was fetched.
+    pub fetched: u64,
+    /// The number of bytes downloaded for fetched responses, as far as advertised by their
+    /// `Content-Length`.
+    pub bytes_downloaded: u64,
+    /// The number of payload bytes served from cache entries.
+    pub bytes_from_cache: u64,
+    /// The number of bytes written to cache entries, including their cache policies.
+    pub bytes_written: u64,
+    /// The number of requests that waited for a concurrent request for the same entry and were
+    /// served its response, however that request obtained it.
+    pub shared: u64,
+}
+
+impl CacheStats {
+    /// The number of requests served from the cache, whether or not they were revalidated.
+    pub fn hits(&self) -> u64 {
+        self.fresh + self.revalidated
+    }
+
+    /// The fraction of requests served from the cache, between `0` and `1`.
+    pub fn hit_rate(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.hits() as f64 / self.requests as f64
+        }
+    }
+}
+
+impl Display for CacheStats {
+    /// A single line of space-separated `key=value` pairs, e.g., `requests=10 hits=8
+    /// hit_rate=80.0% ...`.
+    ///
+    /// Keys are only ever added, not renamed or removed, such that scripts can parse the line.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "requests={} hits={} hit_rate={:.1}% fresh={} revalidated={} fetched={} \
+             bytes_downloaded={} bytes_from_cache={} bytes_written={} shared={}",
+            self.requests,
+            self.hits(),
+            self.hit_rate() * 100.0,
+            self.fresh,
+            self.revalidated,
+            self.fetched,
+            self.bytes_downloaded,
+            self.bytes_from_cache,
+            self.bytes_written,
+            self.shared,
+        )
+    }
+}
+
+/// The counters behind [`CacheStats`], shared by a client and its clones.
+#[derive(Debug, Default)]
+pub(crate) struct StatsCounters {
+    requests: AtomicU64,
+    fresh: AtomicU64,
+    revalidated: AtomicU64,
+    fetched: AtomicU64,
+    bytes_downloaded: AtomicU64,
+    bytes_from_cache: AtomicU64,
+    bytes_written: AtomicU64,
+    shared: AtomicU64,
+}
+
+impl StatsCounters {
+    /// Record a request served from the cache without contacting the server.
+    pub(crate) fn fresh(&self, bytes: u64) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.fresh.fetch_add(1, Ordering::Relaxed);
+        self.bytes_from_cache.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Record a request served from the cache after revalidation.
+    pub(crate) fn revalidated(&self, bytes: u64) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.revalidated.fetch_add(1, Ordering::Relaxed);
+        self.bytes_from_cache.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Record a request for which a new response was fetched.
+    pub(crate) fn fetched(&self, bytes: Option<u64>) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.fetched.fetch_add(1, Ordering::Relaxed);
+        self.bytes_downloaded
+            .fetch_add(bytes.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    /// Record a request served with the response to a concurrent request for the same entry.
+    pub(crate) fn shared(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.shared.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record bytes written to a cache entry.
+    pub(crate) fn written(&self, bytes: u64) {
+        self.bytes_written.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> CacheStats {
+        CacheStats {
+            requests: self.requests.load(Ordering::Relaxed),
+            fresh: self.fresh.load(Ordering::Relaxed),
+            revalidated: self.revalidated.load(Ordering::Relaxed),
+            fetched: self.fetched.load(Ordering::Relaxed),
+            bytes_downloaded: self.bytes_downloaded.load(Ordering::Relaxed),
+            bytes_from_cache: self.bytes_from_cache.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            shared: self.shared.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CacheStats, StatsCounters};
+
+    #[test]
+    fn summary() {
+        let counters = StatsCounters::default();
+        counters.fresh(100);
+        counters.fresh(50);
+        counters.revalidated(10);
+        counters.fetched(Some(1000));
+        counters.written(1000);
+        counters.shared();
+        let stats = counters.snapshot();
+        assert_eq!(stats.hits(), 3);
+        assert_eq!(
+            stats.to_string(),
+            "requests=5 hits=3 hit_rate=60.0% fresh=2 revalidated=1 fetched=1 \
+             bytes_downloaded=1000 bytes_from_cache=160 bytes_written=1000 shared=1"
+        );
+        assert_eq!(CacheStats::default().hit_rate(), 0.0);
+    }
+}
@@ -0,0 +1,167 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+
+use rustc_hash::FxHashMap;
+
+use crate::httpcache::CachePolicy;
+use crate::rkyvutil::OwnedArchive;
+
+/// A type-erased, deserialized payload.
+type AnyPayload = dyn Any + Send + Sync;
+
+/// An in-memory cache of recently deserialized payloads, keyed by the path of their cache entry.
+///
+/// Payloads are held by weak reference, alongside the cache policy of the entry they were read
+/// from. Only the `max_live_entries` most recently inserted payloads are kept alive by the cache
+/// itself; any others are reclaimed as soon as the last caller holding on to them drops them.
+///
+/// Payloads of different types can share the same cache, as they're downcast on the way out. A
+/// lookup with the wrong type is treated as a miss.
+pub(crate) struct WeakCache {
+    max_live_entries: usize,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// The payloads we know about, whether or not they're still alive.
+    entries: FxHashMap<PathBuf, Entry>,
+    /// Strong references to the most recently inserted payloads.
+    live: VecDeque<Arc<AnyPayload>>,
+}
+
+struct Entry {
+    payload: Weak<AnyPayload>,
+    cache_policy: Arc<OwnedArchive<CachePolicy>>,
+}
+
+impl WeakCache {
+    pub(crate) fn new(max_live_entries: usize) -> Self {
+        Self {
+            max_live_entries,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Returns the payload for the given cache entry, along with the cache policy it was stored
+    /// with, if it's still alive.
+    pub(crate) fn get<T: Send + Sync + 'static>(
+        &self,
+        path: &Path,
+    ) -> Option<(Arc<T>, Arc<OwnedArchive<CachePolicy>>)> {
+        let mut state = self.state.lock().unwrap();
+        let entry = state.entries.get(path)?;
+        let Some(payload) = entry.payload.upgrade() else {
+            state.entries.remove(path);
+            return None;
+        };
+        let payload = payload.downcast::<T>().ok()?;
+        Some((payload, entry.cache_policy.clone()))
+    }
+
+    /// Insert a payload for the given cache entry, replacing any existing payload.
+    pub(crate) fn insert<T: Send + Sync + 'static>(
+        &self,
+        path: PathBuf,
+        payload: Arc<T>,
+        cache_policy: OwnedArchive<CachePolicy>,
+    ) {
+        let payload: Arc<AnyPayload> = payload;
+        let mut state = self.state.lock().unwrap();
+        state.entries.insert(
+            path,
+            Entry {
+                payload: Arc::downgrade(&payload),
+                cache_policy: Arc::new(cache_policy),
+            },
+        );
+        if self.max_live_entries > 0 {
+            state.live.push_back(payload);
+            while state.live.len() > self.max_live_entries {
+                state.live.pop_front();
+            }
+        }
+        // Drop any entries whose payloads have been reclaimed, so that the map stays bounded by
+        // the number of live payloads.
+        if state.entries.len() > self.max_live_entries.saturating_mul(2) {
+            state
+                .entries
+                .retain(|_, entry| entry.payload.strong_count() > 0);
+        }
+    }
+
+    /// Forget the payload for the given cache entry, if any.
+    pub(crate) fn remove(&self, path: &Path) {
+        self.state.lock().unwrap().entries.remove(path);
+    }
+}
+
+impl std::fmt::Debug for WeakCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("WeakCache")
+            .field("max_live_entries", &self.max_live_entries)
+            .field("entries", &state.entries.len())
+            .field("live", &state.live.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use url::Url;
+
+    use crate::httpcache::{CachePolicy, CachePolicyBuilder};
+    use crate::rkyvutil::OwnedArchive;
+
+    use super::WeakCache;
+
+    fn cache_policy() -> OwnedArchive<CachePolicy> {
+        let request = reqwest::Request::new(
+            http::Method::GET,
+            Url::parse("https://example.com/").unwrap(),
+        );
+        let response = http::Response::builder()
+            .status(200)
+            .header("cache-control", "max-age=60")
+            .body("")
+            .unwrap();
+        CachePolicyBuilder::new(&request)
+            .build(&reqwest::Response::from(response))
+            .to_archived()
+    }
+
+    #[test]
+    fn live_entries() {
+        let cache = WeakCache::new(1);
+        cache.insert("a".into(), Arc::new(1u32), cache_policy());
+        let (held, _) = cache.get::<u32>(Path::new("a")).unwrap();
+        assert_eq!(*held, 1);
+
+        // A lookup with the wrong type is a miss.
+        assert!(cache.get::<u64>(Path::new("a")).is_none());
+
+        // Once evicted from the live set, a payload survives only while a caller holds on to it.
+        cache.insert("b".into(), Arc::new(2u32), cache_policy());
+        cache.insert("c".into(), Arc::new(3u32), cache_policy());
+        assert_eq!(*cache.get::<u32>(Path::new("a")).unwrap().0, 1);
+        assert!(cache.get::<u32>(Path::new("b")).is_none());
+        assert_eq!(*cache.get::<u32>(Path::new("c")).unwrap().0, 3);
+
+        drop(held);
+        assert!(cache.get::<u32>(Path::new("a")).is_none());
+    }
+
+    #[test]
+    fn remove() {
+        let cache = WeakCache::new(4);
+        cache.insert("a".into(), Arc::new(1u32), cache_policy());
+        cache.remove(Path::new("a"));
+        assert!(cache.get::<u32>(Path::new("a")).is_none());
+    }
+}
@@ -14,7 +14,7 @@ use distribution_types::{
 use platform_tags::Tags;
 use pypi_types::Metadata21;
 use uv_cache::{Cache, CacheBucket, Timestamp, WheelCache};
-use uv_client::{CacheControl, CachedClientError, Connectivity, RegistryClient};
+use uv_client::{CacheControl, CachedClientError, Connectivity, HashVerifier, RegistryClient};
 use uv_fs::metadata_if_exists;
 use uv_git::GitSource;
 use uv_traits::{BuildContext, NoBinary, NoBuild};
@@ -210,15 +210,40 @@ impl<'a, Context: BuildContext + Send + Sync> DistributionDatabase<'a, Context>
 
                 let download = |response: reqwest::Response| {
                     async {
-                        let reader = response
-                            .bytes_stream()
-                            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
-                            .into_async_read();
+                        // If the URL carries a hash (e.g., `#sha256=...`), hash the download as
+                        // it's read.
+                        let mut verifier = HashVerifier::from_url(wheel.url.raw());
+                        let verify = verifier.is_some();
+                        let mut reader = Box::pin(
+                            response
+                                .bytes_stream()
+                                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+                                .inspect_ok(|chunk| {
+                                    if let Some(verifier) = verifier.as_mut() {
+                                        verifier.update(chunk);
+                                    }
+                                }),
+                        )
+                        .into_async_read();
 
                         // Download and unzip the wheel to a temporary directory.
                         let temp_dir =
                             tempfile::tempdir_in(self.cache.root()).map_err(Error::CacheWrite)?;
-                        uv_extract::stream::unzip(reader.compat(), temp_dir.path()).await?;
+                        uv_extract::stream::unzip((&mut reader).compat(), temp_dir.path()).await?;
+
+                        // Check the hash before the wheel makes it into the cache, reading past
+                        // the end of the archive in case unzipping stopped short of it.
+                        if verify {
+                            futures::io::copy(&mut reader, &mut futures::io::sink())
+                                .await
+                                .map_err(|err| {
+                                    Error::Client(uv_client::ErrorKind::Io(err).into())
+                                })?;
+                        }
+                        drop(reader);
+                        if let Some(verifier) = verifier {
+                            verifier.verify()?;
+                        }
 
                         // Persist the temporary directory to the directory store.
                         let archive = self
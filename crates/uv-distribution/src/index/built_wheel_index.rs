@@ -86,14 +86,14 @@ impl BuiltWheelIndex {
     /// The `shard` should point to a directory containing the built distributions for a specific
     /// source distribution. For example, given the built wheel cache structure:
     /// ```text
-    /// built-wheels-v0/
+    /// built-wheels-v1/
     /// └── pypi
     ///     └── django-allauth-0.51.0.tar.gz
     ///         ├── django_allauth-0.51.0-py3-none-any.whl
     ///         └── metadata.json
     /// ```
     ///
-    /// The `shard` should be `built-wheels-v0/pypi/django-allauth-0.51.0.tar.gz`.
+    /// The `shard` should be `built-wheels-v1/pypi/django-allauth-0.51.0.tar.gz`.
     fn find(shard: &CacheShard, tags: &Tags) -> Option<CachedWheel> {
         let mut candidate: Option<CachedWheel> = None;
 
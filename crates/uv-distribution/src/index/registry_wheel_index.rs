@@ -8,7 +8,7 @@ use distribution_types::{CachedRegistryDist, FlatIndexLocation, IndexLocations,
 use pep440_rs::Version;
 use platform_tags::Tags;
 use uv_cache::{Cache, CacheBucket, WheelCache};
-use uv_fs::{directories, symlinks};
+use uv_fs::{directories_following_symlinks, symlinks};
 use uv_normalize::PackageName;
 
 use crate::index::cached_wheel::CachedWheel;
@@ -104,7 +104,7 @@ impl<'a> RegistryWheelIndex<'a> {
             );
 
             // For registry wheels, the cache structure is: `<index>/<package-name>/<version>/`.
-            for shard in directories(&cache_shard) {
+            for shard in directories_following_symlinks(&cache_shard) {
                 // Read the existing metadata from the cache, if it exists.
                 let cache_shard = cache_shard.shard(shard);
                 let manifest_entry = cache_shard.entry(MANIFEST);
@@ -6,7 +6,7 @@ use fs_err as fs;
 use tempfile::NamedTempFile;
 use tracing::{error, warn};
 
-use uv_warnings::warn_user;
+use uv_warnings::{warn_user, warn_user_once};
 
 pub use crate::path::*;
 
@@ -104,6 +104,92 @@ pub async fn write_atomic(path: impl AsRef<Path>, data: impl AsRef<[u8]>) -> std
     Ok(())
 }
 
+/// Write `data` to `path` atomically, staging the temporary file in `temp_dir`.
+///
+/// An atomic rename is only possible within a single filesystem, so `temp_dir` must be on the
+/// same filesystem as `path`, as checked (once, up front) by [`atomic_temp_dir`].
+#[cfg(feature = "tokio")]
+pub async fn write_atomic_in(
+    path: impl AsRef<Path>,
+    data: impl AsRef<[u8]>,
+    temp_dir: impl AsRef<Path>,
+) -> std::io::Result<()> {
+    let temp_file = NamedTempFile::new_in(temp_dir)?;
+    fs_err::tokio::write(&temp_file, &data).await?;
+    temp_file.persist(&path).map_err(|err| {
+        std::io::Error::new(
+            std::io::ErrorKind::Other,
+            format!(
+                "Failed to persist temporary file to {}: {}",
+                path.normalized_display(),
+                err.error
+            ),
+        )
+    })?;
+    Ok(())
+}
+
+/// Returns the directory in which to stage temporary files that will be renamed into `dir` (see
+/// [`write_atomic_in`]): `temp_dir`, if it's on the same filesystem as `dir`, and `dir` itself
+/// otherwise.
+///
+/// If `temp_dir` is on a different filesystem (or its filesystem can't be determined), a warning
+/// is emitted.
+pub fn atomic_temp_dir<'a>(dir: &'a Path, temp_dir: &'a Path) -> &'a Path {
+    atomic_temp_dir_with(dir, temp_dir, same_device)
+}
+
+fn atomic_temp_dir_with<'a>(
+    dir: &'a Path,
+    temp_dir: &'a Path,
+    same_device: fn(&Path, &Path) -> std::io::Result<bool>,
+) -> &'a Path {
+    match same_device(dir, temp_dir) {
+        Ok(true) => temp_dir,
+        Ok(false) => {
+            warn_user_once!(
+                "The temporary directory {} is on a different filesystem than {}; writing temporary files alongside their destination instead",
+                temp_dir.normalized_display(),
+                dir.normalized_display(),
+            );
+            dir
+        }
+        Err(err) => {
+            warn!(
+                "Failed to determine whether {} and {} are on the same filesystem: {err}",
+                temp_dir.normalized_display(),
+                dir.normalized_display(),
+            );
+            dir
+        }
+    }
+}
+
+/// Returns `true` if the two (existing) paths are on the same filesystem.
+#[cfg(unix)]
+fn same_device(a: &Path, b: &Path) -> std::io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    Ok(fs::metadata(a)?.dev() == fs::metadata(b)?.dev())
+}
+
+/// Returns `true` if the two (existing) paths are on the same volume.
+#[cfg(windows)]
+fn same_device(a: &Path, b: &Path) -> std::io::Result<bool> {
+    let prefix = |path: &Path| -> std::io::Result<Option<std::ffi::OsString>> {
+        Ok(fs::canonicalize(path)?
+            .components()
+            .next()
+            .and_then(|component| match component {
+                std::path::Component::Prefix(prefix) => {
+                    Some(prefix.as_os_str().to_ascii_lowercase())
+                }
+                _ => None,
+            }))
+    };
+    Ok(prefix(a)? == prefix(b)?)
+}
+
 /// Write `data` to `path` atomically using a temporary file and atomic rename.
 pub fn write_atomic_sync(path: impl AsRef<Path>, data: impl AsRef<[u8]>) -> std::io::Result<()> {
     let temp_file = NamedTempFile::new_in(
@@ -168,6 +254,64 @@ pub fn directories(path: impl AsRef<Path>) -> impl Iterator<Item = PathBuf> {
         .map(|entry| entry.path())
 }
 
+/// Iterate over the subdirectories of a directory, including symlinks to directories.
+///
+/// Symlinks that resolve to the directory itself or one of its ancestors are skipped, so that
+/// nested scans can't loop.
+///
+/// Unlike [`directories`], this may lead outside the directory, so it must not be used to find
+/// directories to remove.
+///
+/// If the directory does not exist, returns an empty iterator.
+pub fn directories_following_symlinks(path: impl AsRef<Path>) -> impl Iterator<Item = PathBuf> {
+    let parent = std::fs::canonicalize(path.as_ref()).ok();
+    path.as_ref()
+        .read_dir()
+        .ok()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| match entry {
+            Ok(entry) => Some(entry),
+            Err(err) => {
+                warn!("Failed to read entry: {}", err);
+                None
+            }
+        })
+        .filter(move |entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_symlink() => {
+                is_symlinked_dir(&entry.path(), parent.as_deref())
+            }
+            Ok(file_type) => file_type.is_dir(),
+            Err(err) => {
+                warn!(
+                    "Failed to read file type of {}: {}",
+                    entry.path().display(),
+                    err
+                );
+                false
+            }
+        })
+        .map(|entry| entry.path())
+}
+
+/// Returns `true` if the symlink at `path` resolves to a directory that isn't `parent` (the
+/// canonical path of the directory containing it) or one of its ancestors.
+fn is_symlinked_dir(path: &Path, parent: Option<&Path>) -> bool {
+    match std::fs::canonicalize(path) {
+        Ok(target) => {
+            if parent.is_some_and(|parent| parent.starts_with(&target)) {
+                warn!("Skipping symlink cycle at: {}", path.display());
+                return false;
+            }
+            target.is_dir()
+        }
+        Err(err) => {
+            warn!("Failed to resolve symlink at {}: {}", path.display(), err);
+            false
+        }
+    }
+}
+
 /// Iterate over the symlinks in a directory.
 ///
 /// If the directory does not exist, returns an empty iterator.
@@ -261,3 +405,76 @@ pub fn metadata_if_exists(path: impl AsRef<Path>) -> std::io::Result<Option<std:
         Err(err) => Err(err),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::atomic_temp_dir_with;
+
+    #[test]
+    fn atomic_temp_dir_same_device() {
+        let parent = Path::new("/cache/simple-v1/pypi");
+        let temp_dir = Path::new("/cache/tmp");
+        assert_eq!(
+            atomic_temp_dir_with(parent, temp_dir, |_, _| Ok(true)),
+            temp_dir
+        );
+    }
+
+    #[test]
+    fn atomic_temp_dir_cross_device() {
+        let parent = Path::new("/cache/simple-v1/pypi");
+        let temp_dir = Path::new("/tmp");
+        assert_eq!(
+            atomic_temp_dir_with(parent, temp_dir, |_, _| Ok(false)),
+            parent
+        );
+        assert_eq!(
+            atomic_temp_dir_with(parent, temp_dir, |_, _| Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "missing"
+            ))),
+            parent
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn directories_following_symlinks() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let store = tempfile::tempdir()?;
+        let root = dir.path().join("root");
+        fs_err::create_dir_all(root.join("flask"))?;
+        fs_err::create_dir(store.path().join("django"))?;
+        fs_err::write(root.join("flask.msgpack"), "")?;
+        std::os::unix::fs::symlink(store.path().join("django"), root.join("django"))?;
+        std::os::unix::fs::symlink(store.path().join("missing"), root.join("missing"))?;
+        std::os::unix::fs::symlink(&root, root.join("cycle"))?;
+        std::os::unix::fs::symlink(dir.path(), root.join("parent"))?;
+
+        let mut directories = super::directories_following_symlinks(&root)
+            .map(|path| path.file_name().unwrap().to_owned())
+            .collect::<Vec<_>>();
+        directories.sort();
+        assert_eq!(directories, ["django", "flask"]);
+
+        // Whereas symlinks are never listed as directories otherwise.
+        let directories = super::directories(&root)
+            .map(|path| path.file_name().unwrap().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(directories, ["flask"]);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn same_device() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let nested = dir.path().join("nested");
+        fs_err::create_dir(&nested)?;
+        assert!(super::same_device(dir.path(), &nested)?);
+        assert!(super::same_device(dir.path(), Path::new("/nonexistent")).is_err());
+        Ok(())
+    }
+}
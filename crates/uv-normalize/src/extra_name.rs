@@ -10,6 +10,9 @@ use crate::{validate_and_normalize_owned, validate_and_normalize_ref, InvalidNam
 /// Converts the name to lowercase and collapses any run of the characters `-`, `_` and `.`
 /// down to a single `-`, e.g., `---`, `.`, and `__` all get converted to just `-`.
 ///
+/// As with [`crate::PackageName`], a name with a leading or trailing separator is rejected rather
+/// than stripped, as is the empty name.
+///
 /// See:
 /// - <https://peps.python.org/pep-0685/#specification/>
 /// - <https://packaging.python.org/en/latest/specifications/name-normalization/>
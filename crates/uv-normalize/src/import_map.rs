@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::{InvalidNameError, PackageName};
+
+/// A table mapping top-level import names (e.g., `cv2`) to the names of the distributions that
+/// provide them (e.g., `opencv-python`).
+///
+/// The table itself is provided by the caller, as it's large and changes over time; see
+/// [`PackageName::from_import_name`] for the lookup. Distribution names are validated and
+/// normalized as the table is built, while import names are kept as-is, since Python imports
+/// are case-sensitive.
+///
+/// Deserializes from a map of import names to distribution names, e.g.,
+/// `{ "cv2": "opencv-python", "PIL": "Pillow" }`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct ImportMap(HashMap<String, PackageName>);
+
+impl ImportMap {
+    /// Build a table from pairs of import names and distribution names.
+    pub fn new<I, D>(entries: impl IntoIterator<Item = (I, D)>) -> Result<Self, InvalidNameError>
+    where
+        I: Into<String>,
+        D: AsRef<str>,
+    {
+        entries
+            .into_iter()
+            .map(|(import, distribution)| {
+                Ok((import.into(), PackageName::from_str(distribution.as_ref())?))
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    /// Returns the distribution that provides the given top-level import name, if known.
+    pub(crate) fn get(&self, import: &str) -> Option<&PackageName> {
+        self.0.get(import)
+    }
+
+    /// Returns the number of import names in the table.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if the table is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+use crate::PackageName;
+
+/// The number of names in the first segment of a [`PackageNameInterner`], as a power of two.
+const FIRST_SEGMENT_BITS: u32 = 5;
+
+/// The number of segments in a [`PackageNameInterner`], which is enough to hold a name for every
+/// [`PackageId`].
+const SEGMENTS: usize = (u32::BITS - FIRST_SEGMENT_BITS + 1) as usize;
+
+/// A compact identifier for a [`PackageName`], as assigned by a [`PackageNameInterner`].
+///
+/// Identifiers are only meaningful in the interner that assigned them. They're assigned
+/// sequentially from zero, so they can also be used to index into dense per-package tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PackageId(u32);
+
+impl PackageId {
+    /// Returns the identifier as an index, e.g., into a table of per-package data.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A thread-safe interner that assigns a stable [`PackageId`] to each distinct [`PackageName`].
+///
+/// Graph-heavy code (like the resolver) can then work with `u32`s, which are cheap to copy,
+/// compare, and hash, and only resolve them back to names at the edges.
+///
+/// Names are never removed, so an identifier remains valid (and resolves to the same name) for
+/// as long as the interner lives. Resolving an identifier doesn't take a lock: names are stored
+/// in segments of doubling size that are never moved once allocated.
+pub struct PackageNameInterner {
+    ids: RwLock<HashMap<PackageName, PackageId>>,
+    names: [OnceLock<Box<[OnceLock<PackageName>]>>; SEGMENTS],
+}
+
+impl PackageNameInterner {
+    pub fn new() -> Self {
+        Self {
+            ids: RwLock::default(),
+            names: std::array::from_fn(|_| OnceLock::new()),
+        }
+    }
+
+    /// Returns the identifier of the given name, assigning a new one if the name hasn't been
+    /// interned before.
+    ///
+    /// # Panics
+    ///
+    /// Panics if more than `u32::MAX` distinct names are interned.
+    pub fn intern(&self, name: &PackageName) -> PackageId {
+        if let Some(id) = self.ids.read().unwrap().get(name) {
+            return *id;
+        }
+        let mut ids = self.ids.write().unwrap();
+        // Another thread may have interned the name in the meantime.
+        if let Some(id) = ids.get(name) {
+            return *id;
+        }
+        let id = PackageId(u32::try_from(ids.len()).expect("too many package names to intern"));
+        let (segment, offset) = Self::locate(id);
+        let segment = self.names[segment].get_or_init(|| {
+            let len = 1usize << (FIRST_SEGMENT_BITS as usize + segment);
+            std::iter::repeat_with(OnceLock::new).take(len).collect()
+        });
+        // The slot is filled before the identifier is published, so it can always be resolved.
+        segment[offset]
+            .set(name.clone())
+            .expect("identifiers are assigned once");
+        ids.insert(name.clone(), id);
+        id
+    }
+
+    /// Returns the identifier of the given name, if it has been interned.
+    pub fn get(&self, name: &PackageName) -> Option<PackageId> {
+        self.ids.read().unwrap().get(name).copied()
+    }
+
+    /// Returns the name with the given identifier.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the identifier wasn't assigned by this interner.
+    pub fn resolve(&self, id: PackageId) -> &PackageName {
+        let (segment, offset) = Self::locate(id);
+        self.names[segment]
+            .get()
+            .and_then(|segment| segment[offset].get())
+            .expect("package identifier was assigned by a different interner")
+    }
+
+    /// Returns the number of interned names.
+    pub fn len(&self) -> usize {
+        self.ids.read().unwrap().len()
+    }
+
+    /// Returns `true` if no names have been interned.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the segment holding the name with the given identifier, and its offset within the
+    /// segment.
+    fn locate(id: PackageId) -> (usize, usize) {
+        // Segment `k` holds `2^(FIRST_SEGMENT_BITS + k)` names, so shifting the identifier by the
+        // size of the first segment makes the position of its highest bit the segment number.
+        let shifted = u64::from(id.0) + (1 << FIRST_SEGMENT_BITS);
+        let bits = u64::BITS - 1 - shifted.leading_zeros();
+        let segment = (bits - FIRST_SEGMENT_BITS) as usize;
+        let offset = (shifted - (1 << bits)) as usize;
+        (segment, offset)
+    }
+}
+
+impl Default for PackageNameInterner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for PackageNameInterner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PackageNameInterner")
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::PackageName;
+
+    use super::{PackageId, PackageNameInterner};
+
+    #[test]
+    fn intern() {
+        let interner = PackageNameInterner::new();
+        let flask = PackageName::from_str("Flask").unwrap();
+        let django = PackageName::from_str("django").unwrap();
+
+        let id = interner.intern(&flask);
+        assert_eq!(id, PackageId(0));
+        assert_eq!(interner.intern(&django), PackageId(1));
+        assert_eq!(
+            interner.intern(&PackageName::from_str("flask").unwrap()),
+            id
+        );
+        assert_eq!(interner.resolve(id), &flask);
+        assert_eq!(interner.get(&django), Some(PackageId(1)));
+        assert_eq!(interner.len(), 2);
+    }
+
+    #[test]
+    fn locate() {
+        assert_eq!(PackageNameInterner::locate(PackageId(0)), (0, 0));
+        assert_eq!(PackageNameInterner::locate(PackageId(31)), (0, 31));
+        assert_eq!(PackageNameInterner::locate(PackageId(32)), (1, 0));
+        assert_eq!(PackageNameInterner::locate(PackageId(95)), (1, 63));
+        assert_eq!(PackageNameInterner::locate(PackageId(96)), (2, 0));
+        let (segment, _) = PackageNameInterner::locate(PackageId(u32::MAX));
+        assert!(segment < super::SEGMENTS);
+    }
+
+    #[test]
+    fn concurrent() {
+        let interner = PackageNameInterner::new();
+        let names: Vec<PackageName> = (0..1000)
+            .map(|i| PackageName::from_str(&format!("package-{i}")).unwrap())
+            .collect();
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for name in &names {
+                        let id = interner.intern(name);
+                        assert_eq!(interner.resolve(id), name);
+                    }
+                });
+            }
+        });
+        assert_eq!(interner.len(), names.len());
+    }
+}
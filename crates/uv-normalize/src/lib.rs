@@ -1,11 +1,20 @@
 use std::error::Error;
 use std::fmt::{Display, Formatter};
+use std::ops::Range;
 
 pub use extra_name::ExtraName;
+pub use import_map::ImportMap;
+pub use interner::{PackageId, PackageNameInterner};
 pub use package_name::PackageName;
+pub use package_name_with_display::PackageNameWithDisplay;
+pub use requirement_spans::RequirementSpans;
 
 mod extra_name;
+mod import_map;
+mod interner;
 mod package_name;
+mod package_name_with_display;
+mod requirement_spans;
 
 /// Validate and normalize an owned package or extra name.
 pub(crate) fn validate_and_normalize_owned(name: String) -> Result<String, InvalidNameError> {
@@ -17,13 +26,17 @@ pub(crate) fn validate_and_normalize_owned(name: String) -> Result<String, Inval
 }
 
 /// Validate and normalize an unowned package or extra name.
+///
+/// Leading and trailing separators (as in `-foo` or `foo.`) are rejected rather than stripped,
+/// since stripping them would equate an invalid name with a valid one.
 pub(crate) fn validate_and_normalize_ref(
     name: impl AsRef<str>,
 ) -> Result<String, InvalidNameError> {
-    let mut normalized = String::with_capacity(name.as_ref().len());
+    let name = name.as_ref();
+    let mut normalized = String::with_capacity(name.len());
 
     let mut last = None;
-    for char in name.as_ref().bytes() {
+    for (offset, char) in name.bytes().enumerate() {
         match char {
             b'A'..=b'Z' => {
                 normalized.push(char.to_ascii_lowercase() as char);
@@ -34,19 +47,23 @@ pub(crate) fn validate_and_normalize_ref(
             b'-' | b'_' | b'.' => {
                 match last {
                     // Names can't start with punctuation.
-                    None => return Err(InvalidNameError(name.as_ref().to_string())),
+                    None => return Err(InvalidNameError::new(name, InvalidNameReason::Leading)),
                     Some(b'-') | Some(b'_') | Some(b'.') => {}
                     Some(_) => normalized.push('-'),
                 }
             }
-            _ => return Err(InvalidNameError(name.as_ref().to_string())),
+            _ => return Err(InvalidNameError::new(name, InvalidNameReason::Char(offset))),
         }
         last = Some(char);
     }
 
-    // Names can't end with punctuation.
-    if matches!(last, Some(b'-') | Some(b'_') | Some(b'.')) {
-        return Err(InvalidNameError(name.as_ref().to_string()));
+    // Names can't be empty, or end with punctuation.
+    match last {
+        None => return Err(InvalidNameError::new(name, InvalidNameReason::Empty)),
+        Some(b'-') | Some(b'_') | Some(b'.') => {
+            return Err(InvalidNameError::new(name, InvalidNameReason::Trailing))
+        }
+        Some(_) => {}
     }
 
     Ok(normalized)
@@ -54,58 +71,355 @@ pub(crate) fn validate_and_normalize_ref(
 
 /// Returns `true` if the name is already normalized.
 fn is_normalized(name: impl AsRef<str>) -> Result<bool, InvalidNameError> {
+    match check_normalized(name.as_ref()) {
+        Some(normalized) => Ok(normalized),
+        // The name is invalid; validate it fully to locate the offending character.
+        None => validate_and_normalize_ref(name).map(|_| false),
+    }
+}
+
+/// Returns whether the name is already normalized, or `None` if it's invalid, without allocating.
+///
+/// Names that aren't normalized are only validated up to the first character that needs
+/// normalizing.
+pub(crate) fn check_normalized(name: &str) -> Option<bool> {
     let mut last = None;
-    for char in name.as_ref().bytes() {
+    for char in name.bytes() {
         match char {
             b'A'..=b'Z' => {
                 // Uppercase characters need to be converted to lowercase.
-                return Ok(false);
+                return Some(false);
             }
             b'a'..=b'z' | b'0'..=b'9' => {}
             b'_' | b'.' => {
                 // `_` and `.` are normalized to `-`.
-                return Ok(false);
+                return Some(false);
             }
             b'-' => {
                 match last {
                     // Names can't start with punctuation.
-                    None => return Err(InvalidNameError(name.as_ref().to_string())),
+                    None => return None,
                     Some(b'-') => {
                         // Runs of `-` are normalized to a single `-`.
-                        return Ok(false);
+                        return Some(false);
                     }
                     Some(_) => {}
                 }
             }
-            _ => return Err(InvalidNameError(name.as_ref().to_string())),
+            _ => return None,
         }
         last = Some(char);
     }
 
-    // Names can't end with punctuation.
-    if matches!(last, Some(b'-') | Some(b'_') | Some(b'.')) {
-        return Err(InvalidNameError(name.as_ref().to_string()));
+    // Names can't be empty, or end with punctuation.
+    if matches!(last, None | Some(b'-') | Some(b'_') | Some(b'.')) {
+        return None;
     }
 
-    Ok(true)
+    Some(true)
 }
 
+/// An invalid package or extra name, along with the reason it was rejected.
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub struct InvalidNameError(String);
+pub struct InvalidNameError {
+    name: String,
+    reason: InvalidNameReason,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum InvalidNameReason {
+    /// The name is empty.
+    Empty,
+    /// The name contains a disallowed character at the given byte offset.
+    Char(usize),
+    /// The name starts with punctuation.
+    Leading,
+    /// The name ends with punctuation.
+    Trailing,
+}
+
+impl InvalidNameError {
+    fn new(name: &str, reason: InvalidNameReason) -> Self {
+        Self {
+            name: name.to_string(),
+            reason,
+        }
+    }
+
+    /// The name as given, before normalization.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The byte offset of the first offending character within the name, or `None` if the name
+    /// is empty.
+    ///
+    /// For names that end with punctuation, this is the offset of the final character.
+    pub fn position(&self) -> Option<usize> {
+        match self.reason {
+            InvalidNameReason::Empty => None,
+            InvalidNameReason::Char(offset) => Some(offset),
+            InvalidNameReason::Leading => Some(0),
+            // Trailing punctuation is always ASCII.
+            InvalidNameReason::Trailing => Some(self.name.len() - 1),
+        }
+    }
+
+    /// The first offending character within the name, if any.
+    fn char(&self) -> Option<char> {
+        self.name[self.position()?..].chars().next()
+    }
+}
 
 impl Display for InvalidNameError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = &self.name;
+        match (self.reason, self.char()) {
+            (InvalidNameReason::Char(offset), Some(char)) => write!(
+                f,
+                "Not a valid package or extra name: invalid character {char:?} at position {offset} in \"{name}\"."
+            )?,
+            (InvalidNameReason::Leading, Some(char)) => write!(
+                f,
+                "Not a valid package or extra name: \"{name}\" starts with {char:?}."
+            )?,
+            (InvalidNameReason::Trailing, Some(char)) => write!(
+                f,
+                "Not a valid package or extra name: \"{name}\" ends with {char:?}."
+            )?,
+            _ => write!(f, "Not a valid package or extra name: \"{name}\".")?,
+        }
         write!(
             f,
-            "Not a valid package or extra name: \"{}\". Names must start and end with a letter or \
-            digit and may only contain -, _, ., and alphanumeric characters.",
-            self.0
+            " Names must start and end with a letter or digit and may only contain -, _, ., and \
+            alphanumeric characters."
         )
     }
 }
 
 impl Error for InvalidNameError {}
 
+/// An error extracting a package name from a `.dist-info` directory.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InvalidDistInfoError {
+    /// The directory name doesn't match the `{name}-{version}.dist-info` pattern.
+    Pattern(String),
+    /// The name portion of the directory name isn't a valid package name.
+    Name(String, InvalidNameError),
+    /// None of the `RECORD` entries are within a `.dist-info` directory.
+    Missing,
+}
+
+impl Display for InvalidDistInfoError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pattern(dir) => write!(
+                f,
+                "Not a valid `.dist-info` directory name: \"{dir}\". Expected \"{{name}}-{{version}}.dist-info\"."
+            ),
+            Self::Name(dir, _) => write!(
+                f,
+                "The `.dist-info` directory name \"{dir}\" has an invalid package name"
+            ),
+            Self::Missing => write!(f, "No `.dist-info` directory found in `RECORD`"),
+        }
+    }
+}
+
+impl Error for InvalidDistInfoError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Name(_, err) => Some(err),
+            Self::Pattern(_) | Self::Missing => None,
+        }
+    }
+}
+
+/// An error extracting a package name from a wheel filename.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InvalidWheelFilenameError {
+    /// The filename doesn't have a `.whl` extension.
+    Extension(String),
+    /// The filename doesn't match the
+    /// `{name}-{version}(-{build tag})?-{python tag}-{abi tag}-{platform tag}.whl` pattern.
+    Pattern(String),
+    /// The name portion of the filename isn't a valid package name.
+    Name(String, InvalidNameError),
+}
+
+impl Display for InvalidWheelFilenameError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Extension(filename) => write!(
+                f,
+                "Not a valid wheel filename: \"{filename}\". Expected a `.whl` extension."
+            ),
+            Self::Pattern(filename) => write!(
+                f,
+                "Not a valid wheel filename: \"{filename}\". Expected \"{{name}}-{{version}}(-{{build tag}})?-{{python tag}}-{{abi tag}}-{{platform tag}}.whl\"."
+            ),
+            Self::Name(filename, _) => write!(
+                f,
+                "The wheel filename \"{filename}\" has an invalid package name"
+            ),
+        }
+    }
+}
+
+impl Error for InvalidWheelFilenameError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Name(_, err) => Some(err),
+            Self::Extension(_) | Self::Pattern(_) => None,
+        }
+    }
+}
+
+/// An error normalizing a percent-encoded package name, e.g., from the `href` of a link on an
+/// index page.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InvalidUrlEncodedNameError {
+    /// The name contains a `%` that isn't followed by two hexadecimal digits, at the given byte
+    /// offset.
+    Escape(String, usize),
+    /// The decoded name isn't valid UTF-8.
+    Utf8(String),
+    /// The decoded name isn't a valid package name.
+    Name(String, InvalidNameError),
+}
+
+impl Display for InvalidUrlEncodedNameError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Escape(name, offset) => write!(
+                f,
+                "Invalid percent-encoding in package name \"{name}\" at byte {offset}: expected `%` to be followed by two hexadecimal digits"
+            ),
+            Self::Utf8(name) => write!(
+                f,
+                "The percent-encoded package name \"{name}\" does not decode to valid UTF-8"
+            ),
+            Self::Name(name, _) => write!(
+                f,
+                "The percent-encoded package name \"{name}\" is not a valid package name"
+            ),
+        }
+    }
+}
+
+impl Error for InvalidUrlEncodedNameError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Name(_, err) => Some(err),
+            Self::Escape(..) | Self::Utf8(_) => None,
+        }
+    }
+}
+
+/// An error normalizing a package name read as raw bytes, e.g., from a zip archive.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InvalidNameBytesError {
+    /// The bytes aren't valid UTF-8 from the given byte offset on. The name is included as
+    /// decoded lossily, for display.
+    Utf8(String, usize),
+    /// The name isn't a valid package name.
+    Name(String, InvalidNameError),
+}
+
+impl Display for InvalidNameBytesError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Utf8(name, offset) => write!(
+                f,
+                "The package name \"{name}\" is not valid UTF-8 (at byte {offset})"
+            ),
+            Self::Name(name, _) => write!(f, "\"{name}\" is not a valid package name"),
+        }
+    }
+}
+
+impl Error for InvalidNameBytesError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Name(_, err) => Some(err),
+            Self::Utf8(..) => None,
+        }
+    }
+}
+
+/// An error parsing a package name followed by its extras, e.g., `requests[security,socks]`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InvalidNameWithExtrasError {
+    /// The extras aren't enclosed in a single pair of brackets at the end of the input.
+    Brackets(String),
+    /// The brackets hold an empty extra, as in `foo[]` or `foo[,bar]`.
+    EmptyExtra(String),
+    /// The name preceding the extras isn't a valid package name.
+    Name(String, InvalidNameError),
+    /// One of the extras isn't a valid extra name.
+    Extra(String, InvalidNameError),
+}
+
+impl Display for InvalidNameWithExtrasError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Brackets(input) => write!(
+                f,
+                "Unbalanced brackets in \"{input}\". Expected \"{{name}}\" or \"{{name}}[{{extra}},...]\"."
+            ),
+            Self::EmptyExtra(input) => write!(f, "Empty extra in \"{input}\""),
+            Self::Name(input, _) => write!(f, "\"{input}\" has an invalid package name"),
+            Self::Extra(input, _) => write!(f, "\"{input}\" has an invalid extra name"),
+        }
+    }
+}
+
+impl Error for InvalidNameWithExtrasError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Name(_, err) | Self::Extra(_, err) => Some(err),
+            Self::Brackets(_) | Self::EmptyExtra(_) => None,
+        }
+    }
+}
+
+/// An error extracting a package name from a PEP 508 requirement.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InvalidRequirementNameError {
+    /// The byte range of the name within the requirement.
+    span: Range<usize>,
+    err: InvalidNameError,
+}
+
+impl InvalidRequirementNameError {
+    /// The byte range of the (invalid) name within the requirement, as written.
+    ///
+    /// If the requirement is missing a name, the range is empty.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+}
+
+impl Display for InvalidRequirementNameError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.span.is_empty() {
+            write!(f, "Expected a package name at byte {}", self.span.start)
+        } else {
+            write!(
+                f,
+                "Not a valid package name at bytes {}..{}",
+                self.span.start, self.span.end
+            )
+        }
+    }
+}
+
+impl Error for InvalidRequirementNameError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.err)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -182,4 +496,105 @@ mod tests {
             assert!(is_normalized(input).is_err());
         }
     }
+
+    /// Separators only ever collapse between letters and digits. Leading and trailing separators
+    /// are rejected, rather than stripped or kept, such that no input normalizes to a name like
+    /// `-foo` or `foo-`.
+    #[test]
+    fn leading_and_trailing_separators() {
+        let failures = [
+            "", "-", "_", ".", "--", "-foo", "foo-", "_foo", "foo_", ".foo", "foo.", "_foo_",
+            ".foo.", "-foo-", "__foo", "foo..", "-._foo", "foo_.-", "-Foo-Bar", "foo-bar_",
+        ];
+        for input in failures {
+            assert!(validate_and_normalize_ref(input).is_err(), "{input:?}");
+            assert!(
+                validate_and_normalize_owned(input.to_string()).is_err(),
+                "{input:?}"
+            );
+            // `_` and `.` are never normalized, so they're only rejected once normalizing.
+            assert!(!matches!(is_normalized(input), Ok(true)), "{input:?}");
+        }
+
+        let cases = [
+            ("f", "f"),
+            ("F", "f"),
+            ("1", "1"),
+            ("f-o", "f-o"),
+            ("f_-_o", "f-o"),
+            ("Foo.-_.Bar", "foo-bar"),
+            ("foo__bar..baz--qux", "foo-bar-baz-qux"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(validate_and_normalize_ref(input).unwrap(), expected);
+            assert_eq!(
+                validate_and_normalize_owned(input.to_string()).unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn error_position() {
+        // Leading digits are allowed; leading punctuation is not.
+        assert_eq!(validate_and_normalize_ref("3to2").unwrap(), "3to2");
+        let err = validate_and_normalize_ref("-foo").unwrap_err();
+        assert_eq!(err.position(), Some(0));
+        assert_eq!(
+            err.to_string(),
+            "Not a valid package or extra name: \"-foo\" starts with '-'. Names must start and \
+            end with a letter or digit and may only contain -, _, ., and alphanumeric characters."
+        );
+
+        let err = validate_and_normalize_ref("foo/bar").unwrap_err();
+        assert_eq!(err.name(), "foo/bar");
+        assert_eq!(err.position(), Some(3));
+        assert!(err
+            .to_string()
+            .starts_with("Not a valid package or extra name: invalid character '/' at position 3 in \"foo/bar\"."));
+
+        let err = validate_and_normalize_ref("foo\\bar").unwrap_err();
+        assert_eq!(err.position(), Some(3));
+
+        let err = validate_and_normalize_ref("foo bar").unwrap_err();
+        assert_eq!(err.position(), Some(3));
+        assert!(err
+            .to_string()
+            .contains("invalid character ' ' at position 3 in \"foo bar\""));
+
+        let err = validate_and_normalize_ref(" foo").unwrap_err();
+        assert_eq!(err.position(), Some(0));
+
+        let err = validate_and_normalize_ref("foo\t").unwrap_err();
+        assert_eq!(err.position(), Some(3));
+        assert!(err.to_string().contains("invalid character '\\t'"));
+
+        let err = validate_and_normalize_ref("foo@1.0").unwrap_err();
+        assert_eq!(err.position(), Some(3));
+
+        let err = validate_and_normalize_ref("alpha-α").unwrap_err();
+        assert_eq!(err.position(), Some(6));
+        assert!(err
+            .to_string()
+            .contains("invalid character 'α' at position 6"));
+
+        let err = validate_and_normalize_ref("foo_").unwrap_err();
+        assert_eq!(err.position(), Some(3));
+        assert!(err.to_string().contains("\"foo_\" ends with '_'"));
+
+        let err = validate_and_normalize_ref("").unwrap_err();
+        assert_eq!(err.position(), None);
+
+        // The error is the same whether or not the name is validated via the fast path.
+        for input in ["foo/bar", "-foo", "foo-", "", "foo bar"] {
+            assert_eq!(
+                is_normalized(input).unwrap_err(),
+                validate_and_normalize_ref(input).unwrap_err()
+            );
+            assert_eq!(
+                validate_and_normalize_owned(input.to_string()).unwrap_err(),
+                validate_and_normalize_ref(input).unwrap_err()
+            );
+        }
+    }
 }
@@ -1,16 +1,31 @@
 use std::borrow::Cow;
+use std::fmt::Display;
 use std::str::FromStr;
 
 use serde::{Deserialize, Deserializer, Serialize};
 
-use crate::{validate_and_normalize_owned, validate_and_normalize_ref, InvalidNameError};
+use crate::{
+    check_normalized, validate_and_normalize_owned, validate_and_normalize_ref, ExtraName,
+    ImportMap, InvalidDistInfoError, InvalidNameBytesError, InvalidNameError,
+    InvalidNameWithExtrasError, InvalidRequirementNameError, InvalidUrlEncodedNameError,
+    InvalidWheelFilenameError, RequirementSpans,
+};
 
 /// The normalized name of a package.
 ///
 /// Converts the name to lowercase and collapses any run of the characters `-`, `_` and `.`
 /// down to a single `-`, e.g., `---`, `.`, and `__` all get converted to just `-`.
 ///
+/// Names must start and end with a letter or digit, so a name with a leading or trailing
+/// separator (like `-foo` or `foo_`) is rejected, rather than stripped, as with
+/// `packaging.utils.canonicalize_name(name, validate=True)`. So is the empty name.
+///
 /// See: <https://packaging.python.org/en/latest/specifications/name-normalization/>
+///
+/// Names are ordered lexicographically by the bytes of their normalized form, as in
+/// [`str::cmp`]. Since separators are normalized to `-`, which sorts before any letter or digit,
+/// `foo-bar` sorts before `foo2` and `foobar`, regardless of how the names were spelled. Digits
+/// are compared one at a time, so `foo10` sorts before `foo9`.
 #[derive(
     Debug,
     Clone,
@@ -34,6 +49,15 @@ impl PackageName {
         validate_and_normalize_owned(name).map(Self)
     }
 
+    /// Returns `true` if the given string is a valid package name that's already normalized,
+    /// i.e., if [`PackageName::from_str`] would return it unchanged.
+    ///
+    /// Unlike parsing the name, this never allocates, so it's suited to checking many names that
+    /// are expected to be normalized already (e.g., those in a lockfile).
+    pub fn is_normalized(name: &str) -> bool {
+        check_normalized(name) == Some(true)
+    }
+
     /// Escape this name with underscores (`_`) instead of dashes (`-`)
     ///
     /// See: <https://packaging.python.org/en/latest/specifications/recording-installed-packages/#recording-installed-packages>
@@ -58,6 +82,326 @@ impl PackageName {
             Cow::Borrowed(self.0.as_str())
         }
     }
+
+    /// Return the name of the `.dist-info` directory of the given version of this package, e.g.,
+    /// `zope_interface-6.1.dist-info`.
+    ///
+    /// The name is escaped with [`PackageName::as_dist_info_name`], and the version such that it
+    /// contains no `-`: in the local segment (following the `+`, as in `1.0+cpu`), separators are
+    /// normalized to `.` and letters are lowercased, as PEP 440 specifies; elsewhere, `-` is
+    /// replaced with `_`. A version that's already normalized (e.g., a formatted
+    /// `pep440_rs::Version`) is otherwise written as given.
+    pub fn dist_info_dir_name(&self, version: impl Display) -> String {
+        format!(
+            "{}-{}.dist-info",
+            self.as_dist_info_name(),
+            escape_dist_info_version(&version.to_string())
+        )
+    }
+
+    /// Return the name of the `.data` directory of the given version of this package, e.g.,
+    /// `zope_interface-6.1.data`, which holds the files of a wheel that are installed outside of
+    /// `purelib` or `platlib`.
+    ///
+    /// The name and version are escaped as in [`PackageName::dist_info_dir_name`].
+    pub fn data_dir_name(&self, version: impl Display) -> String {
+        format!(
+            "{}-{}.data",
+            self.as_dist_info_name(),
+            escape_dist_info_version(&version.to_string())
+        )
+    }
+
+    /// Return the path of this package's project page relative to the root of a simple
+    /// repository, i.e., the normalized name followed by a trailing slash (as in `flask/` for
+    /// `/simple/flask/`).
+    ///
+    /// The simple repository API requires the normalized name, and index servers commonly
+    /// redirect (or fail) on URLs without the trailing slash.
+    ///
+    /// See: <https://packaging.python.org/en/latest/specifications/simple-repository-api/>
+    pub fn simple_index_path(&self) -> String {
+        format!("{}/", self.0)
+    }
+
+    /// Extract the package name from a `.dist-info` directory name, the reverse of
+    /// [`PackageName::as_dist_info_name`].
+    ///
+    /// The directory name must be of the form `{name}-{version}.dist-info`, where the name and
+    /// version are separated by the last `-`.
+    pub fn from_dist_info_dir(dir: &str) -> Result<Self, InvalidDistInfoError> {
+        let Some((name, version)) = dir
+            .strip_suffix(".dist-info")
+            .and_then(|stem| stem.rsplit_once('-'))
+        else {
+            return Err(InvalidDistInfoError::Pattern(dir.to_string()));
+        };
+        if name.is_empty() || version.is_empty() {
+            return Err(InvalidDistInfoError::Pattern(dir.to_string()));
+        }
+        Self::from_str(name).map_err(|err| InvalidDistInfoError::Name(dir.to_string(), err))
+    }
+
+    /// Extract the package name from a wheel filename, e.g., `flask-login` from
+    /// `Flask_Login-0.6.3-py3-none-any.whl`, along with the rest of the filename following the
+    /// name (i.e., `0.6.3-py3-none-any`, without the extension).
+    ///
+    /// The filename must be of the form
+    /// `{name}-{version}(-{build tag})?-{python tag}-{abi tag}-{platform tag}.whl`. The name and
+    /// version are separated by the first `-` that's followed by a digit, since wheels escape
+    /// `-` in the name as `_`, but older wheels don't always do so. The rest of the filename is
+    /// only checked for the right number of components; the version and tags aren't parsed.
+    pub fn from_wheel_filename(filename: &str) -> Result<(Self, &str), InvalidWheelFilenameError> {
+        let Some(stem) = filename.strip_suffix(".whl") else {
+            return Err(InvalidWheelFilenameError::Extension(filename.to_string()));
+        };
+        let Some(separator) = stem
+            .as_bytes()
+            .windows(2)
+            .position(|window| window[0] == b'-' && window[1].is_ascii_digit())
+        else {
+            return Err(InvalidWheelFilenameError::Pattern(filename.to_string()));
+        };
+        let (name, rest) = (&stem[..separator], &stem[separator + 1..]);
+        let components = rest.split('-').collect::<Vec<_>>();
+        if name.is_empty()
+            || !matches!(components.len(), 4 | 5)
+            || components.iter().any(|component| component.is_empty())
+        {
+            return Err(InvalidWheelFilenameError::Pattern(filename.to_string()));
+        }
+        let name = validate_and_normalize_ref(name)
+            .map_err(|err| InvalidWheelFilenameError::Name(filename.to_string(), err))?;
+        Ok((Self(name), rest))
+    }
+
+    /// Extract the package name from the `.dist-info` directory referenced by the entries of a
+    /// `RECORD` file.
+    ///
+    /// The first entry within a top-level `.dist-info` directory (such as the `RECORD` itself)
+    /// determines the name.
+    pub fn from_record_paths<'a>(
+        paths: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Self, InvalidDistInfoError> {
+        let dir = paths
+            .into_iter()
+            .filter_map(|path| path.split_once('/').map(|(dir, _)| dir))
+            .find(|dir| dir.ends_with(".dist-info"))
+            .ok_or(InvalidDistInfoError::Missing)?;
+        Self::from_dist_info_dir(dir)
+    }
+
+    /// Look up the distribution that provides the given import name in the given table, e.g.,
+    /// `opencv-python` for `cv2`.
+    ///
+    /// Only the top-level module is considered, such that `yaml.constructor` maps to the same
+    /// distribution as `yaml`. Returns `None` for imports that aren't in the table, rather than
+    /// guessing (e.g., assuming that the import name is the distribution name).
+    pub fn from_import_name(import: &str, table: &ImportMap) -> Option<Self> {
+        let top_level = import.split('.').next().unwrap_or(import);
+        table.get(top_level).cloned()
+    }
+
+    /// Extract the package name from a PEP 508 requirement, e.g.,
+    /// `Flask[async] >= 3.0 ; python_version >= "3.8"`, along with the byte ranges of the name,
+    /// extras, version specifier (or URL), and marker within the requirement as written.
+    ///
+    /// Only the name is validated; the other components are merely located, for use in
+    /// diagnostics. On failure, the error points at the range of the invalid name.
+    pub fn from_requirement(
+        requirement: &str,
+    ) -> Result<(Self, RequirementSpans), InvalidRequirementNameError> {
+        let spans = RequirementSpans::split(requirement);
+        let name = &requirement[spans.name.clone()];
+        match validate_and_normalize_ref(name) {
+            Ok(normalized) => Ok((Self(normalized), spans)),
+            Err(err) => Err(InvalidRequirementNameError {
+                span: spans.name,
+                err,
+            }),
+        }
+    }
+
+    /// Parse a package name followed by its extras, e.g., `requests[security,socks]`, normalizing
+    /// both the name and each extra.
+    ///
+    /// The extras are optional, but if there are brackets, they must close the input and hold at
+    /// least one extra, with no empty extras between commas. Whitespace around the name and each
+    /// extra is ignored.
+    pub fn parse_with_extras(
+        input: &str,
+    ) -> Result<(Self, Vec<ExtraName>), InvalidNameWithExtrasError> {
+        let (name, extras) = match input.split_once('[') {
+            Some((name, rest)) => {
+                let extras = rest
+                    .trim_end()
+                    .strip_suffix(']')
+                    .filter(|extras| !extras.contains(['[', ']']))
+                    .ok_or_else(|| InvalidNameWithExtrasError::Brackets(input.to_string()))?;
+                (name, Some(extras))
+            }
+            None => (input, None),
+        };
+        if name.contains(']') {
+            return Err(InvalidNameWithExtrasError::Brackets(input.to_string()));
+        }
+
+        let name = validate_and_normalize_ref(name.trim())
+            .map(Self)
+            .map_err(|err| InvalidNameWithExtrasError::Name(input.to_string(), err))?;
+        let extras = extras
+            .into_iter()
+            .flat_map(|extras| extras.split(','))
+            .map(|extra| {
+                let extra = extra.trim();
+                if extra.is_empty() {
+                    return Err(InvalidNameWithExtrasError::EmptyExtra(input.to_string()));
+                }
+                ExtraName::from_str(extra)
+                    .map_err(|err| InvalidNameWithExtrasError::Extra(input.to_string(), err))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok((name, extras))
+    }
+
+    /// Validate and normalize a package name read as raw bytes, e.g., from the central directory
+    /// of a zip archive or the body of a response.
+    ///
+    /// The bytes must be valid UTF-8; they're validated in place, without an intermediate
+    /// `String`.
+    pub fn from_bytes(name: &[u8]) -> Result<Self, InvalidNameBytesError> {
+        let name = std::str::from_utf8(name).map_err(|err| {
+            InvalidNameBytesError::Utf8(
+                String::from_utf8_lossy(name).into_owned(),
+                err.valid_up_to(),
+            )
+        })?;
+        validate_and_normalize_ref(name)
+            .map(Self)
+            .map_err(|err| InvalidNameBytesError::Name(name.to_string(), err))
+    }
+
+    /// Normalize a package name that may be percent-encoded, as in the `href` of a link on a
+    /// `--find-links` page or a simple API page, e.g., `zope%2Einterface`.
+    ///
+    /// The name is percent-decoded before it's validated and normalized, such that encoded
+    /// separators (like `%2D` for `-`) and letters are treated as if they were written as-is. A
+    /// `%` that isn't followed by two hexadecimal digits is an error, rather than being passed
+    /// through.
+    pub fn from_url_encoded(name: &str) -> Result<Self, InvalidUrlEncodedNameError> {
+        let decoded = percent_decode(name)?;
+        validate_and_normalize_owned(decoded)
+            .map(Self)
+            .map_err(|err| InvalidUrlEncodedNameError::Name(name.to_string(), err))
+    }
+
+    /// Returns `true` if the name matches the given shell-style glob pattern.
+    ///
+    /// The pattern is normalized in the same way as a package name before matching: letters are
+    /// lowercased, and any run of `-`, `_`, and `.` is collapsed to a single `-`. As such,
+    /// separators in the pattern are interchangeable, e.g., `django_*` and `django-*` both match
+    /// `django-rest-framework` (as well as `Django_REST_framework`, which normalizes to the same).
+    ///
+    /// In the pattern, `*` matches any sequence of characters (including separators, and the
+    /// empty sequence), while `?` matches any single character.
+    pub fn matches_pattern(&self, pattern: &str) -> bool {
+        let mut normalized = Vec::with_capacity(pattern.len());
+        for byte in pattern.bytes() {
+            match byte {
+                b'-' | b'_' | b'.' => {
+                    if normalized.last() != Some(&b'-') {
+                        normalized.push(b'-');
+                    }
+                }
+                _ => normalized.push(byte.to_ascii_lowercase()),
+            }
+        }
+        glob_match(&normalized, self.0.as_bytes())
+    }
+
+    /// Return the shard this name belongs to when partitioning names by their first letter, as in
+    /// the per-letter directory layout used by many index mirrors.
+    ///
+    /// Names are bucketed by the first character of their normalized form, which is always an
+    /// ASCII lowercase letter or digit. As such, there are 27 shards: one for each letter `a`
+    /// through `z`, plus a single `0` shard that holds every name starting with a digit.
+    pub fn index_shard(&self) -> char {
+        match self.0.as_bytes().first() {
+            Some(&byte @ b'a'..=b'z') => char::from(byte),
+            _ => '0',
+        }
+    }
+}
+
+/// Escape a version for use in the name of a `.dist-info` or `.data` directory, as described on
+/// [`PackageName::dist_info_dir_name`].
+fn escape_dist_info_version(version: &str) -> String {
+    let (public, local) = match version.split_once('+') {
+        Some((public, local)) => (public, Some(local)),
+        None => (version, None),
+    };
+    let mut escaped = public.replace('-', "_");
+    if let Some(local) = local {
+        escaped.push('+');
+        escaped.extend(local.chars().map(|char| match char {
+            '-' | '_' => '.',
+            char => char.to_ascii_lowercase(),
+        }));
+    }
+    escaped
+}
+
+/// Decode the percent-encoded bytes of `input`, which must decode to valid UTF-8.
+fn percent_decode(input: &str) -> Result<String, InvalidUrlEncodedNameError> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut pos = 0;
+    while pos < bytes.len() {
+        if bytes[pos] == b'%' {
+            let byte = bytes
+                .get(pos + 1..pos + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .filter(|hex| hex.bytes().all(|byte| byte.is_ascii_hexdigit()))
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| InvalidUrlEncodedNameError::Escape(input.to_string(), pos))?;
+            decoded.push(byte);
+            pos += 3;
+        } else {
+            decoded.push(bytes[pos]);
+            pos += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| InvalidUrlEncodedNameError::Utf8(input.to_string()))
+}
+
+/// Match `text` against a glob `pattern`, in which `*` matches any sequence of bytes and `?`
+/// matches any single byte.
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // The position of the last `*` in the pattern, and of the text when it was reached, so that we
+    // can backtrack to let the `*` consume one more byte.
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&byte) if byte == b'?' || byte == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => {
+                let Some((star, star_t)) = backtrack else {
+                    return false;
+                };
+                p = star + 1;
+                t = star_t + 1;
+                backtrack = Some((star, star_t + 1));
+            }
+        }
+    }
+    pattern[p..].iter().all(|&byte| byte == b'*')
 }
 
 impl From<&PackageName> for PackageName {
@@ -67,6 +411,14 @@ impl From<&PackageName> for PackageName {
     }
 }
 
+impl TryFrom<&[u8]> for PackageName {
+    type Error = InvalidNameBytesError;
+
+    fn try_from(name: &[u8]) -> Result<Self, Self::Error> {
+        Self::from_bytes(name)
+    }
+}
+
 impl FromStr for PackageName {
     type Err = InvalidNameError;
 
@@ -96,3 +448,475 @@ impl AsRef<str> for PackageName {
         &self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{
+        ImportMap, InvalidDistInfoError, InvalidNameBytesError, InvalidNameWithExtrasError,
+        InvalidUrlEncodedNameError, RequirementSpans,
+    };
+
+    use super::PackageName;
+
+    #[test]
+    fn simple_index_path() {
+        let name = PackageName::from_str("Zope_Interface").unwrap();
+        assert_eq!(name.simple_index_path(), "zope-interface/");
+        let name = PackageName::from_str("flask").unwrap();
+        assert_eq!(name.simple_index_path(), "flask/");
+    }
+
+    #[test]
+    fn is_normalized() {
+        for name in ["flask", "zope-interface", "1okay", "a"] {
+            assert!(PackageName::is_normalized(name), "{name}");
+            assert_eq!(PackageName::from_str(name).unwrap().as_ref(), name);
+        }
+        for name in [
+            "Flask",
+            "zope_interface",
+            "zope.interface",
+            "zope--interface",
+            "-flask",
+            "flask-",
+            "",
+            "fl ask",
+        ] {
+            assert!(!PackageName::is_normalized(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn parse_with_extras() {
+        let cases = [
+            ("requests", "requests", vec![]),
+            (
+                "Requests[Security,SOCKS]",
+                "requests",
+                vec!["security", "socks"],
+            ),
+            (
+                " zope.interface [ test_extra , docs ] ",
+                "zope-interface",
+                vec!["test-extra", "docs"],
+            ),
+        ];
+        for (input, name, extras) in cases {
+            let (parsed, parsed_extras) = PackageName::parse_with_extras(input).unwrap();
+            assert_eq!(parsed.as_ref(), name, "{input}");
+            let parsed_extras: Vec<&str> = parsed_extras.iter().map(AsRef::as_ref).collect();
+            assert_eq!(parsed_extras, extras, "{input}");
+        }
+
+        for input in [
+            "foo[",
+            "foo]",
+            "foo[bar",
+            "foo[bar]baz",
+            "foo[[bar]]",
+            "foo]bar[",
+        ] {
+            assert_eq!(
+                PackageName::parse_with_extras(input),
+                Err(InvalidNameWithExtrasError::Brackets(input.to_string())),
+                "{input}"
+            );
+        }
+        for input in ["foo[]", "foo[,bar]", "foo[bar,]", "foo[ ]"] {
+            assert_eq!(
+                PackageName::parse_with_extras(input),
+                Err(InvalidNameWithExtrasError::EmptyExtra(input.to_string())),
+                "{input}"
+            );
+        }
+        assert!(matches!(
+            PackageName::parse_with_extras("-foo[bar]"),
+            Err(InvalidNameWithExtrasError::Name(..))
+        ));
+        assert!(matches!(
+            PackageName::parse_with_extras("foo[bar!]"),
+            Err(InvalidNameWithExtrasError::Extra(..))
+        ));
+    }
+
+    #[test]
+    fn from_bytes() {
+        assert_eq!(
+            PackageName::from_bytes(b"Zope.Interface").unwrap().as_ref(),
+            "zope-interface"
+        );
+        assert_eq!(
+            PackageName::try_from(b"flask".as_slice()).unwrap().as_ref(),
+            "flask"
+        );
+        assert_eq!(
+            PackageName::from_bytes(b"fl\xffask"),
+            Err(InvalidNameBytesError::Utf8("fl\u{fffd}ask".to_string(), 2))
+        );
+        assert!(matches!(
+            PackageName::from_bytes("fläsk".as_bytes()),
+            Err(InvalidNameBytesError::Name(..))
+        ));
+    }
+
+    #[test]
+    fn from_url_encoded() {
+        let cases = [
+            ("flask", "flask"),
+            // Encoded separators.
+            ("zope%2Dinterface", "zope-interface"),
+            ("zope%2einterface", "zope-interface"),
+            ("Zope%5FInterface", "zope-interface"),
+            // Encoded letters, in either case of hexadecimal digit.
+            ("%46lask", "flask"),
+            ("fl%61%73%6B", "flask"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                PackageName::from_url_encoded(input).unwrap().as_ref(),
+                expected,
+                "{input}"
+            );
+        }
+
+        assert_eq!(
+            PackageName::from_url_encoded("foo%2"),
+            Err(InvalidUrlEncodedNameError::Escape("foo%2".to_string(), 3))
+        );
+        assert_eq!(
+            PackageName::from_url_encoded("foo%zzbar"),
+            Err(InvalidUrlEncodedNameError::Escape(
+                "foo%zzbar".to_string(),
+                3
+            ))
+        );
+        assert_eq!(
+            PackageName::from_url_encoded("foo%FFbar"),
+            Err(InvalidUrlEncodedNameError::Utf8("foo%FFbar".to_string()))
+        );
+        // A decoded name must still be valid, e.g., an encoded space is rejected.
+        assert!(matches!(
+            PackageName::from_url_encoded("foo%20bar"),
+            Err(InvalidUrlEncodedNameError::Name(..))
+        ));
+    }
+
+    #[test]
+    fn from_import_name() {
+        let table = ImportMap::new([
+            ("cv2", "opencv-python"),
+            ("PIL", "Pillow"),
+            ("yaml", "PyYAML"),
+        ])
+        .unwrap();
+        let cases = [
+            ("cv2", Some("opencv-python")),
+            ("PIL", Some("pillow")),
+            ("PIL.Image", Some("pillow")),
+            ("yaml.constructor", Some("pyyaml")),
+            // Imports are case-sensitive.
+            ("pil", None),
+            ("requests", None),
+        ];
+        for (import, name) in cases {
+            assert_eq!(
+                PackageName::from_import_name(import, &table),
+                name.map(|name| PackageName::from_str(name).unwrap()),
+                "{import:?}"
+            );
+        }
+
+        assert!(ImportMap::new([("cv2", "opencv python")]).is_err());
+    }
+
+    #[test]
+    fn dist_info_dir_name() {
+        let cases = [
+            ("flask", "3.0.0", "flask-3.0.0"),
+            ("zope.interface", "6.1", "zope_interface-6.1"),
+            ("Foo__Bar-.-baz", "1.0", "foo_bar_baz-1.0"),
+            ("a-b_c.d", "2.0.post1", "a_b_c_d-2.0.post1"),
+            // Local segments are normalized, and no `-` is left in the version.
+            ("torch", "2.1.0+cpu", "torch-2.1.0+cpu"),
+            ("torch", "2.1.0+CPU-cxx11_abi", "torch-2.1.0+cpu.cxx11.abi"),
+            ("foo", "1.0-1", "foo-1.0_1"),
+        ];
+        for (input, version, stem) in cases {
+            let name = PackageName::from_str(input).unwrap();
+            let dist_info = name.dist_info_dir_name(version);
+            assert_eq!(dist_info, format!("{stem}.dist-info"), "{input:?}");
+            assert_eq!(
+                name.data_dir_name(version),
+                format!("{stem}.data"),
+                "{input:?}"
+            );
+            // The directory name round-trips.
+            assert_eq!(PackageName::from_dist_info_dir(&dist_info).unwrap(), name);
+        }
+    }
+
+    #[test]
+    fn from_dist_info_dir() {
+        let cases = [
+            ("flask-3.0.0.dist-info", "flask"),
+            ("Flask-3.0.0.dist-info", "flask"),
+            ("zope_interface-6.1.dist-info", "zope-interface"),
+            ("typing_extensions-4.9.0.dist-info", "typing-extensions"),
+        ];
+        for (input, name) in cases {
+            assert_eq!(
+                PackageName::from_dist_info_dir(input).unwrap(),
+                PackageName::from_str(name).unwrap(),
+                "{input:?}"
+            );
+        }
+
+        let failures = [
+            "flask-3.0.0",
+            "flask.dist-info",
+            "flask-.dist-info",
+            "-3.0.0.dist-info",
+            "fl!ask-3.0.0.dist-info",
+        ];
+        for input in failures {
+            assert!(PackageName::from_dist_info_dir(input).is_err(), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn from_wheel_filename() {
+        let cases = [
+            (
+                "Flask_Login-0.6.3-py3-none-any.whl",
+                "flask-login",
+                "0.6.3-py3-none-any",
+            ),
+            (
+                "zope.interface-6.1-cp312-cp312-macosx_11_0_arm64.whl",
+                "zope-interface",
+                "6.1-cp312-cp312-macosx_11_0_arm64",
+            ),
+            (
+                "foo_bar-1.0-1build-py3-none-any.whl",
+                "foo-bar",
+                "1.0-1build-py3-none-any",
+            ),
+            // Older wheels don't always escape `-` in the name.
+            (
+                "python-dateutil-2.8.2-py2.py3-none-any.whl",
+                "python-dateutil",
+                "2.8.2-py2.py3-none-any",
+            ),
+        ];
+        for (input, name, rest) in cases {
+            assert_eq!(
+                PackageName::from_wheel_filename(input).unwrap(),
+                (PackageName::from_str(name).unwrap(), rest),
+                "{input:?}"
+            );
+        }
+
+        let failures = [
+            "flask-3.0.0-py3-none-any.tar.gz",
+            "flask-3.0.0-py3-none-any",
+            "flask-py3-none-any.whl",
+            "flask-3.0.0-py3-none.whl",
+            "flask-3.0.0-1-2-py3-none-any.whl",
+            "flask-3.0.0--none-any.whl",
+            "-3.0.0-py3-none-any.whl",
+            "fl!ask-3.0.0-py3-none-any.whl",
+        ];
+        for input in failures {
+            assert!(
+                PackageName::from_wheel_filename(input).is_err(),
+                "{input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn from_record_paths() {
+        let record = [
+            "flask/__init__.py",
+            "flask/app.py",
+            "../../bin/flask",
+            "Flask-3.0.0.dist-info/METADATA",
+            "Flask-3.0.0.dist-info/RECORD",
+        ];
+        assert_eq!(
+            PackageName::from_record_paths(record).unwrap(),
+            PackageName::from_str("flask").unwrap()
+        );
+        assert_eq!(
+            PackageName::from_record_paths(["flask/__init__.py"]),
+            Err(InvalidDistInfoError::Missing)
+        );
+    }
+
+    #[test]
+    fn from_requirement() {
+        let requirement = r#"  Flask_Login [async, dotenv] >= 3.0, < 4 ; python_version >= "3.8" "#;
+        let (name, spans) = PackageName::from_requirement(requirement).unwrap();
+        assert_eq!(name, PackageName::from_str("flask-login").unwrap());
+        assert_eq!(&requirement[spans.name], "Flask_Login");
+        assert_eq!(&requirement[spans.extras.unwrap()], "[async, dotenv]");
+        assert_eq!(&requirement[spans.version.unwrap()], ">= 3.0, < 4");
+        assert_eq!(
+            &requirement[spans.marker.unwrap()],
+            r#"python_version >= "3.8""#
+        );
+
+        assert_eq!(
+            PackageName::from_requirement("flask").unwrap().1,
+            RequirementSpans {
+                name: 0..5,
+                extras: None,
+                version: None,
+                marker: None,
+            }
+        );
+        assert_eq!(
+            PackageName::from_requirement("flask(>=3);os_name=='nt'")
+                .unwrap()
+                .1,
+            RequirementSpans {
+                name: 0..5,
+                extras: None,
+                version: Some(5..10),
+                marker: Some(11..24),
+            }
+        );
+
+        // A URL may contain a `;`, so the marker must be preceded by whitespace.
+        let requirement = "pip @ https://example.com/pip.whl;v=1 ; os_name == 'nt'";
+        let (_, spans) = PackageName::from_requirement(requirement).unwrap();
+        assert_eq!(
+            &requirement[spans.version.unwrap()],
+            "@ https://example.com/pip.whl;v=1"
+        );
+        assert_eq!(&requirement[spans.marker.unwrap()], "os_name == 'nt'");
+    }
+
+    #[test]
+    fn from_requirement_invalid() {
+        let cases = [
+            ("fl$ask >= 3.0", 0..6),
+            ("  -flask[async]", 2..8),
+            ("flask- ; os_name == 'nt'", 0..6),
+            (">= 3.0", 0..0),
+            ("   ", 3..3),
+        ];
+        for (input, span) in cases {
+            let err = PackageName::from_requirement(input).unwrap_err();
+            assert_eq!(err.span(), span, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn matches_pattern() {
+        let name = PackageName::from_str("django_rest_framework").unwrap();
+        for pattern in [
+            "django-rest-framework",
+            "django-*",
+            "django_*",
+            "Django.*",
+            "django*",
+            "*framework",
+            "*-rest-*",
+            "django-rest-?ramework",
+            "*",
+        ] {
+            assert!(name.matches_pattern(pattern), "{pattern:?}");
+        }
+        for pattern in [
+            "django",
+            "django-",
+            "flask-*",
+            "*-rest",
+            "django-rest-?framework",
+            "",
+        ] {
+            assert!(!name.matches_pattern(pattern), "{pattern:?}");
+        }
+
+        // Runs of separators in the pattern collapse, as they do in names.
+        let name = PackageName::from_str("zope.interface").unwrap();
+        assert!(name.matches_pattern("zope__-interface"));
+        assert!(name.matches_pattern("ZOPE.*"));
+    }
+
+    /// The order of names is part of the public contract; see the docs on [`PackageName`].
+    #[test]
+    fn ordering() {
+        let sorted = [
+            "1password",
+            "a",
+            "a-b",
+            "a-b-c",
+            "a0",
+            "aa",
+            "django",
+            "django-rest-framework",
+            "django2",
+            "djangorestframework",
+            "foo-bar",
+            "foo10",
+            "foo2",
+            "foo9",
+            "foobar",
+            "z",
+        ];
+        let names: Vec<PackageName> = sorted
+            .iter()
+            .map(|name| PackageName::from_str(name).unwrap())
+            .collect();
+        for window in names.windows(2) {
+            assert!(window[0] < window[1], "{} < {}", window[0], window[1]);
+        }
+
+        // The order only depends on the normalized form.
+        let mut shuffled: Vec<PackageName> = [
+            "Foo_Bar",
+            "FOOBAR",
+            "foo.10",
+            "Foo9",
+            "foo2",
+            "Django_REST_Framework",
+        ]
+        .iter()
+        .map(|name| PackageName::from_str(name).unwrap())
+        .collect();
+        shuffled.sort();
+        let shuffled: Vec<&str> = shuffled.iter().map(AsRef::as_ref).collect();
+        assert_eq!(
+            shuffled,
+            [
+                "django-rest-framework",
+                "foo-10",
+                "foo-bar",
+                "foo2",
+                "foo9",
+                "foobar"
+            ]
+        );
+    }
+
+    #[test]
+    fn index_shard() {
+        let cases = [
+            ("flask", 'f'),
+            ("Flask", 'f'),
+            ("Zope.Interface", 'z'),
+            ("a", 'a'),
+            ("1password", '0'),
+            ("42", '0'),
+            ("9-to-5", '0'),
+        ];
+        for (input, shard) in cases {
+            let name = PackageName::from_str(input).unwrap();
+            assert_eq!(name.index_shard(), shard, "{input:?}");
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{InvalidNameError, PackageName};
+
+/// A [`PackageName`] along with the name as it was originally written, e.g., `Flask` for `flask`.
+///
+/// Unlike [`PackageName`], which (de)serializes as the bare normalized name, this type
+/// (de)serializes as a struct of both forms, e.g., `{ name = "flask", display = "Flask" }`. Use it
+/// wherever the original spelling should be preserved for humans (e.g., in a lockfile), while
+/// keying on the normalized name.
+///
+/// When deserializing, `name` is normalized, and `display` must normalize to the same name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct PackageNameWithDisplay {
+    name: PackageName,
+    display: String,
+}
+
+impl PackageNameWithDisplay {
+    /// Create a validated, normalized package name, retaining the name as written.
+    pub fn new(display: String) -> Result<Self, InvalidNameError> {
+        let name = PackageName::from_str(&display)?;
+        Ok(Self { name, display })
+    }
+
+    /// The normalized name.
+    pub fn name(&self) -> &PackageName {
+        &self.name
+    }
+
+    /// The name as it was originally written.
+    pub fn display(&self) -> &str {
+        &self.display
+    }
+
+    /// Discard the name as it was originally written.
+    pub fn into_name(self) -> PackageName {
+        self.name
+    }
+}
+
+impl From<PackageNameWithDisplay> for PackageName {
+    fn from(name: PackageNameWithDisplay) -> Self {
+        name.name
+    }
+}
+
+impl FromStr for PackageNameWithDisplay {
+    type Err = InvalidNameError;
+
+    fn from_str(display: &str) -> Result<Self, Self::Err> {
+        Self::new(display.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for PackageNameWithDisplay {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Raw {
+            name: String,
+            display: String,
+        }
+
+        let raw = Raw::deserialize(deserializer)?;
+        let name = PackageName::from_str(&raw.name).map_err(serde::de::Error::custom)?;
+        let display = Self::new(raw.display).map_err(serde::de::Error::custom)?;
+        if display.name != name {
+            return Err(serde::de::Error::custom(format!(
+                "The display name \"{}\" doesn't match the package name \"{name}\"",
+                display.display
+            )));
+        }
+        Ok(display)
+    }
+}
+
+impl std::fmt::Display for PackageNameWithDisplay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.display.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::PackageName;
+
+    use super::PackageNameWithDisplay;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Lock {
+        bare: PackageName,
+        both: PackageNameWithDisplay,
+    }
+
+    #[test]
+    fn round_trip() {
+        let lock = Lock {
+            bare: PackageName::from_str("Flask").unwrap(),
+            both: PackageNameWithDisplay::from_str("Flask").unwrap(),
+        };
+        let serialized = toml::to_string(&lock).unwrap();
+        assert_eq!(
+            serialized,
+            "bare = \"flask\"\n\n[both]\nname = \"flask\"\ndisplay = \"Flask\"\n"
+        );
+        assert_eq!(toml::from_str::<Lock>(&serialized).unwrap(), lock);
+    }
+
+    #[test]
+    fn deserialize() {
+        // The name is normalized on the way in.
+        let lock: Lock = toml::from_str(
+            "bare = \"flask\"\nboth = { name = \"Zope_Interface\", display = \"zope.interface\" }",
+        )
+        .unwrap();
+        assert_eq!(lock.both.name().as_ref(), "zope-interface");
+        assert_eq!(lock.both.display(), "zope.interface");
+
+        // The display name must match the name.
+        assert!(toml::from_str::<Lock>(
+            "bare = \"flask\"\nboth = { name = \"flask\", display = \"Django\" }"
+        )
+        .is_err());
+        // The bare form is only accepted for `PackageName`.
+        assert!(toml::from_str::<Lock>("bare = \"flask\"\nboth = \"Flask\"").is_err());
+    }
+}
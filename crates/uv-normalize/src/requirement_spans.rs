@@ -0,0 +1,86 @@
+use std::ops::Range;
+
+/// The byte ranges of the components of a PEP 508 requirement, e.g.,
+/// `Flask[async] >= 3.0 ; python_version >= "3.8"`, relative to the original input.
+///
+/// The ranges are in terms of the input as written (i.e., prior to normalization), such that they
+/// can be used to point at the offending part of a requirement in diagnostics. Leading and
+/// trailing whitespace is excluded from each range.
+///
+/// See [`PackageName::from_requirement`](crate::PackageName::from_requirement).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequirementSpans {
+    /// The name, e.g., `Flask`.
+    pub name: Range<usize>,
+    /// The extras, including the brackets, e.g., `[async]`.
+    pub extras: Option<Range<usize>>,
+    /// The version specifier, e.g., `>= 3.0`, or the URL, including the `@`.
+    pub version: Option<Range<usize>>,
+    /// The marker, excluding the `;`, e.g., `python_version >= "3.8"`.
+    pub marker: Option<Range<usize>>,
+}
+
+impl RequirementSpans {
+    /// Split a requirement into its components, without validating any of them.
+    ///
+    /// The name extends up to the first whitespace or the first character that can start one of
+    /// the other components, such that an invalid character in the name (as in `foo$bar`) is part
+    /// of the name's range.
+    pub(crate) fn split(requirement: &str) -> Self {
+        let start = skip_whitespace(requirement, 0);
+        let end = requirement[start..]
+            .find(|c: char| c.is_whitespace() || "[(<>=!~;@".contains(c))
+            .map_or(requirement.len(), |offset| start + offset);
+        let name = start..end;
+
+        let mut pos = skip_whitespace(requirement, end);
+        let extras = if requirement[pos..].starts_with('[') {
+            // An unterminated list of extras extends to the end of the input.
+            let close = requirement[pos..]
+                .find(']')
+                .map_or(requirement.len(), |offset| pos + offset + 1);
+            let extras = pos..close;
+            pos = skip_whitespace(requirement, close);
+            Some(extras)
+        } else {
+            None
+        };
+
+        // A URL may itself contain a `;`, so for URL requirements, the marker must be preceded by
+        // whitespace.
+        let rest = &requirement[pos..];
+        let semicolon = if rest.starts_with('@') {
+            rest.match_indices(';')
+                .map(|(offset, _)| offset)
+                .find(|&offset| rest[..offset].ends_with(char::is_whitespace))
+        } else {
+            rest.find(';')
+        };
+        let (version, marker) = match semicolon {
+            Some(offset) => (
+                trim(requirement, pos..pos + offset),
+                trim(requirement, pos + offset + 1..requirement.len()),
+            ),
+            None => (trim(requirement, pos..requirement.len()), None),
+        };
+
+        Self {
+            name,
+            extras,
+            version,
+            marker,
+        }
+    }
+}
+
+/// Return the position of the first non-whitespace character at or after `pos`.
+fn skip_whitespace(input: &str, pos: usize) -> usize {
+    input.len() - input[pos..].trim_start().len()
+}
+
+/// Trim the whitespace from both ends of the range, returning `None` if nothing is left.
+fn trim(input: &str, range: Range<usize>) -> Option<Range<usize>> {
+    let start = skip_whitespace(input, range.start);
+    let end = range.start + input[range].trim_end().len();
+    (start < end).then_some(start..end)
+}
@@ -2159,12 +2159,12 @@ fn cache_errors_are_non_fatal() -> Result<()> {
         .context("Expected a python interpreter cache file")??
         .path();
     let cache_files = [
-        PathBuf::from("simple-v0/pypi/numpy.msgpack"),
+        PathBuf::from("simple-v1/pypi/numpy.msgpack"),
         PathBuf::from(
-            "wheels-v0/pypi/python-dateutil/python_dateutil-2.8.2-py2.py3-none-any.msgpack",
+            "wheels-v1/pypi/python-dateutil/python_dateutil-2.8.2-py2.py3-none-any.msgpack",
         ),
-        PathBuf::from("wheels-v0/url/4b8be67c801a7ecb/flask/flask-3.0.0-py3-none-any.msgpack"),
-        PathBuf::from("built-wheels-v0/url/6781bd6440ae72c2/werkzeug/metadata.msgpack"),
+        PathBuf::from("wheels-v1/url/4b8be67c801a7ecb/flask/flask-3.0.0-py3-none-any.msgpack"),
+        PathBuf::from("built-wheels-v1/url/6781bd6440ae72c2/werkzeug/metadata.msgpack"),
         interpreter_cache,
     ];
 